@@ -0,0 +1,81 @@
+use chrono::{TimeZone, Utc};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use pullcaps::bench::{bucket_urls, page_urls};
+use pullcaps::models::Comment;
+use pullcaps::{Client, Filter, SortDirection};
+use reqwest::Url;
+
+const PAGE_SIZE: i64 = 100;
+
+fn filter() -> Filter {
+    Filter::new()
+        .subreddit("rust")
+        .author("reddit")
+        .query("async")
+        .sort(SortDirection::Desc)
+        .after(Utc.timestamp_opt(0, 0).unwrap())
+}
+
+/// Returns `count` full pages of comments, created one second apart, newest first.
+fn pages(count: i64) -> Vec<Vec<Comment>> {
+    (0..count)
+        .map(|page| {
+            (0..PAGE_SIZE)
+                .map(|i| {
+                    let created_utc = 1_000_000 - page * PAGE_SIZE - i;
+                    serde_json::from_value(serde_json::json!({
+                        "author": "reddit",
+                        "subreddit": "rust",
+                        "subreddit_id": "t5_2s7lj",
+                        "id": format!("c{}", created_utc),
+                        "score": 1,
+                        "created_utc": created_utc,
+                        "body": "",
+                        "parent_id": "t3_abc",
+                    }))
+                    .unwrap()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn bench_pages(c: &mut Criterion) {
+    let client = Client::builder().batch_size(PAGE_SIZE).build();
+    let url = Url::parse("https://api.pushshift.io/reddit/comment/search").unwrap();
+    let mut group = c.benchmark_group("page_urls");
+    for count in [10, 100] {
+        let pages = pages(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &pages, |b, pages| {
+            b.iter_batched(
+                || pages.clone(),
+                |pages| page_urls(&client, &url, filter(), pages),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_buckets(c: &mut Criterion) {
+    let client = Client::builder().batch_size(PAGE_SIZE).build();
+    let url = Url::parse("https://api.pushshift.io/reddit/comment/search").unwrap();
+    let mut group = c.benchmark_group("bucket_urls");
+    for count in [10, 1000] {
+        let buckets: Vec<_> = (0..count)
+            .map(|i| {
+                let after = Utc.timestamp_opt(i * 3600, 0).unwrap();
+                (after, after + chrono::Duration::hours(1))
+            })
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &buckets,
+            |b, buckets| b.iter(|| bucket_urls(&client, &url, filter(), buckets)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pages, bench_buckets);
+criterion_main!(benches);
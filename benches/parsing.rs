@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pullcaps::bench::parse_comments;
+
+/// Returns the body of a response with `count` comments, with the fields PushShift
+/// returns by default.
+fn body(count: usize) -> Vec<u8> {
+    let data: Vec<_> = (0..count)
+        .map(|i| {
+            serde_json::json!({
+                "all_awardings": [],
+                "author": format!("author{}", i),
+                "author_flair_text": "flair",
+                "author_fullname": format!("t2_{}", i),
+                "body": "Some text, with \"quotes\" and a newline\nin it. ".repeat(5),
+                "created_utc": 1_600_000_000 + i,
+                "id": format!("c{}", i),
+                "is_submitter": false,
+                "link_id": "t3_abc",
+                "locked": false,
+                "parent_id": "t3_abc",
+                "permalink": format!("/r/rust/comments/abc/_/c{}/", i),
+                "retrieved_on": 1_600_000_100 + i,
+                "score": 1,
+                "stickied": false,
+                "subreddit": "rust",
+                "subreddit_id": "t5_2s7lj",
+                "total_awards_received": 0,
+            })
+        })
+        .collect();
+    serde_json::to_vec(&serde_json::json!({ "data": data })).unwrap()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_comments");
+    for count in [100, 1000] {
+        let body = body(count);
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &body, |b, body| {
+            b.iter(|| parse_comments(body))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,232 @@
+//! Summaries computed by PushShift's aggregations, rather than by fetching every item.
+use crate::models::DELETED_AUTHOR;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// An overview of the activity of a single author, see [`Client::author_summary`].
+///
+/// [`Client::author_summary`]: crate::Client::author_summary
+#[derive(Clone, Debug)]
+pub struct AuthorSummary {
+    /// The name of the author.
+    pub name: String,
+
+    /// The total number of posts made by the author.
+    pub posts: i64,
+
+    /// The total number of comments made by the author.
+    pub comments: i64,
+
+    /// The date of the author's oldest post or comment.
+    pub first_activity: Option<DateTime<Utc>>,
+
+    /// The date of the author's most recent post or comment.
+    pub last_activity: Option<DateTime<Utc>>,
+
+    /// The author's activity in each subreddit they've posted or commented in, most
+    /// active first.
+    pub subreddits: Vec<SubredditActivity>,
+}
+
+/// The number of posts and comments an author has made in a single subreddit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubredditActivity {
+    pub subreddit: String,
+    pub posts: i64,
+    pub comments: i64,
+}
+
+impl SubredditActivity {
+    /// Returns the total number of posts and comments.
+    pub fn total(&self) -> i64 {
+        self.posts + self.comments
+    }
+}
+
+/// The number of posts and comments made by a single author, see [`Client::top_authors`].
+///
+/// [`Client::top_authors`]: crate::Client::top_authors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorActivity {
+    pub author: String,
+    pub posts: i64,
+    pub comments: i64,
+}
+
+impl AuthorActivity {
+    /// Returns the total number of posts and comments.
+    pub fn total(&self) -> i64 {
+        self.posts + self.comments
+    }
+}
+
+/// Combines per-author post and comment counts, returning the `n` most active authors.
+///
+/// Deleted authors are left out, as they're not a single author.
+pub(crate) fn top_authors(
+    posts: Vec<(String, i64)>,
+    comments: Vec<(String, i64)>,
+    n: usize,
+) -> Vec<AuthorActivity> {
+    let mut authors: HashMap<String, AuthorActivity> = HashMap::new();
+    let counts = posts
+        .into_iter()
+        .map(|(author, count)| (author, count, 0))
+        .chain(
+            comments
+                .into_iter()
+                .map(|(author, count)| (author, 0, count)),
+        );
+    for (author, posts, comments) in counts {
+        if author == DELETED_AUTHOR {
+            continue;
+        }
+        let activity = authors
+            .entry(author.clone())
+            .or_insert_with(|| AuthorActivity {
+                author,
+                posts: 0,
+                comments: 0,
+            });
+        activity.posts += posts;
+        activity.comments += comments;
+    }
+
+    let mut authors: Vec<_> = authors.into_values().collect();
+    authors.sort_by(|a, b| {
+        b.total()
+            .cmp(&a.total())
+            .then_with(|| a.author.cmp(&b.author))
+    });
+    authors.truncate(n);
+    authors
+}
+
+/// The activity of an author on a single endpoint.
+#[derive(Debug, Default)]
+pub(crate) struct Activity {
+    pub(crate) total: i64,
+    pub(crate) oldest: Option<DateTime<Utc>>,
+    pub(crate) newest: Option<DateTime<Utc>>,
+    /// The number of items in each subreddit.
+    pub(crate) subreddits: Vec<(String, i64)>,
+}
+
+impl AuthorSummary {
+    /// Combines the activity of an author on the posts and comments endpoints.
+    pub(crate) fn new(name: String, posts: Activity, comments: Activity) -> Self {
+        let mut subreddits: HashMap<String, SubredditActivity> = HashMap::new();
+        let counts = posts
+            .subreddits
+            .into_iter()
+            .map(|(subreddit, count)| (subreddit, count, 0))
+            .chain(
+                comments
+                    .subreddits
+                    .into_iter()
+                    .map(|(subreddit, count)| (subreddit, 0, count)),
+            );
+        for (subreddit, posts, comments) in counts {
+            let activity =
+                subreddits
+                    .entry(subreddit.clone())
+                    .or_insert_with(|| SubredditActivity {
+                        subreddit,
+                        posts: 0,
+                        comments: 0,
+                    });
+            activity.posts += posts;
+            activity.comments += comments;
+        }
+
+        let mut subreddits: Vec<_> = subreddits.into_values().collect();
+        subreddits.sort_by(|a, b| {
+            b.total()
+                .cmp(&a.total())
+                .then_with(|| a.subreddit.cmp(&b.subreddit))
+        });
+
+        Self {
+            name,
+            posts: posts.total,
+            comments: comments.total,
+            first_activity: posts.oldest.into_iter().chain(comments.oldest).min(),
+            last_activity: posts.newest.into_iter().chain(comments.newest).max(),
+            subreddits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(ts: i64) -> Option<DateTime<Utc>> {
+        Some(Utc.timestamp_opt(ts, 0).unwrap())
+    }
+
+    #[test]
+    fn test_top_authors() {
+        let posts = vec![("a".to_string(), 5), ("[deleted]".to_string(), 50)];
+        let comments = vec![
+            ("b".to_string(), 4),
+            ("a".to_string(), 1),
+            ("c".to_string(), 1),
+        ];
+
+        let top = top_authors(posts, comments, 2);
+        assert_eq!(
+            top,
+            vec![
+                AuthorActivity {
+                    author: "a".to_string(),
+                    posts: 5,
+                    comments: 1,
+                },
+                AuthorActivity {
+                    author: "b".to_string(),
+                    posts: 0,
+                    comments: 4,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_author_summary() {
+        let posts = Activity {
+            total: 3,
+            oldest: date(10),
+            newest: date(50),
+            subreddits: vec![("rust".to_string(), 2), ("golang".to_string(), 1)],
+        };
+        let comments = Activity {
+            total: 5,
+            oldest: date(20),
+            newest: date(60),
+            subreddits: vec![("golang".to_string(), 5)],
+        };
+
+        let summary = AuthorSummary::new("reddit".to_string(), posts, comments);
+        assert_eq!(summary.posts, 3);
+        assert_eq!(summary.comments, 5);
+        assert_eq!(summary.first_activity, date(10));
+        assert_eq!(summary.last_activity, date(60));
+        assert_eq!(
+            summary.subreddits,
+            vec![
+                SubredditActivity {
+                    subreddit: "golang".to_string(),
+                    posts: 1,
+                    comments: 5,
+                },
+                SubredditActivity {
+                    subreddit: "rust".to_string(),
+                    posts: 2,
+                    comments: 0,
+                },
+            ]
+        );
+    }
+}
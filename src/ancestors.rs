@@ -0,0 +1,34 @@
+use crate::models::{Comment, Post};
+
+/// The comments above a comment, and the post it was made on; see
+/// [`Client::get_ancestors`].
+///
+/// [`Client::get_ancestors`]: crate::Client::get_ancestors
+#[derive(Clone, Debug)]
+pub struct Ancestors {
+    /// The comments the comment is (indirectly) a reply to, starting with the top-level
+    /// comment and ending with the comment's direct parent.  This is empty for a
+    /// top-level comment.
+    pub comments: Vec<Comment>,
+
+    /// The post the comment was made on, if PushShift has it.
+    pub post: Option<Post>,
+
+    /// Whether every ancestor was found.
+    pub(crate) complete: bool,
+}
+
+impl Ancestors {
+    /// Returns whether every ancestor was found, up to and including the post.
+    ///
+    /// Ancestors which PushShift didn't archive can't be found, in which case the chain
+    /// of comments stops below them.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Returns the top-level comment of the chain, if any.
+    pub fn top_level(&self) -> Option<&Comment> {
+        self.comments.first()
+    }
+}
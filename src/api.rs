@@ -0,0 +1,148 @@
+use crate::models::PostId;
+use reqwest::Url;
+
+/// The base URL of the PushShift API, which every endpoint's path is relative to.
+pub(crate) const API_URL: &str = "https://api.pushshift.io/";
+
+/// The version of the PushShift API a [`Client`] talks to.
+///
+/// [`Client`]: crate::Client
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// The original, unauthenticated PushShift API.
+    Legacy,
+    /// The authenticated PushShift API introduced in 2023, which requires a bearer
+    /// token and is only available to verified moderators.
+    Authenticated,
+}
+
+impl ApiVersion {
+    pub fn new() -> Self {
+        Self::Legacy
+    }
+
+    /// Returns the base URL of the API, which the URL of every endpoint is built from.
+    ///
+    /// This is always PushShift's own URL; if [failover](crate::ClientBuilder::failover)
+    /// is configured, each request is rerouted to the current backend as it's sent.
+    pub(crate) fn base_url(&self) -> Url {
+        Url::parse(API_URL).unwrap()
+    }
+
+    /// Returns the URL of the given path, relative to the base URL of the API.
+    fn join(&self, path: &str) -> Url {
+        self.base_url().join(path).unwrap()
+    }
+
+    /// Returns the URL used to search the given endpoint.
+    pub(crate) fn url(&self, endpoint: Endpoint) -> Url {
+        self.join(match (self, endpoint) {
+            (Self::Legacy, Endpoint::Comments) => "reddit/comment/search/",
+            (Self::Legacy, Endpoint::Posts) => "reddit/submission/search/",
+            (Self::Authenticated, Endpoint::Comments) => "reddit/search/comment/",
+            (Self::Authenticated, Endpoint::Posts) => "reddit/search/submission/",
+        })
+    }
+
+    /// Returns the URL listing the IDs of every comment on the given post.
+    pub(crate) fn comment_ids_url(&self, post_id: &PostId) -> Url {
+        let mut url = self.join("reddit/submission/comment_ids/");
+        url.path_segments_mut()
+            .unwrap()
+            .pop_if_empty()
+            .push(post_id.as_str());
+        url
+    }
+
+    /// Returns the URL used to search for subreddits by name.
+    pub(crate) fn subreddit_search_url(&self) -> Url {
+        self.join(match self {
+            Self::Legacy => "reddit/subreddit/search/",
+            Self::Authenticated => "reddit/search/subreddit/",
+        })
+    }
+
+    /// Returns the URL describing the server, including its rate limit.
+    pub(crate) fn meta_url(&self) -> Url {
+        self.join("meta")
+    }
+
+    /// Returns the largest number of items which can be requested at once.
+    pub fn max_batch_size(&self) -> i64 {
+        match self {
+            Self::Legacy => 100,
+            Self::Authenticated => 1000,
+        }
+    }
+
+    /// Maps a (legacy) query parameter name onto the name used by this API version.
+    pub(crate) fn param_name<'a>(&self, name: &'a str) -> &'a str {
+        match self {
+            Self::Legacy => name,
+            Self::Authenticated => match name {
+                "after" => "since",
+                "before" => "until",
+                "sort" => "order",
+                "sort_type" => "sort",
+                "metadata" => "track_total_hits",
+                _ => name,
+            },
+        }
+    }
+}
+
+impl Default for ApiVersion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The searchable PushShift endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Searches comments.
+    Comments,
+    /// Searches posts (submissions).
+    Posts,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_param_names() {
+        assert_eq!(ApiVersion::Legacy.param_name("after"), "after");
+        assert_eq!(ApiVersion::Authenticated.param_name("after"), "since");
+        assert_eq!(ApiVersion::Authenticated.param_name("sort_type"), "sort");
+        assert_eq!(ApiVersion::Authenticated.param_name("sort"), "order");
+        assert_eq!(ApiVersion::Authenticated.param_name("author"), "author");
+    }
+
+    #[test]
+    fn test_comment_ids_url() {
+        let post_id: PostId = "t3_abc123".parse().unwrap();
+        assert_eq!(
+            ApiVersion::Legacy.comment_ids_url(&post_id).as_str(),
+            "https://api.pushshift.io/reddit/submission/comment_ids/abc123"
+        );
+    }
+
+    #[test]
+    fn test_urls_share_base() {
+        for version in [ApiVersion::Legacy, ApiVersion::Authenticated] {
+            let base = version.base_url();
+            let post_id: PostId = "abc".parse().unwrap();
+            for url in [
+                version.url(Endpoint::Comments),
+                version.url(Endpoint::Posts),
+                version.comment_ids_url(&post_id),
+                version.subreddit_search_url(),
+                version.meta_url(),
+            ] {
+                assert!(url.as_str().starts_with(base.as_str()), "{}", url);
+            }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! Writing fetched posts and comments to compressed archives.
+//!
+//! An [`ArchiveWriter`] writes items as newline-delimited JSON, compressed with zstd (or
+//! gzip), using the same layout as the official PushShift dumps: comments are written
+//! to files named like `RC_2021-03.zst` and posts to files named like `RS_2021-03.zst`,
+//! so archives produced with pullcaps can be read by existing dump tooling.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() -> Result<(), pullcaps::archive::ArchiveError> {
+//! use pullcaps::archive::{ArchiveWriter, Rotation};
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::new();
+//! let mut archive = ArchiveWriter::new("rust-dumps").rotation(Rotation::Daily);
+//!
+//! let comments = client.get_comments(Filter::new().subreddit("rust")).await;
+//! archive.write_all(comments).await?;
+//!
+//! for path in archive.finish()? {
+//!     println!("wrote {}", path.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::models::Content;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use lru::LruCache;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// The prefix of the files comments are written to.
+const COMMENTS_PREFIX: &str = "RC";
+
+/// The prefix of the files posts are written to.
+const POSTS_PREFIX: &str = "RS";
+
+/// The most files an archive keeps open at once, by default.
+const DEFAULT_MAX_OPEN_FILES: usize = 16;
+
+/// How an archive's files are compressed.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard, as used by the official PushShift dumps.  This is the default.
+    #[default]
+    Zstd,
+    /// Gzip, which is slower and larger but more widely supported.
+    Gzip,
+}
+
+impl Compression {
+    /// Returns the extension of files compressed this way.
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Zstd => "zst",
+            Self::Gzip => "gz",
+        }
+    }
+}
+
+/// When an archive starts a new file.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    /// A file for each month (in UTC) items were created in, e.g. `RC_2021-03.zst`, as
+    /// in the official PushShift dumps.  This is the default.
+    #[default]
+    Monthly,
+    /// A file for each day (in UTC) items were created in, e.g. `RC_2021-03-17.zst`.
+    Daily,
+    /// A new file once the current one has had the given number of (uncompressed)
+    /// bytes written to it.  Files are named after the day their first item was
+    /// created, and numbered, e.g. `RC_2021-03-17_1.zst`.
+    Size(u64),
+}
+
+/// Writes posts and comments to compressed NDJSON files, see the
+/// [module documentation](self).
+///
+/// Items are appended to any files which already exist, as zstd and gzip both allow
+/// compressed files to be concatenated, so an archive can be extended by writing to it
+/// again.  Items aren't sorted: each is written to the file for its date as it arrives,
+/// and a file stays open while items are written to others, up to
+/// [a limit](ArchiveWriter::max_open_files).
+///
+/// Files are only complete once the writer is [finished](ArchiveWriter::finish).  A
+/// writer which is dropped finishes its files too, but ignores any errors.
+pub struct ArchiveWriter {
+    dir: PathBuf,
+    compression: Compression,
+    rotation: Rotation,
+    /// The files being written, keyed by name, the least recently written of which is
+    /// finished when another needs opening.
+    files: LruCache<String, ArchiveFile>,
+    /// The name of the file of each kind being written for size-based rotation.
+    current: HashMap<&'static str, String>,
+    /// The number of files of each kind opened for size-based rotation.
+    parts: HashMap<&'static str, usize>,
+    /// Every file written to, in the order they were opened.
+    written: Vec<PathBuf>,
+}
+
+/// A file being written to.
+struct ArchiveFile {
+    encoder: Encoder,
+    /// The uncompressed bytes written to the file by this writer.
+    bytes: u64,
+}
+
+enum Encoder {
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+}
+
+impl Encoder {
+    fn new(path: &Path, compression: Compression) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let file = BufWriter::new(file);
+        Ok(match compression {
+            Compression::Zstd => Self::Zstd(zstd::Encoder::new(file, 0)?),
+            Compression::Gzip => Self::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+        })
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Zstd(encoder) => encoder.write_all(bytes),
+            Self::Gzip(encoder) => encoder.write_all(bytes),
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            Self::Zstd(encoder) => encoder.finish()?,
+            Self::Gzip(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl ArchiveWriter {
+    /// Creates a writer for an archive in the given directory, which is created if
+    /// necessary when the first item is written.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            compression: Compression::default(),
+            rotation: Rotation::default(),
+            files: LruCache::new(NonZeroUsize::new(DEFAULT_MAX_OPEN_FILES).unwrap()),
+            current: HashMap::new(),
+            parts: HashMap::new(),
+            written: Vec::new(),
+        }
+    }
+
+    /// Sets how the archive's files are compressed.
+    #[must_use]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets when the archive starts a new file.
+    #[must_use]
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Sets the most files kept open at once, each with an encoder of its own.
+    ///
+    /// Once the limit is reached, the file least recently written to is finished to make
+    /// room for the next, and is appended to (as a separate compressed frame) if items
+    /// are written to it again.  Defaults to 16.
+    #[must_use]
+    pub fn max_open_files(mut self, max: usize) -> Self {
+        self.files
+            .resize(NonZeroUsize::new(max).unwrap_or(NonZeroUsize::MIN));
+        self
+    }
+
+    /// Writes a post or comment to the archive.
+    pub fn write<C: Into<Content>>(&mut self, content: C) -> Result<(), ArchiveError> {
+        let content = content.into();
+        let (prefix, mut line) = match &content {
+            Content::Post(post) => (POSTS_PREFIX, serde_json::to_vec(post)?),
+            Content::Comment(comment) => (COMMENTS_PREFIX, serde_json::to_vec(comment)?),
+        };
+        line.push(b'\n');
+
+        let file = self.file_for(prefix, content.attrs().date)?;
+        file.encoder.write_all(&line)?;
+        file.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Writes every item of a stream to the archive, returning the number of items
+    /// written.
+    ///
+    /// The files aren't finished, so that more items can be written afterwards.
+    pub async fn write_all<S, C>(&mut self, items: S) -> Result<usize, ArchiveError>
+    where
+        S: Stream<Item = C>,
+        C: Into<Content>,
+    {
+        let mut items = Box::pin(items);
+        let mut count = 0;
+        while let Some(item) = items.next().await {
+            self.write(item)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Finishes every file, returning the paths of the files written to.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        while let Some((_, file)) = self.files.pop_lru() {
+            file.encoder.finish()?;
+        }
+        Ok(std::mem::take(&mut self.written))
+    }
+
+    /// Returns the file an item of the given kind, created at the given date, should be
+    /// written to, opening it if necessary.
+    fn file_for(
+        &mut self,
+        prefix: &'static str,
+        date: DateTime<Utc>,
+    ) -> Result<&mut ArchiveFile, ArchiveError> {
+        let name = match self.rotation {
+            Rotation::Monthly => format!("{}_{}", prefix, date.format("%Y-%m")),
+            Rotation::Daily => format!("{}_{}", prefix, date.format("%Y-%m-%d")),
+            Rotation::Size(max) => {
+                let current = self.current.get(prefix);
+                match current.and_then(|name| self.files.peek(name).map(|file| (name, file))) {
+                    Some((name, file)) if file.bytes < max => name.clone(),
+                    _ => {
+                        // Finish the full file rather than waiting for it to be evicted.
+                        if let Some(file) = current.and_then(|name| self.files.pop(name)) {
+                            file.encoder.finish()?;
+                        }
+                        let part = self.parts.entry(prefix).or_default();
+                        *part += 1;
+                        let name = format!("{}_{}_{}", prefix, date.format("%Y-%m-%d"), part);
+                        self.current.insert(prefix, name.clone());
+                        name
+                    }
+                }
+            }
+        };
+
+        if !self.files.contains(&name) {
+            fs::create_dir_all(&self.dir)?;
+            let path = self
+                .dir
+                .join(format!("{}.{}", name, self.compression.extension()));
+            let encoder = Encoder::new(&path, self.compression)?;
+            if !self.written.contains(&path) {
+                self.written.push(path);
+            }
+            let file = ArchiveFile { encoder, bytes: 0 };
+            if let Some((_, evicted)) = self.files.push(name.clone(), file) {
+                evicted.encoder.finish()?;
+            }
+        }
+        Ok(self.files.get_mut(&name).unwrap())
+    }
+}
+
+impl Drop for ArchiveWriter {
+    fn drop(&mut self) {
+        while let Some((_, file)) = self.files.pop_lru() {
+            let _ = file.encoder.finish();
+        }
+    }
+}
+
+/// An error encountered while writing an archive with an [`ArchiveWriter`].
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// A file couldn't be written.
+    Io(io::Error),
+    /// An item couldn't be serialized.
+    Serialize(serde_json::Error),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to write archive: {}", error),
+            Self::Serialize(error) => write!(f, "failed to serialize item: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Serialize(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ArchiveError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serialize(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Comment, Post};
+    use crate::test_util::comment;
+    use std::io::Read;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn post(id: &str, created_utc: i64) -> Post {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "created_utc": created_utc,
+            "author": "Ferris",
+            "subreddit": "rust",
+        }))
+        .unwrap()
+    }
+
+    /// Decompresses an archive file, returning the IDs of the items in it.
+    fn ids(path: &Path) -> Vec<String> {
+        let file = File::open(path).unwrap();
+        let mut json = String::new();
+        match path.extension().unwrap().to_str().unwrap() {
+            "zst" => zstd::Decoder::new(file).unwrap().read_to_string(&mut json),
+            _ => flate2::read::MultiGzDecoder::new(file).read_to_string(&mut json),
+        }
+        .unwrap();
+        json.lines()
+            .map(|line| serde_json::from_str::<Comment>(line).unwrap().attrs.id)
+            .collect()
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<&str> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = ArchiveWriter::new(dir.path()).rotation(Rotation::Daily);
+        archive.write(comment("a", DAY + 2)).unwrap();
+        archive.write(post("b", DAY + 1)).unwrap();
+        archive.write(comment("c", 10)).unwrap();
+        archive.write(comment("d", DAY + 5)).unwrap();
+        let paths = archive.finish().unwrap();
+
+        assert_eq!(
+            names(&paths),
+            vec![
+                "RC_1970-01-02.zst",
+                "RS_1970-01-02.zst",
+                "RC_1970-01-01.zst"
+            ]
+        );
+        // Returning to a day appends to its file.
+        assert_eq!(ids(&paths[0]), vec!["a", "d"]);
+        assert_eq!(ids(&paths[1]), vec!["b"]);
+        assert_eq!(ids(&paths[2]), vec!["c"]);
+    }
+
+    #[test]
+    fn test_files_stay_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = ArchiveWriter::new(dir.path())
+            .compression(Compression::Gzip)
+            .rotation(Rotation::Daily);
+        archive.write(comment("a", DAY + 2)).unwrap();
+        archive.write(comment("b", 10)).unwrap();
+        archive.write(comment("c", DAY + 5)).unwrap();
+        let paths = archive.finish().unwrap();
+
+        // Both items were written to the same gzip member, rather than the file being
+        // reopened for the second.
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(File::open(&paths[0]).unwrap())
+            .read_to_string(&mut json)
+            .unwrap();
+        assert_eq!(json.lines().count(), 2);
+
+        // With a single file open at a time, each day's file is appended to instead.
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = ArchiveWriter::new(dir.path())
+            .rotation(Rotation::Daily)
+            .max_open_files(1);
+        archive.write(comment("a", DAY + 2)).unwrap();
+        archive.write(comment("b", 10)).unwrap();
+        archive.write(comment("c", DAY + 5)).unwrap();
+        let paths = archive.finish().unwrap();
+        assert_eq!(ids(&paths[0]), vec!["a", "c"]);
+        assert_eq!(ids(&paths[1]), vec!["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_size_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = ArchiveWriter::new(dir.path())
+            .compression(Compression::Gzip)
+            .rotation(Rotation::Size(1));
+        let comments = vec![comment("a", 3), comment("b", 2), comment("c", 1)];
+        assert_eq!(
+            archive
+                .write_all(futures::stream::iter(comments))
+                .await
+                .unwrap(),
+            3
+        );
+        let paths = archive.finish().unwrap();
+
+        assert_eq!(
+            names(&paths),
+            vec![
+                "RC_1970-01-01_1.gz",
+                "RC_1970-01-01_2.gz",
+                "RC_1970-01-01_3.gz"
+            ]
+        );
+        assert_eq!(ids(&paths[1]), vec!["b"]);
+    }
+}
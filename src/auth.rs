@@ -0,0 +1,86 @@
+use futures::future::BoxFuture;
+use std::sync::{Arc, RwLock};
+
+/// Fetches a new bearer token, see [`ClientBuilder::refresh_auth_token`].
+///
+/// [`ClientBuilder::refresh_auth_token`]: crate::ClientBuilder::refresh_auth_token
+pub(crate) type TokenRefresher = Arc<dyn Fn() -> BoxFuture<'static, Option<String>> + Send + Sync>;
+
+/// The bearer token sent with every request, which can be replaced when it expires.
+#[derive(Default)]
+pub(crate) struct Auth {
+    token: RwLock<Option<String>>,
+    refresher: Option<TokenRefresher>,
+    /// Held while refreshing, so that requests which are rejected at the same time
+    /// only refresh the token once.
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl Auth {
+    pub(crate) fn set_token(&mut self, token: String) {
+        *self.token.get_mut().unwrap() = Some(token);
+    }
+
+    pub(crate) fn set_refresher(&mut self, refresher: TokenRefresher) {
+        self.refresher = Some(refresher);
+    }
+
+    /// Returns the current token, if any.
+    pub(crate) fn token(&self) -> Option<String> {
+        self.token.read().unwrap().clone()
+    }
+
+    /// Replaces the token after a request made with `expired` was rejected, returning
+    /// whether the request should be retried with the new token.
+    pub(crate) async fn refresh(&self, expired: Option<&str>) -> bool {
+        let refresher = match &self.refresher {
+            Some(refresher) => refresher,
+            None => return false,
+        };
+
+        let _refreshing = self.refreshing.lock().await;
+        if self.token().as_deref() != expired {
+            // Another request already refreshed the token while we were waiting.
+            return true;
+        }
+
+        match refresher().await {
+            Some(token) => {
+                *self.token.write().unwrap() = Some(token);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_refresh_once() {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let mut auth = Auth::default();
+        auth.set_token("old".to_string());
+
+        let counter = refreshes.clone();
+        auth.set_refresher(Arc::new(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(async move { Some(format!("new-{}", n)) })
+        }));
+
+        // Both requests were rejected with the old token, but only one refreshes it.
+        let (a, b) = futures::join!(auth.refresh(Some("old")), auth.refresh(Some("old")));
+        assert!(a && b);
+        assert_eq!(auth.token().as_deref(), Some("new-1"));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_refresher() {
+        let auth = Auth::default();
+        assert!(!auth.refresh(None).await);
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Authors resolved by a [`Client`](crate::Client), mapping usernames to fullnames
+/// (`t2_…`) and back.
+#[derive(Default)]
+pub(crate) struct AuthorCache {
+    /// Fullnames, keyed by lowercase username (as usernames are case-insensitive).
+    ids: Mutex<HashMap<String, String>>,
+    /// The most recently seen username of each fullname.
+    names: Mutex<HashMap<String, String>>,
+}
+
+impl AuthorCache {
+    pub(crate) fn insert(&self, name: &str, id: &str) {
+        self.ids
+            .lock()
+            .unwrap()
+            .insert(name.to_lowercase(), id.to_string());
+        self.names
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), name.to_string());
+    }
+
+    /// Returns the fullname of the author with the given username, if it's known.
+    pub(crate) fn id(&self, name: &str) -> Option<String> {
+        self.ids.lock().unwrap().get(&name.to_lowercase()).cloned()
+    }
+
+    /// Returns the username of the author with the given fullname, if it's known.
+    pub(crate) fn name(&self, id: &str) -> Option<String> {
+        self.names.lock().unwrap().get(id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_author_cache() {
+        let cache = AuthorCache::default();
+        cache.insert("Reddit", "t2_1w72");
+
+        assert_eq!(cache.id("reddit").as_deref(), Some("t2_1w72"));
+        assert_eq!(cache.name("t2_1w72").as_deref(), Some("Reddit"));
+        assert_eq!(cache.id("spez"), None);
+    }
+}
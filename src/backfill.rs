@@ -0,0 +1,655 @@
+//! Resumable backfills of historical content.
+//!
+//! A [`Backfill`] splits a date range into buckets for each of a set of filters, and
+//! fetches the buckets with bounded concurrency, passing each page of items to a
+//! callback.  Completed buckets can be recorded in a state file, so that a backfill
+//! which is restarted (e.g. after a crash) skips the buckets it already finished.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() {
+//! use chrono::{Duration, NaiveDate};
+//! use pullcaps::backfill::Backfill;
+//! use pullcaps::{Client, Filter};
+//!
+//! let after = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+//! let before = NaiveDate::from_ymd_opt(2022, 1, 1).unwrap();
+//! let filters = vec![Filter::new().subreddit("rust"), Filter::new().subreddit("golang")];
+//!
+//! let report = Backfill::comments(Client::new(), filters, after, before)
+//!     .bucket_duration(Duration::weeks(1))
+//!     .state_file("backfill.json")
+//!     .run(|comments| async move {
+//!         println!("archiving {} comments", comments.len());
+//!         Ok::<_, std::io::Error>(())
+//!     })
+//!     .await
+//!     .unwrap();
+//!
+//! println!("fetched {} comments, {} gaps", report.items, report.gaps.len());
+//! # }
+//! ```
+use crate::api::Endpoint;
+use crate::client::Item;
+use crate::models::{Comment, Post};
+use crate::{Client, Filter, IntoDateTime};
+use async_stream::stream;
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// A bucket of a [`Backfill`]: the items matching one of its filters which were created
+/// in a range of dates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bucket {
+    /// The index of the bucket's filter in the backfill's filters.
+    pub filter: usize,
+    pub after: DateTime<Utc>,
+    pub before: DateTime<Utc>,
+}
+
+/// The outcome of a [`Backfill`].
+#[derive(Debug)]
+pub struct BackfillReport<E> {
+    /// The number of buckets the backfill was split into.
+    pub planned: usize,
+    /// The number of buckets skipped, because a previous run completed them.
+    pub skipped: usize,
+    /// The number of buckets completed by this run.
+    pub completed: usize,
+    /// The number of items passed to the callback.
+    pub items: usize,
+    /// Buckets which weren't completed because a request failed (see
+    /// [`ClientBuilder::on_error`](crate::ClientBuilder::on_error)), or because more
+    /// items were created in a second than could be paged through (see
+    /// [`SkipReason::OverfullSecond`](crate::SkipReason::OverfullSecond)).
+    pub gaps: Vec<Bucket>,
+    /// Buckets which weren't completed because the callback failed.
+    pub failures: Vec<(Bucket, E)>,
+    /// Completed buckets whose items don't match PushShift's count of them.  This is
+    /// always empty unless the backfill [verifies](Backfill::verify) its buckets.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// A bucket of a [`Backfill`] in which the number of unique items fetched differs from
+/// the number PushShift says match.
+///
+/// The items missing from the bucket can be fetched with [`Backfill::refetch`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub bucket: Bucket,
+    /// The number of matching items according to PushShift, or `None` if it couldn't
+    /// be fetched.
+    pub expected: Option<i64>,
+    /// The number of items with distinct IDs which were fetched.
+    pub fetched: usize,
+    /// The IDs of the fetched items, and when they were created.
+    seen: HashMap<String, DateTime<Utc>>,
+}
+
+impl<E> BackfillReport<E> {
+    /// Returns whether every bucket has now been completed.
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty() && self.failures.is_empty()
+    }
+}
+
+/// Fetches the content matching a set of filters in a range of dates, bucket by bucket.
+///
+/// See the [module documentation](self).
+pub struct Backfill<T> {
+    client: Client,
+    filters: Vec<Filter>,
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+    bucket_duration: Duration,
+    concurrency: usize,
+    state_file: Option<PathBuf>,
+    verify: bool,
+    endpoint: Endpoint,
+    item: PhantomData<fn() -> T>,
+}
+
+impl Backfill<Post> {
+    /// Creates a backfill of the posts matching any of `filters` created between
+    /// `after` and `before`.
+    pub fn posts<A: IntoDateTime, B: IntoDateTime>(
+        client: Client,
+        filters: Vec<Filter>,
+        after: A,
+        before: B,
+    ) -> Self {
+        Self::new(client, filters, after, before, Endpoint::Posts)
+    }
+
+    /// Runs the backfill, passing each page of posts to `process`.
+    ///
+    /// A bucket is only recorded as complete once every page in it has been processed
+    /// successfully; a bucket in which a request or the callback fails, or part of
+    /// which can't be paged through, is reported and is fetched again from the start by
+    /// the next run.  Returns an error if the state
+    /// file can't be read or written.
+    pub async fn run<F, Fut, E>(&self, process: F) -> io::Result<BackfillReport<E>>
+    where
+        F: Fn(Vec<Post>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        run(self, process).await
+    }
+
+    /// Returns the posts missing from the given buckets of a verified backfill.
+    ///
+    /// Each bucket is split in half repeatedly, skipping the halves in which every post
+    /// PushShift knows about was fetched, until the rest are small enough to fetch
+    /// again in a page.  Only posts which weren't fetched by the backfill are yielded.
+    ///
+    /// A part of a bucket which couldn't be checked or fetched again, because a request
+    /// failed (see [`ClientBuilder::on_error`](crate::ClientBuilder::on_error)), is
+    /// yielded as an `Err`, and the rest of the buckets are still refetched.  Some of its
+    /// posts may have been yielded already.
+    pub fn refetch(
+        &self,
+        discrepancies: Vec<Discrepancy>,
+    ) -> impl Stream<Item = Result<Post, Bucket>> + '_ {
+        refetch(self, discrepancies)
+    }
+}
+
+impl Backfill<Comment> {
+    /// Creates a backfill of the comments matching any of `filters` created between
+    /// `after` and `before`.
+    pub fn comments<A: IntoDateTime, B: IntoDateTime>(
+        client: Client,
+        filters: Vec<Filter>,
+        after: A,
+        before: B,
+    ) -> Self {
+        Self::new(client, filters, after, before, Endpoint::Comments)
+    }
+
+    /// Runs the backfill, passing each page of comments to `process`; see
+    /// [`Backfill::<Post>::run`](Backfill#method.run).
+    pub async fn run<F, Fut, E>(&self, process: F) -> io::Result<BackfillReport<E>>
+    where
+        F: Fn(Vec<Comment>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        run(self, process).await
+    }
+
+    /// Returns the comments missing from the given buckets of a verified backfill; see
+    /// [`Backfill::<Post>::refetch`](Backfill#method.refetch).
+    pub fn refetch(
+        &self,
+        discrepancies: Vec<Discrepancy>,
+    ) -> impl Stream<Item = Result<Comment, Bucket>> + '_ {
+        refetch(self, discrepancies)
+    }
+}
+
+impl<T> Backfill<T> {
+    fn new<A: IntoDateTime, B: IntoDateTime>(
+        client: Client,
+        filters: Vec<Filter>,
+        after: A,
+        before: B,
+        endpoint: Endpoint,
+    ) -> Self {
+        Self {
+            client,
+            filters,
+            after: after.into_date_time(),
+            before: before.into_date_time(),
+            bucket_duration: Duration::days(1),
+            concurrency: DEFAULT_CONCURRENCY,
+            state_file: None,
+            verify: false,
+            endpoint,
+            item: PhantomData,
+        }
+    }
+
+    /// Sets the length of time covered by each bucket.  Defaults to a day.
+    #[must_use]
+    pub fn bucket_duration(mut self, duration: Duration) -> Self {
+        self.bucket_duration = duration.max(Duration::seconds(1));
+        self
+    }
+
+    /// Sets the number of buckets fetched at once.  Defaults to 4.
+    ///
+    /// Every bucket shares the client's rate limiter, so this mostly bounds the number
+    /// of pages waiting to be processed.
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Records completed buckets in the given file, so that they're skipped if the
+    /// backfill is run again.
+    #[must_use]
+    pub fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Checks each bucket once it's completed, by comparing the number of unique items
+    /// fetched against the number PushShift says match (which takes an extra request
+    /// per bucket).  Buckets which don't match are reported as
+    /// [discrepancies](BackfillReport::discrepancies).
+    ///
+    /// Buckets skipped because a previous run completed them aren't checked.
+    #[must_use]
+    pub fn verify(mut self) -> Self {
+        self.verify = true;
+        self
+    }
+
+    /// Returns the buckets the backfill is split into, for each filter in turn.
+    pub fn plan(&self) -> Vec<Bucket> {
+        (0..self.filters.len())
+            .flat_map(|filter| {
+                plan_dates(self.after, self.before, self.bucket_duration)
+                    .into_iter()
+                    .map(move |(after, before)| Bucket {
+                        filter,
+                        after,
+                        before,
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the filter which fetches the items in the given bucket.
+    fn bucket_filter(&self, bucket: &Bucket) -> Filter {
+        let mut filter = self.filters[bucket.filter]
+            .clone()
+            .between(bucket.after, bucket.before);
+        filter.limit = None;
+        filter
+    }
+
+    /// Returns the key under which completion of a bucket is recorded, which changes
+    /// if the bucket's filter does.
+    fn bucket_key(&self, bucket: &Bucket) -> String {
+        let endpoint = match self.endpoint {
+            Endpoint::Comments => "comments",
+            Endpoint::Posts => "posts",
+        };
+        let query = serde_urlencoded::to_string(self.bucket_filter(bucket)).unwrap_or_default();
+        format!("{}?{}", endpoint, query)
+    }
+}
+
+/// The outcome of fetching a single bucket.
+enum Outcome<E> {
+    Completed(usize, Option<Discrepancy>),
+    Gap(usize),
+    Failed(usize, E),
+}
+
+/// Runs a backfill, see [`Backfill::run`].
+async fn run<T, F, Fut, E>(backfill: &Backfill<T>, process: F) -> io::Result<BackfillReport<E>>
+where
+    T: Item,
+    F: Fn(Vec<T>) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    let state = State::load(backfill.state_file.clone())?;
+    let buckets = backfill.plan();
+    let mut report = BackfillReport {
+        planned: buckets.len(),
+        skipped: 0,
+        completed: 0,
+        items: 0,
+        gaps: Vec::new(),
+        failures: Vec::new(),
+        discrepancies: Vec::new(),
+    };
+
+    let pending: Vec<(Bucket, String)> = buckets
+        .into_iter()
+        .map(|bucket| {
+            let key = backfill.bucket_key(&bucket);
+            (bucket, key)
+        })
+        .filter(|(_, key)| !state.is_complete(key))
+        .collect();
+    report.skipped = report.planned - pending.len();
+
+    let process = &process;
+    let mut outcomes = stream::iter(pending)
+        .map(|(bucket, key)| async move {
+            let filter = backfill.bucket_filter(&bucket);
+            // Pagination can skip part of the bucket without a request failing, which
+            // is recorded as a failed range.
+            let (client, tracking) = backfill.client.tracking();
+            let mut pages = client.sequential_pages::<T>(backfill.endpoint, filter.clone());
+
+            let mut items = 0;
+            let mut seen = HashMap::new();
+            while let Some(page) = pages.next().await {
+                let page = match page {
+                    Ok(page) => page,
+                    Err(()) => return (bucket, key, Outcome::Gap(items)),
+                };
+                items += page.len();
+                if backfill.verify {
+                    seen.extend(page.iter().map(|item| {
+                        let attrs = item.attrs();
+                        (attrs.id.clone(), attrs.date)
+                    }));
+                }
+                if let Err(error) = process(page).await {
+                    return (bucket, key, Outcome::Failed(items, error));
+                }
+            }
+            if !tracking.failed.lock().unwrap().is_empty() {
+                return (bucket, key, Outcome::Gap(items));
+            }
+
+            let discrepancy = if backfill.verify {
+                let expected = backfill
+                    .client
+                    .metadata(backfill.endpoint, &filter)
+                    .await
+                    .map(|metadata| metadata.total_results);
+                (expected != Some(seen.len() as i64)).then(|| Discrepancy {
+                    bucket: bucket.clone(),
+                    expected,
+                    fetched: seen.len(),
+                    seen,
+                })
+            } else {
+                None
+            };
+            (bucket, key, Outcome::Completed(items, discrepancy))
+        })
+        .buffer_unordered(backfill.concurrency);
+
+    while let Some((bucket, key, outcome)) = outcomes.next().await {
+        match outcome {
+            Outcome::Completed(items, discrepancy) => {
+                state.complete(key)?;
+                report.completed += 1;
+                report.items += items;
+                report.discrepancies.extend(discrepancy);
+            }
+            Outcome::Gap(items) => {
+                report.gaps.push(bucket);
+                report.items += items;
+            }
+            Outcome::Failed(items, error) => {
+                report.failures.push((bucket, error));
+                report.items += items;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Fetches the items missing from buckets of a backfill, see [`Backfill::refetch`].
+fn refetch<T: Item>(
+    backfill: &Backfill<T>,
+    discrepancies: Vec<Discrepancy>,
+) -> impl Stream<Item = Result<T, Bucket>> + '_ {
+    stream! {
+        for discrepancy in discrepancies {
+            let mut seen = discrepancy.seen;
+            let mut windows = vec![(discrepancy.bucket.after, discrepancy.bucket.before)];
+
+            while let Some((after, before)) = windows.pop() {
+                let bucket = Bucket {
+                    filter: discrepancy.bucket.filter,
+                    after,
+                    before,
+                };
+                let filter = backfill.bucket_filter(&bucket);
+                let expected = match backfill.client.metadata(backfill.endpoint, &filter).await {
+                    Some(metadata) => metadata.total_results,
+                    None => {
+                        yield Err(bucket);
+                        continue;
+                    }
+                };
+                let fetched = seen
+                    .values()
+                    .filter(|date| (after..=before).contains(*date))
+                    .count();
+                if expected <= fetched as i64 {
+                    continue;
+                }
+
+                if expected > backfill.client.batch_size() {
+                    if let Some((first, second)) = split_dates(after, before) {
+                        windows.push(second);
+                        windows.push(first);
+                        continue;
+                    }
+                }
+
+                let mut pages = backfill.client.sequential_pages::<T>(backfill.endpoint, filter);
+                while let Some(page) = pages.next().await {
+                    let page = match page {
+                        Ok(page) => page,
+                        Err(()) => {
+                            yield Err(bucket);
+                            break;
+                        }
+                    };
+                    for item in page {
+                        let attrs = item.attrs();
+                        if !seen.contains_key(&attrs.id) {
+                            seen.insert(attrs.id.clone(), attrs.date);
+                            yield Ok(item);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An inclusive range of dates.
+type DateRange = (DateTime<Utc>, DateTime<Utc>);
+
+/// Splits a range of dates in half, unless it covers a single second.
+fn split_dates(after: DateTime<Utc>, before: DateTime<Utc>) -> Option<(DateRange, DateRange)> {
+    if after >= before {
+        return None;
+    }
+    let middle = after + Duration::seconds((before - after).num_seconds() / 2);
+    Some(((after, middle), (middle + Duration::seconds(1), before)))
+}
+
+/// Splits a range of dates into consecutive, non-overlapping buckets.
+///
+/// PushShift's date filters are inclusive with second resolution, so each bucket ends
+/// a second before the next one starts.
+fn plan_dates(
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+    duration: Duration,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut buckets = Vec::new();
+    let mut start = after;
+    while start <= before {
+        let end = (start + duration - Duration::seconds(1)).min(before);
+        buckets.push((start, end));
+        start = end + Duration::seconds(1);
+    }
+    buckets
+}
+
+/// The keys of the completed buckets, optionally persisted to a file.
+struct State {
+    path: Option<PathBuf>,
+    completed: Mutex<HashSet<String>>,
+}
+
+impl State {
+    fn load(path: Option<PathBuf>) -> io::Result<Self> {
+        let completed = match &path {
+            Some(path) => match fs::read(path) {
+                Ok(contents) => serde_json::from_slice(&contents)?,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => HashSet::new(),
+                Err(error) => return Err(error),
+            },
+            None => HashSet::new(),
+        };
+        Ok(Self {
+            path,
+            completed: Mutex::new(completed),
+        })
+    }
+
+    fn is_complete(&self, key: &str) -> bool {
+        self.completed.lock().unwrap().contains(key)
+    }
+
+    /// Records that a bucket is complete, replacing the state file atomically.
+    fn complete(&self, key: String) -> io::Result<()> {
+        let mut completed = self.completed.lock().unwrap();
+        completed.insert(key);
+
+        if let Some(path) = &self.path {
+            let mut keys: Vec<&String> = completed.iter().collect();
+            keys.sort();
+
+            let mut tmp = path.clone().into_os_string();
+            tmp.push(".tmp");
+            fs::write(&tmp, serde_json::to_vec_pretty(&keys)?)?;
+            fs::rename(&tmp, path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(ts: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(ts, 0).unwrap()
+    }
+
+    #[test]
+    fn test_plan() {
+        let backfill = Backfill::posts(
+            Client::builder().discover_rate_limit(false).build(),
+            vec![Filter::new(), Filter::new().subreddit("rust")],
+            date(0),
+            date(250),
+        )
+        .bucket_duration(Duration::seconds(100));
+
+        let plan = backfill.plan();
+        assert_eq!(plan.len(), 6);
+        assert_eq!((plan[0].after, plan[0].before), (date(0), date(99)));
+        assert_eq!((plan[2].after, plan[2].before), (date(200), date(250)));
+        assert_eq!(plan[3].filter, 1);
+        assert_ne!(backfill.bucket_key(&plan[0]), backfill.bucket_key(&plan[3]));
+    }
+
+    #[test]
+    fn test_split_dates() {
+        assert_eq!(
+            split_dates(date(0), date(9)),
+            Some(((date(0), date(4)), (date(5), date(9))))
+        );
+        assert_eq!(
+            split_dates(date(0), date(1)),
+            Some(((date(0), date(0)), (date(1), date(1))))
+        );
+        assert_eq!(split_dates(date(0), date(0)), None);
+    }
+
+    #[tokio::test]
+    async fn test_skipped_second_is_a_gap() {
+        let full = r#"{"data": [
+            {"id": "3", "created_utc": 10},
+            {"id": "2", "created_utc": 10}
+        ]}"#;
+        // The backend ignores the ID bound, so the second can't be paged through.
+        let responses = vec![
+            (200, full),
+            (200, full),
+            (200, full),
+            (200, r#"{"data": []}"#),
+        ];
+        let url = crate::test_util::serve(responses).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .batch_size(2)
+            .discover_rate_limit(false)
+            .build();
+
+        let backfill = Backfill::comments(client, vec![Filter::new()], date(0), date(100))
+            .bucket_duration(Duration::seconds(1000));
+        let report = backfill
+            .run(|_| async { Ok::<_, io::Error>(()) })
+            .await
+            .unwrap();
+        assert_eq!(report.items, 2);
+        assert_eq!(report.completed, 0);
+        assert_eq!(report.gaps, backfill.plan());
+    }
+
+    #[tokio::test]
+    async fn test_refetch_reports_failed_windows() {
+        let metadata = r#"{"data": [], "metadata": {"total_results": 1}}"#;
+        let page = r#"{"data": [{"id": "a", "created_utc": 150}]}"#;
+        let url = crate::test_util::serve(vec![(500, ""), (200, metadata), (200, page)]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .batch_size(2)
+            .discover_rate_limit(false)
+            .build();
+
+        let backfill = Backfill::comments(client, vec![Filter::new()], date(0), date(199));
+        let discrepancy = |after, before| Discrepancy {
+            bucket: Bucket {
+                filter: 0,
+                after: date(after),
+                before: date(before),
+            },
+            expected: Some(1),
+            fetched: 0,
+            seen: HashMap::new(),
+        };
+        let refetched: Vec<_> = backfill
+            .refetch(vec![discrepancy(0, 99), discrepancy(100, 199)])
+            .map(|item| item.map(|comment| comment.attrs.id))
+            .collect()
+            .await;
+        assert_eq!(
+            refetched,
+            vec![Err(discrepancy(0, 99).bucket), Ok("a".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let state = State::load(Some(path.clone())).unwrap();
+        state.complete("a".to_string()).unwrap();
+        assert!(state.is_complete("a"));
+
+        let state = State::load(Some(path)).unwrap();
+        assert!(state.is_complete("a"));
+        assert!(!state.is_complete("b"));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::models::{Comment, Post};
+use crate::Filter;
+
+/// The number of queries of a batch in flight at once, unless the client limits the
+/// concurrency of its buckets.
+pub(crate) const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+/// A one-shot query run as part of a batch, see [`Client::run_batch`].
+///
+/// [`Client::run_batch`]: crate::Client::run_batch
+#[non_exhaustive]
+#[derive(Clone)]
+pub enum BatchQuery {
+    /// Counts the posts matching a filter, like [`Client::post_metadata`].
+    ///
+    /// [`Client::post_metadata`]: crate::Client::post_metadata
+    CountPosts(Filter),
+    /// Counts the comments matching a filter, like [`Client::comment_metadata`].
+    ///
+    /// [`Client::comment_metadata`]: crate::Client::comment_metadata
+    CountComments(Filter),
+    /// Looks up the most recent post matching a filter, like [`Client::latest_post`].
+    ///
+    /// [`Client::latest_post`]: crate::Client::latest_post
+    LatestPost(Filter),
+    /// Looks up the most recent comment matching a filter, like
+    /// [`Client::latest_comment`].
+    ///
+    /// [`Client::latest_comment`]: crate::Client::latest_comment
+    LatestComment(Filter),
+    /// Checks whether any post or comment matches a filter, like [`Client::exists`].
+    ///
+    /// [`Client::exists`]: crate::Client::exists
+    Exists(Filter),
+}
+
+/// The result of a [`BatchQuery`].
+// Posts are larger than the other results, but boxing them would make matching on
+// results more awkward for little benefit.
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum BatchResult {
+    /// The number of items matching a [`BatchQuery::CountPosts`] or
+    /// [`BatchQuery::CountComments`] query.
+    Count(i64),
+    /// The most recent post matching a [`BatchQuery::LatestPost`] query, or `None` if
+    /// no post matches.
+    Post(Option<Post>),
+    /// The most recent comment matching a [`BatchQuery::LatestComment`] query, or `None`
+    /// if no comment matches.
+    Comment(Option<Comment>),
+    /// Whether anything matches an [`BatchQuery::Exists`] query.
+    Exists(bool),
+}
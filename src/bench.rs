@@ -0,0 +1,43 @@
+//! Entry points into the crate's internals for the benchmarks in `benches/`.  These
+//! aren't part of the public API.
+use crate::models::Comment;
+use crate::pagination::Cursor;
+use crate::{Client, Filter};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+
+/// Builds the URL of each page of a query, advancing through `pages` as though each
+/// had been returned for the URL before it, the way a paginated stream does.
+pub fn page_urls(client: &Client, url: &Url, filter: Filter, pages: Vec<Vec<Comment>>) -> Vec<Url> {
+    let mut request = client.page_request(filter);
+    let mut cursor = Cursor::new(request.filter.sort);
+    let mut urls = Vec::with_capacity(pages.len());
+    for page in pages {
+        urls.push(request.query.url(url));
+        cursor.advance(page);
+        cursor.apply(&mut request.query);
+    }
+    urls
+}
+
+/// Builds the URL of the first page of each of the buckets of a chunked query.
+pub fn bucket_urls(
+    client: &Client,
+    url: &Url,
+    filter: Filter,
+    buckets: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> Vec<Url> {
+    let request = client.page_request(filter);
+    buckets
+        .iter()
+        .map(|(after, before)| request.bounded(*after, *before).query.url(url))
+        .collect()
+}
+
+/// Parses the body of a response into its comments, as a page is parsed once its body
+/// has arrived.
+pub fn parse_comments(body: &[u8]) -> Vec<Comment> {
+    crate::response::parse_response(body, false, |_| {})
+        .map(|response| response.data)
+        .unwrap_or_default()
+}
@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The state of a client's circuit breaker, see [`ClientBuilder::circuit_breaker`].
+///
+/// [`ClientBuilder::circuit_breaker`]: crate::ClientBuilder::circuit_breaker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are being made as normal.
+    Closed,
+    /// Too many consecutive requests failed, so requests are paused until the next probe.
+    Open,
+    /// A single probe request has been let through, to check whether PushShift has
+    /// recovered.
+    HalfOpen,
+}
+
+/// Pauses all requests after a run of consecutive failures, e.g. while PushShift is
+/// down, periodically letting a single request through to probe whether it's back.
+pub(crate) struct CircuitBreaker {
+    /// The number of consecutive failures which opens the circuit.
+    threshold: u32,
+    /// How long to wait between probes while the circuit is open.
+    probe_interval: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    /// When the circuit was opened, or last probed.
+    opened_at: Option<Instant>,
+    /// Whether a probe has been let through since the circuit was last opened.
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, probe_interval: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            probe_interval,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Returns the current state of the circuit.
+    pub(crate) fn state(&self) -> CircuitState {
+        let state = self.state.lock().unwrap();
+        match (state.opened_at, state.probing) {
+            (None, _) => CircuitState::Closed,
+            (Some(_), false) => CircuitState::Open,
+            (Some(_), true) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Waits until a request is permitted: immediately while the circuit is closed, and
+    /// otherwise until it's this request's turn to probe.
+    ///
+    /// If a probe never reports back (e.g. because its stream was dropped), another is
+    /// let through after the probe interval.
+    pub(crate) async fn until_closed(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let opened_at = match state.opened_at {
+                    Some(opened_at) => opened_at,
+                    None => return,
+                };
+
+                let now = Instant::now();
+                let next_probe = opened_at + self.probe_interval;
+                if next_probe <= now {
+                    state.opened_at = Some(now);
+                    state.probing = true;
+                    return;
+                }
+                next_probe - now
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Records that a request succeeded, closing the circuit.
+    pub(crate) fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    /// Records that a request failed, opening the circuit if there have been too many
+    /// consecutive failures or if a probe failed.
+    pub(crate) fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        if state.probing || state.failures >= self.threshold {
+            state.opened_at = Some(Instant::now());
+            state.probing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let start = Instant::now();
+        breaker.until_closed().await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // A failed probe opens the circuit again straight away.
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        breaker.until_closed().await;
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// How many of a stream's requests may be retried, see [`ClientBuilder::retry_budget`].
+///
+/// [`ClientBuilder::retry_budget`]: crate::ClientBuilder::retry_budget
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RetryBudget {
+    /// The most retries as a proportion of the requests made so far.
+    pub(crate) ratio: f64,
+    /// The most retries in total.
+    pub(crate) max_retries: usize,
+}
+
+/// The retries spent by a single stream.
+#[derive(Debug)]
+pub(crate) struct StreamBudget {
+    budget: RetryBudget,
+    requests: AtomicUsize,
+    retries: AtomicUsize,
+    exhausted: AtomicBool,
+}
+
+impl StreamBudget {
+    pub(crate) fn new(budget: RetryBudget) -> Self {
+        Self {
+            budget,
+            requests: AtomicUsize::new(0),
+            retries: AtomicUsize::new(0),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Spends a retry, returning whether the budget allowed it.
+    ///
+    /// A stream can always retry its first failure, so that a single failure early on
+    /// doesn't exhaust a budget which is a proportion of very few requests.
+    pub(crate) fn try_retry(&self) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+        let requests = self.requests.load(Ordering::SeqCst);
+        self.retries
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |retries| {
+                let within_ratio = retries as f64 <= self.budget.ratio * requests as f64;
+                (retries < self.budget.max_retries && within_ratio).then_some(retries + 1)
+            })
+            .is_ok()
+    }
+
+    /// Marks the budget as exhausted, returning whether it wasn't already.
+    pub(crate) fn exhaust(&self) -> bool {
+        !self.exhausted.swap(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(ratio: f64, max_retries: usize) -> StreamBudget {
+        StreamBudget::new(RetryBudget { ratio, max_retries })
+    }
+
+    #[test]
+    fn test_ratio() {
+        let budget = budget(0.1, 100);
+
+        // The first failure can always be retried.
+        budget.record_request();
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+
+        // A second retry needs another 9 requests.
+        for _ in 0..9 {
+            budget.record_request();
+        }
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+    }
+
+    #[test]
+    fn test_max_retries() {
+        let budget = budget(1.0, 2);
+        for _ in 0..10 {
+            budget.record_request();
+        }
+        assert!(budget.try_retry());
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+
+        assert!(budget.exhaust());
+        assert!(!budget.exhaust());
+        assert!(budget.is_exhausted());
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Mutex;
+use tokio::sync::Semaphore;
+
+/// Room for the items a single stream has fetched but not yet yielded, see
+/// [`ClientBuilder::max_buffered_items`].
+///
+/// [`ClientBuilder::max_buffered_items`]: crate::ClientBuilder::max_buffered_items
+#[derive(Debug)]
+pub(crate) struct ItemBuffer {
+    permits: Semaphore,
+    capacity: usize,
+    /// The room which has been reserved and not released since.
+    reserved: Mutex<usize>,
+}
+
+impl ItemBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let capacity = capacity.clamp(1, Semaphore::MAX_PERMITS);
+        Self {
+            permits: Semaphore::new(capacity),
+            capacity,
+            reserved: Mutex::new(0),
+        }
+    }
+
+    /// Waits until there's room for `n` more items, and reserves it, returning how much
+    /// room was reserved.
+    ///
+    /// At most the whole buffer is reserved, so that a page larger than the buffer can
+    /// still be fetched once the buffer is empty.
+    pub(crate) async fn reserve(&self, n: usize) -> usize {
+        let n = n.clamp(1, self.capacity);
+        // The semaphore is never closed.
+        if let Ok(permits) = self.permits.acquire_many(n as u32).await {
+            permits.forget();
+        }
+        *self.reserved.lock().unwrap() += n;
+        n
+    }
+
+    /// Frees up room for `n` items, e.g. once they've been yielded.
+    ///
+    /// No more room is freed up than was reserved, so the buffer never grows.
+    pub(crate) fn release(&self, n: usize) {
+        let mut reserved = self.reserved.lock().unwrap();
+        let n = n.min(*reserved);
+        *reserved -= n;
+        self.permits.add_permits(n);
+    }
+
+    #[cfg(test)]
+    fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let buffer = ItemBuffer::new(100);
+        assert_eq!(buffer.reserve(60).await, 60);
+        assert_eq!(buffer.available(), 40);
+
+        // There isn't room for another 60 items until some are released.
+        let blocked = timeout(Duration::from_millis(10), buffer.reserve(60)).await;
+        assert!(blocked.is_err());
+        buffer.release(20);
+        assert_eq!(buffer.reserve(60).await, 60);
+        assert_eq!(buffer.available(), 0);
+
+        // Releasing more than was reserved doesn't grow the buffer.
+        buffer.release(1000);
+        assert_eq!(buffer.available(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_reserve_more_than_capacity() {
+        let buffer = ItemBuffer::new(10);
+        assert_eq!(buffer.reserve(50).await, 10);
+        buffer.release(50);
+        assert_eq!(buffer.available(), 10);
+    }
+}
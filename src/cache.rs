@@ -0,0 +1,128 @@
+//! Caches for PushShift responses.
+//!
+//! Historical PushShift data rarely changes, so re-running the same queries (e.g. while
+//! iterating on an analysis) can be served from a cache rather than spending the rate
+//! limit again.  Configure a cache using [`ClientBuilder::cache`].
+//!
+//! Responses can be kept in memory using a [`MemoryCache`], or (with the `disk-cache`
+//! feature enabled) on disk using a `DiskCache`.
+//!
+//! [`ClientBuilder::cache`]: crate::ClientBuilder::cache
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "disk-cache")]
+mod disk;
+
+#[cfg(feature = "disk-cache")]
+pub use disk::DiskCache;
+
+/// A store for the raw bodies of successful responses, keyed by the full request URL.
+pub trait Cache: Send + Sync {
+    /// Returns the cached body of the request with the given key, if there is one.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Stores the body of the request with the given key.
+    fn insert(&self, key: &str, body: &[u8]);
+
+    /// Removes the body of the request with the given key, if it's cached.
+    ///
+    /// The default implementation doesn't evict anything, so a response which turns
+    /// out to be unusable may be served from the cache again.
+    fn remove(&self, _key: &str) {}
+}
+
+/// A [`Cache`] which keeps responses in memory.
+///
+/// # Example
+/// ```rust
+/// use pullcaps::cache::MemoryCache;
+/// use pullcaps::Client;
+/// use std::time::Duration;
+///
+/// let client = Client::builder()
+///     .cache(MemoryCache::with_ttl(Duration::from_secs(60 * 60)))
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    ttl: Option<Duration>,
+    entries: Mutex<HashMap<String, (Instant, Vec<u8>)>>,
+}
+
+impl MemoryCache {
+    /// Creates a cache whose entries never expire.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache whose entries expire once `ttl` has elapsed since they were stored.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            ..Self::default()
+        }
+    }
+
+    /// Removes every entry from the cache.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn is_fresh(&self, stored: Instant) -> bool {
+        self.ttl.is_none_or(|ttl| stored.elapsed() < ttl)
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((stored, body)) if self.is_fresh(*stored) => Some(body.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: &str, body: &[u8]) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (stored, _)| self.is_fresh(*stored));
+        entries.insert(key.to_string(), (Instant::now(), body.to_vec()));
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = MemoryCache::new();
+        assert_eq!(cache.get("a"), None);
+
+        cache.insert("a", b"body");
+        assert_eq!(cache.get("a").as_deref(), Some(&b"body"[..]));
+
+        cache.remove("a");
+        assert_eq!(cache.get("a"), None);
+
+        cache.insert("a", b"body");
+        cache.clear();
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn test_memory_cache_expiry() {
+        let cache = MemoryCache::with_ttl(Duration::ZERO);
+        cache.insert("a", b"body");
+        assert_eq!(cache.get("a"), None);
+    }
+}
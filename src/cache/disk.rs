@@ -0,0 +1,215 @@
+use super::Cache;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+const EXTENSION: &str = "gz";
+
+/// A [`Cache`] which stores compressed responses in a directory, so that they survive
+/// between runs of a program.
+///
+/// Each response is stored in its own file, named after a hash of the request.  If a
+/// [size limit](DiskCache::max_size) is set, the least recently written responses are
+/// removed to stay within it.
+///
+/// # Example
+/// ```rust,no_run
+/// use pullcaps::cache::DiskCache;
+/// use pullcaps::Client;
+///
+/// let cache = DiskCache::new("pushshift-cache")
+///     .unwrap()
+///     .max_size(1024 * 1024 * 1024);
+///
+/// let client = Client::builder().cache(cache).build();
+/// ```
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_size: Option<u64>,
+    /// Serializes writes and evictions.
+    lock: Mutex<()>,
+}
+
+impl DiskCache {
+    /// Creates a cache storing responses in `dir`, creating the directory if necessary.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            max_size: None,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Limits the total size, in bytes, of the (compressed) responses in the cache.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Returns the directory in which responses are stored.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Removes the cached response for the given key, if there is one.
+    pub fn invalidate(&self, key: &str) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        match fs::remove_file(self.path(key)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes every cached response which was stored more than `age` ago.
+    pub fn invalidate_older_than(&self, age: Duration) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let now = SystemTime::now();
+        for (path, _, modified) in self.entries()? {
+            if now.duration_since(modified).unwrap_or_default() > age {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        for (path, _, _) in self.entries()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let hash = Sha256::digest(key.as_bytes());
+        let name: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(name).with_extension(EXTENSION)
+    }
+
+    /// Returns the path, size and modification time of every cached response.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                let metadata = fs::metadata(&path)?;
+                entries.push((path, metadata.len(), metadata.modified()?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Removes the oldest responses until the cache fits within its size limit.
+    fn evict(&self, max_size: u64) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, len, _) in entries {
+            if size <= max_size {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+
+    fn write(&self, key: &str, body: &[u8]) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body)?;
+        let compressed = encoder.finish()?;
+
+        let _lock = self.lock.lock().unwrap();
+        // Write to a temporary file first, so that a crash can't leave a partial response.
+        let path = self.path(key);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, compressed)?;
+        fs::rename(tmp, path)?;
+
+        match self.max_size {
+            Some(max_size) => self.evict(max_size),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Cache for DiskCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let file = fs::File::open(self.path(key)).ok()?;
+        let mut body = Vec::new();
+        GzDecoder::new(file).read_to_end(&mut body).ok()?;
+        Some(body)
+    }
+
+    fn insert(&self, key: &str, body: &[u8]) {
+        // The cache is only an optimisation, so failing to write to it isn't an error.
+        let _ = self.write(key, body);
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = self.invalidate(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path()).unwrap();
+
+        assert_eq!(cache.get("a"), None);
+        cache.insert("a", b"body");
+        assert_eq!(cache.get("a").as_deref(), Some(&b"body"[..]));
+
+        // Responses persist between instances.
+        let cache = DiskCache::new(dir.path()).unwrap();
+        assert_eq!(cache.get("a").as_deref(), Some(&b"body"[..]));
+
+        cache.invalidate("a").unwrap();
+        assert_eq!(cache.get("a"), None);
+        cache.invalidate("a").unwrap();
+    }
+
+    #[test]
+    fn test_disk_cache_max_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path()).unwrap().max_size(0);
+
+        cache.insert("a", b"body");
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn test_disk_cache_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path()).unwrap();
+
+        cache.insert("a", b"body");
+        cache.insert("b", b"body");
+        cache
+            .invalidate_older_than(Duration::from_secs(60))
+            .unwrap();
+        assert!(cache.get("a").is_some());
+
+        cache.clear().unwrap();
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), None);
+    }
+}
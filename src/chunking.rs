@@ -0,0 +1,352 @@
+use crate::models::AsAttrs;
+use crate::{Filter, SortType};
+use chrono::{DateTime, Duration, Utc};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::ops::Div;
+
+/// The number of items we'd like each bucket of a chunked query to contain, by default.
+pub(crate) const DEFAULT_BUCKET_VOLUME: i64 = 25;
+
+/// The most buckets a single query is chunked into, by default.
+pub(crate) const DEFAULT_MAX_BUCKETS: i64 = 200;
+
+/// The most distinct values asked for when chunking a query sorted by score or number
+/// of comments.  Values outside of the most common ones still fall into some bucket,
+/// it's just a bigger one.
+pub(crate) const VALUE_HISTOGRAM_SIZE: usize = 1000;
+
+/// An inclusive range of values of the field a query is sorted by, either end of which
+/// may be unbounded.
+pub(crate) type ValueRange = (Option<i64>, Option<i64>);
+
+/// Determines whether a [`Client`] chunks large queries into buckets, see
+/// [`ClientBuilder::chunking`].
+///
+/// [`Client`]: crate::Client
+/// [`ClientBuilder::chunking`]: crate::ClientBuilder::chunking
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chunking {
+    /// Queries sorted by creation date with more results than the
+    /// [chunk threshold](crate::ClientBuilder::chunk_threshold) are split into buckets
+    /// which are fetched concurrently.
+    Auto,
+    /// Every query is paginated through sequentially, without asking PushShift for its
+    /// metadata first.
+    Disabled,
+}
+
+impl Chunking {
+    pub fn new() -> Self {
+        Self::Auto
+    }
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits the range between `oldest` and `newest` into buckets of equal width.
+pub(crate) fn chunked(
+    total: i64,
+    volume: i64,
+    max_buckets: i64,
+    oldest: DateTime<Utc>,
+    newest: DateTime<Utc>,
+) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> {
+    // We make the (somewhat suspicious) assumption that posts are evenly distributed
+    // through time.  Chunk the problem down into buckets where, assuming posts _are_
+    // evenly distributed, we expect around `volume` posts. We also put up upper bound
+    // of `max_buckets` chunks to avoid creating an enormous amount of streams.
+    let buckets = (total / volume.max(1)).min(max_buckets - 1);
+    let bucket_width = (newest - oldest).div((buckets + 1) as i32).num_seconds();
+
+    (0..=buckets).map(move |c| {
+        let l = if c == 0 {
+            oldest
+        } else {
+            oldest + Duration::seconds((c * bucket_width) + 1)
+        };
+        let r = if c == buckets {
+            newest
+        } else {
+            oldest + Duration::seconds((c + 1) * bucket_width)
+        };
+        (l, r)
+    })
+}
+
+/// Returns the `created_utc` histogram frequency used to estimate how items are
+/// distributed between `oldest` and `newest`.
+pub(crate) fn histogram_frequency(oldest: DateTime<Utc>, newest: DateTime<Utc>) -> &'static str {
+    let span = newest - oldest;
+    if span <= Duration::days(14) {
+        "hour"
+    } else if span <= Duration::days(2 * 365) {
+        "day"
+    } else if span <= Duration::days(15 * 365) {
+        "week"
+    } else {
+        "month"
+    }
+}
+
+/// Splits the range between `oldest` and `newest` into buckets which each contain
+/// roughly `volume` items, according to the given `histogram`.
+///
+/// Each histogram entry gives the start of a bin together with the number of items
+/// in that bin; bins are assumed to extend up to the start of the next bin.  A bin
+/// is never split, so a bucket may contain more items than desired if a single bin does.
+pub(crate) fn density_chunked(
+    histogram: &[(DateTime<Utc>, i64)],
+    total: i64,
+    volume: i64,
+    max_buckets: i64,
+    oldest: DateTime<Utc>,
+    newest: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let target = (total / max_buckets).max(volume);
+
+    let mut buckets = Vec::new();
+    let mut start = oldest;
+    let mut count = 0;
+
+    for (i, (_, doc_count)) in histogram.iter().enumerate() {
+        count += doc_count;
+        if count < target {
+            continue;
+        }
+
+        let end = histogram
+            .get(i + 1)
+            .map_or(newest, |(next, _)| *next - Duration::seconds(1))
+            .min(newest);
+        if end >= start {
+            buckets.push((start, end));
+            start = end + Duration::seconds(1);
+            count = 0;
+        }
+    }
+
+    if start <= newest {
+        buckets.push((start, newest));
+    }
+
+    buckets
+}
+
+/// Splits the values in `histogram` into contiguous ranges which each contain roughly
+/// `volume` items, in ascending order.
+///
+/// Each histogram entry gives a value together with the number of items with that
+/// value, ordered by value.  The first and last ranges are unbounded, so that every
+/// item falls into a range, even if its value is missing from the histogram.
+pub(crate) fn value_chunked(
+    histogram: &[(i64, i64)],
+    total: i64,
+    volume: i64,
+    max_buckets: i64,
+) -> Vec<ValueRange> {
+    let target = (total / max_buckets.max(1)).max(volume);
+
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut count = 0;
+
+    for (i, &(value, doc_count)) in histogram.iter().enumerate() {
+        if doc_count >= target {
+            // A value with a bucket's worth of items gets a bucket of its own, whose
+            // items don't need sorting and so needn't be held in memory.
+            if start.is_none_or(|start| start < value) {
+                ranges.push((start, Some(value.saturating_sub(1))));
+            }
+            ranges.push((Some(value), Some(value)));
+            start = Some(value.saturating_add(1));
+            count = 0;
+            continue;
+        }
+
+        count += doc_count;
+        if count < target {
+            continue;
+        }
+        if let Some((next, _)) = histogram.get(i + 1) {
+            ranges.push((start, Some(next - 1)));
+            start = Some(*next);
+            count = 0;
+        }
+    }
+
+    ranges.push((start, None));
+    ranges
+}
+
+/// Returns whether every item in a range has the same value.
+pub(crate) fn is_single_value(range: ValueRange) -> bool {
+    matches!(range, (Some(low), Some(high)) if low == high)
+}
+
+/// Returns the values in both of two ranges.
+fn intersect(a: ValueRange, b: ValueRange) -> ValueRange {
+    let low = match (a.0, b.0) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    };
+    let high = match (a.1, b.1) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    (low, high)
+}
+
+/// Restricts a query sorted by score or number of comments to the items in a range of
+/// values, as well as whatever range it was already restricted to.
+pub(crate) fn restrict_values(mut filter: Filter, range: ValueRange) -> Filter {
+    filter.value_range = sort_field(&filter.sort_type).map(|field| {
+        let range = match filter.value_range {
+            Some((_, outer)) => intersect(outer, range),
+            None => range,
+        };
+        (field, range)
+    });
+    filter
+}
+
+/// Returns the query parameters restricting the given field to a range of values.
+///
+/// PushShift only takes a single comparison per parameter value, so each bound is
+/// passed as a parameter of its own.
+pub(crate) fn value_range_pairs(name: &str, range: ValueRange) -> Vec<(String, String)> {
+    let low = range.0.map(|low| format!(">{}", low.saturating_sub(1)));
+    let high = range.1.map(|high| format!("<{}", high.saturating_add(1)));
+    low.into_iter()
+        .chain(high)
+        .map(|bound| (name.to_string(), bound))
+        .collect()
+}
+
+/// Shuffles `items` with the given seed, in the same order on every platform, see
+/// [`Filter::shuffled`].
+pub(crate) fn shuffle_seeded<T>(items: &mut [T], seed: u64) {
+    items.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+}
+
+/// Returns the name of the field a query is sorted by, if it isn't sorted by date.
+pub(crate) fn sort_field(sort_type: &SortType) -> Option<&'static str> {
+    match sort_type {
+        SortType::CreatedDate => None,
+        SortType::Score => Some("score"),
+        SortType::NumComments => Some("num_comments"),
+    }
+}
+
+/// Returns the value of the field an item is sorted by, for queries which aren't sorted
+/// by date.
+pub(crate) fn sort_value<T: AsAttrs>(item: &T, sort_type: &SortType) -> Option<i64> {
+    match sort_type {
+        SortType::CreatedDate => None,
+        SortType::Score => Some(item.attrs().score.into()),
+        SortType::NumComments => item.num_comments(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(ts: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(ts, 0).unwrap()
+    }
+
+    #[test]
+    fn test_chunked_caps_buckets() {
+        let buckets: Vec<_> = chunked(10_000, 25, 10, date(0), date(100_000)).collect();
+        assert_eq!(buckets.len(), 10);
+        assert_eq!(buckets[0].0, date(0));
+        assert_eq!(buckets[9].1, date(100_000));
+
+        let buckets: Vec<_> = chunked(100, 25, 10, date(0), date(100_000)).collect();
+        assert_eq!(buckets.len(), 5);
+    }
+
+    #[test]
+    fn test_density_chunked_follows_histogram() {
+        // A quiet first day, followed by a very busy second day.
+        let histogram = vec![
+            (date(0), 10),
+            (date(3600), 5),
+            (date(86_400), 30),
+            (date(90_000), 30),
+            (date(93_600), 30),
+        ];
+        let buckets = density_chunked(
+            &histogram,
+            105,
+            25,
+            DEFAULT_MAX_BUCKETS,
+            date(0),
+            date(100_000),
+        );
+
+        assert_eq!(
+            buckets,
+            vec![
+                (date(0), date(89_999)),
+                (date(90_000), date(93_599)),
+                (date(93_600), date(100_000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_chunked() {
+        let histogram = vec![(0, 10), (1, 30), (2, 5), (5, 20), (100, 2)];
+        assert_eq!(
+            value_chunked(&histogram, 67, 25, DEFAULT_MAX_BUCKETS),
+            vec![
+                (None, Some(0)),
+                (Some(1), Some(1)),
+                (Some(2), Some(99)),
+                (Some(100), None)
+            ]
+        );
+
+        // Values with a bucket's worth of items get buckets of their own.
+        assert_eq!(
+            value_chunked(&[(3, 40), (7, 40)], 80, 25, DEFAULT_MAX_BUCKETS),
+            vec![
+                (None, Some(2)),
+                (Some(3), Some(3)),
+                (Some(4), Some(6)),
+                (Some(7), Some(7)),
+                (Some(8), None)
+            ]
+        );
+
+        assert_eq!(
+            value_chunked(&[], 0, 25, DEFAULT_MAX_BUCKETS),
+            vec![(None, None)]
+        );
+    }
+
+    #[test]
+    fn test_shuffle_seeded_is_reproducible() {
+        // Shuffled queries promise the same order for the same seed, so this mustn't
+        // change between platforms or releases.
+        let mut items: Vec<_> = (0..10).collect();
+        shuffle_seeded(&mut items, 42);
+        assert_eq!(items, vec![0, 3, 5, 7, 9, 4, 8, 1, 6, 2]);
+    }
+
+    #[test]
+    fn test_density_chunked_covers_range() {
+        let histogram = vec![(date(0), 1), (date(10), 1)];
+        let buckets = density_chunked(&histogram, 2, 25, DEFAULT_MAX_BUCKETS, date(5), date(20));
+        assert_eq!(buckets, vec![(date(5), date(20))]);
+    }
+}
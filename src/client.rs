@@ -1,52 +1,181 @@
-use crate::models::{AsAttrs, Comment, Post};
-use crate::{Filter, SortType};
+use crate::aggregations::{top_authors, Activity, AuthorActivity, AuthorSummary};
+use crate::ancestors::Ancestors;
+use crate::auth::Auth;
+use crate::authors::AuthorCache;
+use crate::batch::{BatchQuery, BatchResult, DEFAULT_BATCH_CONCURRENCY};
+use crate::breaker::{CircuitBreaker, CircuitState};
+use crate::budget::{RetryBudget, StreamBudget};
+use crate::buffer::ItemBuffer;
+use crate::cache::Cache;
+use crate::chunking::{
+    chunked, density_chunked, histogram_frequency, is_single_value, restrict_values,
+    shuffle_seeded, sort_field, sort_value, value_chunked, value_range_pairs, Chunking, ValueRange,
+    DEFAULT_BUCKET_VOLUME, DEFAULT_MAX_BUCKETS, VALUE_HISTOGRAM_SIZE,
+};
+use crate::concurrency;
+use crate::control::StreamControl;
+use crate::error::snippet;
+use crate::failover::Failover;
+use crate::filter::query_pairs;
+use crate::health::Health;
+use crate::hooks::Hooks;
+use crate::limiter::{
+    parse_retry_after, Limiter, Meta, Priority, RateLimitBackend, RateLimitMode, RateLimitStatus,
+};
+use crate::models::{
+    AsAttrs, Author, Comment, CommentId, Content, Metadata, ParentId, Post, PostId,
+};
+use crate::overview::{AuthorStatus, UserOverview};
+use crate::pagination::{Cursor, PageQuery};
+use crate::plan::{requests_for, QueryPlan};
+use crate::pool::{ClientPool, Member};
+use crate::response::{parse_response, PushShiftResponse, ResponseParser};
+use crate::sample::sample_dates;
+use crate::search::Searched;
+use crate::skipped::{SkipReason, Skipped};
+use crate::stream::{
+    arranged, bounded, dedup_boundaries, dedup_boundary_pages, in_order, in_order_by,
+    merge_by_date, prefetch, take_items, with_deadline, with_idle_limit, with_idle_timeout,
+    CommentStream, FailedRange, PostStream, Tracking,
+};
+use crate::subreddits::{self, SubredditName};
+use crate::timing::{PageTiming, PageTimings, WaitTimer};
+use crate::transfer::{Body, TransferCounter, TransferStats};
+#[cfg(feature = "warc")]
+use crate::warc::ResponseHead;
+use crate::{ApiVersion, Endpoint, Error, Filter, SortDirection, SortType};
 use async_stream::stream;
-use chrono::{DateTime, Duration, Utc};
-use futures::stream::{self, select_all, Stream, StreamExt};
-use governor::{Quota, RateLimiter};
-use once_cell::sync::OnceCell;
-use reqwest::{IntoUrl, Url};
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::future::{self, BoxFuture, FutureExt, WeakShared};
+use futures::stream::{self, Stream, StreamExt};
+use rand::seq::IteratorRandom;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER, USER_AGENT};
+#[cfg(feature = "compression")]
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use reqwest::{StatusCode, Url};
 use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
-use std::num::NonZeroU32;
-use std::ops::Div;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::mem;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{timeout_at, Instant};
 
-type PSRateLimiter = RateLimiter<
-    governor::state::NotKeyed,
-    governor::state::InMemoryState,
-    governor::clock::DefaultClock,
-    governor::middleware::NoOpMiddleware,
->;
-
-const BATCH_SIZE: i64 = 50;
-const DESIRED_BUCKET_VOLUME: i64 = 25;
-
-/// A global rate limiter, used to limit PS API queries to 1 per second.
-fn rate_limiter() -> &'static PSRateLimiter {
-    static PS_RATE_LIMITER: OnceCell<PSRateLimiter> = OnceCell::new();
-    PS_RATE_LIMITER
-        .get_or_init(|| RateLimiter::direct(Quota::per_second(NonZeroU32::new(1).unwrap())))
-}
+const DEFAULT_BATCH_SIZE: i64 = 50;
 
-#[derive(Deserialize, Debug)]
-struct PushShiftMetadata {
-    total_results: i64,
+/// What a client found out about the dates of the items matching a query, see
+/// [`Client::get_date_bounds`].
+enum DateBounds {
+    /// Few enough items match that the query isn't worth chunking.
+    Few,
+    /// The total number of matching items, along with the dates of the oldest and the
+    /// newest of them.
+    Many(i64, DateTime<Utc>, DateTime<Utc>),
+    /// A request failed (or didn't return metadata), so nothing is known.
+    Unknown,
 }
 
-#[derive(Deserialize, Debug)]
-struct PushShiftResponse<T> {
-    data: Vec<T>,
-    metadata: Option<PushShiftMetadata>,
-}
+/// The number of times a request rejected with `429 Too Many Requests` is retried.
+const MAX_RATE_LIMIT_RETRIES: usize = 5;
 
 #[derive(Clone, Serialize)]
 struct PushShiftQueryParams<'a> {
     #[serde(flatten)]
     inner: &'a Filter,
-    sort: Option<&'static str>,
     limit: i64,
     metadata: bool,
+    aggs: Option<&'static str>,
+    agg_size: Option<usize>,
+    frequency: Option<&'static str>,
+}
+
+impl<'a> PushShiftQueryParams<'a> {
+    fn new(inner: &'a Filter, limit: i64) -> Self {
+        Self {
+            inner,
+            limit,
+            metadata: false,
+            aggs: None,
+            agg_size: None,
+            frequency: None,
+        }
+    }
+}
+
+impl PushShiftQueryParams<'_> {
+    /// Serializes these parameters into query pairs, using the parameter names
+    /// expected by the given API version.
+    fn to_pairs(&self, version: ApiVersion) -> Vec<(String, String)> {
+        let mut pairs = query_pairs(self, version);
+        if let Some((field, range)) = self.inner.value_range {
+            pairs.extend(value_range_pairs(version.param_name(field), range));
+        }
+        pairs
+    }
+
+    /// Serializes these parameters into a query whose date and ID bounds can be changed
+    /// without serializing the rest of them again.
+    fn to_page_query(&self, version: ApiVersion) -> PageQuery {
+        // Serialize a placeholder for each bound, so that they keep their places.
+        let mut inner = self.inner.clone();
+        inner.before = Some(DateTime::<Utc>::UNIX_EPOCH);
+        inner.after = Some(DateTime::<Utc>::UNIX_EPOCH);
+        inner.before_relative = None;
+        inner.after_relative = None;
+        inner.before_id = Some(String::new());
+        inner.after_id = Some(String::new());
+        let pairs = PushShiftQueryParams {
+            inner: &inner,
+            ..self.clone()
+        }
+        .to_pairs(version);
+
+        PageQuery::new(
+            pairs,
+            version.param_name("before"),
+            version.param_name("after"),
+            self.inner.before,
+            self.inner.after,
+        )
+        .with_ids(self.inner.before_id.clone(), self.inner.after_id.clone())
+        .with_relative(
+            self.inner.before_relative.clone(),
+            self.inner.after_relative.clone(),
+        )
+    }
+}
+
+/// A query to paginate through: its filter, shared between the buckets of a chunked
+/// query, together with its parameters serialized for the first page.
+#[derive(Clone)]
+pub(crate) struct PageRequest {
+    pub(crate) filter: Arc<Filter>,
+    pub(crate) query: PageQuery,
+    batch_size: i64,
+}
+
+impl PageRequest {
+    /// Returns a request for the items in one bucket of this query.
+    pub(crate) fn bounded(&self, after: DateTime<Utc>, before: DateTime<Utc>) -> Self {
+        Self {
+            filter: self.filter.clone(),
+            query: self.query.bounded(after, before),
+            batch_size: self.batch_size,
+        }
+    }
+
+    /// Returns the `(after, before)` date range of the request.
+    fn range(&self) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        (
+            self.query.after.or_else(|| self.filter.after_date()),
+            self.query.before.or_else(|| self.filter.before_date()),
+        )
+    }
 }
 
 /// An opinionated asynchronous `Client` to make requests to the PushShift API.
@@ -57,16 +186,109 @@ struct PushShiftQueryParams<'a> {
 #[derive(Clone)]
 pub struct Client {
     client: reqwest::Client,
-    limiter: &'static PSRateLimiter,
+    config: Arc<Config>,
+    limiter: Arc<Limiter>,
+    in_flight: Arc<InFlight>,
+    /// Pauses this client's requests, for a stream created with a [`StreamControl`].
+    control: Option<StreamControl>,
+    /// The retries spent by the stream this client is making requests for, if it has a
+    /// retry budget.
+    retry_budget: Option<Arc<StreamBudget>>,
+    /// The room for items fetched but not yet consumed by the stream this client is
+    /// making requests for, if buffering is capped.
+    item_buffer: Option<Arc<ItemBuffer>>,
+    /// Records the failures and progress of the stream this client is making requests
+    /// for.
+    tracking: Option<Tracking>,
+    /// Adds up the time spent waiting for the rate limiter by the request this client
+    /// is making.
+    waited: Option<Arc<WaitTimer>>,
+    /// Collects the timings of the pages fetched for a timed page stream.
+    page_timings: Option<PageTimings>,
+}
+
+/// Requests which are currently being made by a client (or its clones), keyed by their
+/// URL and query string, along with the stream they're made for (see
+/// [`Client::stream_key`]).  Identical concurrent requests wait on the same response,
+/// rather than each using up the rate limit.
+type InFlight = Mutex<HashMap<(String, StreamKey), WeakShared<BoxFuture<'static, Option<Bytes>>>>>;
+
+/// Identifies the pause control and retry budget a request is made with, if any.
+type StreamKey = (Option<usize>, Option<usize>);
+
+/// The body of a response, as returned by [`Client::fetch_coalesced`].
+struct Fetched {
+    body: Bytes,
+    /// Whether the body should be cached once it's been checked: it wasn't served from
+    /// the cache, and wasn't shared with another request which will cache it.  Only
+    /// then was the body passed to the request's [`BodySink`] as it arrived.
+    uncached: bool,
+}
+
+/// Is passed each chunk of a response's body as it arrives, once decompressed.
+type BodySink = Box<dyn FnMut(&[u8]) + Send>;
+
+type ErrorHandler = Arc<dyn Fn(&Error) + Send + Sync>;
+type SkipHandler = Arc<dyn Fn(&Skipped) + Send + Sync>;
+
+/// Whether a failed request suggests that PushShift is down, as opposed to it rejecting
+/// this particular request.
+enum Outage {
+    Down,
+    Up,
+    /// PushShift is up, but kept rejecting the request for exceeding its rate limit.
+    RateLimited,
+}
+
+/// Configuration shared between clones of a [`Client`].
+struct Config {
+    api_version: ApiVersion,
+    auth: Auth,
+    headers: HeaderMap,
+    /// Sends requests through middleware instead of the plain `reqwest` client.
+    #[cfg(feature = "middleware")]
+    middleware: Option<reqwest_middleware::ClientWithMiddleware>,
+    authors: AuthorCache,
+    max_concurrent_buckets: Option<usize>,
+    batch_size: i64,
+    bucket_volume: i64,
+    max_buckets: i64,
+    chunk_threshold: Option<i64>,
+    chunking: Chunking,
+    error_handler: Option<ErrorHandler>,
+    skip_handler: Option<SkipHandler>,
+    hooks: Option<Arc<dyn Hooks>>,
+    strict: bool,
+    timeout: Option<Duration>,
+    stream_deadline: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    cache: Option<Arc<dyn Cache>>,
+    incomplete_retries: usize,
+    retry_budget: Option<RetryBudget>,
+    prefetch_pages: usize,
+    max_buffered_items: Option<usize>,
+    circuit_breaker: Option<CircuitBreaker>,
+    failover: Option<Failover>,
+    pool: Option<ClientPool>,
+    id_pagination: bool,
+    discover_rate_limit: bool,
+    /// Asks for responses to be compressed with zstd.
+    #[cfg(feature = "compression")]
+    compression: bool,
+    transfer: TransferCounter,
+    /// Records requests and their responses.
+    #[cfg(feature = "warc")]
+    warc: Option<Arc<crate::warc::WarcWriter>>,
 }
 
 impl Client {
     /// Creates a new client for the PushShift API.
     ///
     /// # Note
-    /// Requests to the PushShift API are rate limited using a global rate limiter.
-    /// The first time a client is constructed a request is made to PushShift to
-    /// determine the global rate limit.
+    /// Requests to the PushShift API are rate limited by a rate limiter owned by
+    /// the client (and shared with its clones), at the rate PushShift advertises (see
+    /// [`ClientBuilder::discover_rate_limit`]).  Use [`ClientBuilder::rate_limit_mode`]
+    /// to share a single rate limiter between all clients in the process.
     ///
     /// # Example
     /// ```rust
@@ -94,9 +316,138 @@ impl Client {
     /// # }
     /// ```
     pub fn with_client(client: reqwest::Client) -> Self {
-        Self {
-            client,
-            limiter: rate_limiter(),
+        Self::builder().reqwest_client(client).build()
+    }
+
+    /// Creates a new [`Client`] which sends requests through the given
+    /// [`reqwest_middleware`] client.
+    ///
+    /// See [`ClientBuilder::middleware_client`].
+    #[cfg(feature = "middleware")]
+    pub fn with_middleware(client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        Self::builder().middleware_client(client).build()
+    }
+
+    /// Creates a [`ClientBuilder`] to configure a [`Client`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{ApiVersion, Client};
+    ///
+    /// let client = Client::builder()
+    ///     .api_version(ApiVersion::Authenticated)
+    ///     .auth_token("my-token")
+    ///     .build();
+    /// ```
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Returns the state of the client's circuit breaker, which is always
+    /// [`CircuitState::Closed`] unless one is set with [`ClientBuilder::circuit_breaker`].
+    pub fn circuit_state(&self) -> CircuitState {
+        self.config
+            .circuit_breaker
+            .as_ref()
+            .map_or(CircuitState::Closed, CircuitBreaker::state)
+    }
+
+    /// Returns the base URL of the backend requests are currently made to, which is
+    /// PushShift's unless [failover](ClientBuilder::failover) is configured.
+    pub fn backend(&self) -> Url {
+        match &self.config.failover {
+            Some(failover) => failover.current(),
+            None => self.config.api_version.base_url(),
+        }
+    }
+
+    /// Returns how much of the client's rate limit is available, e.g. to decide whether
+    /// to start another stream now or defer it.
+    ///
+    /// The rate limiter is shared by clones of the client (and by every client in
+    /// [`RateLimitMode::Shared`]), so their requests are counted too.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    /// let status = client.rate_limit_status();
+    /// println!("next request in {:?}, {} queued", status.wait, status.queued);
+    /// ```
+    pub fn rate_limit_status(&self) -> RateLimitStatus {
+        self.limiter.status()
+    }
+
+    /// Returns how many response bodies the client (and its clones) has downloaded,
+    /// and their size before and after decompression.
+    ///
+    /// Responses are only compressed with the `compression` feature enabled.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    /// let stats = client.transfer_stats();
+    /// println!("{} bytes downloaded", stats.transferred_bytes);
+    /// ```
+    pub fn transfer_stats(&self) -> TransferStats {
+        self.config.transfer.stats()
+    }
+
+    /// Checks that the backend is up, returning how long it took to answer along with
+    /// the shard health and rate limit it reports, e.g. before starting a job.
+    ///
+    /// The check bypasses the rate limiter, so it isn't held up by the client's other
+    /// requests.  Returns `None` if the request fails; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    /// match client.ping().await {
+    ///     Some(health) if health.is_healthy() => println!("up ({:?})", health.latency),
+    ///     Some(_) => println!("degraded"),
+    ///     None => println!("down"),
+    /// }
+    /// # }
+    /// ```
+    pub async fn ping(&self) -> Option<Health> {
+        let (url, request) = self.meta_request();
+        let start = std::time::Instant::now();
+        let response = match self.send(request).await {
+            Ok(response) => response,
+            Err(error) => {
+                self.report(error);
+                return None;
+            }
+        };
+        if !response.status().is_success() {
+            self.report(status_error(response).await);
+            return None;
+        }
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(error) => {
+                self.report(Error::Http(error));
+                return None;
+            }
+        };
+        let latency = start.elapsed();
+
+        match serde_json::from_slice::<Meta>(&body) {
+            Ok(meta) => Some(Health::new(meta, latency)),
+            Err(error) => {
+                self.report(Error::InvalidResponse {
+                    url,
+                    error,
+                    body: snippet(&body),
+                });
+                None
+            }
         }
     }
 
@@ -108,7 +459,7 @@ impl Client {
     ///
     /// This method chunks the search space up into many different buckets which
     /// are independently queried - as such there is no guarantee of the order
-    /// that results are returned.
+    /// that results are returned, unless [`Filter::ordered`] is set.
     ///
     /// # Example
     /// ```rust,no_run
@@ -125,9 +476,15 @@ impl Client {
     /// }
     /// # }
     /// ```
-    pub async fn get_comments(&self, filter: Filter) -> Pin<Box<dyn Stream<Item = Comment> + '_>> {
-        let url = Url::parse("https://api.pushshift.io/reddit/comment/search/").unwrap();
-        self._stream(url, filter).await
+    pub async fn get_comments(&self, filter: Filter) -> CommentStream {
+        let url = self.config.api_version.url(Endpoint::Comments);
+        let (client, tracking) = self.tracking();
+        CommentStream::tracked(
+            client
+                ._stream(url, filter, self.config.max_concurrent_buckets)
+                .await,
+            tracking,
+        )
     }
 
     /// Returns a [`Stream`] of [`Post`]'s matching the given query filter.
@@ -138,7 +495,7 @@ impl Client {
     ///
     /// This method chunks the search space up into many different buckets which
     /// are independently queried - as such there is no guarantee of the order
-    /// that results are returned.
+    /// that results are returned, unless [`Filter::ordered`] is set.
     ///
     /// # Example
     /// ```rust,no_run
@@ -159,186 +516,3994 @@ impl Client {
     /// }
     /// # }
     /// ```
-    pub async fn get_posts(&self, filter: Filter) -> Pin<Box<dyn Stream<Item = Post> + '_>> {
-        let url = Url::parse("https://api.pushshift.io/reddit/submission/search/").unwrap();
-        self._stream(url, filter).await
+    pub async fn get_posts(&self, filter: Filter) -> PostStream {
+        let url = self.config.api_version.url(Endpoint::Posts);
+        let (client, tracking) = self.tracking();
+        PostStream::tracked(
+            client
+                ._stream(url, filter, self.config.max_concurrent_buckets)
+                .await,
+            tracking,
+        )
     }
 
-    /// Creates a [`Stream`], either chunked or unchunked depending on the context.
-    async fn _stream<T: 'static + DeserializeOwned + AsAttrs>(
+    /// Returns a [`Stream`] of pages of [`Post`]'s matching the given query filter.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// This yields the same posts as [`Client::get_posts`], but a page at a time, as they're
+    /// returned by PushShift.  This is convenient for writing posts in batches, e.g. to a
+    /// database.  Pages contain at most [`ClientBuilder::batch_size`] posts, and are never
+    /// empty.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let mut pages = client.get_post_pages(Filter::new().subreddit("rust")).await;
+    /// while let Some(page) = pages.next().await {
+    ///     println!("inserting {} posts", page.len());
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_post_pages(
         &self,
-        url: Url,
         filter: Filter,
-    ) -> Pin<Box<dyn Stream<Item = T> + '_>> {
-        if matches!(filter.sort_type, SortType::CreatedDate) {
-            // TODO: for now we only implement chunked requests for filters
-            //       that sort by date; we'd need a similar sort of logic
-            //       to chunk requests based on the other attributes.
-            if let Some((total, oldest, newest)) =
-                self.get_date_bounds::<Post>(url.clone(), &filter).await
-            {
-                return Box::pin(
-                    select_all(chunked(total, oldest, newest).map(|(l, r)| {
-                        Box::pin(self.paginated(url.clone(), filter.clone().before(r).after(l)))
-                    }))
-                    .flat_map(stream::iter),
-                );
-            }
-        }
-
-        Box::pin(self.paginated(url, filter).flat_map(stream::iter))
+    ) -> Pin<Box<dyn Stream<Item = Vec<Post>> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Posts);
+        self._pages(url, filter, self.config.max_concurrent_buckets)
+            .await
     }
 
-    /// Performs a single request to the PushShift API, returning the deserialized result.
-    async fn _get<T: DeserializeOwned>(
+    /// Returns a [`Stream`] of pages of [`Comment`]'s matching the given query filter.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// See [`Client::get_post_pages`].
+    pub async fn get_comment_pages(
         &self,
-        url: Url,
-        params: PushShiftQueryParams<'_>,
-    ) -> Option<PushShiftResponse<T>> {
-        self.limiter.until_ready().await;
-        let response = self.client.get(url).query(&params).send().await;
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = Vec<Comment>> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Comments);
+        self._pages(url, filter, self.config.max_concurrent_buckets)
+            .await
+    }
 
-        if let Ok(response) = response {
-            if let Ok(parsed_response) = response.json::<PushShiftResponse<T>>().await {
-                return Some(parsed_response);
-            }
-        }
+    /// Returns a [`Stream`] of pages of [`Post`]'s matching the given query filter, each
+    /// with the [timings](PageTiming) of the requests made for it.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// This yields the same pages as [`Client::get_post_pages`].  Each page comes with
+    /// the timings of the pages PushShift returned since the previous page was yielded,
+    /// which unless the query is [chunked](ClientBuilder::chunking) or
+    /// [ordered](Filter::ordered) is just the page itself.  Hooks see every page's timing
+    /// too, through [`Hooks::on_page`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let mut pages = client.get_timed_post_pages(Filter::new().subreddit("rust")).await;
+    /// while let Some((page, timings)) = pages.next().await {
+    ///     for timing in timings {
+    ///         println!(
+    ///             "{} posts: waited {:?}, requested {:?}, PushShift took {:?}",
+    ///             timing.items,
+    ///             timing.rate_limit_wait,
+    ///             timing.request_time(),
+    ///             timing.took,
+    ///         );
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_timed_post_pages(
+        &self,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (Vec<Post>, Vec<PageTiming>)> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Posts);
+        self._timed_pages(url, filter).await
+    }
 
-        None
+    /// Returns a [`Stream`] of pages of [`Comment`]'s matching the given query filter,
+    /// each with the [timings](PageTiming) of the requests made for it.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// See [`Client::get_timed_post_pages`].
+    pub async fn get_timed_comment_pages(
+        &self,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (Vec<Comment>, Vec<PageTiming>)> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Comments);
+        self._timed_pages(url, filter).await
     }
 
-    /// Determines the oldest and most recent dates of items corresponding to this query,
-    /// together with the total number of matching items.
-    async fn get_date_bounds<'a, T: DeserializeOwned + AsAttrs>(
+    /// Creates a [`Stream`] of pages, handing out the timings of the pages fetched for
+    /// it along with each page.
+    async fn _timed_pages<T: Item>(
         &self,
         url: Url,
-        params: &Filter,
-    ) -> Option<(i64, DateTime<Utc>, DateTime<Utc>)> {
-        let newest: PushShiftResponse<T> = self
-            ._get(
-                url.clone(),
-                PushShiftQueryParams {
-                    inner: params,
-                    sort: Some("desc"),
-                    limit: 1,
-                    metadata: true,
-                },
-            )
-            .await?;
-
-        // Only want to do this for queries with lots of results.
-        let total_results = if let Some(metadata) = &newest.metadata {
-            metadata.total_results
-        } else {
-            return None;
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (Vec<T>, Vec<PageTiming>)> + Send>> {
+        let timings = PageTimings::default();
+        let client = Self {
+            page_timings: Some(timings.clone()),
+            ..self.clone()
         };
-
-        if total_results <= BATCH_SIZE {
-            return None;
-        }
-
-        let oldest: PushShiftResponse<T> = self
-            ._get(
-                url,
-                PushShiftQueryParams {
-                    inner: params,
-                    sort: Some("asc"),
-                    limit: 1,
-                    metadata: false,
-                },
-            )
-            .await?;
-        Some((
-            total_results,
-            oldest.data[0].attrs().date,
-            newest.data[0].attrs().date,
-        ))
+        let pages = client
+            ._pages(url, filter, self.config.max_concurrent_buckets)
+            .await;
+        Box::pin(pages.map(move |page| (page, std::mem::take(&mut *timings.lock().unwrap()))))
     }
 
-    /// Returns paginated items from the given URL together with the given query parameters.
-    /// Any errors that occur during this process will be ignored.
-    fn paginated<T, U>(&self, url: U, mut params: Filter) -> impl Stream<Item = Vec<T>> + '_
-    where
-        T: 'static + DeserializeOwned + AsAttrs,
-        U: IntoUrl,
-    {
-        let url = url.into_url().unwrap();
-
-        stream! {
-            loop {
-                let inner_params = PushShiftQueryParams {
-                    inner: &params,
-                    sort: None,
-                    limit: BATCH_SIZE,
-                    metadata: false,
-                };
-
-                if let Some(parsed_response) = self._get::<T>(url.clone(), inner_params).await {
-                    if let Some(last_content) = parsed_response.data.last() {
-                        params = params.before(last_content.attrs().date.clone());
-                    } else {
-                        break;
-                    }
+    /// Drives a stream of [`Post`]'s matching the given filter on a background task,
+    /// returning a channel through which the posts are received.
+    ///
+    /// Unlike the streams returned by [`Client::get_posts`], posts are fetched ahead of
+    /// the receiver without it being polled, which makes it easy to fan posts out to a
+    /// pool of workers.  At most `capacity` posts are buffered; the background task
+    /// stops once the receiver is dropped.
+    ///
+    /// # Panics
+    /// Panics if called from outside a Tokio runtime, which the task is spawned onto.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let mut posts = client.spawn_posts(Filter::new().subreddit("rust"), 100);
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some(post) = posts.recv().await {
+    ///         println!("post: {}", post.comment_url);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn spawn_posts(&self, filter: Filter, capacity: usize) -> mpsc::Receiver<Post> {
+        self.spawn(Endpoint::Posts, filter, capacity)
+    }
 
-                    // If we got less than the batch size of results then there's
-                    // not going to be any more results in the next query.
-                    let should_break = parsed_response.data.len() < BATCH_SIZE as usize;
+    /// Drives a stream of [`Comment`]'s matching the given filter on a background task,
+    /// returning a channel through which the comments are received.
+    ///
+    /// See [`Client::spawn_posts`].
+    pub fn spawn_comments(&self, filter: Filter, capacity: usize) -> mpsc::Receiver<Comment> {
+        self.spawn(Endpoint::Comments, filter, capacity)
+    }
 
-                    yield parsed_response.data;
+    /// Drives a stream of items from the given endpoint on a new task, sending them to
+    /// the returned channel.
+    fn spawn<T: Item>(
+        &self,
+        endpoint: Endpoint,
+        filter: Filter,
+        capacity: usize,
+    ) -> mpsc::Receiver<T> {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let client = self.clone();
 
-                    if should_break {
-                        break;
-                    } else {
-                        continue;
-                    }
+        tokio::spawn(async move {
+            let url = client.config.api_version.url(endpoint);
+            let mut items = client
+                ._stream::<T>(url, filter, client.config.max_concurrent_buckets)
+                .await;
+            while let Some(item) = items.next().await {
+                if tx.send(item).await.is_err() {
+                    break;
                 }
-
-                break;
             }
-        }
+        });
+
+        rx
     }
-}
 
-impl Default for Client {
-    fn default() -> Self {
-        Self::new()
+    /// Returns a [`Stream`] of [`Post`]'s matching the given query filter, together with
+    /// a [`StreamControl`] which can pause and resume it.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// Pausing the stream frees up the rate limit (which is shared by clones of the
+    /// client) for other queries, e.g. interactive ones, without losing the stream's
+    /// progress.  Otherwise this is the same as [`Client::get_posts`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let (control, mut posts) = client.get_posts_controlled(Filter::new().subreddit("rust"));
+    ///
+    /// while let Some(post) = posts.next().await {
+    ///     println!("{}", post.comment_url);
+    ///     # let user_is_searching = false;
+    ///     if user_is_searching {
+    ///         control.pause();
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn get_posts_controlled(
+        &self,
+        filter: Filter,
+    ) -> (StreamControl, Pin<Box<dyn Stream<Item = Post> + Send>>) {
+        self.controlled(Endpoint::Posts, filter)
     }
-}
 
-fn chunked(
-    total: i64,
-    oldest: DateTime<Utc>,
-    newest: DateTime<Utc>,
-) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> {
-    // We make the (somewhat suspicious) assumption that posts are evenly distributed
-    // through time.  Chunk the problem down into buckets where, assuming posts _are_
-    // evenly distributed, we expect around 50 posts. We also put up upper bound
-    // of 200 chunks to avoid creating an enormous amount of streams.
-    let buckets = (total / DESIRED_BUCKET_VOLUME).min(200);
-    let bucket_width = (newest - oldest).div((buckets + 1) as i32).num_seconds();
-
-    (0..=buckets).map(move |c| {
-        let l = if c == 0 {
-            oldest
-        } else {
-            oldest + Duration::seconds((c * bucket_width) + 1)
-        };
-        let r = if c == buckets {
-            newest
-        } else {
-            oldest + Duration::seconds((c + 1) * bucket_width)
+    /// Returns a [`Stream`] of [`Comment`]'s matching the given query filter, together
+    /// with a [`StreamControl`] which can pause and resume it.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// See [`Client::get_posts_controlled`].
+    pub fn get_comments_controlled(
+        &self,
+        filter: Filter,
+    ) -> (StreamControl, Pin<Box<dyn Stream<Item = Comment> + Send>>) {
+        self.controlled(Endpoint::Comments, filter)
+    }
+
+    /// Creates a stream of items from the given endpoint whose requests are made by a
+    /// clone of the client, which waits while the stream is paused.
+    fn controlled<T: Item>(
+        &self,
+        endpoint: Endpoint,
+        filter: Filter,
+    ) -> (StreamControl, Pin<Box<dyn Stream<Item = T> + Send>>) {
+        let control = StreamControl::new();
+        let client = Self {
+            control: Some(control.clone()),
+            ..self.clone()
         };
-        (l, r)
-    })
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let items = stream! {
+            let url = client.config.api_version.url(endpoint);
+            let mut items = client
+                ._stream::<T>(url, filter, client.config.max_concurrent_buckets)
+                .await;
+            while let Some(item) = items.next().await {
+                yield item;
+            }
+        };
+        (control, Box::pin(items))
+    }
 
-    #[test]
-    fn test_client_is_send_and_sync() {
-        fn is_send_and_sync<T: Send + Sync>() {}
-        is_send_and_sync::<Client>();
+    /// Returns a [`Stream`] of all [`Content`] - both posts and comments - matching the
+    /// given query filter.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// # Ordering
+    ///
+    /// Posts and comments are queried independently (sharing the client's rate limiter)
+    /// and interleaved as they arrive.  If [`Filter::ordered`] is set the two are instead
+    /// merged by creation date.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::models::Content;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// // Everything /u/reddit has ever written.
+    /// let mut content = client.get_content(Filter::new().author("reddit")).await;
+    ///
+    /// while let Some(content) = content.next().await {
+    ///     match content {
+    ///         Content::Post(post) => println!("post: {}", post.comment_url),
+    ///         Content::Comment(comment) => println!("comment: {}", comment.body),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_content(&self, filter: Filter) -> Pin<Box<dyn Stream<Item = Content> + Send>> {
+        let ordered = filter.ordered;
+        let direction = filter.sort;
+
+        let posts = self
+            .get_posts(filter.clone())
+            .await
+            .map(Content::from)
+            .boxed();
+        let comments = self.get_comments(filter).await.map(Content::from).boxed();
+
+        if ordered {
+            Box::pin(merge_by_date(vec![posts, comments], direction))
+        } else {
+            Box::pin(stream::select(posts, comments))
+        }
+    }
+
+    /// Returns a [`Stream`] of an approximately uniform random sample of `n` of the
+    /// [`Post`]'s matching the given query filter, without fetching every match.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// Random dates are drawn according to PushShift's histogram of when the matching
+    /// posts were created, and the post created at or just before each date is fetched,
+    /// with one request per post.  Posts created after a quiet spell are a little more
+    /// likely to be drawn, and a post drawn twice is only yielded once, so the stream
+    /// may contain slightly fewer than `n` posts.  Queries with few enough matches to
+    /// fetch in a page or two are fetched in full and sampled exactly.  If the number of
+    /// matches can't be looked up, the failure is reported to the
+    /// [error handler](ClientBuilder::on_error) and the stream is empty.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let mut posts = client.sample_posts(Filter::new().subreddit("rust"), 100).await;
+    /// while let Some(post) = posts.next().await {
+    ///     println!("post: {}", post.comment_url);
+    /// }
+    /// # }
+    /// ```
+    pub async fn sample_posts(&self, filter: Filter, n: usize) -> PostStream {
+        PostStream::new(self.sample(Endpoint::Posts, filter, n).await)
+    }
+
+    /// Returns a [`Stream`] of an approximately uniform random sample of `n` of the
+    /// [`Comment`]'s matching the given query filter.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// See [`Client::sample_posts`].
+    pub async fn sample_comments(&self, filter: Filter, n: usize) -> CommentStream {
+        CommentStream::new(self.sample(Endpoint::Comments, filter, n).await)
+    }
+
+    /// Creates a [`Stream`] of a random sample of the items matching a filter.
+    async fn sample<T: Item>(
+        &self,
+        endpoint: Endpoint,
+        filter: Filter,
+        n: usize,
+    ) -> Pin<Box<dyn Stream<Item = T> + Send>> {
+        let url = self.config.api_version.url(endpoint);
+        let mut filter = filter.sort_type(SortType::CreatedDate);
+        filter.limit = None;
+
+        let (oldest, newest) = match self.get_date_bounds::<T>(url.clone(), &filter).await {
+            DateBounds::Many(_, oldest, newest) => (oldest, newest),
+            // Without knowing how many items there are, fetching all of them could
+            // mean downloading millions; the failure has already been reported.
+            DateBounds::Unknown => return Box::pin(stream::empty()),
+            DateBounds::Few => {
+                // There are few enough items to fetch all of them.
+                let items: Vec<T> = self
+                    ._stream(url, filter, self.config.max_concurrent_buckets)
+                    .await
+                    .collect()
+                    .await;
+                let sample = items
+                    .into_iter()
+                    .choose_multiple(&mut rand::thread_rng(), n);
+                return Box::pin(stream::iter(sample));
+            }
+        };
+        let histogram = self
+            .get_histogram(url.clone(), &filter, oldest, newest)
+            .await;
+        let dates = sample_dates(
+            &mut rand::thread_rng(),
+            histogram.as_deref(),
+            oldest,
+            newest,
+            n,
+        );
+
+        let client = self.clone();
+        let concurrency = self.config.max_concurrent_buckets.unwrap_or(n).max(1);
+        let mut seen = HashSet::new();
+        let items = stream::iter(dates)
+            .map(move |date| {
+                let (client, url) = (client.clone(), url.clone());
+                let filter = filter.clone().before(date).sort(SortDirection::Desc);
+                async move {
+                    let response = client
+                        ._get::<T>(url, PushShiftQueryParams::new(&filter, 1))
+                        .await?;
+                    response.data.into_iter().next()
+                }
+            })
+            .buffer_unordered(concurrency)
+            .filter_map(future::ready)
+            .filter(move |item| future::ready(seen.insert(item.attrs().id.clone())));
+        Box::pin(items)
+    }
+
+    /// Returns PushShift's metadata for a query for posts matching the given filter,
+    /// including the total number of matching posts and how many shards answered.
+    ///
+    /// Returns `None` if the request fails; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// if let Some(metadata) = client.post_metadata(Filter::new().subreddit("rust")).await {
+    ///     if !metadata.is_complete() {
+    ///         println!("only some shards answered, so the total may be too low");
+    ///     }
+    ///     println!("{} posts", metadata.total_results);
+    /// }
+    /// # }
+    /// ```
+    pub async fn post_metadata(&self, filter: Filter) -> Option<Metadata> {
+        self.metadata(Endpoint::Posts, &filter).await
+    }
+
+    /// Returns PushShift's metadata for a query for comments matching the given filter.
+    ///
+    /// See [`Client::post_metadata`].
+    pub async fn comment_metadata(&self, filter: Filter) -> Option<Metadata> {
+        self.metadata(Endpoint::Comments, &filter).await
+    }
+
+    /// Returns the most items returned by a single request.
+    pub(crate) fn batch_size(&self) -> i64 {
+        self.config.batch_size
+    }
+
+    pub(crate) async fn metadata(&self, endpoint: Endpoint, filter: &Filter) -> Option<Metadata> {
+        let url = self.config.api_version.url(endpoint);
+        let response: PushShiftResponse<serde_json::Value> = self
+            ._get(
+                url,
+                PushShiftQueryParams {
+                    metadata: true,
+                    ..PushShiftQueryParams::new(filter, 0)
+                },
+            )
+            .await?;
+        response.metadata
+    }
+
+    /// Works out the requests [`Client::get_posts`] would make for the given filter,
+    /// without fetching any posts.
+    ///
+    /// Planning a chunked query needs a few requests of its own, to find out how many
+    /// posts match and how they're distributed over time; these are the only requests
+    /// made.  This is useful to estimate how long a query takes, or to check how a
+    /// filter is serialized.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let plan = client.plan_posts(Filter::new().subreddit("rust")).await;
+    /// println!(
+    ///     "{} buckets, about {:?}",
+    ///     plan.requests.len(),
+    ///     plan.estimated_duration()
+    /// );
+    /// # }
+    /// ```
+    pub async fn plan_posts(&self, filter: Filter) -> QueryPlan {
+        self.plan::<Post>(Endpoint::Posts, filter).await
+    }
+
+    /// Works out the requests [`Client::get_comments`] would make for the given filter,
+    /// without fetching any comments.
+    ///
+    /// See [`Client::plan_posts`].
+    pub async fn plan_comments(&self, filter: Filter) -> QueryPlan {
+        self.plan::<Comment>(Endpoint::Comments, filter).await
+    }
+
+    /// Returns the URL of the request the client would make for the first page of items
+    /// matching the given filter, e.g. to log the query or replay it by hand.
+    ///
+    /// The query string uses the parameter names of the configured [`ApiVersion`].  Note
+    /// that [`Client::get_posts`] usually splits a search into several date ranges, each
+    /// with its own request; see [`Client::plan_posts`] for the full set.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{Client, Endpoint, Filter};
+    ///
+    /// let client = Client::new();
+    /// let url = client.url_for(Endpoint::Posts, &Filter::new().subreddit("rust"));
+    /// assert!(url.query().unwrap().contains("subreddit=rust"));
+    /// ```
+    pub fn url_for(&self, endpoint: Endpoint, filter: &Filter) -> Url {
+        let limit = filter.limit.map_or(self.config.batch_size, |limit| {
+            limit.clamp(1, self.config.batch_size)
+        });
+        self.request_url(self.config.api_version.url(endpoint), filter, limit)
+    }
+
+    /// Returns the most recent [`Post`] matching the given filter, using a single request.
+    ///
+    /// Returns `None` if there is no such post, or if the request fails; see
+    /// [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// if let Some(post) = client.latest_post(Filter::new().subreddit("rust")).await {
+    ///     println!("latest post: {}", post.comment_url);
+    /// }
+    /// # }
+    /// ```
+    pub async fn latest_post(&self, filter: Filter) -> Option<Post> {
+        self.first(Endpoint::Posts, filter, SortDirection::Desc)
+            .await
+    }
+
+    /// Returns the oldest [`Post`] matching the given filter, using a single request.
+    ///
+    /// Returns `None` if there is no such post, or if the request fails.
+    pub async fn oldest_post(&self, filter: Filter) -> Option<Post> {
+        self.first(Endpoint::Posts, filter, SortDirection::Asc)
+            .await
+    }
+
+    /// Returns the most recent [`Comment`] matching the given filter, using a single request.
+    ///
+    /// Returns `None` if there is no such comment, or if the request fails.
+    pub async fn latest_comment(&self, filter: Filter) -> Option<Comment> {
+        self.first(Endpoint::Comments, filter, SortDirection::Desc)
+            .await
+    }
+
+    /// Returns the oldest [`Comment`] matching the given filter, using a single request.
+    ///
+    /// Returns `None` if there is no such comment, or if the request fails.
+    pub async fn oldest_comment(&self, filter: Filter) -> Option<Comment> {
+        self.first(Endpoint::Comments, filter, SortDirection::Asc)
+            .await
+    }
+
+    /// Returns whether any post or comment matches the given filter.
+    ///
+    /// Posts are checked first, so this takes a single request if a post matches, and
+    /// two otherwise.  Returns `None` if a request fails; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let filter = Filter::new().author("reddit").subreddit("rust");
+    ///
+    /// if client.exists(filter).await == Some(true) {
+    ///     println!("/u/reddit has been active in /r/rust");
+    /// }
+    /// # }
+    /// ```
+    pub async fn exists(&self, filter: Filter) -> Option<bool> {
+        for endpoint in [Endpoint::Posts, Endpoint::Comments] {
+            let url = self.config.api_version.url(endpoint);
+            let response: PushShiftResponse<serde_json::Value> = self
+                ._get(url, PushShiftQueryParams::new(&filter, 1))
+                .await?;
+            if !response.data.is_empty() {
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+
+    /// Runs many small one-shot queries, such as counts and lookups of the latest item,
+    /// returning a [`Stream`] of their results as they complete, together with the index
+    /// of the query each result is for.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// Queries are started in the order they're given, with at most
+    /// [`ClientBuilder::max_concurrent_buckets`] (or 4) in flight at once, so their
+    /// requests take their turn on the rate limiter alongside any streams the client is
+    /// running, rather than crowding them out.  Each query's requests are made with the
+    /// [priority](Filter::priority) of its filter.  A query's result is `None` if a
+    /// request for it fails; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{BatchQuery, BatchResult, Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let subreddits = ["rust", "golang", "python"];
+    ///
+    /// let queries = subreddits.iter().map(|subreddit| {
+    ///     BatchQuery::CountPosts(Filter::new().subreddit(*subreddit).last_days(1))
+    /// });
+    /// let mut results = client.run_batch(queries);
+    ///
+    /// while let Some((i, result)) = results.next().await {
+    ///     if let Some(BatchResult::Count(count)) = result {
+    ///         println!("/r/{}: {} posts today", subreddits[i], count);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn run_batch<I>(
+        &self,
+        queries: I,
+    ) -> Pin<Box<dyn Stream<Item = (usize, Option<BatchResult>)> + Send>>
+    where
+        I: IntoIterator<Item = BatchQuery>,
+    {
+        let queries: Vec<BatchQuery> = queries.into_iter().collect();
+        let concurrency = self
+            .config
+            .max_concurrent_buckets
+            .unwrap_or(DEFAULT_BATCH_CONCURRENCY)
+            .max(1);
+        let client = self.clone();
+        let results = stream::iter(queries.into_iter().enumerate())
+            .map(move |(i, query)| {
+                let client = client.clone();
+                async move { (i, client.run_query(query).await) }
+            })
+            .buffer_unordered(concurrency);
+        Box::pin(results)
+    }
+
+    /// Runs a single query of a batch, returning `None` if a request fails.
+    async fn run_query(&self, query: BatchQuery) -> Option<BatchResult> {
+        let result = match query {
+            BatchQuery::CountPosts(filter) => {
+                BatchResult::Count(self.metadata(Endpoint::Posts, &filter).await?.total_results)
+            }
+            BatchQuery::CountComments(filter) => BatchResult::Count(
+                self.metadata(Endpoint::Comments, &filter)
+                    .await?
+                    .total_results,
+            ),
+            BatchQuery::LatestPost(filter) => BatchResult::Post(
+                self.try_first(Endpoint::Posts, filter, SortDirection::Desc)
+                    .await?,
+            ),
+            BatchQuery::LatestComment(filter) => BatchResult::Comment(
+                self.try_first(Endpoint::Comments, filter, SortDirection::Desc)
+                    .await?,
+            ),
+            BatchQuery::Exists(filter) => BatchResult::Exists(self.exists(filter).await?),
+        };
+        Some(result)
+    }
+
+    /// Returns the IDs of every comment PushShift has archived on the given post.
+    ///
+    /// Unlike searching for the post's comments, this doesn't depend on the comments
+    /// having been indexed for search yet, so it can be used to check that a thread
+    /// was retrieved completely.  Returns `None` if the request fails; see
+    /// [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::models::PostId;
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    /// let post_id: PostId = "t3_abc123".parse().unwrap();
+    ///
+    /// if let Some(ids) = client.get_comment_ids(&post_id).await {
+    ///     println!("{} comments", ids.len());
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_comment_ids(&self, post_id: &PostId) -> Option<Vec<CommentId>> {
+        let url = self.config.api_version.comment_ids_url(post_id);
+        let (fetched, response) = self
+            .fetch_response::<CommentId>(&url, Priority::default(), |error| self.report(error))
+            .await?;
+        self.cache_body(&url, &fetched);
+        Some(response.data)
+    }
+
+    /// Returns the comments above the given comment, up to the post it was made on.
+    ///
+    /// Each comment only records its direct parent, so this takes a request for each
+    /// level of the thread above the comment; the post is looked up alongside the first
+    /// of them.  To find the ancestors of several comments, use
+    /// [`Client::get_ancestors_of`], which shares these requests between them.
+    /// Returns `None` if a request fails, see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let comment = client.latest_comment(Filter::new().subreddit("rust")).await.unwrap();
+    /// if let Some(ancestors) = client.get_ancestors(&comment).await {
+    ///     if let Some(post) = &ancestors.post {
+    ///         println!("{}", post.title);
+    ///     }
+    ///     for parent in &ancestors.comments {
+    ///         println!("> {}", parent.body);
+    ///     }
+    ///     println!("{}", comment.body);
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_ancestors(&self, comment: &Comment) -> Option<Ancestors> {
+        let ancestors = self.get_ancestors_of(std::slice::from_ref(comment)).await?;
+        ancestors.into_iter().next()
+    }
+
+    /// Returns the ancestors of each of the given comments, in the same order; see
+    /// [`Client::get_ancestors`].
+    ///
+    /// The comments are walked up their threads together, so each level takes a single
+    /// request for the parents of every comment which hasn't reached its post yet (or
+    /// one for each [batch](ApiVersion::max_batch_size) of them).  The posts are looked
+    /// up in one request alongside the first level.  Returns `None` if a request fails.
+    pub async fn get_ancestors_of(&self, comments: &[Comment]) -> Option<Vec<Ancestors>> {
+        let posts = async {
+            let mut ids: Vec<PostId> = comments.iter().filter_map(Comment::post_id).collect();
+            ids.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+            ids.dedup_by(|a, b| a.as_str() == b.as_str());
+            let ids: Vec<&str> = ids.iter().map(PostId::as_str).collect();
+            let posts = self.get_by_ids::<Post>(Endpoint::Posts, &ids).await?;
+            Some(
+                posts
+                    .into_iter()
+                    .map(|post| (post.attrs.id.clone(), post))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+
+        let chains = async {
+            // The ancestors found so far for each comment, along with its next parent,
+            // or whether it reached its post once it's finished.
+            let mut chains: Vec<(Vec<Comment>, Result<CommentId, bool>)> = comments
+                .iter()
+                .map(|comment| match &comment.parent_id {
+                    Some(ParentId::Comment(id)) => (Vec::new(), Ok(id.clone())),
+                    parent => (Vec::new(), Err(parent.is_some())),
+                })
+                .collect();
+            loop {
+                for ((found, next), comment) in chains.iter_mut().zip(comments) {
+                    if let Ok(id) = next {
+                        // Guard against a malformed thread whose comments reply to each
+                        // other.
+                        if id.as_str() == comment.attrs.id
+                            || found.iter().any(|c| c.attrs.id == id.as_str())
+                        {
+                            *next = Err(false);
+                        }
+                    }
+                }
+                let mut ids: Vec<&str> = chains
+                    .iter()
+                    .filter_map(|(_, next)| next.as_ref().ok().map(CommentId::as_str))
+                    .collect();
+                if ids.is_empty() {
+                    return Some(chains);
+                }
+                ids.sort_unstable();
+                ids.dedup();
+                let parents: HashMap<String, Comment> = self
+                    .get_by_ids::<Comment>(Endpoint::Comments, &ids)
+                    .await?
+                    .into_iter()
+                    .map(|comment| (comment.attrs.id.clone(), comment))
+                    .collect();
+
+                for (found, next) in &mut chains {
+                    let Ok(id) = next else { continue };
+                    *next = match parents.get(id.as_str()) {
+                        Some(parent) => {
+                            found.push(parent.clone());
+                            match &parent.parent_id {
+                                Some(ParentId::Comment(id)) => Ok(id.clone()),
+                                grandparent => Err(grandparent.is_some()),
+                            }
+                        }
+                        None => Err(false),
+                    };
+                }
+            }
+        };
+
+        let (chains, posts) = match future::join(chains, posts).await {
+            (Some(chains), Some(posts)) => (chains, posts),
+            _ => return None,
+        };
+        let ancestors = chains
+            .into_iter()
+            .zip(comments)
+            .map(|((mut comments, reached_post), comment)| {
+                let post = comment
+                    .post_id()
+                    .and_then(|id| posts.get(id.as_str()).cloned());
+                comments.reverse();
+                Ancestors {
+                    complete: matches!(reached_post, Err(true)) && post.is_some(),
+                    comments,
+                    post,
+                }
+            })
+            .collect();
+        Some(ancestors)
+    }
+
+    /// Looks up the items with the given IDs (without their prefixes), in batches of as
+    /// many IDs as can be requested at once.  IDs which PushShift doesn't have are
+    /// skipped.
+    async fn get_by_ids<T: DeserializeOwned + Serialize + Send + 'static>(
+        &self,
+        endpoint: Endpoint,
+        ids: &[&str],
+    ) -> Option<Vec<T>> {
+        let version = self.config.api_version;
+        let mut items = Vec::with_capacity(ids.len());
+        for batch in ids.chunks(version.max_batch_size() as usize) {
+            let mut url = version.url(endpoint);
+            url.query_pairs_mut()
+                .append_pair("ids", &batch.join(","))
+                .append_pair(version.param_name("limit"), &batch.len().to_string());
+            let response: PushShiftResponse<T> = self.get_url(url, Priority::default()).await?;
+            items.extend(response.data);
+        }
+        Some(items)
+    }
+
+    /// Returns the first item matching the given filter, when sorted by creation date
+    /// in the given direction.
+    async fn first<T: DeserializeOwned + Serialize + Send + 'static>(
+        &self,
+        endpoint: Endpoint,
+        filter: Filter,
+        direction: SortDirection,
+    ) -> Option<T> {
+        self.try_first(endpoint, filter, direction).await.flatten()
+    }
+
+    /// Like [`Client::first`], but returning `Some(None)` if nothing matches the filter,
+    /// and `None` only if the request fails.
+    async fn try_first<T: DeserializeOwned + Serialize + Send + 'static>(
+        &self,
+        endpoint: Endpoint,
+        filter: Filter,
+        direction: SortDirection,
+    ) -> Option<Option<T>> {
+        let url = self.config.api_version.url(endpoint);
+        let filter = filter.sort_type(SortType::CreatedDate).sort(direction);
+        let response: PushShiftResponse<T> = self
+            ._get(url, PushShiftQueryParams::new(&filter, 1))
+            .await?;
+        Some(response.data.into_iter().next())
+    }
+
+    /// Returns the fullname (e.g. `t2_1w72`) of the author with the given username.
+    ///
+    /// Unlike usernames, fullnames stay the same if an account is renamed, so they can
+    /// be used to follow an author over time; see [`Filter::author_id`].  The author is
+    /// looked up from their most recent content, and is cached by the client (and its
+    /// clones).  Returns `None` if the author can't be found, or a request fails.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// if let Some(id) = client.resolve_author("reddit").await {
+    ///     let comments = client.get_comments(Filter::new().author_id(id)).await;
+    /// }
+    /// # }
+    /// ```
+    pub async fn resolve_author(&self, name: &str) -> Option<String> {
+        if let Some(id) = self.config.authors.id(name) {
+            return Some(id);
+        }
+        let (_, id) = self.find_author(Filter::new().author(name)).await?;
+        Some(id)
+    }
+
+    /// Returns the most recent username of the author with the given fullname, e.g.
+    /// `t2_1w72`.
+    ///
+    /// See [`Client::resolve_author`].
+    pub async fn resolve_author_id(&self, fullname: &str) -> Option<String> {
+        if let Some(name) = self.config.authors.name(fullname) {
+            return Some(name);
+        }
+        let (name, _) = self.find_author(Filter::new().author_id(fullname)).await?;
+        Some(name)
+    }
+
+    /// Finds the username and fullname of the author of the most recent comment (or
+    /// failing that, post) matching the given filter, caching them.
+    async fn find_author(&self, filter: Filter) -> Option<(String, String)> {
+        self.try_find_author(filter).await.flatten()
+    }
+
+    /// Like [`Client::find_author`], but returning `Some(None)` if the author can't be
+    /// found, and `None` only if a request fails.
+    async fn try_find_author(&self, filter: Filter) -> Option<Option<(String, String)>> {
+        let filter = filter.fields(["author", "author_fullname"]);
+        let author = match self
+            .try_first::<Comment>(Endpoint::Comments, filter.clone(), SortDirection::Desc)
+            .await?
+        {
+            Some(comment) => comment.author,
+            None => match self
+                .try_first::<Post>(Endpoint::Posts, filter, SortDirection::Desc)
+                .await?
+            {
+                Some(post) => post.author,
+                None => return Some(None),
+            },
+        };
+
+        match author {
+            Author::Known { id: Some(id), name } => {
+                self.config.authors.insert(&name, &id);
+                Some(Some((name, id)))
+            }
+            _ => Some(None),
+        }
+    }
+
+    /// Returns an overview of the activity of the given author, without fetching all of
+    /// their posts and comments.
+    ///
+    /// This uses PushShift's aggregations to count the author's posts and comments in
+    /// each subreddit, and so only takes a few requests.  Returns `None` if any of the
+    /// requests fail; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    ///
+    /// if let Some(summary) = client.author_summary("reddit").await {
+    ///     for activity in summary.subreddits.iter().take(5) {
+    ///         println!("/r/{}: {}", activity.subreddit, activity.total());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn author_summary(&self, name: &str) -> Option<AuthorSummary> {
+        let filter = Filter::new().author(name);
+        let posts = self.activity::<Post>(Endpoint::Posts, &filter).await?;
+        let comments = self
+            .activity::<Comment>(Endpoint::Comments, &filter)
+            .await?;
+        Some(AuthorSummary::new(name.to_string(), posts, comments))
+    }
+
+    /// Returns the posts and comments of the given author, together with how many of
+    /// each they've made and when they were first and last active.
+    ///
+    /// The totals and dates are found using PushShift's aggregations, in a few requests
+    /// made up front; the posts and comments are fetched as the streams are polled.
+    /// An author without any content still gets an (empty) overview, whose
+    /// [status](UserOverview::status) says whether they're known to exist.  Returns
+    /// `None` if any of the up front requests fail; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::models::Content;
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    ///
+    /// if let Some(overview) = client.get_user_overview("reddit").await {
+    ///     println!("{} posts and comments", overview.total());
+    ///
+    ///     let mut content = overview.content().take(10).boxed();
+    ///     while let Some(content) = content.next().await {
+    ///         match content {
+    ///             Content::Post(post) => println!("post: {}", post.title),
+    ///             Content::Comment(comment) => println!("comment: {}", comment.body),
+    ///         }
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_user_overview(&self, name: &str) -> Option<UserOverview> {
+        let filter = Filter::new().author(name);
+        let (posts, comments) = future::join(
+            self.activity::<Post>(Endpoint::Posts, &filter),
+            self.activity::<Comment>(Endpoint::Comments, &filter),
+        )
+        .await;
+        let (posts, comments) = (posts?, comments?);
+        let status = self
+            .author_status(name, posts.total + comments.total)
+            .await?;
+
+        // The streams are ordered so that they can be interleaved by date.
+        let filter = filter.sort(SortDirection::Desc).ordered();
+        Some(UserOverview {
+            name: name.to_string(),
+            posts: self.get_posts(filter.clone()).await,
+            comments: self.get_comments(filter).await,
+            post_count: posts.total,
+            comment_count: comments.total,
+            first_activity: posts.oldest.into_iter().chain(comments.oldest).min(),
+            last_activity: posts.newest.into_iter().chain(comments.newest).max(),
+            status,
+        })
+    }
+
+    /// Works out what's known of an author who has made `total` posts and comments,
+    /// returning `None` if a request fails.
+    async fn author_status(&self, name: &str, total: i64) -> Option<AuthorStatus> {
+        if total > 0 {
+            return Some(AuthorStatus::Active);
+        }
+        let id = match self.config.authors.id(name) {
+            Some(id) => id,
+            None => return Some(AuthorStatus::NotFound),
+        };
+        let status = match self.try_find_author(Filter::new().author_id(&id)).await? {
+            Some((current, _)) if !current.eq_ignore_ascii_case(name) => {
+                AuthorStatus::Renamed { id, name: current }
+            }
+            _ => AuthorStatus::NoContent { id },
+        };
+        Some(status)
+    }
+
+    /// Returns whether the author with the given username is known to exist, i.e. if
+    /// PushShift has any of their posts or comments, or the client has
+    /// [resolved](Client::resolve_author) them before.
+    ///
+    /// PushShift only indexes content, so `false` means that the account may not
+    /// exist, may never have posted, or may be shadowbanned; see
+    /// [`UserOverview::status`] to tell some of these apart.  Returns `None` if a
+    /// request fails; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::new();
+    /// if client.author_exists("reddit").await == Some(false) {
+    ///     println!("no trace of /u/reddit");
+    /// }
+    /// # }
+    /// ```
+    pub async fn author_exists(&self, name: &str) -> Option<bool> {
+        if self.config.authors.id(name).is_some() {
+            return Some(true);
+        }
+        self.exists(Filter::new().author(name)).await
+    }
+
+    /// Returns the `n` authors who've made the most posts and comments matching the given
+    /// filter, most active first.
+    ///
+    /// This uses PushShift's aggregations, so it only takes two requests.  The `n` most
+    /// prolific post authors and comment authors are combined, so an author who isn't
+    /// among the top `n` for either posts or comments may be missing.  Returns `None` if
+    /// either of the requests fail; see [`ClientBuilder::on_error`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let filter = Filter::new().subreddit("rust").last_days(7);
+    ///
+    /// for author in client.top_authors(filter, 10).await.unwrap_or_default() {
+    ///     println!("/u/{}: {}", author.author, author.total());
+    /// }
+    /// # }
+    /// ```
+    pub async fn top_authors(&self, filter: Filter, n: usize) -> Option<Vec<AuthorActivity>> {
+        let posts = self.author_counts(Endpoint::Posts, &filter, n).await?;
+        let comments = self.author_counts(Endpoint::Comments, &filter, n).await?;
+        Some(top_authors(posts, comments, n))
+    }
+
+    /// Counts the items matching the given filter made by each of the `n` most
+    /// prolific authors.
+    async fn author_counts(
+        &self,
+        endpoint: Endpoint,
+        filter: &Filter,
+        n: usize,
+    ) -> Option<Vec<(String, i64)>> {
+        let url = self.config.api_version.url(endpoint);
+        let response: PushShiftResponse<serde_json::Value> = self
+            ._get(
+                url,
+                PushShiftQueryParams {
+                    aggs: Some("author"),
+                    // Leave room for `[deleted]`, which isn't a real author.
+                    agg_size: Some(n + 1),
+                    ..PushShiftQueryParams::new(filter, 0)
+                },
+            )
+            .await?;
+        Some(response.terms("author"))
+    }
+
+    /// Counts the items matching the given filter in each subreddit, along with the dates
+    /// of the oldest and newest of them.
+    async fn activity<T: Item>(&self, endpoint: Endpoint, filter: &Filter) -> Option<Activity> {
+        let url = self.config.api_version.url(endpoint);
+
+        let newest_params = filter.clone().sort(SortDirection::Desc);
+        let newest: PushShiftResponse<T> = self
+            ._get(
+                url.clone(),
+                PushShiftQueryParams {
+                    metadata: true,
+                    aggs: Some("subreddit"),
+                    ..PushShiftQueryParams::new(&newest_params, 1)
+                },
+            )
+            .await?;
+
+        let mut activity = Activity {
+            total: newest
+                .metadata
+                .as_ref()
+                .map_or(newest.data.len() as i64, |metadata| metadata.total_results),
+            newest: newest.data.first().map(|item| item.attrs().date),
+            subreddits: newest.terms("subreddit"),
+            ..Activity::default()
+        };
+
+        activity.oldest = if activity.total > 1 {
+            let oldest_params = filter.clone().sort(SortDirection::Asc);
+            let oldest: PushShiftResponse<T> = self
+                ._get(url, PushShiftQueryParams::new(&oldest_params, 1))
+                .await?;
+            oldest.data.first().map(|item| item.attrs().date)
+        } else {
+            activity.newest
+        };
+
+        Some(activity)
+    }
+
+    /// Returns a [`Stream`] of items matching the given query filter from an endpoint
+    /// which the client doesn't otherwise support, e.g. a new or mirror-specific route.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// The endpoint is queried in the same way as [`Client::get_posts`], so its items
+    /// must have an `id` and `created_utc`.  The path is relative to the base URL of the
+    /// client's [API version](ClientBuilder::api_version), and is sent to the current
+    /// backend like any other request, but can also be a full URL.
+    /// [Strict mode](ClientBuilder::strict) doesn't apply to these items, as the client
+    /// doesn't know which fields `T` captures.
+    ///
+    /// # Panics
+    /// Panics if the path isn't a valid URL path.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Comment {
+    ///     body: String,
+    /// }
+    ///
+    /// let client = Client::new();
+    /// let mut comments = client
+    ///     .search::<Comment>("/reddit/comment/search/", Filter::new().author("reddit"))
+    ///     .await;
+    ///
+    /// while let Some(comment) = comments.next().await {
+    ///     println!("{}", comment.body);
+    /// }
+    /// # }
+    /// ```
+    pub async fn search<T>(
+        &self,
+        path: &str,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = T> + Send>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let url = self
+            .config
+            .api_version
+            .base_url()
+            .join(path)
+            .expect("invalid search path");
+        let items = self
+            ._stream::<Searched<T>>(url, filter, self.config.max_concurrent_buckets)
+            .await;
+        Box::pin(items.map(|searched| searched.item))
+    }
+
+    /// Returns a [`Stream`] of [`Post`]'s in the subreddits matching the filter's
+    /// [subreddit pattern](Filter::subreddit_matching), together with the name of the
+    /// subreddit each post came from.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// Like [`Client::get_posts_multi`], the subreddits are queried concurrently, sharing
+    /// the client's rate limiter fairly.  Without a pattern, this queries the filter's
+    /// subreddit, if it has one.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let filter = Filter::new().subreddit_matching("ask*").last_days(1);
+    /// let mut posts = client.get_posts_matching(filter).await;
+    ///
+    /// while let Some((subreddit, post)) = posts.next().await {
+    ///     println!("/r/{}: {}", subreddit, post.comment_url);
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_posts_matching(
+        &self,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (String, Post)> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Posts);
+        self._stream_matching(url, filter).await
+    }
+
+    /// Returns a [`Stream`] of [`Comment`]'s in the subreddits matching the filter's
+    /// [subreddit pattern](Filter::subreddit_matching), together with the name of the
+    /// subreddit each comment came from.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// See [`Client::get_posts_matching`] for details.
+    pub async fn get_comments_matching(
+        &self,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (String, Comment)> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Comments);
+        self._stream_matching(url, filter).await
+    }
+
+    /// Creates a [`Stream`] of the items in each subreddit matching the filter's
+    /// subreddit pattern, tagged with the subreddit they came from.
+    async fn _stream_matching<T: Item>(
+        &self,
+        url: Url,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (String, T)> + Send>> {
+        let pages = self._pages_matching(url, filter).await;
+        Box::pin(pages.flat_map(|(subreddit, page)| {
+            stream::iter(page.into_iter().map(move |item| (subreddit.clone(), item)))
+        }))
+    }
+
+    /// Returns a [`Stream`] of [`Post`]'s matching any of the given query filters, together
+    /// with the index of the filter each post matched.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// The filters are queried concurrently, sharing the client's rate limiter fairly:
+    /// each filter has at most one request waiting on the rate limiter at a time, and
+    /// requests are permitted in the order they were made.  A post matching several
+    /// filters is yielded once for each of them.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let subreddits = ["rust", "golang", "python"];
+    ///
+    /// let filters = subreddits
+    ///     .iter()
+    ///     .map(|subreddit| Filter::new().subreddit(*subreddit).last_days(1))
+    ///     .collect();
+    /// let mut posts = client.get_posts_multi(filters);
+    ///
+    /// while let Some((i, post)) = posts.next().await {
+    ///     println!("/r/{}: {}", subreddits[i], post.comment_url);
+    /// }
+    /// # }
+    /// ```
+    pub fn get_posts_multi(
+        &self,
+        filters: Vec<Filter>,
+    ) -> Pin<Box<dyn Stream<Item = (usize, Post)> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Posts);
+        self._stream_multi(url, filters)
+    }
+
+    /// Returns a [`Stream`] of [`Comment`]'s matching any of the given query filters,
+    /// together with the index of the filter each comment matched.
+    ///
+    /// [`Stream`]: futures::Stream
+    ///
+    /// See [`Client::get_posts_multi`] for how the filters share the rate limiter.
+    pub fn get_comments_multi(
+        &self,
+        filters: Vec<Filter>,
+    ) -> Pin<Box<dyn Stream<Item = (usize, Comment)> + Send>> {
+        let url = self.config.api_version.url(Endpoint::Comments);
+        self._stream_multi(url, filters)
+    }
+
+    /// Creates a [`Stream`] for each filter, merging them as items arrive.
+    fn _stream_multi<T: Item>(
+        &self,
+        url: Url,
+        filters: Vec<Filter>,
+    ) -> Pin<Box<dyn Stream<Item = (usize, T)> + Send>> {
+        let streams = filters.into_iter().enumerate().map(|(i, filter)| {
+            // Fetching one bucket at a time limits each filter to a single request in
+            // the rate limiter's queue, so that they take turns.
+            let (client, url) = (self.clone(), url.clone());
+            stream::once(async move { client._stream(url, filter, Some(1)).await })
+                .flatten()
+                .map(move |item| (i, item))
+                .boxed()
+        });
+
+        Box::pin(stream::select_all(streams))
+    }
+
+    /// Creates a [`Stream`], ending it early if the configured stream deadline passes.
+    async fn _stream<T: Item>(
+        &self,
+        url: Url,
+        filter: Filter,
+        max_concurrent_buckets: Option<usize>,
+    ) -> Pin<Box<dyn Stream<Item = T> + Send>> {
+        let pages = self._pages(url, filter, max_concurrent_buckets).await;
+        Box::pin(pages.flat_map(stream::iter))
+    }
+
+    /// Creates a [`Stream`] of pages, querying each subreddit matching the filter's
+    /// subreddit pattern if it has one.
+    async fn _pages<T: Item>(
+        &self,
+        url: Url,
+        filter: Filter,
+        max_concurrent_buckets: Option<usize>,
+    ) -> Pin<Box<dyn Stream<Item = Vec<T>> + Send>> {
+        if filter.subreddit_pattern.is_none() {
+            return self._pages_one(url, filter, max_concurrent_buckets).await;
+        }
+        let pages = self._pages_matching(url, filter).await;
+        Box::pin(pages.map(|(_, page)| page))
+    }
+
+    /// Resolves the subreddits matching the filter's subreddit pattern, then creates a
+    /// [`Stream`] of pages for each of them, merging them as pages arrive.  Each page
+    /// is tagged with the subreddit it came from.
+    async fn _pages_matching<T: Item>(
+        &self,
+        url: Url,
+        mut filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = (String, Vec<T>)> + Send>> {
+        let subreddits = match filter.subreddit_pattern.take() {
+            Some(pattern) => match self.matching_subreddits(&pattern, filter.priority).await {
+                Some(subreddits) => subreddits,
+                None => {
+                    // None of the query could be fetched.
+                    let range = FailedRange {
+                        after: filter.after_date(),
+                        before: filter.before_date(),
+                        values: filter.value_range.map(|(_, range)| range),
+                    };
+                    self.fail_range(&url, &filter, range, None);
+                    return Box::pin(stream::empty());
+                }
+            },
+            None => filter.subreddit.clone().into_iter().collect(),
+        };
+
+        let streams = subreddits.into_iter().map(|subreddit| {
+            // As with multiple filters, each subreddit has a single request in the rate
+            // limiter's queue at a time, so that they take turns.
+            let (client, url) = (self.clone(), url.clone());
+            let filter = filter.clone().subreddit(subreddit.clone());
+            stream::once(async move { client._pages_one(url, filter, Some(1)).await })
+                .flatten()
+                .map(move |page| (subreddit.clone(), page))
+                .boxed()
+        });
+
+        Box::pin(stream::select_all(streams))
+    }
+
+    /// Returns the names of the subreddits matching a pattern, found by paging through
+    /// PushShift's subreddit search, or `None` if a request fails.
+    async fn matching_subreddits(&self, pattern: &str, priority: Priority) -> Option<Vec<String>> {
+        let url = self.config.api_version.subreddit_search_url();
+        let filter = Filter::new()
+            .query(subreddits::search_term(pattern))
+            .priority(priority);
+        let request = self.page_request(filter);
+        let mut pages = Box::pin(
+            self.with_retry_budget()
+                .try_pages::<SubredditName>(url, request),
+        );
+
+        let mut names = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.ok()?;
+            names.extend(
+                page.into_iter()
+                    .map(|subreddit| subreddit.display_name)
+                    .filter(|name| subreddits::matches(pattern, name)),
+            );
+        }
+        names.sort_unstable();
+        names.dedup();
+        Some(names)
+    }
+
+    /// Creates a [`Stream`] of pages, ending it early if the configured stream deadline
+    /// passes, or if it goes idle for longer than the configured idle timeout.
+    async fn _pages_one<T: Item>(
+        &self,
+        url: Url,
+        filter: Filter,
+        max_concurrent_buckets: Option<usize>,
+    ) -> Pin<Box<dyn Stream<Item = Vec<T>> + Send>> {
+        let client = self.with_retry_budget();
+        let unplanned = FailedRange {
+            after: filter.after_date(),
+            before: filter.before_date(),
+            values: filter.value_range.map(|(_, range)| range),
+        };
+        let pages = client.build_pages(url, filter, max_concurrent_buckets);
+        // The idle timeout also covers the requests made to plan the stream.
+        let idle = self.config.idle_timeout;
+        let planning = async {
+            match idle {
+                Some(idle) => with_idle_limit(pages, idle, client.control.as_ref()).await,
+                None => Some(pages.await),
+            }
+        };
+        let pages = match self.config.stream_deadline {
+            Some(deadline) => {
+                let deadline = Instant::now() + deadline;
+                // The deadline also covers the requests made to plan the stream.
+                match timeout_at(deadline, planning).await {
+                    Ok(pages) => pages.map(|pages| {
+                        let client = client.clone();
+                        Box::pin(with_deadline(pages, deadline, move || {
+                            client.report(Error::DeadlineExceeded)
+                        })) as Pin<Box<dyn Stream<Item = Vec<T>> + Send>>
+                    }),
+                    Err(_) => {
+                        self.report(Error::DeadlineExceeded);
+                        return Box::pin(stream::empty());
+                    }
+                }
+            }
+            None => planning.await,
+        };
+        let pages = match pages {
+            Some(pages) => pages,
+            None => {
+                if let Some(tracking) = &client.tracking {
+                    tracking.failed.lock().unwrap().push(unplanned);
+                }
+                client.report(Error::IdleTimeout);
+                return Box::pin(stream::empty());
+            }
+        };
+
+        match idle {
+            Some(idle) => {
+                let control = client.control.clone();
+                Box::pin(with_idle_timeout(pages, idle, control, move || {
+                    if let Some(tracking) = &client.tracking {
+                        tracking.cut_short();
+                    }
+                    client.report(Error::IdleTimeout)
+                }))
+            }
+            None => pages,
+        }
+    }
+
+    /// Returns a clone of the client with a fresh retry budget and item buffer, if they
+    /// are configured, for the requests of a new stream.
+    fn with_retry_budget(&self) -> Self {
+        Self {
+            tracking: self.tracking.as_ref().map(Tracking::scoped),
+            retry_budget: self
+                .config
+                .retry_budget
+                .map(|budget| Arc::new(StreamBudget::new(budget))),
+            item_buffer: self
+                .config
+                .max_buffered_items
+                .map(|max| Arc::new(ItemBuffer::new(max))),
+            ..self.clone()
+        }
+    }
+
+    /// Passes the timing of a page to the hooks, and to the timed page stream it was
+    /// fetched for.
+    fn record_page(&self, timing: PageTiming) {
+        if let Some(hooks) = &self.config.hooks {
+            hooks.on_page(&timing);
+        }
+        if let Some(timings) = &self.page_timings {
+            timings.lock().unwrap().push(timing);
+        }
+    }
+
+    /// Returns a clone of the client which records the failures and progress of its
+    /// streams, together with the record.
+    pub(crate) fn tracking(&self) -> (Self, Tracking) {
+        let tracking = Tracking::new();
+        let client = Self {
+            tracking: Some(tracking.clone()),
+            ..self.clone()
+        };
+        (client, tracking)
+    }
+
+    /// Reports that a stream failed to fetch the items in a date range, and records
+    /// the range for the stream if it's recording failures.
+    fn fail_range(&self, url: &Url, filter: &Filter, range: FailedRange, count: Option<u64>) {
+        if let Some(tracking) = &self.tracking {
+            tracking.failed.lock().unwrap().push(range.clone());
+        }
+        let filter = range.apply(filter.clone());
+        self.skip(Skipped {
+            url: self.request_url(url.clone(), &filter, self.batch_size()),
+            reason: SkipReason::FailedRange(range.clone()),
+            count,
+        });
+        self.report(Error::RangeFailed(range));
+    }
+
+    /// Reports that a stream moved past a second without fetching every item created in
+    /// it, see [`SkipReason::OverfullSecond`], and records the second as a failed range
+    /// if it's recording failures.
+    fn skip_second(&self, url: Url, params: &Filter, date: DateTime<Utc>) {
+        if let Some(tracking) = &self.tracking {
+            tracking.failed.lock().unwrap().push(FailedRange {
+                after: Some(date),
+                before: Some(date),
+                values: params.value_range.map(|(_, range)| range),
+            });
+        }
+        self.skip(Skipped {
+            url,
+            reason: SkipReason::OverfullSecond { date },
+            count: None,
+        });
+    }
+
+    /// Frees up the room taken in the stream's item buffer by each page as it's pulled
+    /// out of `pages`, and counts it towards the stream's progress.
+    fn consume_pages<T: Item>(
+        &self,
+        pages: Pin<Box<dyn Stream<Item = Vec<T>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = Vec<T>> + Send>> {
+        let buffer = self.item_buffer.clone();
+        let progress = self
+            .tracking
+            .as_ref()
+            .map(|tracking| tracking.progress.clone());
+        if buffer.is_none() && progress.is_none() {
+            return pages;
+        }
+        Box::pin(pages.inspect(move |page| {
+            if let Some(buffer) = &buffer {
+                buffer.release(page.len());
+            }
+            if let Some(progress) = &progress {
+                progress.record(page.len());
+            }
+        }))
+    }
+
+    /// Spends one of the stream's retries, returning whether the retry may go ahead.
+    ///
+    /// Reports an [`Error::RetryBudgetExhausted`] the first time the stream runs out.
+    fn spend_retry(&self) -> bool {
+        let budget = match &self.retry_budget {
+            Some(budget) => budget,
+            None => return true,
+        };
+        if budget.try_retry() {
+            return true;
+        }
+        if budget.exhaust() {
+            self.report(Error::RetryBudgetExhausted);
+        }
+        false
+    }
+
+    /// Creates a [`Stream`] of pages, either chunked or unchunked depending on the context.
+    async fn build_pages<T: Item>(
+        &self,
+        url: Url,
+        filter: Filter,
+        max_concurrent_buckets: Option<usize>,
+    ) -> Pin<Box<dyn Stream<Item = Vec<T>> + Send>> {
+        if let Some(limit) = filter.limit {
+            // Chunking would give us an arbitrary selection of results, rather than the
+            // first few in sorted order, and is a waste of requests for small limits anyway.
+            let limit = limit.max(0) as usize;
+            let request = self.page_request(filter);
+            let pages = self.consume_pages(self.paginated(url, request, Some(limit as u64)));
+            return Box::pin(take_items(pages, limit));
+        }
+
+        if let Some((total, mut buckets)) = self.plan_buckets::<T>(url.clone(), &filter).await {
+            if let Some(tracking) = &self.tracking {
+                tracking
+                    .buckets
+                    .lock()
+                    .unwrap()
+                    .extend(buckets.iter().copied());
+            }
+            if filter.newest_first {
+                buckets.sort_by_key(|bucket| std::cmp::Reverse(bucket.1));
+            }
+            if let Some(seed) = filter.shuffle_seed {
+                shuffle_seeded(&mut buckets, seed);
+            }
+
+            // Merging needs a page from every bucket before it can yield anything, and
+            // newest first or shuffled delivery holds back later buckets, so none of
+            // them can be capped without stalling.
+            let client = if filter.ordered || filter.newest_first || filter.shuffle_seed.is_some() {
+                Self {
+                    item_buffer: None,
+                    ..self.clone()
+                }
+            } else {
+                self.clone()
+            };
+            let per_bucket = total.map(|total| total.max(0) as u64 / buckets.len().max(1) as u64);
+            // The buckets share the filter and its serialized parameters, and only differ
+            // in their date bounds.
+            let request = self.page_request(filter.clone());
+            let bucket_streams: Vec<_> = buckets
+                .iter()
+                .map(|(l, r)| client.paginated(url.clone(), request.bounded(*l, *r), per_bucket))
+                .collect();
+
+            if filter.ordered {
+                // Merging interleaves the buckets item by item, so the pages have
+                // to be put back together afterwards.
+                let items = merge_by_date(
+                    bucket_streams
+                        .into_iter()
+                        .map(|pages| pages.flat_map(stream::iter))
+                        .collect(),
+                    filter.sort,
+                );
+                let page_size = self.config.batch_size as usize;
+                let pages = dedup_boundaries(items, &buckets).chunks(page_size);
+                return client.consume_pages(Box::pin(pages));
+            }
+
+            let pages: Pin<Box<dyn Stream<Item = Vec<T>> + Send>> =
+                if let Some(seed) = filter.shuffle_seed {
+                    // Each bucket is sorted and then shuffled with a seed of its own, so that
+                    // the order doesn't depend on how PushShift orders items created in the
+                    // same second, or on how the buckets' requests happened to interleave.
+                    let page_size = self.config.batch_size as usize;
+                    let shuffle = move |i: usize, items: &mut Vec<T>| {
+                        items.sort_by(|a, b| a.attrs().sort_key().cmp(&b.attrs().sort_key()));
+                        shuffle_seeded(items, seed.wrapping_add(i as u64));
+                    };
+                    Box::pin(in_order_by(
+                        bucket_streams,
+                        max_concurrent_buckets,
+                        page_size,
+                        shuffle,
+                    ))
+                } else if filter.newest_first {
+                    Box::pin(in_order(bucket_streams, max_concurrent_buckets))
+                } else {
+                    Box::pin(stream::iter(bucket_streams).flatten_unordered(max_concurrent_buckets))
+                };
+            let pages = self.consume_pages(pages);
+            return Box::pin(dedup_boundary_pages(pages, &buckets));
+        }
+
+        if let Some((total, ranges)) = self.plan_value_buckets::<T>(url.clone(), &filter).await {
+            // Buckets are held back until the ones before them are delivered, so they
+            // can't be capped without stalling.
+            let client = Self {
+                item_buffer: None,
+                ..self.clone()
+            };
+            let (sort_type, direction) = (filter.sort_type.clone(), filter.sort);
+            let compare = move |a: &T, b: &T| {
+                let order = sort_value(a, &sort_type)
+                    .cmp(&sort_value(b, &sort_type))
+                    .then_with(|| a.attrs().sort_key().cmp(&b.attrs().sort_key()));
+                match direction {
+                    SortDirection::Asc => order,
+                    SortDirection::Desc => order.reverse(),
+                }
+            };
+            let page_size = self.config.batch_size as usize;
+            let per_bucket = Some(total.max(0) as u64 / ranges.len().max(1) as u64);
+            let bucket_streams: Vec<_> = ranges
+                .iter()
+                .map(|range| {
+                    // Each bucket is fetched by date, which PushShift can paginate
+                    // through reliably.
+                    let filter =
+                        restrict_values(filter.clone(), *range).sort_type(SortType::CreatedDate);
+                    let request = client.page_request(filter);
+                    let pages = client.paginated(url.clone(), request, per_bucket);
+                    let compare = compare.clone();
+                    if is_single_value(*range) {
+                        // The items already come in order, so however many there are,
+                        // they can be passed along a page at a time.
+                        Box::pin(pages.map(move |mut page| {
+                            page.sort_by(&compare);
+                            page
+                        }))
+                    } else {
+                        Box::pin(arranged(pages, page_size, move |items| {
+                            items.sort_by(&compare)
+                        })) as Pin<Box<dyn Stream<Item = Vec<T>> + Send>>
+                    }
+                })
+                .collect();
+
+            let pages = in_order(bucket_streams, max_concurrent_buckets);
+            return client.consume_pages(Box::pin(pages));
+        }
+
+        let request = self.page_request(filter);
+        self.consume_pages(self.paginated(url, request, None))
+    }
+
+    /// Splits a query into buckets by creation date if it's worth chunking, returning
+    /// the total number of matching items (if it was looked up) together with the
+    /// buckets.
+    async fn plan_buckets<T: Item>(
+        &self,
+        url: Url,
+        filter: &Filter,
+    ) -> Option<(Option<i64>, Vec<(DateTime<Utc>, DateTime<Utc>)>)> {
+        // Queries sorted by other attributes are chunked by value instead, see
+        // `plan_value_buckets`.
+        if !matches!(filter.sort_type, SortType::CreatedDate) {
+            return None;
+        }
+        if let Some(windows) = &filter.windows {
+            return Some((None, windows.clone()));
+        }
+        if self.config.chunking == Chunking::Disabled {
+            return None;
+        }
+
+        let (total, oldest, newest) = match self.get_date_bounds::<T>(url.clone(), filter).await {
+            DateBounds::Many(total, oldest, newest) => (total, oldest, newest),
+            DateBounds::Few | DateBounds::Unknown => return None,
+        };
+
+        // Prefer chunking based on how items are actually distributed over time,
+        // falling back to assuming that they're evenly distributed.
+        let (volume, max_buckets) = (self.config.bucket_volume, self.config.max_buckets);
+        let buckets = match self.get_histogram(url, filter, oldest, newest).await {
+            Some(histogram) => {
+                density_chunked(&histogram, total, volume, max_buckets, oldest, newest)
+            }
+            None => chunked(total, volume, max_buckets, oldest, newest).collect(),
+        };
+        Some((Some(total), buckets))
+    }
+
+    /// Splits a query sorted by score or number of comments into buckets by value if
+    /// it's worth chunking, returning the total number of matching items together with
+    /// the buckets, ordered in the query's sort direction.
+    async fn plan_value_buckets<T: Item>(
+        &self,
+        url: Url,
+        filter: &Filter,
+    ) -> Option<(i64, Vec<ValueRange>)> {
+        let field = sort_field(&filter.sort_type)?;
+        if self.config.chunking == Chunking::Disabled {
+            return None;
+        }
+
+        let histogram = self.get_value_histogram(url, filter, field).await?;
+        let total = histogram.iter().map(|(_, count)| count).sum();
+        if let Some(tracking) = &self.tracking {
+            tracking.progress.add_total(total);
+        }
+        let threshold = self
+            .config
+            .chunk_threshold
+            .unwrap_or(self.config.batch_size);
+        if total <= threshold {
+            return None;
+        }
+
+        let (volume, max_buckets) = (self.config.bucket_volume, self.config.max_buckets);
+        let mut ranges = value_chunked(&histogram, total, volume, max_buckets);
+        if matches!(filter.sort, SortDirection::Desc) {
+            ranges.reverse();
+        }
+        Some((total, ranges))
+    }
+
+    /// Works out the requests a stream of items from the given endpoint would make.
+    async fn plan<T: Item>(&self, endpoint: Endpoint, filter: Filter) -> QueryPlan {
+        let url = self.config.api_version.url(endpoint);
+        let batch_size = self.config.batch_size;
+
+        if let Some(limit) = filter.limit {
+            return QueryPlan {
+                total_results: None,
+                requests: vec![self.request_url(url, &filter, limit.clamp(1, batch_size))],
+                windows: Vec::new(),
+                estimated_requests: requests_for(limit - 1, batch_size),
+            };
+        }
+
+        if let Some((total, buckets)) = self.plan_buckets::<T>(url.clone(), &filter).await {
+            let requests: Vec<_> = buckets
+                .iter()
+                .map(|(l, r)| {
+                    let filter = filter.clone().before(*r).after(*l);
+                    self.request_url(url.clone(), &filter, batch_size)
+                })
+                .collect();
+            // Each bucket ends with a partial page.
+            let estimated_requests =
+                requests.len() + total.map_or(0, |total| (total / batch_size) as usize);
+            return QueryPlan {
+                total_results: total,
+                requests,
+                windows: buckets,
+                estimated_requests,
+            };
+        }
+
+        if let Some((total, ranges)) = self.plan_value_buckets::<T>(url.clone(), &filter).await {
+            let requests: Vec<_> = ranges
+                .iter()
+                .map(|range| {
+                    let filter =
+                        restrict_values(filter.clone(), *range).sort_type(SortType::CreatedDate);
+                    self.request_url(url.clone(), &filter, batch_size)
+                })
+                .collect();
+            let estimated_requests = requests.len() + (total / batch_size) as usize;
+            return QueryPlan {
+                total_results: Some(total),
+                requests,
+                windows: Vec::new(),
+                estimated_requests,
+            };
+        }
+
+        let total_results = self
+            .metadata(endpoint, &filter)
+            .await
+            .map(|metadata| metadata.total_results);
+        QueryPlan {
+            total_results,
+            requests: vec![self.request_url(url, &filter, batch_size)],
+            windows: Vec::new(),
+            estimated_requests: total_results.map_or(1, |total| requests_for(total, batch_size)),
+        }
+    }
+
+    /// Prepares a query to be paginated through, serializing its parameters.
+    pub(crate) fn page_request(&self, filter: Filter) -> PageRequest {
+        let batch_size = filter.limit.map_or(self.config.batch_size, |limit| {
+            limit.clamp(1, self.config.batch_size)
+        });
+        let query =
+            PushShiftQueryParams::new(&filter, batch_size).to_page_query(self.config.api_version);
+        PageRequest {
+            filter: Arc::new(filter),
+            query,
+            batch_size,
+        }
+    }
+
+    /// Returns the URL of a request for a page of items matching the given filter.
+    fn request_url(&self, url: Url, filter: &Filter, limit: i64) -> Url {
+        self.params_url(url, PushShiftQueryParams::new(filter, limit))
+    }
+
+    /// Adds the given query parameters to a URL.
+    fn params_url(&self, mut url: Url, params: PushShiftQueryParams<'_>) -> Url {
+        let pairs = params.to_pairs(self.config.api_version);
+        url.query_pairs_mut().extend_pairs(&pairs);
+        url
+    }
+
+    /// Performs a single request to the PushShift API, returning the deserialized result.
+    async fn _get<T: DeserializeOwned + Serialize + Send + 'static>(
+        &self,
+        url: Url,
+        params: PushShiftQueryParams<'_>,
+    ) -> Option<PushShiftResponse<T>> {
+        let priority = params.inner.priority;
+        let url = self.params_url(url, params);
+        self.get_url(url, priority).await
+    }
+
+    /// Performs a single request for a URL which already includes its query parameters,
+    /// returning the deserialized result.
+    async fn get_url<T: DeserializeOwned + Serialize + Send + 'static>(
+        &self,
+        url: Url,
+        priority: Priority,
+    ) -> Option<PushShiftResponse<T>> {
+        for attempt in 0..=self.config.incomplete_retries {
+            let on_error = |error: Error| {
+                if let Error::InvalidItem { id, .. } = &error {
+                    self.skip(Skipped {
+                        url: url.clone(),
+                        reason: SkipReason::InvalidItem { id: id.clone() },
+                        count: Some(1),
+                    });
+                }
+                self.report(error);
+            };
+            let (fetched, response) = self.fetch_response::<T>(&url, priority, on_error).await?;
+
+            match response.metadata.as_ref().filter(|m| !m.is_complete()) {
+                // Only complete responses are cached, so that an incomplete one isn't
+                // served again.
+                None => self.cache_body(&url, &fetched),
+                Some(metadata) => {
+                    self.report(Error::IncompleteResults(metadata.clone()));
+                    if attempt < self.config.incomplete_retries && self.spend_retry() {
+                        #[cfg(feature = "metrics")]
+                        crate::telemetry::retry();
+                        if let Some(hooks) = &self.config.hooks {
+                            hooks.on_retry(
+                                &url,
+                                attempt + 1,
+                                &Error::IncompleteResults(metadata.clone()),
+                            );
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            #[cfg(feature = "metrics")]
+            crate::telemetry::items(response.data.len());
+            return Some(response);
+        }
+
+        None
+    }
+
+    /// Fetches and parses the response to a request, passing any items which can't be
+    /// parsed to `on_error`.
+    ///
+    /// The body is parsed as it arrives, unless it was served from the cache or shared
+    /// with another request, in which case it's parsed once it's been fetched.
+    async fn fetch_response<T: DeserializeOwned + Serialize + Send + 'static>(
+        &self,
+        url: &Url,
+        priority: Priority,
+        on_error: impl FnMut(Error),
+    ) -> Option<(Fetched, PushShiftResponse<T>)> {
+        let parser = Arc::new(Mutex::new(ResponseParser::<T>::new(self.config.strict)));
+        let sink = {
+            let parser = parser.clone();
+            Box::new(move |chunk: &[u8]| parser.lock().unwrap().feed(chunk))
+        };
+        let fetched = self.fetch_coalesced(url.clone(), priority, sink).await?;
+
+        let parsed = if fetched.uncached {
+            let parser = mem::replace(
+                &mut *parser.lock().unwrap(),
+                ResponseParser::new(self.config.strict),
+            );
+            parser.finish(on_error)
+        } else {
+            parse_response(&fetched.body, self.config.strict, on_error)
+        };
+        match parsed {
+            Ok(response) => Some((fetched, response)),
+            Err(error) => {
+                self.report(Error::InvalidResponse {
+                    url: url.clone(),
+                    error,
+                    body: snippet(&fetched.body),
+                });
+                None
+            }
+        }
+    }
+
+    /// Fetches the body of a request from the cache, or otherwise sharing the response
+    /// with any identical requests which are already in flight.  If this request makes
+    /// the fetch, the body is passed to `sink` as it arrives.
+    ///
+    /// The body isn't cached until the caller has checked it, see [`Client::cache_body`].
+    async fn fetch_coalesced(
+        &self,
+        url: Url,
+        priority: Priority,
+        sink: BodySink,
+    ) -> Option<Fetched> {
+        let key = url.to_string();
+
+        if let Some(body) = self.config.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Some(Fetched {
+                body: Bytes::from(body),
+                uncached: false,
+            });
+        }
+
+        // A shared request is paused and spends retries as the stream which made it
+        // would, so it's only shared between requests made under the same control and
+        // retry budget.
+        let key = (key, self.stream_key());
+        let (response, leader) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key).and_then(WeakShared::upgrade) {
+                Some(response) => (response, false),
+                None => {
+                    let client = self.clone();
+                    let response = async move { client.fetch(url, priority, sink).await }
+                        .boxed()
+                        .shared();
+                    in_flight.retain(|_, response| response.upgrade().is_some());
+                    if let Some(weak) = response.downgrade() {
+                        in_flight.insert(key.clone(), weak);
+                    }
+                    (response, true)
+                }
+            }
+        };
+
+        let body = response.await;
+        self.in_flight.lock().unwrap().remove(&key);
+        // Only the request which made the fetch caches its body.
+        body.map(|body| Fetched {
+            body,
+            uncached: leader,
+        })
+    }
+
+    /// Returns the key of the pause control and retry budget this client makes its
+    /// requests with.
+    fn stream_key(&self) -> StreamKey {
+        (
+            self.control.as_ref().map(StreamControl::id),
+            self.retry_budget
+                .as_ref()
+                .map(|budget| Arc::as_ptr(budget) as usize),
+        )
+    }
+
+    /// Caches the body of a response, once the caller has checked that it's worth
+    /// serving again.
+    fn cache_body(&self, url: &Url, fetched: &Fetched) {
+        if let (Some(cache), true) = (&self.config.cache, fetched.uncached) {
+            cache.insert(url.as_str(), &fetched.body);
+        }
+    }
+
+    /// Fetches the body of a request from the current backend, retrying if we're rate
+    /// limited.
+    async fn fetch(&self, url: Url, priority: Priority, sink: BodySink) -> Option<Bytes> {
+        let (backend, url) = match &self.config.failover {
+            Some(failover) => {
+                let (backend, url) = failover.route(&url);
+                (Some(backend), url)
+            }
+            None => (None, url),
+        };
+
+        let body = match &self.config.pool {
+            Some(pool) => {
+                let member = pool.next();
+                let client = Self {
+                    client: member.client.clone(),
+                    limiter: member.limiter.clone(),
+                    ..self.clone()
+                };
+                client.fetch_once(url, priority, sink).await
+            }
+            None => self.fetch_once(url, priority, sink).await,
+        };
+        if let Some(breaker) = &self.config.circuit_breaker {
+            match body {
+                Ok(_) => breaker.record_success(),
+                Err(Outage::Down) => breaker.record_failure(),
+                Err(Outage::Up | Outage::RateLimited) => {}
+            }
+        }
+        if let (Some(failover), Some(backend)) = (&self.config.failover, backend) {
+            match body {
+                Ok(_) => failover.record_success(backend),
+                Err(Outage::Down | Outage::RateLimited) => {
+                    if let Some((from, to)) = failover.record_failure(backend) {
+                        if let Some(hooks) = &self.config.hooks {
+                            hooks.on_failover(&from, &to);
+                        }
+                    }
+                }
+                Err(Outage::Up) => {}
+            }
+        }
+        body.ok()
+    }
+
+    /// Fetches the body of a request, passing it to `sink` as it arrives, and returning
+    /// whether PushShift looks to be down if the request fails.
+    async fn fetch_once(
+        &self,
+        url: Url,
+        priority: Priority,
+        mut sink: BodySink,
+    ) -> Result<Bytes, Outage> {
+        let mut refreshed = false;
+        for attempt in 0..=MAX_RATE_LIMIT_RETRIES {
+            if let Some(control) = &self.control {
+                control.until_resumed().await;
+            }
+            if let Some(budget) = &self.retry_budget {
+                // The stream has given up.
+                if budget.is_exhausted() {
+                    return Err(Outage::Up);
+                }
+                budget.record_request();
+            }
+            if let Some(breaker) = &self.config.circuit_breaker {
+                breaker.until_closed().await;
+            }
+            // The slot is taken before waiting for the rate limiter, so that a request
+            // which is let through by the limiter is sent straight away, rather than
+            // spending its turn waiting for other requests to finish.
+            let _in_flight = concurrency::acquire().await;
+            let waiting = std::time::Instant::now();
+            if self.config.discover_rate_limit {
+                self.limiter.discover(self.discover_rate_limit()).await;
+            }
+            self.limiter.until_ready(priority).await;
+            if let Some(waited) = &self.waited {
+                waited.record(waiting.elapsed());
+            }
+            #[cfg(feature = "metrics")]
+            crate::telemetry::rate_limit_wait(waiting.elapsed());
+            let mut request = self
+                .client
+                .get(url.clone())
+                .headers(self.config.headers.clone());
+            let token = self.config.auth.token();
+            if let Some(token) = &token {
+                request = request.bearer_auth(token);
+            }
+            if let Some(timeout) = self.config.timeout {
+                request = request.timeout(timeout);
+            }
+            #[cfg(feature = "compression")]
+            if self.config.compression {
+                request = request.header(ACCEPT_ENCODING, "zstd");
+            }
+
+            #[cfg(feature = "warc")]
+            let recorded = self
+                .config
+                .warc
+                .as_ref()
+                .and_then(|_| request.try_clone()?.build().ok());
+
+            #[cfg(feature = "metrics")]
+            crate::telemetry::request();
+            if let Some(hooks) = &self.config.hooks {
+                hooks.on_request(&url);
+            }
+            let start = std::time::Instant::now();
+            let response = match self.send(request).await {
+                Ok(response) => response,
+                Err(error) => {
+                    self.report(error);
+                    return Err(Outage::Down);
+                }
+            };
+            #[cfg(feature = "warc")]
+            let recorded = recorded.map(|request| (request, ResponseHead::new(&response)));
+
+            let status = response.status();
+            #[cfg(feature = "metrics")]
+            crate::telemetry::response(status, start.elapsed());
+            if let Some(hooks) = &self.config.hooks {
+                hooks.on_response(&url, status, start.elapsed());
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after);
+                self.limiter.throttle(retry_after);
+                if attempt < MAX_RATE_LIMIT_RETRIES && !self.spend_retry() {
+                    return Err(Outage::Up);
+                }
+                self.retrying(&url, attempt, status_error(response).await);
+                continue;
+            }
+
+            if status == StatusCode::UNAUTHORIZED
+                && !refreshed
+                && self.config.auth.refresh(token.as_deref()).await
+                && self.spend_retry()
+            {
+                refreshed = true;
+                self.retrying(&url, attempt, status_error(response).await);
+                continue;
+            }
+
+            if !status.is_success() {
+                self.report(status_error(response).await);
+                return Err(if status.is_server_error() {
+                    Outage::Down
+                } else {
+                    Outage::Up
+                });
+            }
+            // Only a successful response counts towards lifting a throttle.
+            self.limiter.record_success();
+
+            // The body as it was sent is only needed to record it.
+            #[cfg(feature = "warc")]
+            let keep_raw = recorded.is_some();
+            #[cfg(not(feature = "warc"))]
+            let keep_raw = false;
+            return match read_body(response, keep_raw, &mut sink).await {
+                Ok(body) => {
+                    self.config.transfer.record(&body);
+                    #[cfg(feature = "metrics")]
+                    crate::telemetry::body(body.raw_len, body.decoded.len());
+                    if let Some(hooks) = &self.config.hooks {
+                        hooks.on_body(&url, body.raw_len, body.decoded.len());
+                    }
+                    // The body is recorded as it was sent, to match the response's
+                    // headers.
+                    #[cfg(feature = "warc")]
+                    if let (Some(warc), Some((request, head)), Some(raw)) =
+                        (&self.config.warc, recorded, &body.raw)
+                    {
+                        if let Err(error) = warc.record(&request, &head, raw) {
+                            self.report(Error::Warc(error));
+                        }
+                    }
+                    Ok(body.decoded)
+                }
+                Err(error) => {
+                    self.report(error);
+                    Err(Outage::Down)
+                }
+            };
+        }
+
+        self.report(Error::RateLimited);
+        Err(Outage::RateLimited)
+    }
+
+    /// Fetches the interval between requests permitted by the rate limit PushShift
+    /// advertises on its meta endpoint.
+    async fn discover_rate_limit(&self) -> Option<Duration> {
+        let (_, request) = self.meta_request();
+        let response = self.send(request).await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let meta: Meta = serde_json::from_slice(&response.bytes().await.ok()?).ok()?;
+        meta.period()
+    }
+
+    /// Builds a request for the meta endpoint of the current backend, which bypasses
+    /// the rate limiter.
+    fn meta_request(&self) -> (Url, reqwest::RequestBuilder) {
+        let url = self.config.api_version.meta_url();
+        let url = match &self.config.failover {
+            Some(failover) => failover.route(&url).1,
+            None => url,
+        };
+        let mut request = self
+            .client
+            .get(url.clone())
+            .headers(self.config.headers.clone());
+        if let Some(token) = self.config.auth.token() {
+            request = request.bearer_auth(token);
+        }
+        if let Some(timeout) = self.config.timeout {
+            request = request.timeout(timeout);
+        }
+        (url, request)
+    }
+
+    /// Sends a request, through the middleware client if there is one.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, Error> {
+        #[cfg(feature = "middleware")]
+        if let Some(middleware) = &self.config.middleware {
+            let request = request.build().map_err(Error::Http)?;
+            return Ok(middleware.execute(request).await?);
+        }
+        request.send().await.map_err(Error::Http)
+    }
+
+    /// Notifies the hooks that a request is about to be retried, unless it's out of
+    /// attempts.
+    fn retrying(&self, url: &Url, attempt: usize, error: Error) {
+        if attempt >= MAX_RATE_LIMIT_RETRIES {
+            return;
+        }
+        #[cfg(feature = "metrics")]
+        crate::telemetry::retry();
+        if let Some(hooks) = self.config.hooks.as_ref() {
+            hooks.on_retry(url, attempt + 1, &error);
+        }
+    }
+
+    /// Passes a record of skipped data to the configured handler and hooks, if any.
+    fn skip(&self, skipped: Skipped) {
+        if let Some(handler) = &self.config.skip_handler {
+            handler(&skipped);
+        }
+        if let Some(hooks) = &self.config.hooks {
+            hooks.on_skipped(&skipped);
+        }
+    }
+
+    /// Passes an error to the configured error handler and hooks, if any.
+    fn report(&self, error: Error) {
+        #[cfg(feature = "metrics")]
+        crate::telemetry::error();
+        if let Some(handler) = &self.config.error_handler {
+            handler(&error);
+        }
+        if let Some(hooks) = &self.config.hooks {
+            match error {
+                Error::InvalidItem { .. } | Error::UnknownFields { .. } => {
+                    hooks.on_item_error(&error)
+                }
+                _ => hooks.on_error(&error),
+            }
+        }
+    }
+
+    /// Determines the oldest and most recent dates of items corresponding to this query,
+    /// together with the total number of matching items, if there are enough of them to
+    /// be worth chunking.
+    async fn get_date_bounds<T: Item>(&self, url: Url, params: &Filter) -> DateBounds {
+        let newest_params = params.clone().sort(SortDirection::Desc);
+        let newest: PushShiftResponse<T> = match self
+            ._get(
+                url.clone(),
+                PushShiftQueryParams {
+                    metadata: true,
+                    ..PushShiftQueryParams::new(&newest_params, 1)
+                },
+            )
+            .await
+        {
+            Some(newest) => newest,
+            None => return DateBounds::Unknown,
+        };
+
+        // Only want to do this for queries with lots of results.
+        let total_results = match &newest.metadata {
+            Some(metadata) => metadata.total_results,
+            None => return DateBounds::Unknown,
+        };
+        if let Some(tracking) = &self.tracking {
+            tracking.progress.add_total(total_results);
+        }
+
+        let threshold = self
+            .config
+            .chunk_threshold
+            .unwrap_or(self.config.batch_size);
+        if total_results <= threshold {
+            return DateBounds::Few;
+        }
+
+        let oldest_params = params.clone().sort(SortDirection::Asc);
+        let oldest: Option<PushShiftResponse<T>> = self
+            ._get(url, PushShiftQueryParams::new(&oldest_params, 1))
+            .await;
+        let dates = oldest.as_ref().and_then(|oldest| {
+            let oldest = oldest.data.first()?.attrs().date;
+            Some((oldest, newest.data.first()?.attrs().date))
+        });
+        match dates {
+            Some((oldest, newest)) => DateBounds::Many(total_results, oldest, newest),
+            None => DateBounds::Unknown,
+        }
+    }
+
+    /// Returns a histogram of the number of items matching this query over time,
+    /// as `(bin start, item count)` pairs ordered by date.
+    async fn get_histogram(
+        &self,
+        url: Url,
+        params: &Filter,
+        oldest: DateTime<Utc>,
+        newest: DateTime<Utc>,
+    ) -> Option<Vec<(DateTime<Utc>, i64)>> {
+        let response: PushShiftResponse<serde_json::Value> = self
+            ._get(
+                url,
+                PushShiftQueryParams {
+                    aggs: Some("created_utc"),
+                    frequency: Some(histogram_frequency(oldest, newest)),
+                    ..PushShiftQueryParams::new(params, 0)
+                },
+            )
+            .await?;
+
+        let mut histogram = response
+            .aggs
+            .get("created_utc")?
+            .iter()
+            .map(|bin| {
+                Some((
+                    Utc.timestamp_opt(bin.key.as_i64()?, 0).single()?,
+                    bin.doc_count,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        histogram.sort_by_key(|(date, _)| *date);
+
+        if histogram.is_empty() {
+            None
+        } else {
+            Some(histogram)
+        }
+    }
+
+    /// Returns the number of items matching this query with each value of `field`, as
+    /// `(value, item count)` pairs ordered by value.
+    async fn get_value_histogram(
+        &self,
+        url: Url,
+        params: &Filter,
+        field: &'static str,
+    ) -> Option<Vec<(i64, i64)>> {
+        let response: PushShiftResponse<serde_json::Value> = self
+            ._get(
+                url,
+                PushShiftQueryParams {
+                    aggs: Some(field),
+                    agg_size: Some(VALUE_HISTOGRAM_SIZE),
+                    ..PushShiftQueryParams::new(params, 0)
+                },
+            )
+            .await?;
+
+        let mut histogram = response
+            .aggs
+            .get(field)?
+            .iter()
+            .map(|bin| Some((bin.key.as_i64()?, bin.doc_count)))
+            .collect::<Option<Vec<_>>>()?;
+        histogram.sort_by_key(|(value, _)| *value);
+        Some(histogram)
+    }
+
+    /// Returns paginated items from the given URL together with the given query parameters.
+    /// Any errors that occur during this process will be ignored.
+    ///
+    /// `expected` is an estimate of the number of matching items, if one is known.
+    ///
+    /// If prefetching is configured, pages are fetched by a background task.  If the
+    /// stream's buffering is capped, each page waits for room in the item buffer first.
+    fn paginated<T: Item>(
+        &self,
+        url: Url,
+        request: PageRequest,
+        expected: Option<u64>,
+    ) -> Pin<Box<dyn Stream<Item = Vec<T>> + Send>> {
+        let pages = self.clone().pages(url, request, expected);
+        let pages: Pin<Box<dyn Stream<Item = Vec<T>> + Send>> = match &self.item_buffer {
+            Some(buffer) => {
+                let page_size = self.batch_size() as usize;
+                Box::pin(bounded(Box::pin(pages), buffer.clone(), page_size))
+            }
+            None => Box::pin(pages),
+        };
+        match self.config.prefetch_pages {
+            0 => Box::pin(pages),
+            n => Box::pin(prefetch(pages, n)),
+        }
+    }
+
+    /// Returns a [`Stream`] of pages fetched one request at a time, without chunking.
+    ///
+    /// Unlike other streams, a failed request is yielded as an `Err` (after which the
+    /// stream ends), so that callers can tell whether every page was fetched.
+    pub(crate) fn sequential_pages<T: Item>(
+        &self,
+        endpoint: Endpoint,
+        filter: Filter,
+    ) -> Pin<Box<dyn Stream<Item = Result<Vec<T>, ()>> + Send>> {
+        let url = self.config.api_version.url(endpoint);
+        let request = self.page_request(filter);
+        Box::pin(self.with_retry_budget().try_pages(url, request))
+    }
+
+    /// Returns paginated items from the given URL together with the given query parameters.
+    ///
+    /// If a request fails the stream ends, reporting the range of dates it didn't get to,
+    /// along with how many of the `expected` items it hadn't fetched yet.
+    fn pages<T: Item>(
+        self,
+        url: Url,
+        request: PageRequest,
+        expected: Option<u64>,
+    ) -> impl Stream<Item = Vec<T>> + Send {
+        let client = self.clone();
+        let params = request.filter.clone();
+        let (mut after, mut before) = request.range();
+        let values = params.value_range.map(|(_, range)| range);
+        // Only a query sorted by date moves through its range as pages are fetched.
+        let by_date = matches!(params.sort_type, SortType::CreatedDate);
+        let sort = params.sort;
+        let mut fetched = 0;
+        let mut pages = Box::pin(self.try_pages::<T>(url.clone(), request));
+        // What's left of the range, in case the stream is cut short.
+        let remaining = move |after, before| FailedRange {
+            after,
+            before,
+            values,
+        };
+        let query = client
+            .tracking
+            .as_ref()
+            .map(|tracking| tracking.start(remaining(after, before)));
+
+        stream! {
+            while let Some(page) = pages.next().await {
+                match page {
+                    Ok(page) => {
+                        fetched += page.len() as u64;
+                        if let Some(last) = page.last().filter(|_| by_date) {
+                            let date = Some(last.attrs().date);
+                            match sort {
+                                SortDirection::Asc => after = date,
+                                SortDirection::Desc => before = date,
+                            }
+                        }
+                        if let (Some(tracking), Some(query)) = (&client.tracking, query) {
+                            tracking.update(query, Some(remaining(after, before)));
+                        }
+                        yield page;
+                    }
+                    Err(()) => {
+                        let count = expected.map(|expected| expected.saturating_sub(fetched));
+                        client.fail_range(&url, &params, remaining(after, before), count);
+                    }
+                }
+            }
+            if let (Some(tracking), Some(query)) = (&client.tracking, query) {
+                tracking.update(query, None);
+            }
+        }
+    }
+
+    /// Returns paginated items from the given URL, ending with an `Err` if a request fails.
+    fn try_pages<T: Item>(
+        self,
+        url: Url,
+        request: PageRequest,
+    ) -> impl Stream<Item = Result<Vec<T>, ()>> + Send {
+        // Only the date and ID bounds change from page to page, so the rest of the
+        // query was only serialized once.
+        let PageRequest {
+            filter: params,
+            mut query,
+            batch_size,
+        } = request;
+
+        // Items are only ordered by ID when they're sorted by creation date.
+        let by_id = self.config.id_pagination && matches!(params.sort_type, SortType::CreatedDate);
+
+        stream! {
+            let mut cursor = if by_id {
+                Cursor::by_id(params.sort)
+            } else {
+                Cursor::new(params.sort)
+            };
+
+            loop {
+                let page_url = query.url(&url);
+                let start = std::time::Instant::now();
+                let waited = Arc::new(WaitTimer::default());
+                let client = Self {
+                    waited: Some(waited.clone()),
+                    ..self.clone()
+                };
+                let response = client.get_url::<T>(page_url.clone(), params.priority).await;
+                let parsed_response = match response {
+                    Some(parsed_response) => parsed_response,
+                    None => {
+                        yield Err(());
+                        break;
+                    }
+                };
+                self.record_page(PageTiming {
+                    url: page_url.clone(),
+                    items: parsed_response.data.len(),
+                    elapsed: start.elapsed(),
+                    rate_limit_wait: waited.total(),
+                    took: parsed_response
+                        .metadata
+                        .as_ref()
+                        .and_then(Metadata::execution_time),
+                });
+
+                // If we got less than the batch size of results then there's
+                // not going to be any more results in the next query.
+                let should_break = parsed_response.data.len() < batch_size as usize;
+
+                let mut page = cursor.advance(parsed_response.data);
+                if page.is_empty() && !should_break {
+                    // A full page of items we've already seen; they must all share
+                    // a single second, which the cursor moves through by ID instead.
+                    // When paginating by ID, the backend must have ignored the ID
+                    // bound, and the cursor falls back to paginating by date.
+                    if let Some(date) = cursor.stalled() {
+                        self.skip_second(page_url, &params, date);
+                    }
+                } else {
+                    // Items are only dropped once the cursor has moved past them.
+                    params.removed_text.apply(&mut page);
+                    if !page.is_empty() {
+                        yield Ok(page);
+                    }
+
+                    // A short page from a single second only ends that second.
+                    if should_break && !cursor.next_second() {
+                        break;
+                    }
+                }
+
+                cursor.apply(&mut query);
+            }
+        }
+    }
+}
+
+/// Reads the body of a response, decompressing it if need be, and passing each chunk to
+/// `sink` as it arrives.  A compressed body is only kept as it was sent if `keep_raw`
+/// is set.
+#[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+async fn read_body(
+    mut response: reqwest::Response,
+    keep_raw: bool,
+    sink: &mut (dyn FnMut(&[u8]) + Send),
+) -> Result<Body, Error> {
+    #[cfg(feature = "compression")]
+    if response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding == "zstd")
+    {
+        return crate::transfer::read_zstd(response, keep_raw, sink).await;
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(Error::Http)? {
+        sink(&chunk);
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Body::identity(Bytes::from(body)))
+}
+
+/// Creates an [`Error::Status`] for an unsuccessful response, reading the start of its
+/// body.
+async fn status_error(response: reqwest::Response) -> Error {
+    let status = response.status();
+    let url = response.url().clone();
+    let body = response.bytes().await.unwrap_or_default();
+    Error::Status {
+        status,
+        url,
+        body: snippet(&body),
+    }
+}
+
+/// An item returned by one of the PushShift search endpoints.
+pub(crate) trait Item: 'static + DeserializeOwned + Serialize + AsAttrs + Send {}
+
+impl<T: 'static + DeserializeOwned + Serialize + AsAttrs + Send> Item for T {}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `ClientBuilder` can be used to create a [`Client`] with custom configuration.
+#[must_use]
+pub struct ClientBuilder {
+    client: Option<reqwest::Client>,
+    config: Config,
+    rate_limit_mode: RateLimitMode,
+    rate_limit_backend: Option<Arc<dyn RateLimitBackend>>,
+}
+
+impl ClientBuilder {
+    /// Constructs a new `ClientBuilder`.
+    ///
+    /// This is the same as [`Client::builder()`].
+    pub fn new() -> Self {
+        Self {
+            client: None,
+            config: Config {
+                api_version: ApiVersion::default(),
+                auth: Auth::default(),
+                headers: HeaderMap::new(),
+                #[cfg(feature = "middleware")]
+                middleware: None,
+                authors: AuthorCache::default(),
+                max_concurrent_buckets: None,
+                batch_size: DEFAULT_BATCH_SIZE,
+                bucket_volume: DEFAULT_BUCKET_VOLUME,
+                max_buckets: DEFAULT_MAX_BUCKETS,
+                chunk_threshold: None,
+                chunking: Chunking::default(),
+                error_handler: None,
+                skip_handler: None,
+                hooks: None,
+                strict: false,
+                timeout: None,
+                stream_deadline: None,
+                idle_timeout: None,
+                cache: None,
+                incomplete_retries: 0,
+                retry_budget: None,
+                prefetch_pages: 0,
+                max_buffered_items: None,
+                circuit_breaker: None,
+                failover: None,
+                pool: None,
+                id_pagination: false,
+                discover_rate_limit: true,
+                #[cfg(feature = "compression")]
+                compression: true,
+                transfer: TransferCounter::default(),
+                #[cfg(feature = "warc")]
+                warc: None,
+            },
+            rate_limit_mode: RateLimitMode::default(),
+            rate_limit_backend: None,
+        }
+    }
+
+    /// Sets the backing [`reqwest::Client`] used to make requests.
+    pub fn reqwest_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sends requests through a [`reqwest_middleware`] client, so that its middleware
+    /// (e.g. retries or tracing) applies to requests to PushShift.  This takes
+    /// precedence over [`ClientBuilder::reqwest_client`].
+    ///
+    /// Errors returned by the middleware itself are reported as [`Error::Middleware`].
+    #[cfg(feature = "middleware")]
+    pub fn middleware_client(mut self, client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        self.config.middleware = Some(client);
+        self
+    }
+
+    /// Sets the version of the PushShift API to target.
+    ///
+    /// Defaults to [`ApiVersion::Legacy`].
+    pub fn api_version(mut self, api_version: ApiVersion) -> Self {
+        self.config.api_version = api_version;
+        self
+    }
+
+    /// Sets a bearer token that is sent along with every request.
+    ///
+    /// A token is required by [`ApiVersion::Authenticated`].
+    pub fn auth_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.config.auth.set_token(token.into());
+        self
+    }
+
+    /// Sets a callback which fetches a new bearer token when a request is rejected with
+    /// `401 Unauthorized`, e.g. because the [token](ClientBuilder::auth_token) expired.
+    ///
+    /// The rejected request is retried once with the new token, which is then used for
+    /// every later request.  If the callback returns `None`, the request fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// async fn fetch_token() -> Option<String> {
+    ///     // e.g. exchange a refresh token with your auth proxy.
+    ///     Some("new-token".to_string())
+    /// }
+    ///
+    /// let client = Client::builder()
+    ///     .auth_token("my-token")
+    ///     .refresh_auth_token(fetch_token)
+    ///     .build();
+    /// ```
+    pub fn refresh_auth_token<F, Fut>(mut self, refresh: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        self.config
+            .auth
+            .set_refresher(Arc::new(move || refresh().boxed()));
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request.
+    ///
+    /// Some mirrors of the PushShift API ask for a descriptive user agent, e.g. one
+    /// including contact details.
+    ///
+    /// # Panics
+    /// Panics if the user agent isn't a valid header value.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::builder()
+    ///     .user_agent("my-archiver/1.0 (admin@example.com)")
+    ///     .build();
+    /// ```
+    pub fn user_agent<V>(self, user_agent: V) -> Self
+    where
+        V: TryInto<HeaderValue>,
+        V::Error: fmt::Debug,
+    {
+        self.default_header(USER_AGENT, user_agent)
+    }
+
+    /// Sets a header which is sent with every request, replacing any previous value of
+    /// the header.
+    ///
+    /// # Panics
+    /// Panics if the name or value aren't valid in a header.
+    pub fn default_header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: fmt::Debug,
+        V: TryInto<HeaderValue>,
+        V::Error: fmt::Debug,
+    {
+        let name = name.try_into().expect("invalid header name");
+        let value = value.try_into().expect("invalid header value");
+        self.config.headers.insert(name, value);
+        self
+    }
+
+    /// Sets how requests made by the client are rate limited.
+    ///
+    /// Defaults to [`RateLimitMode::PerClient`], in which case every client built
+    /// (and all of its clones) is throttled independently of other clients.
+    pub fn rate_limit_mode(mut self, mode: RateLimitMode) -> Self {
+        self.rate_limit_mode = mode;
+        self
+    }
+
+    /// Sets a [`RateLimitBackend`] which keeps the rate limit quota outside of the
+    /// process, so that it can be shared with other processes (e.g. with a
+    /// `RedisRateLimiter`, behind the `redis` feature).
+    ///
+    /// This replaces the in-process rate limiter, and takes precedence over the
+    /// [rate limit mode](ClientBuilder::rate_limit_mode).  Responses telling the client
+    /// to slow down still only slow down this client.
+    pub fn rate_limit_backend<B>(mut self, backend: B) -> Self
+    where
+        B: RateLimitBackend + 'static,
+    {
+        self.rate_limit_backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Sets whether the client discovers PushShift's rate limit from its meta endpoint.
+    ///
+    /// Enabled by default: the rate limit is discovered before the client's first
+    /// request, and again every hour, so that the client uses whatever allowance
+    /// PushShift currently advertises.  Until it's been discovered (or if it can't be)
+    /// requests are limited to 1 per second.
+    pub fn discover_rate_limit(mut self, discover: bool) -> Self {
+        self.config.discover_rate_limit = discover;
+        self
+    }
+
+    /// Sets whether the client paginates queries sorted by creation date by ID, for
+    /// backends which support the `before_id` and `after_id` parameters.
+    ///
+    /// Disabled by default, as PushShift itself only supports paginating by date, which
+    /// has second resolution: pages overlap by the items created in the same second.
+    /// (If more items were created in one second than fit in a page, the rest of them
+    /// are fetched by ID whether or not this is enabled, which only works on backends
+    /// supporting ID bounds.)  ID bounds are exclusive and unique, so pages follow on
+    /// from each other exactly.  See also [`Filter::before_id`] and [`Filter::after_id`].
+    pub fn id_pagination(mut self, enabled: bool) -> Self {
+        self.config.id_pagination = enabled;
+        self
+    }
+
+    /// Sets whether the client asks for responses to be compressed with zstd.
+    ///
+    /// Enabled by default with the `compression` feature.  Compressed responses are
+    /// decompressed as they're downloaded; a backend which doesn't support zstd just
+    /// answers uncompressed.  See [`Client::transfer_stats`] for how much this saves.
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, enabled: bool) -> Self {
+        self.config.compression = enabled;
+        self
+    }
+
+    /// Records every request the client makes, together with PushShift's response, to
+    /// the given WARC file.  See the [`warc`](crate::warc) module.
+    ///
+    /// Errors writing to the file are reported to the
+    /// [error handler](ClientBuilder::on_error) as [`Error::Warc`], without failing the
+    /// request.
+    #[cfg(feature = "warc")]
+    pub fn warc(mut self, writer: crate::warc::WarcWriter) -> Self {
+        self.config.warc = Some(Arc::new(writer));
+        self
+    }
+
+    /// Sets the maximum number of buckets of a chunked query that are fetched concurrently.
+    ///
+    /// By default every bucket is queried at once, with all of them competing for the
+    /// rate limiter, except for queries which deliver their buckets one after another,
+    /// i.e. those using [`Filter::newest_first`] or [`Filter::shuffled`] or sorted by
+    /// score or number of comments, which fetch 4 buckets at a time.  Bounding this limits the memory used by buffered pages and
+    /// makes progress through each bucket more predictable.  This has no effect on
+    /// queries using [`Filter::ordered`].
+    pub fn max_concurrent_buckets(mut self, max: usize) -> Self {
+        self.config.max_concurrent_buckets = Some(max);
+        self
+    }
+
+    /// Sets the number of items requested from PushShift at a time.
+    ///
+    /// Defaults to 50.  Larger batches mean fewer (rate limited) requests are needed
+    /// to fetch the same number of items.  The batch size is capped at the maximum
+    /// supported by the configured [`ApiVersion`], see [`ApiVersion::max_batch_size`].
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.config.batch_size = batch_size;
+        self
+    }
+
+    /// Sets the number of items each bucket of a chunked query should contain.
+    ///
+    /// Defaults to 25.  Larger buckets mean fewer buckets are queried concurrently,
+    /// but each of them requires more pages to be fetched.
+    pub fn bucket_volume(mut self, bucket_volume: i64) -> Self {
+        self.config.bucket_volume = bucket_volume.max(1);
+        self
+    }
+
+    /// Sets the most buckets a single query is chunked into.
+    ///
+    /// Defaults to 200.  For huge queries, more buckets mean each bucket needs fewer
+    /// pages, at the cost of more streams (and more memory).
+    pub fn max_buckets(mut self, max_buckets: i64) -> Self {
+        self.config.max_buckets = max_buckets.max(1);
+        self
+    }
+
+    /// Sets the number of matching items above which a query is chunked into buckets,
+    /// rather than paginated through as a whole.
+    ///
+    /// Defaults to the [batch size](ClientBuilder::batch_size), i.e. queries are only
+    /// chunked if they need more than one page.
+    pub fn chunk_threshold(mut self, threshold: i64) -> Self {
+        self.config.chunk_threshold = Some(threshold.max(0));
+        self
+    }
+
+    /// Sets whether large queries are chunked into buckets which are fetched
+    /// concurrently.
+    ///
+    /// Defaults to [`Chunking::Auto`].  Chunking relies on the `total_results` reported
+    /// in PushShift's metadata, so [`Chunking::Disabled`] is useful for mirrors which
+    /// rate limit metadata queries heavily or report bogus totals: every query is then
+    /// paginated through one page at a time.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{Chunking, Client};
+    ///
+    /// let client = Client::builder().chunking(Chunking::Disabled).build();
+    /// ```
+    pub fn chunking(mut self, chunking: Chunking) -> Self {
+        self.config.chunking = chunking;
+        self
+    }
+
+    /// Sets a callback which is called with every [`Error`] encountered by the client.
+    ///
+    /// Streams skip over data affected by errors rather than ending, so this is the
+    /// only way to find out about them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::builder()
+    ///     .on_error(|error| eprintln!("pullcaps: {}", error))
+    ///     .build();
+    /// ```
+    pub fn on_error<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.config.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a callback which is called with a [`Skipped`] record whenever the client
+    /// skips over data: the rest of a query, or of one of its buckets, after a request
+    /// fails, or an item which couldn't be deserialized.
+    ///
+    /// Each record includes the request for the skipped data and an estimate of how
+    /// many items were lost, so that a crawl's gaps can be quantified, or refetched.
+    /// The errors behind them are still passed to the [error handler](ClientBuilder::on_error).
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let lost = Arc::new(AtomicU64::new(0));
+    /// let counter = lost.clone();
+    /// let client = Client::builder()
+    ///     .on_skipped(move |skipped| {
+    ///         counter.fetch_add(skipped.count.unwrap_or(0), Ordering::Relaxed);
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn on_skipped<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Skipped) + Send + Sync + 'static,
+    {
+        self.config.skip_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets [`Hooks`] which are notified of the requests, responses, retries and
+    /// errors of the client.
+    ///
+    /// Errors are passed to both the hooks and the [error handler](ClientBuilder::on_error),
+    /// if both are set.
+    pub fn hooks<H>(mut self, hooks: H) -> Self
+    where
+        H: Hooks + 'static,
+    {
+        self.config.hooks = Some(Arc::new(hooks));
+        self
+    }
+
+    /// Enables strict schema checking.
+    ///
+    /// In strict mode, every field of an item which isn't captured by the
+    /// [models](crate::models) is reported as an [`Error::UnknownFields`] to the
+    /// [error handler](ClientBuilder::on_error), so that you can audit what information
+    /// is being dropped.  The item itself is still returned.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    /// Sets a timeout for each individual request made to PushShift.
+    ///
+    /// Requests which take longer than this fail with an [`Error::Http`], and are
+    /// skipped like any other failed request.  By default requests never time out.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a time limit for each stream returned by the client.
+    ///
+    /// Once `deadline` has elapsed since the stream was requested, the stream ends and
+    /// an [`Error::DeadlineExceeded`] is passed to the [error handler](ClientBuilder::on_error).
+    /// By default streams run until they're exhausted.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    /// use std::time::Duration;
+    ///
+    /// let client = Client::builder()
+    ///     .timeout(Duration::from_secs(30))
+    ///     .stream_deadline(Duration::from_secs(600))
+    ///     .build();
+    /// ```
+    pub fn stream_deadline(mut self, deadline: Duration) -> Self {
+        self.config.stream_deadline = Some(deadline);
+        self
+    }
+
+    /// Sets how long each stream returned by the client may go without a page of
+    /// items arriving.
+    ///
+    /// If `idle` passes while planning the stream or waiting for its next page,
+    /// including any retries of their requests, the stream ends and an
+    /// [`Error::IdleTimeout`] is passed to the [error handler](ClientBuilder::on_error),
+    /// so that a supervisor can restart a wedged crawl rather than waiting on it forever.
+    /// The date ranges the stream didn't get to are recorded in its
+    /// [failed ranges](crate::CommentStream::failed_ranges).  Time spent by the consumer
+    /// between items doesn't count, nor does time the stream spends
+    /// [paused](StreamControl::pause).  By default streams wait as long as it takes.
+    pub fn idle_timeout(mut self, idle: Duration) -> Self {
+        self.config.idle_timeout = Some(idle);
+        self
+    }
+
+    /// Sets a cache for the responses to requests made by the client.
+    ///
+    /// Requests which have a cached response are served from the cache, without
+    /// waiting on the rate limiter.  See the [`cache`](crate::cache) module.
+    pub fn cache<C: Cache + 'static>(mut self, cache: C) -> Self {
+        self.config.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Sets how many times a request is retried when PushShift says its results are
+    /// incomplete, because some of its shards didn't answer.
+    ///
+    /// Incomplete results are always reported as an [`Error::IncompleteResults`] to the
+    /// [error handler](ClientBuilder::on_error).  Defaults to 0, in which case the
+    /// incomplete results are used.  If every retry is also incomplete, the results of
+    /// the last one are used.
+    pub fn retry_incomplete(mut self, retries: usize) -> Self {
+        self.config.incomplete_retries = retries;
+        self
+    }
+
+    /// Limits how many of each stream's requests may be retried (because they were rate
+    /// limited, rejected with an expired token or returned incomplete results), so that
+    /// an outage doesn't cause a storm of retries.
+    ///
+    /// At most `ratio` of the requests a stream has made so far (e.g. `0.1` for 10%), and
+    /// at most `max_retries` in total, may be retried.  Once a stream runs out, it
+    /// reports an [`Error::RetryBudgetExhausted`] and ends.  By default retries are
+    /// only limited per request.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// let client = Client::builder().retry_budget(0.1, 100).build();
+    /// ```
+    pub fn retry_budget(mut self, ratio: f64, max_retries: usize) -> Self {
+        self.config.retry_budget = Some(RetryBudget { ratio, max_retries });
+        self
+    }
+
+    /// Sets how many pages of each stream are fetched ahead of the consumer.
+    ///
+    /// By default pages are only fetched when the stream is polled, so a consumer which
+    /// does slow work for each item (such as writing it to a database) also waits for
+    /// every request.  With prefetching, up to `pages` pages are requested by a
+    /// background task while the consumer is busy.  This requires a Tokio runtime.
+    pub fn prefetch_pages(mut self, pages: usize) -> Self {
+        self.config.prefetch_pages = pages;
+        self
+    }
+
+    /// Caps the number of items each stream fetches ahead of its consumer.
+    ///
+    /// By default a chunked query fetches a page from every bucket it queries at once,
+    /// regardless of how quickly the stream is consumed, so a slow consumer of a large
+    /// query can have thousands of items in memory.  With a cap, a page is only requested
+    /// once there's room for it, and room is freed up as the stream yields pages, so that
+    /// at most `max` items (plus the page being consumed) are held by each stream.  A
+    /// page larger than the cap is still fetched once nothing else is buffered.  This has
+    /// no effect on queries using [`Filter::ordered`], [`Filter::newest_first`] or
+    /// [`Filter::shuffled`], or sorted by something other than creation date.
+    pub fn max_buffered_items(mut self, max: usize) -> Self {
+        self.config.max_buffered_items = Some(max);
+        self
+    }
+
+    /// Enables a circuit breaker, which pauses every request made by the client (and its
+    /// clones) after `failures` consecutive requests fail because PushShift looks to be
+    /// down, i.e. because it couldn't be reached or responded with a server error.
+    ///
+    /// While the circuit is open, a single request is let through every `probe_interval`
+    /// to check whether PushShift has recovered, and the rest resume once one succeeds.
+    /// Use [`Client::circuit_state`] to find out whether requests are currently paused.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    /// use std::time::Duration;
+    ///
+    /// let client = Client::builder()
+    ///     .circuit_breaker(10, Duration::from_secs(60))
+    ///     .build();
+    /// ```
+    pub fn circuit_breaker(mut self, failures: u32, probe_interval: Duration) -> Self {
+        self.config.circuit_breaker = Some(CircuitBreaker::new(failures, probe_interval));
+        self
+    }
+
+    /// Sends requests to the first of an ordered list of PushShift-compatible backends
+    /// (e.g. `https://api.pullpush.io/` or a private mirror), failing over to the next
+    /// backend once `failures` consecutive requests to the current one fail.
+    ///
+    /// A request counts as failed if the backend couldn't be reached, responded with a
+    /// server error or kept rate limiting it.  Requests after a failover go to the new
+    /// backend, wrapping around to the first after the last, and [`Hooks::on_failover`]
+    /// is called.  Responses are cached under their PushShift URL, so a cache is shared
+    /// between backends.  Use [`Client::backend`] to find out which backend is in use.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    /// use reqwest::Url;
+    ///
+    /// let backends = ["https://api.pushshift.io/", "https://api.pullpush.io/"];
+    /// let client = Client::builder()
+    ///     .failover(backends.iter().map(|url| Url::parse(url).unwrap()), 5)
+    ///     .build();
+    /// ```
+    pub fn failover<I>(mut self, backends: I, failures: u32) -> Self
+    where
+        I: IntoIterator<Item = Url>,
+    {
+        self.config.failover = Failover::new(backends.into_iter().collect(), failures);
+        self
+    }
+
+    /// Spreads requests across the given clients, taking turns, e.g. to make approved
+    /// high volume collections through several network routes or mirrors.
+    ///
+    /// Each request is sent with the HTTP client of the next client in the pool, and
+    /// waits on that client's rate limiter, so each route keeps to its own rate limit.
+    /// Everything else (authentication, headers, retries, caching and so on) comes from
+    /// this builder, and streams are used as with any other client.  Middleware set on
+    /// the pooled clients isn't used, and [`Client::rate_limit_status`] only reports this
+    /// client's own limiter.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Client;
+    ///
+    /// let routes = ["http://proxy-a.example:3128", "http://proxy-b.example:3128"];
+    /// let clients = routes.iter().map(|proxy| {
+    ///     let http = reqwest::Client::builder()
+    ///         .proxy(reqwest::Proxy::all(*proxy).unwrap())
+    ///         .build()
+    ///         .unwrap();
+    ///     Client::with_client(http)
+    /// });
+    ///
+    /// let client = Client::builder().pool(clients).build();
+    /// ```
+    pub fn pool<I>(mut self, clients: I) -> Self
+    where
+        I: IntoIterator<Item = Client>,
+    {
+        let members = clients
+            .into_iter()
+            .map(|client| Member {
+                client: client.client,
+                limiter: client.limiter,
+            })
+            .collect();
+        self.config.pool = ClientPool::new(members);
+        self
+    }
+
+    /// Returns a [`Client`] that uses this `ClientBuilder` configuration.
+    pub fn build(mut self) -> Client {
+        self.config.batch_size = self
+            .config
+            .batch_size
+            .clamp(1, self.config.api_version.max_batch_size());
+
+        Client {
+            client: self.client.unwrap_or_default(),
+            config: Arc::new(self.config),
+            limiter: match self.rate_limit_backend {
+                Some(backend) => Arc::new(Limiter::with_backend(backend)),
+                None => self.rate_limit_mode.limiter(),
+            },
+            in_flight: Arc::default(),
+            control: None,
+            retry_budget: None,
+            item_buffer: None,
+            tracking: None,
+            waited: None,
+            page_timings: None,
+        }
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_is_send_and_sync() {
+        fn is_send_and_sync<T: Send + Sync>() {}
+        is_send_and_sync::<Client>();
+    }
+
+    #[tokio::test]
+    async fn test_pages_reports_failed_range() {
+        use crate::test_util::serve;
+        use chrono::TimeZone;
+
+        let date = |ts| Some(Utc.timestamp_opt(ts, 0).unwrap());
+        let page = r#"{"data": [
+            {"id": "a", "created_utc": 300},
+            {"id": "b", "created_utc": 200}
+        ]}"#;
+        let url = serve(vec![(200, page), (500, "")]).await;
+
+        let skipped = Arc::new(Mutex::new(Vec::new()));
+        let client = Client::builder()
+            .batch_size(2)
+            .on_skipped({
+                let skipped = skipped.clone();
+                move |record: &Skipped| skipped.lock().unwrap().push(record.clone())
+            })
+            .discover_rate_limit(false)
+            .build();
+        let filter = Filter::new()
+            .sort(SortDirection::Desc)
+            .after(date(100).unwrap());
+        let request = client.page_request(filter);
+        let pages: Vec<Vec<Comment>> = client.pages(url, request, Some(5)).collect().await;
+        assert_eq!(pages.len(), 1);
+
+        // The range from the start of the query up to the last item fetched is lost.
+        let skipped = skipped.lock().unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].count, Some(3));
+        assert_eq!(
+            skipped[0].reason,
+            SkipReason::FailedRange(FailedRange {
+                after: date(100),
+                before: date(200),
+                values: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pages_return_every_item_in_an_overfull_second() {
+        use crate::test_util::serve;
+
+        // More items were created at t=10 than fit in a page.
+        let first = r#"{"data": [
+            {"id": "5", "created_utc": 10},
+            {"id": "4", "created_utc": 10},
+            {"id": "3", "created_utc": 10}
+        ]}"#;
+        let rest = r#"{"data": [
+            {"id": "2", "created_utc": 10},
+            {"id": "1", "created_utc": 10}
+        ]}"#;
+        let next = r#"{"data": [{"id": "0", "created_utc": 9}]}"#;
+        let url = serve(vec![(200, first), (200, first), (200, rest), (200, next)]).await;
+
+        let client = Client::builder()
+            .batch_size(3)
+            .discover_rate_limit(false)
+            .build();
+        let request = client.page_request(Filter::new().sort(SortDirection::Desc));
+        let items: Vec<Comment> = client
+            .pages(url, request, None)
+            .flat_map(stream::iter)
+            .collect()
+            .await;
+        let ids: Vec<_> = items.iter().map(|item| item.attrs.id.as_str()).collect();
+        assert_eq!(ids, vec!["5", "4", "3", "2", "1", "0"]);
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_only_shared_within_a_stream() {
+        let page = r#"{"data": []}"#;
+        let url = crate::test_util::serve(vec![(200, page)]).await;
+        let client = Client::builder().discover_rate_limit(false).build();
+        let controlled = || Client {
+            control: Some(StreamControl::new()),
+            ..client.clone()
+        };
+
+        let paused = controlled();
+        paused.control.as_ref().unwrap().pause();
+        let waiting = tokio::spawn({
+            let url = url.clone();
+            async move {
+                paused
+                    .fetch_coalesced(url, Priority::default(), Box::new(|_| {}))
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The same request from another stream isn't held up by the paused one.
+        let fetched = tokio::time::timeout(
+            Duration::from_secs(5),
+            controlled().fetch_coalesced(url, Priority::default(), Box::new(|_| {})),
+        )
+        .await
+        .unwrap();
+        assert!(fetched.is_some());
+        assert!(!waiting.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_responses_are_not_cached() {
+        let incomplete = r#"{"data": [{"id": "a", "created_utc": 10}], "metadata": {
+            "total_results": 2, "timed_out": true
+        }}"#;
+        let complete = r#"{"data": [
+            {"id": "b", "created_utc": 20},
+            {"id": "a", "created_utc": 10}
+        ]}"#;
+        let url = crate::test_util::serve(vec![(200, incomplete), (200, complete)]).await;
+        let client = Client::builder()
+            .cache(crate::cache::MemoryCache::new())
+            .discover_rate_limit(false)
+            .build();
+
+        let items = || async {
+            let response = client
+                .get_url::<Comment>(url.clone(), Priority::default())
+                .await
+                .unwrap();
+            response.data.len()
+        };
+        // The incomplete response is used, but the request is made again next time.
+        assert_eq!(items().await, 1);
+        assert_eq!(items().await, 2);
+        // The complete one is served from the cache, as the server has nothing left.
+        assert_eq!(items().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_matching_subreddits_pages_through_search() {
+        let first = r#"{"data": [
+            {"display_name": "AskReddit", "id": "2qh1i", "created_utc": 20},
+            {"display_name": "rust", "id": "2s7lj", "created_utc": 10}
+        ]}"#;
+        let rest = r#"{"data": [
+            {"display_name": "AskHistory", "id": "2ssp3", "created_utc": 5}
+        ]}"#;
+        let url = crate::test_util::serve(vec![(200, first), (200, rest)]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .batch_size(2)
+            .discover_rate_limit(false)
+            .build();
+
+        let names = client
+            .matching_subreddits("ask*", Priority::default())
+            .await
+            .unwrap();
+        assert_eq!(names, vec!["AskHistory", "AskReddit"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_subreddit_search_is_a_failed_range() {
+        use chrono::TimeZone;
+
+        let url = crate::test_util::serve(vec![(500, "")]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .discover_rate_limit(false)
+            .build();
+        let after = Utc.timestamp_opt(100, 0).unwrap();
+        let filter = Filter::new().subreddit_matching("ask*").after(after);
+
+        let mut comments = client.get_comments(filter).await;
+        assert!(comments.next().await.is_none());
+        let range = FailedRange {
+            after: Some(after),
+            before: None,
+            values: None,
+        };
+        assert_eq!(comments.failed_ranges(), vec![range]);
+    }
+
+    #[tokio::test]
+    async fn test_ancestors_are_looked_up_a_level_at_a_time() {
+        let first = r#"{"data": [
+            {"id": "p1", "created_utc": 2, "parent_id": "t1_top"},
+            {"id": "p2", "created_utc": 2, "parent_id": "t1_gone"}
+        ]}"#;
+        let second = r#"{"data": [{"id": "top", "created_utc": 1}]}"#;
+        let url = crate::test_util::serve(vec![(200, first), (200, second)]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .discover_rate_limit(false)
+            .build();
+        let comment = |id: &str, parent: &str| -> Comment {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "created_utc": 3, "parent_id": parent
+            }))
+            .unwrap()
+        };
+
+        let comments = [comment("a", "t1_p1"), comment("b", "t1_p2")];
+        let ancestors = client.get_ancestors_of(&comments).await.unwrap();
+        let ids: Vec<Vec<&str>> = ancestors
+            .iter()
+            .map(|ancestors| {
+                ancestors
+                    .comments
+                    .iter()
+                    .map(|c| c.attrs.id.as_str())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(ids, vec![vec!["top", "p1"], vec!["p2"]]);
+        assert!(ancestors.iter().all(|ancestors| !ancestors.is_complete()));
+    }
+
+    #[tokio::test]
+    async fn test_run_batch() {
+        let metadata = r#"{"data": [], "metadata": {"total_results": 5}}"#;
+        let url = crate::test_util::serve(vec![(200, metadata), (500, "")]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .max_concurrent_buckets(1)
+            .discover_rate_limit(false)
+            .build();
+
+        let queries = [
+            BatchQuery::CountPosts(Filter::new()),
+            BatchQuery::CountComments(Filter::new()),
+        ];
+        let results: Vec<_> = client
+            .run_batch(queries)
+            .map(|(i, result)| match result {
+                Some(BatchResult::Count(count)) => (i, Some(count)),
+                Some(_) => panic!("unexpected result"),
+                None => (i, None),
+            })
+            .collect()
+            .await;
+        // The second query's request failed.
+        assert_eq!(results, vec![(0, Some(5)), (1, None)]);
+    }
+
+    #[tokio::test]
+    async fn test_run_batch_caps_queries_in_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Requests(AtomicUsize);
+
+        impl Hooks for Arc<Requests> {
+            fn on_request(&self, _url: &Url) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let url = crate::test_util::serve_then_stall(Vec::new()).await;
+        let requests = Arc::new(Requests::default());
+        let client = Client::builder()
+            .failover([url], 1)
+            .max_concurrent_buckets(2)
+            .hooks(requests.clone())
+            .discover_rate_limit(false)
+            .build();
+
+        let queries = (0..5).map(|i| BatchQuery::CountPosts(Filter::new().author(i.to_string())));
+        let mut results = client.run_batch(queries);
+        // Requests are let through once a second, so without the cap a third would
+        // have been sent by now.
+        let waited = tokio::time::timeout(Duration::from_millis(2500), results.next()).await;
+        assert!(waited.is_err());
+        // Only two of the queries were started, and neither was answered.
+        assert_eq!(requests.0.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_author_status() {
+        let renamed = r#"{"data": [{
+            "id": "a", "created_utc": 10, "author": "new", "author_fullname": "t2_x"
+        }]}"#;
+        let url = crate::test_util::serve(vec![(200, renamed), (500, "")]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .discover_rate_limit(false)
+            .build();
+        client.config.authors.insert("old", "t2_x");
+
+        let status = client.author_status("old", 0).await;
+        let renamed = AuthorStatus::Renamed {
+            id: "t2_x".to_string(),
+            name: "new".to_string(),
+        };
+        assert_eq!(status, Some(renamed));
+        // A failed request isn't taken to mean that the author has no content.
+        assert_eq!(client.author_status("old", 0).await, None);
+        assert_eq!(
+            client.author_status("unknown", 0).await,
+            Some(AuthorStatus::NotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawn_comments() {
+        let page = r#"{"data": [{"id": "a", "created_utc": 10}]}"#;
+        let url = crate::test_util::serve(vec![(200, page)]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .chunking(Chunking::Disabled)
+            .batch_size(2)
+            .discover_rate_limit(false)
+            .build();
+
+        let mut comments = client.spawn_comments(Filter::new(), 1);
+        assert_eq!(comments.recv().await.unwrap().attrs.id, "a");
+        assert!(comments.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_records_failed_range() {
+        use crate::test_util::serve_then_stall;
+        use chrono::TimeZone;
+
+        let date = |ts| Utc.timestamp_opt(ts, 0).unwrap();
+        let page = r#"{"data": [
+            {"id": "b", "created_utc": 20},
+            {"id": "a", "created_utc": 10}
+        ]}"#;
+        let url = serve_then_stall(vec![(200, page)]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .chunking(Chunking::Disabled)
+            .batch_size(2)
+            .idle_timeout(Duration::from_millis(200))
+            .discover_rate_limit(false)
+            .build();
+        let filter = Filter::new().sort(SortDirection::Desc).after(date(0));
+
+        let mut comments = client.get_comments(filter.clone()).await;
+        let ids: Vec<_> = comments.by_ref().map(|c| c.attrs.id).collect().await;
+        assert_eq!(ids, vec!["b", "a"]);
+        // What's left of the range once the next page stalled.
+        let remaining = FailedRange {
+            after: Some(date(0)),
+            before: Some(date(10)),
+            values: None,
+        };
+        assert_eq!(comments.failed_ranges(), vec![remaining]);
+
+        // Planning the stream is covered by the timeout too.
+        let url = serve_then_stall(Vec::new()).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .idle_timeout(Duration::from_millis(200))
+            .discover_rate_limit(false)
+            .build();
+        let mut comments = client.get_comments(filter).await;
+        assert_eq!(comments.by_ref().count().await, 0);
+        let whole = FailedRange {
+            after: Some(date(0)),
+            before: None,
+            values: None,
+        };
+        assert_eq!(comments.failed_ranges(), vec![whole]);
+    }
+
+    #[tokio::test]
+    async fn test_sample_is_empty_if_the_total_is_unknown() {
+        use crate::test_util::serve;
+
+        let page = r#"{"data": [{"id": "a", "created_utc": 10}]}"#;
+        let url = serve(vec![(500, ""), (200, page), (200, page)]).await;
+        let client = Client::builder()
+            .failover([url], 1)
+            .discover_rate_limit(false)
+            .build();
+
+        // The items aren't all fetched just because the total couldn't be looked up.
+        let comments: Vec<_> = client
+            .sample_comments(Filter::new(), 10)
+            .await
+            .collect()
+            .await;
+        assert!(comments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pages_report_skipped_second() {
+        use crate::test_util::serve;
+        use chrono::TimeZone;
+
+        let full = r#"{"data": [
+            {"id": "5", "created_utc": 10},
+            {"id": "4", "created_utc": 10},
+            {"id": "3", "created_utc": 10}
+        ]}"#;
+        let next = r#"{"data": [{"id": "0", "created_utc": 9}]}"#;
+        // The backend ignores the ID bound, so the second can't be paged through.
+        let url = serve(vec![(200, full), (200, full), (200, full), (200, next)]).await;
+
+        let skipped = Arc::new(Mutex::new(Vec::new()));
+        let client = Client::builder()
+            .batch_size(3)
+            .discover_rate_limit(false)
+            .on_skipped({
+                let skipped = skipped.clone();
+                move |record: &Skipped| skipped.lock().unwrap().push(record.clone())
+            })
+            .build();
+        let request = client.page_request(Filter::new().sort(SortDirection::Desc));
+        let items: Vec<Comment> = client
+            .pages(url, request, None)
+            .flat_map(stream::iter)
+            .collect()
+            .await;
+        assert_eq!(items.len(), 4);
+
+        let skipped = skipped.lock().unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(
+            skipped[0].reason,
+            SkipReason::OverfullSecond {
+                date: Utc.timestamp_opt(10, 0).unwrap()
+            }
+        );
+        assert!(skipped[0]
+            .params()
+            .contains(&("before_id".to_string(), "3".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_open_circuit_breaker() {
+        let url = crate::test_util::serve(vec![(500, ""), (503, "")]).await;
+        let client = Client::builder()
+            .circuit_breaker(2, Duration::from_secs(60))
+            .discover_rate_limit(false)
+            .build();
+
+        for _ in 0..2 {
+            assert!(client
+                .fetch(url.clone(), Priority::default(), Box::new(|_| {}))
+                .await
+                .is_none());
+        }
+        assert_eq!(client.circuit_state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_plan_with_windows() {
+        use chrono::TimeZone;
+
+        let date = |ts| Utc.timestamp_opt(ts, 0).unwrap();
+        let windows = vec![(date(100), date(200)), (date(200), date(300))];
+        // Planning with windows doesn't need any requests.
+        let client = Client::builder()
+            .api_version(ApiVersion::Legacy)
+            .chunking(Chunking::Disabled)
+            .discover_rate_limit(false)
+            .build();
+        let plan = client
+            .plan_comments(Filter::new().with_windows(windows.clone()))
+            .await;
+
+        assert_eq!(plan.windows, windows);
+        assert_eq!(plan.total_results, None);
+        assert_eq!(plan.requests.len(), 2);
+        assert!(plan.requests[0].query().unwrap().contains("after=100"));
+    }
+
+    #[test]
+    fn test_page_query_matches_params_url() {
+        use chrono::TimeZone;
+
+        let date = |ts| Utc.timestamp_opt(ts, 0).unwrap();
+        for version in [ApiVersion::Legacy, ApiVersion::Authenticated] {
+            let client = Client::builder()
+                .api_version(version)
+                .discover_rate_limit(false)
+                .build();
+            let url = version.url(Endpoint::Comments);
+            let filter = Filter::new()
+                .subreddit("rust")
+                .author("dcchut")
+                .after(date(100));
+
+            let params = PushShiftQueryParams::new(&filter, 25);
+            let mut query = params.to_page_query(version);
+            assert_eq!(query.url(&url), client.params_url(url.clone(), params));
+
+            query.before = Some(date(200));
+            let filter = filter.clone().before(date(200));
+            let params = PushShiftQueryParams::new(&filter, 25);
+            assert_eq!(query.url(&url), client.params_url(url.clone(), params));
+
+            // Relative bounds are sent as they're written.
+            let filter = filter.before_relative("24h").unwrap();
+            let params = PushShiftQueryParams::new(&filter, 25);
+            let query = params.to_page_query(version);
+            assert_eq!(query.url(&url), client.params_url(url.clone(), params));
+            let before = version.param_name("before");
+            assert!(query
+                .url(&url)
+                .query_pairs()
+                .any(|(key, value)| key == before && value == "24h"));
+
+            // Both bounds of a range of values are sent.
+            let filter = restrict_values(
+                Filter::new().sort_type(SortType::Score),
+                (Some(10), Some(20)),
+            );
+            let params = PushShiftQueryParams::new(&filter, 25);
+            let query = params.to_page_query(version);
+            let page_url = query.url(&url);
+            assert_eq!(page_url, client.params_url(url.clone(), params));
+            let score = version.param_name("score");
+            let bounds: Vec<_> = page_url
+                .query_pairs()
+                .filter(|(key, _)| key == score)
+                .map(|(_, value)| value.into_owned())
+                .collect();
+            assert_eq!(bounds, vec![">9", "<21"]);
+        }
+    }
+
+    #[test]
+    fn test_report_dispatches_to_hooks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counts {
+            items: AtomicUsize,
+            errors: AtomicUsize,
+        }
+
+        impl Hooks for Arc<Counts> {
+            fn on_item_error(&self, _error: &Error) {
+                self.items.fetch_add(1, Ordering::Relaxed);
+            }
+
+            fn on_error(&self, _error: &Error) {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let counts = Arc::new(Counts::default());
+        let client = Client::builder()
+            .hooks(counts.clone())
+            .discover_rate_limit(false)
+            .build();
+        client.report(Error::UnknownFields {
+            id: None,
+            fields: vec!["gilded".to_string()],
+        });
+        client.report(Error::DeadlineExceeded);
+        client.report(Error::RateLimited);
+
+        assert_eq!(counts.items.load(Ordering::Relaxed), 1);
+        assert_eq!(counts.errors.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_default_headers() {
+        let client = Client::builder()
+            .default_header("x-mirror-key", "secret")
+            .user_agent("archiver/1.0")
+            .user_agent("archiver/2.0")
+            .discover_rate_limit(false)
+            .build();
+
+        let headers = &client.config.headers;
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[USER_AGENT], "archiver/2.0");
+        assert_eq!(headers["x-mirror-key"], "secret");
+    }
+
+    #[test]
+    fn test_query_pairs_follow_api_version() {
+        let filter = Filter::new().author("reddit").sort(SortDirection::Desc);
+        let params = PushShiftQueryParams {
+            metadata: true,
+            ..PushShiftQueryParams::new(&filter, 1)
+        };
+
+        let legacy = params.to_pairs(ApiVersion::Legacy);
+        assert!(legacy.contains(&("author".to_string(), "reddit".to_string())));
+        assert!(legacy.contains(&("sort".to_string(), "desc".to_string())));
+        assert!(legacy.contains(&("sort_type".to_string(), "created_utc".to_string())));
+        assert!(!legacy.iter().any(|(key, _)| key == "before"));
+
+        let authenticated = params.to_pairs(ApiVersion::Authenticated);
+        assert!(authenticated.contains(&("order".to_string(), "desc".to_string())));
+        assert!(authenticated.contains(&("sort".to_string(), "created_utc".to_string())));
+        assert!(authenticated.contains(&("track_total_hits".to_string(), "true".to_string())));
+    }
+
+    #[test]
+    fn test_url_for() {
+        let client = Client::builder()
+            .api_version(ApiVersion::Authenticated)
+            .batch_size(250)
+            .discover_rate_limit(false)
+            .build();
+        let filter = Filter::new()
+            .subreddit("rust")
+            .after(Utc.timestamp_opt(1_600_000_000, 0).unwrap())
+            .limit(10);
+
+        let url = client.url_for(Endpoint::Comments, &filter);
+        assert_eq!(url.path(), "/reddit/search/comment/");
+
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert!(pairs.contains(&("subreddit".to_string(), "rust".to_string())));
+        assert!(pairs.contains(&("since".to_string(), "1600000000".to_string())));
+        assert!(pairs.contains(&("limit".to_string(), "10".to_string())));
     }
 }
@@ -0,0 +1,122 @@
+use once_cell::sync::Lazy;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+
+/// The cap on HTTP requests in flight across every client in the process.
+static GOVERNOR: Lazy<Governor> = Lazy::new(Governor::default);
+
+/// Limits the number of HTTP requests in flight at once across every [`Client`] (and
+/// every stream) in the process, or removes the limit with `None`.
+///
+/// This is separate from each client's rate limit, which bounds how often requests
+/// are started: it bounds how many are open at once, and so the memory and sockets
+/// used when many streams are running simultaneously.  Requests beyond the limit wait
+/// until one in flight finishes.  Defaults to no limit.
+///
+/// Lowering the limit doesn't interrupt requests already in flight.
+///
+/// [`Client`]: crate::Client
+pub fn set_max_in_flight_requests(limit: Option<usize>) {
+    GOVERNOR.set_limit(limit.map_or(usize::MAX, |limit| limit.max(1)));
+}
+
+/// Returns the number of HTTP requests currently in flight across every
+/// [`Client`](crate::Client) in the process.
+pub fn in_flight_requests() -> usize {
+    GOVERNOR.in_flight.load(Ordering::SeqCst)
+}
+
+/// Waits until a request can be sent without exceeding the process-wide limit, see
+/// [`set_max_in_flight_requests`].  The request counts as in flight until the returned
+/// guard is dropped.
+pub(crate) async fn acquire() -> InFlightRequest {
+    GOVERNOR.acquire().await
+}
+
+struct Governor {
+    limit: AtomicUsize,
+    in_flight: AtomicUsize,
+    /// Notified when a request finishes or the limit changes.
+    released: Notify,
+}
+
+impl Default for Governor {
+    fn default() -> Self {
+        Self {
+            limit: AtomicUsize::new(usize::MAX),
+            in_flight: AtomicUsize::new(0),
+            released: Notify::new(),
+        }
+    }
+}
+
+impl Governor {
+    fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::SeqCst);
+        self.released.notify_waiters();
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_flight| {
+                (in_flight < self.limit.load(Ordering::SeqCst)).then_some(in_flight + 1)
+            })
+            .is_ok()
+    }
+
+    async fn acquire(&'static self) -> InFlightRequest {
+        loop {
+            // Register for a notification before checking, so that a request finishing
+            // in between isn't missed.
+            let mut released = pin!(self.released.notified());
+            released.as_mut().enable();
+            if self.try_acquire() {
+                return InFlightRequest(self);
+            }
+            released.await;
+        }
+    }
+}
+
+/// A request counted towards the process-wide limit until it's dropped.
+pub(crate) struct InFlightRequest(&'static Governor);
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.0.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_limit() {
+        let governor: &'static Governor = Box::leak(Box::default());
+        governor.set_limit(1);
+
+        let first = governor.acquire().await;
+        let second = tokio::time::timeout(Duration::from_millis(50), governor.acquire()).await;
+        assert!(second.is_err());
+
+        let waiting = tokio::spawn(governor.acquire());
+        drop(first);
+        let _second = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(governor.in_flight.load(Ordering::SeqCst), 1);
+
+        // Raising the limit wakes requests which are waiting.
+        let waiting = tokio::spawn(governor.acquire());
+        governor.set_limit(2);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// A handle to pause and resume a stream, see [`Client::get_posts_controlled`].
+///
+/// While paused, the stream doesn't start any new requests, leaving the rate limit
+/// free for other queries; requests which are already being made are allowed to
+/// finish.  Handles can be cloned, and pausing any of them pauses the stream.
+///
+/// [`Client::get_posts_controlled`]: crate::Client::get_posts_controlled
+#[derive(Clone)]
+pub struct StreamControl {
+    paused: Arc<watch::Sender<bool>>,
+    // Kept so that the sender always has a receiver, and updates aren't rejected.
+    receiver: watch::Receiver<bool>,
+}
+
+impl StreamControl {
+    pub(crate) fn new() -> Self {
+        let (paused, receiver) = watch::channel(false);
+        Self {
+            paused: Arc::new(paused),
+            receiver,
+        }
+    }
+
+    /// Returns an ID shared by the clones of this control, and no other control.
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.paused) as usize
+    }
+
+    /// Pauses the stream before its next request.
+    pub fn pause(&self) {
+        let _ = self.paused.send(true);
+    }
+
+    /// Resumes the stream, if it was paused.
+    pub fn resume(&self) {
+        let _ = self.paused.send(false);
+    }
+
+    /// Returns whether the stream is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Waits until the stream isn't paused.
+    pub(crate) async fn until_resumed(&self) {
+        let mut receiver = self.receiver.clone();
+        while *receiver.borrow_and_update() {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Waits until the stream is paused.
+    pub(crate) async fn until_paused(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow_and_update() {
+            if receiver.changed().await.is_err() {
+                return std::future::pending().await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let control = StreamControl::new();
+        control.until_resumed().await;
+
+        control.pause();
+        assert!(control.is_paused());
+        let waiting = tokio::spawn({
+            let control = control.clone();
+            async move { control.until_resumed().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        control.resume();
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!control.is_paused());
+    }
+}
@@ -0,0 +1,259 @@
+//! Comparisons between snapshots of the same content, e.g. two fetches of a filter made
+//! a week apart.
+//!
+//! [`diff`] matches up the items of two snapshots by ID, returning the items which are
+//! new, the items which are missing from the later snapshot, and a [`Delta`] for each
+//! item in both, describing how its score, comment count and removal status changed.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() {
+//! use futures::StreamExt;
+//! use pullcaps::diff::diff;
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::new();
+//! let filter = Filter::new().subreddit("rust");
+//!
+//! let before: Vec<_> = client.get_posts(filter.clone()).await.collect().await;
+//! // ... some time later ...
+//! let after: Vec<_> = client.get_posts(filter).await.collect().await;
+//!
+//! let diff = diff(before, after);
+//! for changed in &diff.changed {
+//!     println!("{}: {:+}", changed.after.title, changed.delta.score);
+//! }
+//! println!("{} new, {} gone", diff.added.len(), diff.missing.len());
+//! # }
+//! ```
+use crate::models::{Comment, Content, Post, RemovalStatus, Thing};
+use std::collections::HashMap;
+
+/// The state of a post or comment which can change after it's created.
+pub trait Snapshot {
+    /// Returns the score.
+    fn score(&self) -> i32;
+
+    /// Returns the number of comments, if a post.
+    fn num_comments(&self) -> Option<i64>;
+
+    /// Returns whether, and how, the content has been removed.
+    fn removal_status(&self) -> RemovalStatus;
+}
+
+impl Snapshot for Post {
+    fn score(&self) -> i32 {
+        self.attrs.score
+    }
+
+    fn num_comments(&self) -> Option<i64> {
+        self.num_comments
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        Post::removal_status(self)
+    }
+}
+
+impl Snapshot for Comment {
+    fn score(&self) -> i32 {
+        self.attrs.score
+    }
+
+    fn num_comments(&self) -> Option<i64> {
+        None
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        Comment::removal_status(self)
+    }
+}
+
+impl Snapshot for Content {
+    fn score(&self) -> i32 {
+        self.attrs().score
+    }
+
+    fn num_comments(&self) -> Option<i64> {
+        match self {
+            Self::Post(post) => post.num_comments,
+            Self::Comment(_) => None,
+        }
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        Content::removal_status(self)
+    }
+}
+
+/// How a single item changed between two snapshots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delta {
+    /// The change in score.
+    pub score: i32,
+
+    /// The change in the number of comments, if both snapshots know it.
+    pub num_comments: Option<i64>,
+
+    /// The removal status in the earlier snapshot.
+    pub removal_before: RemovalStatus,
+
+    /// The removal status in the later snapshot.
+    pub removal_after: RemovalStatus,
+}
+
+impl Delta {
+    /// Computes how an item changed from `before` to `after`.
+    pub fn between<A: Snapshot + ?Sized, B: Snapshot + ?Sized>(before: &A, after: &B) -> Self {
+        let num_comments = match (before.num_comments(), after.num_comments()) {
+            (Some(before), Some(after)) => Some(after - before),
+            _ => None,
+        };
+        Self {
+            score: after.score() - before.score(),
+            num_comments,
+            removal_before: before.removal_status(),
+            removal_after: after.removal_status(),
+        }
+    }
+
+    /// Returns whether the item was visible in the earlier snapshot, but has been
+    /// removed or deleted since.
+    pub fn removed_since(&self) -> bool {
+        self.removal_before.is_visible() && !self.removal_after.is_visible()
+    }
+
+    /// Returns whether nothing about the item changed.
+    pub fn is_unchanged(&self) -> bool {
+        self.score == 0
+            && self.num_comments.unwrap_or(0) == 0
+            && self.removal_before == self.removal_after
+    }
+}
+
+/// An item found in both snapshots.
+#[derive(Clone, Debug)]
+pub struct Changed<T> {
+    pub before: T,
+    pub after: T,
+    pub delta: Delta,
+}
+
+/// The differences between two snapshots, see [`diff`].
+#[derive(Clone, Debug)]
+pub struct Diff<T> {
+    /// Items only in the later snapshot, in the order they appear there.
+    pub added: Vec<T>,
+
+    /// Items only in the earlier snapshot, in the order they appear there.  These have
+    /// usually been deleted, or no longer match the filter.
+    pub missing: Vec<T>,
+
+    /// Items in both snapshots which changed, in the order they appear in the later
+    /// snapshot.
+    pub changed: Vec<Changed<T>>,
+
+    /// The number of items in both snapshots which didn't change.
+    pub unchanged: usize,
+}
+
+/// Compares two snapshots of the same content, matching up their items by ID.
+///
+/// If a snapshot contains an item more than once, the last copy is used.
+pub fn diff<T, A, B>(before: A, after: B) -> Diff<T>
+where
+    T: Thing + Snapshot,
+    A: IntoIterator<Item = T>,
+    B: IntoIterator<Item = T>,
+{
+    let mut earlier: Vec<Option<T>> = Vec::new();
+    let mut index = HashMap::new();
+    for item in before {
+        match index.get(&item.attrs().id) {
+            Some(&i) => earlier[i] = Some(item),
+            None => {
+                index.insert(item.attrs().id.clone(), earlier.len());
+                earlier.push(Some(item));
+            }
+        }
+    }
+
+    let mut diff = Diff {
+        added: Vec::new(),
+        missing: Vec::new(),
+        changed: Vec::new(),
+        unchanged: 0,
+    };
+    let mut later: Vec<T> = Vec::new();
+    let mut seen = HashMap::new();
+    for item in after {
+        match seen.get(&item.attrs().id) {
+            Some(&i) => later[i] = item,
+            None => {
+                seen.insert(item.attrs().id.clone(), later.len());
+                later.push(item);
+            }
+        }
+    }
+
+    for item in later {
+        let before = index.get(&item.attrs().id).and_then(|&i| earlier[i].take());
+        match before {
+            Some(before) => {
+                let delta = Delta::between(&before, &item);
+                if delta.is_unchanged() {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed.push(Changed {
+                        before,
+                        after: item,
+                        delta,
+                    });
+                }
+            }
+            None => diff.added.push(item),
+        }
+    }
+    diff.missing = earlier.into_iter().flatten().collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::comment;
+
+    fn scored(id: &str, score: i32) -> Comment {
+        let mut comment = comment(id, 0);
+        comment.attrs.score = score;
+        comment
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = vec![scored("a", 1), scored("b", 5), scored("c", 2)];
+        let mut removed = scored("c", 2);
+        removed.body = "[removed]".to_string();
+        let after = vec![scored("d", 1), scored("b", 8), removed, scored("b", 9)];
+
+        let diff = diff(before, after);
+        let ids = |items: &[Comment]| -> Vec<String> {
+            items.iter().map(|item| item.attrs.id.clone()).collect()
+        };
+        assert_eq!(ids(&diff.added), vec!["d"]);
+        assert_eq!(ids(&diff.missing), vec!["a"]);
+        assert_eq!(diff.unchanged, 0);
+
+        let deltas: Vec<(String, Delta)> = diff
+            .changed
+            .iter()
+            .map(|changed| (changed.after.attrs.id.clone(), changed.delta))
+            .collect();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].0, "b");
+        assert_eq!(deltas[0].1.score, 4);
+        assert!(!deltas[0].1.removed_since());
+        assert_eq!(deltas[1].0, "c");
+        assert!(deltas[1].1.removed_since());
+    }
+}
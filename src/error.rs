@@ -0,0 +1,197 @@
+use crate::models::Metadata;
+use crate::stream::FailedRange;
+use chrono::{DateTime, Utc};
+use reqwest::{StatusCode, Url};
+use std::fmt;
+
+/// The most bytes of a response body kept in an [`Error`], for debugging.
+const MAX_SNIPPET_LEN: usize = 512;
+
+/// An error encountered while querying the PushShift API.
+///
+/// Streams returned by a [`Client`] skip over any data affected by an error, rather
+/// than ending; use [`ClientBuilder::on_error`] to be notified of these errors.
+///
+/// [`Client`]: crate::Client
+/// [`ClientBuilder::on_error`]: crate::ClientBuilder::on_error
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// A request couldn't be sent, or its response couldn't be read.
+    Http(reqwest::Error),
+    /// The server responded with an unsuccessful status code.
+    Status {
+        status: StatusCode,
+        /// The URL of the response, after following any redirects.
+        url: Url,
+        /// The start of the response body, e.g. an HTML error page.
+        body: String,
+    },
+    /// The server kept rejecting a request for exceeding its rate limit.
+    RateLimited,
+    /// A response couldn't be parsed.
+    InvalidResponse {
+        /// The URL of the request.
+        url: Url,
+        error: serde_json::Error,
+        /// The start of the response body.
+        body: String,
+    },
+    /// A single item in a response couldn't be deserialized, and was skipped.
+    InvalidItem {
+        /// The ID of the item, if it had one.
+        id: Option<String>,
+        error: serde_json::Error,
+    },
+    /// An item contained fields which aren't captured by the models.  This is only
+    /// reported in [strict mode](crate::ClientBuilder::strict).
+    UnknownFields {
+        /// The ID of the item, if it had one.
+        id: Option<String>,
+        fields: Vec<String>,
+    },
+    /// A stream reached its [deadline](crate::ClientBuilder::stream_deadline), and
+    /// ended early.
+    DeadlineExceeded,
+    /// A stream went longer than its [idle timeout](crate::ClientBuilder::idle_timeout)
+    /// without a page of items arriving, and ended early.
+    IdleTimeout,
+    /// Some of PushShift's shards didn't answer a query, so its results are incomplete.
+    IncompleteResults(Metadata),
+    /// A stream ran out of its [retry budget](crate::ClientBuilder::retry_budget), and
+    /// ended early.
+    RetryBudgetExhausted,
+    /// A stream gave up on part of its query, e.g. a bucket of a chunked query, after
+    /// a request failed.  The rest of the stream carries on.
+    RangeFailed(FailedRange),
+    /// A [middleware](crate::ClientBuilder::middleware_client) failed a request.
+    #[cfg(feature = "middleware")]
+    Middleware(reqwest_middleware::Error),
+    /// A request couldn't be recorded to the client's [WARC file](crate::warc).
+    #[cfg(feature = "warc")]
+    Warc(std::io::Error),
+    /// A [compressed](crate::ClientBuilder::compression) response couldn't be
+    /// decompressed.
+    #[cfg(feature = "compression")]
+    Decompress(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(error) => write!(f, "request failed: {}", error),
+            Self::Status { status, url, body } => {
+                write!(
+                    f,
+                    "server responded with {} for {}: {:?}",
+                    status, url, body
+                )
+            }
+            Self::RateLimited => write!(f, "request was repeatedly rate limited"),
+            Self::InvalidResponse { url, error, body } => {
+                write!(f, "invalid response from {}: {}: {:?}", url, error, body)
+            }
+            Self::InvalidItem {
+                id: Some(id),
+                error,
+            } => {
+                write!(f, "invalid item {}: {}", id, error)
+            }
+            Self::InvalidItem { id: None, error } => write!(f, "invalid item: {}", error),
+            Self::UnknownFields { id, fields } => write!(
+                f,
+                "item {} has unknown fields: {}",
+                id.as_deref().unwrap_or("<unknown>"),
+                fields.join(", ")
+            ),
+            Self::DeadlineExceeded => write!(f, "stream deadline exceeded"),
+            Self::IdleTimeout => write!(f, "stream idle timeout exceeded"),
+            Self::IncompleteResults(metadata) => match &metadata.shards {
+                Some(shards) => write!(
+                    f,
+                    "incomplete results: only {} of {} shards answered",
+                    shards.successful, shards.total
+                ),
+                None => write!(f, "incomplete results: query timed out"),
+            },
+            Self::RetryBudgetExhausted => write!(f, "stream exhausted its retry budget"),
+            Self::RangeFailed(range) => {
+                let date = |date: Option<DateTime<Utc>>| match date {
+                    Some(date) => date.to_rfc3339(),
+                    None => "unbounded".to_string(),
+                };
+                write!(
+                    f,
+                    "failed to fetch items created between {} and {}",
+                    date(range.after),
+                    date(range.before)
+                )
+            }
+            #[cfg(feature = "middleware")]
+            Self::Middleware(error) => write!(f, "middleware failed: {}", error),
+            #[cfg(feature = "warc")]
+            Self::Warc(error) => write!(f, "failed to record request: {}", error),
+            #[cfg(feature = "compression")]
+            Self::Decompress(error) => write!(f, "failed to decompress response: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(error) => Some(error),
+            #[cfg(feature = "middleware")]
+            Self::Middleware(error) => Some(error),
+            #[cfg(feature = "warc")]
+            Self::Warc(error) => Some(error),
+            #[cfg(feature = "compression")]
+            Self::Decompress(error) => Some(error),
+            Self::InvalidResponse { error, .. } | Self::InvalidItem { error, .. } => Some(error),
+            Self::Status { .. }
+            | Self::RateLimited
+            | Self::UnknownFields { .. }
+            | Self::DeadlineExceeded
+            | Self::IdleTimeout
+            | Self::IncompleteResults(_)
+            | Self::RetryBudgetExhausted
+            | Self::RangeFailed(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "middleware")]
+impl From<reqwest_middleware::Error> for Error {
+    fn from(error: reqwest_middleware::Error) -> Self {
+        match error {
+            reqwest_middleware::Error::Reqwest(error) => Self::Http(error),
+            error => Self::Middleware(error),
+        }
+    }
+}
+
+/// Returns the start of a response body as text, for inclusion in an [`Error`].
+pub(crate) fn snippet(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(MAX_SNIPPET_LEN)]);
+    // A multi-byte character may have been cut in half at the end.
+    text.trim_end_matches(char::REPLACEMENT_CHARACTER)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippet() {
+        assert_eq!(
+            snippet(b"<html>Bad Gateway</html>"),
+            "<html>Bad Gateway</html>"
+        );
+
+        let body = "é".repeat(MAX_SNIPPET_LEN);
+        let snippet = snippet(body.as_bytes());
+        assert!(snippet.len() <= MAX_SNIPPET_LEN);
+        assert!(snippet.chars().all(|c| c == 'é'));
+    }
+}
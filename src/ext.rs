@@ -0,0 +1,277 @@
+use crate::models::{AsAttrs, Thing};
+use crate::IntoDateTime;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
+use futures::{future, ready, Stream, StreamExt};
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Adapters for refining streams of posts and comments client-side.
+///
+/// This is implemented for every [`Stream`] of [`Thing`]'s, e.g. those returned by
+/// [`Client::get_posts`] and [`Client::get_comments`].  Where a refinement can be
+/// expressed as a [`Filter`] instead, that's usually better, as PushShift then doesn't
+/// have to return the items which would be thrown away.
+///
+/// # Example
+/// ```rust,no_run
+/// # async fn example() {
+/// use futures::StreamExt;
+/// use pullcaps::{Client, Filter, PullcapsStreamExt};
+///
+/// let client = Client::new();
+///
+/// let mut comments = client
+///     .get_comments(Filter::new().subreddit("rust"))
+///     .await
+///     .dedup_by_id()
+///     .min_score(10);
+///
+/// while let Some(comment) = comments.next().await {
+///     println!("{}", comment.body);
+/// }
+/// # }
+/// ```
+///
+/// [`Client::get_posts`]: crate::Client::get_posts
+/// [`Client::get_comments`]: crate::Client::get_comments
+/// [`Filter`]: crate::Filter
+pub trait PullcapsStreamExt: Stream + Sized
+where
+    Self::Item: Thing,
+{
+    /// Skips any item with the same ID as one yielded earlier, e.g. when merging streams
+    /// whose queries overlap.
+    ///
+    /// The IDs of every item yielded are kept for as long as the stream is.
+    fn dedup_by_id(self) -> impl Stream<Item = Self::Item> {
+        let mut seen = HashSet::new();
+        self.filter(move |item| future::ready(seen.insert(item.attrs().id.clone())))
+    }
+
+    /// Ends the stream at the first item created before `cutoff`.
+    ///
+    /// This is intended for streams sorted newest first (the default), which it ends
+    /// without fetching the rest of the older items.  Items in a stream which isn't
+    /// [ordered](crate::Filter::ordered) aren't sorted, so it may end the stream early.
+    fn until_date<D: IntoDateTime>(self, cutoff: D) -> impl Stream<Item = Self::Item> {
+        let cutoff = cutoff.into_date_time();
+        self.take_while(move |item| future::ready(item.attrs().date >= cutoff))
+    }
+
+    /// Skips any item with a score lower than `score`.
+    fn min_score(self, score: i32) -> impl Stream<Item = Self::Item> {
+        self.filter(move |item| future::ready(item.attrs().score >= score))
+    }
+
+    /// Skips any item not written by the user with the given name, ignoring case.
+    fn by_author<S: Into<String>>(self, name: S) -> impl Stream<Item = Self::Item> {
+        let name = name.into();
+        self.filter(move |item| {
+            let matches = item
+                .author()
+                .name()
+                .is_some_and(|author| author.eq_ignore_ascii_case(&name));
+            future::ready(matches)
+        })
+    }
+
+    /// Groups consecutive items created in the same period (in UTC) together, yielding
+    /// the start of each period with its items.
+    ///
+    /// This is intended for streams sorted by creation date (in either direction), e.g.
+    /// to compute a time series as items arrive.  Items in a stream which isn't
+    /// [ordered](crate::Filter::ordered) aren't sorted, so the same period may be
+    /// yielded more than once.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter, Period, PullcapsStreamExt};
+    ///
+    /// let client = Client::new();
+    ///
+    /// let filter = Filter::new().subreddit("rust").ordered();
+    /// let mut days = client.get_comments(filter).await.group_by_period(Period::Day);
+    /// while let Some((day, comments)) = days.next().await {
+    ///     println!("{}: {} comments", day.date_naive(), comments.len());
+    /// }
+    /// # }
+    /// ```
+    fn group_by_period(self, period: Period) -> GroupByPeriod<Self> {
+        GroupByPeriod {
+            stream: Box::pin(self),
+            period,
+            current: None,
+            done: false,
+        }
+    }
+}
+
+impl<S: Stream> PullcapsStreamExt for S where S::Item: Thing {}
+
+/// A length of time which items can be grouped by, see
+/// [`PullcapsStreamExt::group_by_period`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// A week starting on a Monday.
+    Week,
+    Month,
+}
+
+impl Period {
+    /// Returns the start of the period containing `date`.
+    pub fn start_of(&self, date: DateTime<Utc>) -> DateTime<Utc> {
+        let day = date.date_naive();
+        let start = match self {
+            Self::Day => day,
+            Self::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+            Self::Month => day.with_day(1).unwrap(),
+        };
+        start.and_time(NaiveTime::MIN).and_utc()
+    }
+}
+
+/// A [`Stream`] of items grouped by the period they were created in, returned by
+/// [`PullcapsStreamExt::group_by_period`].
+#[must_use = "streams do nothing unless polled"]
+pub struct GroupByPeriod<S: Stream> {
+    stream: Pin<Box<S>>,
+    period: Period,
+    /// The start of the period being grouped, and its items so far.
+    current: Option<(DateTime<Utc>, Vec<S::Item>)>,
+    done: bool,
+}
+
+// The inner stream is boxed, so the adapter can be moved even when it isn't `Unpin`.
+impl<S: Stream> Unpin for GroupByPeriod<S> {}
+
+impl<S> Stream for GroupByPeriod<S>
+where
+    S: Stream,
+    S::Item: Thing,
+{
+    type Item = (DateTime<Utc>, Vec<S::Item>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while !this.done {
+            let item = match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(item) => item,
+                None => {
+                    this.done = true;
+                    break;
+                }
+            };
+
+            let start = this.period.start_of(item.attrs().date);
+            match &mut this.current {
+                Some((current, items)) if *current == start => items.push(item),
+                _ => {
+                    if let Some(group) = this.current.replace((start, vec![item])) {
+                        return Poll::Ready(Some(group));
+                    }
+                }
+            }
+        }
+        Poll::Ready(this.current.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Author, Comment};
+    use crate::test_util::comment;
+    use chrono::{TimeZone, Utc};
+    use futures::stream;
+
+    async fn ids(comments: impl Stream<Item = Comment>) -> Vec<String> {
+        comments
+            .map(|comment| comment.attrs.id)
+            .collect::<Vec<_>>()
+            .await
+    }
+
+    #[test]
+    fn test_adapters_are_send() {
+        fn is_send<T: Send>(_: T) {}
+        is_send(
+            stream::iter(vec![comment("a", 1)])
+                .dedup_by_id()
+                .min_score(1),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dedup_by_id() {
+        let comments = vec![comment("a", 3), comment("b", 2), comment("a", 3)];
+        let deduped = stream::iter(comments).dedup_by_id();
+        assert_eq!(ids(deduped).await, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_until_date() {
+        let comments = vec![comment("a", 30), comment("b", 20), comment("c", 10)];
+        let cutoff = Utc.timestamp_opt(20, 0).unwrap();
+        let recent = stream::iter(comments).until_date(cutoff);
+        assert_eq!(ids(recent).await, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_period_start_of() {
+        // Wednesday 2021-03-17 12:34:56 UTC.
+        let date = Utc.with_ymd_and_hms(2021, 3, 17, 12, 34, 56).unwrap();
+        let start = |y, m, d| Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+
+        assert_eq!(Period::Day.start_of(date), start(2021, 3, 17));
+        assert_eq!(Period::Week.start_of(date), start(2021, 3, 15));
+        assert_eq!(Period::Month.start_of(date), start(2021, 3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_group_by_period() {
+        const DAY: i64 = 24 * 60 * 60;
+        let comments = vec![
+            comment("a", 2 * DAY + 5),
+            comment("b", 2 * DAY + 1),
+            comment("c", DAY + 7),
+            comment("d", 10),
+        ];
+
+        let days: Vec<(i64, Vec<String>)> = stream::iter(comments)
+            .group_by_period(Period::Day)
+            .map(|(day, comments)| {
+                let ids = comments.into_iter().map(|c| c.attrs.id).collect();
+                (day.timestamp(), ids)
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            days,
+            vec![
+                (2 * DAY, vec!["a".to_string(), "b".to_string()]),
+                (DAY, vec!["c".to_string()]),
+                (0, vec!["d".to_string()]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_min_score_and_by_author() {
+        let mut popular = comment("a", 3);
+        popular.attrs.score = 50;
+        let mut other = comment("b", 2);
+        other.attrs.score = 50;
+        other.author = Author::Known {
+            id: None,
+            name: "dcchut".to_string(),
+        };
+        let comments = vec![popular, other, comment("c", 1)];
+
+        let refined = stream::iter(comments).min_score(10).by_author("Reddit");
+        assert_eq!(ids(refined).await, vec!["a"]);
+    }
+}
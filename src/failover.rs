@@ -0,0 +1,162 @@
+use crate::api::API_URL;
+use reqwest::Url;
+use std::sync::Mutex;
+
+/// An ordered list of PushShift-compatible backends, see [`ClientBuilder::failover`].
+///
+/// Requests go to the current backend until `threshold` of them fail in a row, at which
+/// point subsequent requests go to the next backend in the list (wrapping around to the
+/// first after the last).
+///
+/// [`ClientBuilder::failover`]: crate::ClientBuilder::failover
+pub(crate) struct Failover {
+    backends: Vec<Url>,
+    threshold: u32,
+    state: Mutex<FailoverState>,
+}
+
+#[derive(Default)]
+struct FailoverState {
+    /// The index of the current backend.
+    current: usize,
+    /// The number of consecutive requests to the current backend which have failed.
+    failures: u32,
+}
+
+impl Failover {
+    /// Creates a failover between the given backends, returning `None` if there aren't any.
+    pub(crate) fn new(backends: Vec<Url>, threshold: u32) -> Option<Self> {
+        if backends.is_empty() {
+            return None;
+        }
+        let backends = backends
+            .into_iter()
+            .map(|mut backend| {
+                // Paths are joined onto the backend, so it has to look like a directory.
+                if !backend.path().ends_with('/') {
+                    let path = format!("{}/", backend.path());
+                    backend.set_path(&path);
+                }
+                backend
+            })
+            .collect();
+        Some(Self {
+            backends,
+            threshold: threshold.max(1),
+            state: Mutex::new(FailoverState::default()),
+        })
+    }
+
+    /// Returns the base URL of the current backend.
+    pub(crate) fn current(&self) -> Url {
+        self.backends[self.state.lock().unwrap().current].clone()
+    }
+
+    /// Points a PushShift URL at the current backend, returning the index of the backend
+    /// along with the URL, so that the outcome of the request can be recorded.
+    pub(crate) fn route(&self, url: &Url) -> (usize, Url) {
+        let current = self.state.lock().unwrap().current;
+        let routed = url
+            .as_str()
+            .strip_prefix(API_URL)
+            .and_then(|path| self.backends[current].join(path).ok())
+            .unwrap_or_else(|| url.clone());
+        (current, routed)
+    }
+
+    /// Records that a request to the given backend succeeded.
+    pub(crate) fn record_success(&self, backend: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.current == backend {
+            state.failures = 0;
+        }
+    }
+
+    /// Records that a request to the given backend failed, returning the backends
+    /// switched from and to if this was one failure too many.
+    ///
+    /// Failures of requests made to a backend which has already been switched away
+    /// from are ignored.
+    pub(crate) fn record_failure(&self, backend: usize) -> Option<(Url, Url)> {
+        let mut state = self.state.lock().unwrap();
+        if state.current != backend || self.backends.len() < 2 {
+            return None;
+        }
+        state.failures += 1;
+        if state.failures < self.threshold {
+            return None;
+        }
+
+        state.current = (backend + 1) % self.backends.len();
+        state.failures = 0;
+        Some((
+            self.backends[backend].clone(),
+            self.backends[state.current].clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_route() {
+        let failover = Failover::new(
+            vec![
+                url("https://api.pullpush.io"),
+                url("https://mirror.test/ps"),
+            ],
+            1,
+        )
+        .unwrap();
+        let search = url("https://api.pushshift.io/reddit/comment/search/?author=reddit");
+
+        let (backend, routed) = failover.route(&search);
+        assert_eq!(backend, 0);
+        assert_eq!(
+            routed.as_str(),
+            "https://api.pullpush.io/reddit/comment/search/?author=reddit"
+        );
+
+        failover.record_failure(0);
+        let (_, routed) = failover.route(&search);
+        assert_eq!(
+            routed.as_str(),
+            "https://mirror.test/ps/reddit/comment/search/?author=reddit"
+        );
+
+        // URLs which aren't for PushShift are left alone.
+        let other = url("https://www.reddit.com/api/info.json");
+        assert_eq!(failover.route(&other).1, other);
+    }
+
+    #[test]
+    fn test_failover() {
+        let backends = vec![url("https://a.test"), url("https://b.test")];
+        let failover = Failover::new(backends, 2).unwrap();
+
+        assert_eq!(failover.record_failure(0), None);
+        failover.record_success(0);
+        assert_eq!(failover.record_failure(0), None);
+
+        let (from, to) = failover.record_failure(0).unwrap();
+        assert_eq!(
+            (from.as_str(), to.as_str()),
+            ("https://a.test/", "https://b.test/")
+        );
+        assert_eq!(failover.current().as_str(), "https://b.test/");
+
+        // A request which was already made to the old backend doesn't count.
+        assert_eq!(failover.record_failure(0), None);
+        failover.record_failure(1);
+        assert!(failover.record_failure(1).is_some());
+        assert_eq!(failover.current().as_str(), "https://a.test/");
+
+        assert!(Failover::new(Vec::new(), 1).is_none());
+    }
+}
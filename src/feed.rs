@@ -0,0 +1,277 @@
+//! Rendering the latest results of a query as an [Atom] feed.
+//!
+//! An [`AtomFeed`] fetches the newest posts or comments matching a [`Filter`] and renders
+//! them as an Atom document, e.g. so that a self-hosted dashboard can serve a PushShift
+//! query to ordinary feed readers.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() {
+//! use pullcaps::feed::AtomFeed;
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::new();
+//!
+//! let feed = AtomFeed::new("Posts about tokio in /r/rust").limit(25);
+//! let xml = feed
+//!     .posts(&client, Filter::new().subreddit("rust").title_contains("tokio"))
+//!     .await;
+//! println!("{}", xml);
+//! # }
+//! ```
+//!
+//! [Atom]: https://www.rfc-editor.org/rfc/rfc4287
+use crate::models::{Comment, Content, Post};
+use crate::{Client, Filter, SortDirection, SortType};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::StreamExt;
+use std::fmt::Write;
+
+/// The number of entries in a feed, unless it says otherwise.
+const DEFAULT_LIMIT: usize = 25;
+
+/// An Atom feed of posts or comments, see the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct AtomFeed {
+    title: String,
+    id: Option<String>,
+    link: Option<String>,
+    limit: usize,
+}
+
+impl AtomFeed {
+    /// Creates a feed with the given title.
+    pub fn new<S: Into<String>>(title: S) -> Self {
+        Self {
+            title: title.into(),
+            id: None,
+            link: None,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    /// Sets the feed's permanent, unique identifier, which must be an IRI.
+    ///
+    /// Defaults to the feed's [link](AtomFeed::link) if it has one, or otherwise a URN
+    /// derived from its title.
+    #[must_use]
+    pub fn id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the URL the feed is served from, which feed readers use to refresh it.
+    #[must_use]
+    pub fn link<S: Into<String>>(mut self, link: S) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    /// Sets the number of entries in the feed.  Defaults to 25.
+    #[must_use]
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Fetches the newest posts matching the given filter, and renders them as a feed.
+    ///
+    /// Any sort set on the filter is replaced, so that the feed has the newest posts.
+    pub async fn posts(&self, client: &Client, filter: Filter) -> String {
+        let posts: Vec<Post> = client
+            .get_posts(self.newest(filter))
+            .await
+            .take(self.limit)
+            .collect()
+            .await;
+        self.render(posts)
+    }
+
+    /// Fetches the newest comments matching the given filter, and renders them as a
+    /// feed.
+    ///
+    /// Any sort set on the filter is replaced, so that the feed has the newest comments.
+    pub async fn comments(&self, client: &Client, filter: Filter) -> String {
+        let comments: Vec<Comment> = client
+            .get_comments(self.newest(filter))
+            .await
+            .take(self.limit)
+            .collect()
+            .await;
+        self.render(comments)
+    }
+
+    fn newest(&self, filter: Filter) -> Filter {
+        filter
+            .sort_type(SortType::CreatedDate)
+            .sort(SortDirection::Desc)
+            .limit(self.limit as i64)
+    }
+
+    /// Renders the given posts or comments as a feed, in the order given.
+    pub fn render<I, C>(&self, items: I) -> String
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<Content>,
+    {
+        let items: Vec<Content> = items.into_iter().map(Into::into).collect();
+        let updated = items.iter().map(updated).max().unwrap_or_else(Utc::now);
+        let id = match (&self.id, &self.link) {
+            (Some(id), _) | (None, Some(id)) => id.clone(),
+            (None, None) => format!("urn:pullcaps:feed:{}", slug(&self.title)),
+        };
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        element(&mut xml, 1, "title", &self.title);
+        element(&mut xml, 1, "id", &id);
+        element(&mut xml, 1, "updated", &timestamp(updated));
+        if let Some(link) = &self.link {
+            let _ = writeln!(xml, "  <link rel=\"self\" href=\"{}\"/>", escape(link));
+        }
+        element(&mut xml, 1, "generator", "pullcaps");
+        for item in &items {
+            entry(&mut xml, item);
+        }
+        xml.push_str("</feed>\n");
+        xml
+    }
+}
+
+/// Renders a single post or comment as an entry.
+fn entry(xml: &mut String, item: &Content) {
+    let attrs = item.attrs();
+    let subreddit = &item.subreddit().name;
+    let author = item.author().name().unwrap_or("[deleted]");
+    let (fullname, title, link, content) = match item {
+        Content::Post(post) => (
+            post.id().fullname(),
+            post.title.clone(),
+            Some(post.url()),
+            post.self_text
+                .clone()
+                .filter(|text| !text.is_empty())
+                .unwrap_or_else(|| post.content_url.clone()),
+        ),
+        Content::Comment(comment) => (
+            comment.id().fullname(),
+            format!("Comment by {} in /r/{}", author, subreddit),
+            comment.url(),
+            comment.body.clone(),
+        ),
+    };
+
+    xml.push_str("  <entry>\n");
+    element(xml, 2, "title", &title);
+    element(xml, 2, "id", &format!("tag:reddit.com,2005:{}", fullname));
+    element(xml, 2, "published", &timestamp(attrs.date));
+    element(xml, 2, "updated", &timestamp(updated(item)));
+    if let Some(link) = link {
+        let _ = writeln!(xml, "    <link href=\"{}\"/>", escape(&link));
+    }
+    let _ = writeln!(xml, "    <author><name>{}</name></author>", escape(author));
+    let _ = writeln!(xml, "    <category term=\"{}\"/>", escape(subreddit));
+    let _ = writeln!(
+        xml,
+        "    <content type=\"text\">{}</content>",
+        escape(&content)
+    );
+    xml.push_str("  </entry>\n");
+}
+
+/// Writes a simple text element, indented to the given depth.
+fn element(xml: &mut String, depth: usize, name: &str, text: &str) {
+    let _ = writeln!(
+        xml,
+        "{}<{}>{}</{}>",
+        "  ".repeat(depth),
+        name,
+        escape(text),
+        name
+    );
+}
+
+/// Returns when an item was last updated: when it was edited, or otherwise created.
+fn updated(item: &Content) -> DateTime<Utc> {
+    let attrs = item.attrs();
+    attrs.edited.unwrap_or(attrs.date)
+}
+
+fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escapes text for use in XML content or attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters aren't allowed in XML documents at all.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Turns a title into something usable in a URN.
+fn slug(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::comment;
+
+    #[test]
+    fn test_render() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "author": "ferris",
+            "subreddit": "rust",
+            "title": "Announcing <Rust> & friends",
+            "selftext": "",
+            "url": "https://blog.rust-lang.org/",
+            "permalink": "/r/rust/comments/abc/announcing/",
+        }))
+        .unwrap();
+        let mut reply = comment("def", 1_600_000_100);
+        reply.body = "\"Great\" news".to_string();
+
+        let xml =
+            AtomFeed::new("Rust news").render(vec![Content::Comment(reply), Content::Post(post)]);
+
+        assert!(xml.contains("<id>urn:pullcaps:feed:rust-news</id>"));
+        assert!(xml.contains("<updated>2020-09-13T12:28:20Z</updated>"));
+        assert!(xml.contains("<title>Announcing &lt;Rust&gt; &amp; friends</title>"));
+        assert!(xml.contains("<id>tag:reddit.com,2005:t3_abc</id>"));
+        assert!(
+            xml.contains("<link href=\"https://www.reddit.com/r/rust/comments/abc/announcing/\"/>")
+        );
+        assert!(xml.contains("<content type=\"text\">https://blog.rust-lang.org/</content>"));
+        assert!(xml.contains("<title>Comment by reddit in /r/rust</title>"));
+        assert!(xml.contains("<content type=\"text\">&quot;Great&quot; news</content>"));
+        assert_eq!(xml.matches("<entry>").count(), 2);
+        assert!(xml.ends_with("</feed>\n"));
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(
+            slug("Posts about tokio in /r/rust"),
+            "posts-about-tokio-in-r-rust"
+        );
+    }
+}
@@ -1,34 +1,201 @@
-use chrono::serde::ts_seconds_option;
-use chrono::{DateTime, Utc};
-use serde::Serialize;
+use crate::chunking::ValueRange;
+use crate::models::AsAttrs;
+use crate::{ApiVersion, Priority};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
 
 /// Used to filter a particular query down in some way.
-#[derive(Clone, Default, Serialize)]
+///
+/// As well as using the builder methods, a `Filter` can be deserialized (e.g. from a
+/// configuration file) or parsed from a query string:
+///
+/// ```rust
+/// use pullcaps::Filter;
+///
+/// let filter: Filter = "subreddit=rust&after=1600000000&score=>50".parse().unwrap();
+/// assert_eq!(filter.subreddit.as_deref(), Some("rust"));
+/// ```
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(remote = "Self", default)]
 pub struct Filter {
     pub author: Option<String>,
+
+    /// Restricts results to items by the author with the given fullname, e.g. `t2_1w72`.
+    pub author_fullname: Option<String>,
     pub subreddit: Option<String>,
 
-    #[serde(with = "ts_seconds_option")]
+    /// Restricts results to subreddits whose name matches the given pattern, which are
+    /// resolved by the client before querying them.
+    #[serde(skip_serializing)]
+    pub subreddit_pattern: Option<String>,
+
+    /// Restricts results by score, e.g. `">50"` or `"<10"`.
+    pub score: Option<String>,
+
+    /// Searches the text of items, i.e. the body of comments, or the title and text of
+    /// posts.
+    #[serde(rename = "q")]
+    pub query: Option<String>,
+
+    /// Searches the titles of posts.
+    pub title: Option<String>,
+
+    /// Searches the text of self-posts.
+    pub selftext: Option<String>,
+
+    /// Excludes items matching the given search terms.
+    #[serde(rename = "q:not")]
+    pub query_not: Option<String>,
+
+    /// Restricts results to posts with the given link flair.
+    pub link_flair_text: Option<String>,
+
+    /// Restricts results to items whose author has the given flair.
+    pub author_flair_text: Option<String>,
+
+    /// Restricts results to items whose author has a flair with the given CSS class.
+    pub author_flair_css_class: Option<String>,
+
+    /// Restricts results to self-posts (`true`) or link posts (`false`).
+    pub is_self: Option<bool>,
+
+    /// Restricts results to posts which are (`true`) or aren't (`false`) marked NSFW.
+    pub over_18: Option<bool>,
+
+    /// Restricts results to items which are (`true`) or aren't (`false`) stickied.
+    pub stickied: Option<bool>,
+
+    /// Restricts results to posts which are (`true`) or aren't (`false`) locked.
+    pub locked: Option<bool>,
+
+    /// Restricts results to items which were (`true`) or weren't (`false`) removed by a
+    /// moderator.
+    pub mod_removed: Option<bool>,
+
+    /// Restricts results to items which were (`true`) or weren't (`false`) deleted by
+    /// their author.
+    pub user_removed: Option<bool>,
+
+    /// Restricts results to items distinguished in the given way, e.g. `"moderator"`.
+    pub distinguished: Option<String>,
+
+    #[serde(serialize_with = "date_bound::serialize", skip_deserializing)]
     pub before: Option<DateTime<Utc>>,
 
-    #[serde(with = "ts_seconds_option")]
+    /// A relative `before` bound such as `"24h"`, sent to PushShift as it's written.
+    /// It's ignored if `before` is set.
+    #[serde(
+        rename = "before",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub before_relative: Option<String>,
+
+    #[serde(serialize_with = "date_bound::serialize", skip_deserializing)]
     pub after: Option<DateTime<Utc>>,
 
+    /// A relative `after` bound such as `"7d"`, sent to PushShift as it's written.  It's
+    /// ignored if `after` is set.
+    #[serde(
+        rename = "after",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub after_relative: Option<String>,
+
+    /// Restricts results to items with an ID lower than the given (base 36) ID, on
+    /// backends which support it.
+    pub before_id: Option<String>,
+
+    /// Restricts results to items with an ID higher than the given (base 36) ID, on
+    /// backends which support it.
+    pub after_id: Option<String>,
+
     pub sort_type: SortType,
 
-    #[serde(skip)]
+    pub sort: SortDirection,
+
+    /// Restricts the fields returned for each item.
+    #[serde(with = "comma_separated")]
+    pub fields: Option<Vec<String>>,
+
+    #[serde(skip_serializing)]
     pub limit: Option<i64>,
+
+    #[serde(skip_serializing)]
+    pub ordered: bool,
+
+    /// Delivers the buckets of a chunked query from newest to oldest.
+    #[serde(skip_serializing)]
+    pub newest_first: bool,
+
+    /// Shuffles the results of a chunked query with the given seed.
+    #[serde(skip_serializing)]
+    pub shuffle_seed: Option<u64>,
+
+    /// The `(after, before)` date ranges to split the query into, instead of planning
+    /// its buckets.
+    #[serde(skip_serializing)]
+    pub windows: Option<Vec<(DateTime<Utc>, DateTime<Utc>)>>,
+
+    /// The priority of this query's requests on the client's rate limiter.
+    #[serde(skip_serializing)]
+    pub priority: Priority,
+
+    /// What happens to items whose text is a `[removed]` or `[deleted]` placeholder.
+    #[serde(skip_serializing)]
+    pub removed_text: RemovedText,
+
+    /// Restricts results to items whose value of the given field falls in a range, for
+    /// the buckets of a query chunked by value.  PushShift only takes a single
+    /// comparison per parameter value, so the bounds are added to the query separately.
+    #[serde(skip)]
+    pub(crate) value_range: Option<(&'static str, ValueRange)>,
 }
 
 impl Filter {
     pub fn new() -> Self {
         Self {
             author: None,
+            author_fullname: None,
             subreddit: None,
+            subreddit_pattern: None,
+            score: None,
+            query: None,
+            title: None,
+            selftext: None,
+            query_not: None,
+            link_flair_text: None,
+            author_flair_text: None,
+            author_flair_css_class: None,
+            is_self: None,
+            over_18: None,
+            stickied: None,
+            locked: None,
+            mod_removed: None,
+            user_removed: None,
+            distinguished: None,
             before: None,
+            before_relative: None,
             after: None,
+            after_relative: None,
+            before_id: None,
+            after_id: None,
             sort_type: SortType::default(),
+            sort: SortDirection::default(),
+            fields: None,
             limit: None,
+            ordered: false,
+            newest_first: false,
+            shuffle_seed: None,
+            windows: None,
+            priority: Priority::default(),
+            removed_text: RemovedText::default(),
+            value_range: None,
         }
     }
 
@@ -38,40 +205,882 @@ impl Filter {
         self
     }
 
+    /// Restricts results to items by the author with the given fullname, e.g. `t2_1w72`,
+    /// which (unlike their username) doesn't change if the author is renamed.
+    #[must_use]
+    pub fn author_id<S: Into<String>>(mut self, fullname: S) -> Self {
+        self.author_fullname = Some(fullname.into());
+        self
+    }
+
     #[must_use]
     pub fn subreddit<S: Into<String>>(mut self, subreddit: S) -> Self {
         self.subreddit = Some(subreddit.into());
         self
     }
 
+    /// Excludes items by the given author, e.g. to leave out bot accounts.
+    ///
+    /// This can be called several times to exclude several authors.  Note that calling
+    /// [`Filter::author`] afterwards replaces any exclusions.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new()
+    ///     .subreddit("rust")
+    ///     .exclude_author("AutoModerator")
+    ///     .exclude_author("RemindMeBot");
+    /// assert_eq!(filter.author.as_deref(), Some("!AutoModerator,!RemindMeBot"));
+    /// ```
+    #[must_use]
+    pub fn exclude_author<S: AsRef<str>>(mut self, author: S) -> Self {
+        self.author = Some(exclude(self.author.take(), author.as_ref()));
+        self
+    }
+
+    /// Excludes items from the given subreddit.
+    ///
+    /// This can be called several times to exclude several subreddits.  Note that calling
+    /// [`Filter::subreddit`] afterwards replaces any exclusions.
+    #[must_use]
+    pub fn exclude_subreddit<S: AsRef<str>>(mut self, subreddit: S) -> Self {
+        self.subreddit = Some(exclude(self.subreddit.take(), subreddit.as_ref()));
+        self
+    }
+
+    /// Restricts results to subreddits whose name matches the given pattern, ignoring
+    /// case, where `*` matches any number of characters and `?` matches a single
+    /// character.
+    ///
+    /// PushShift can't search by pattern, so the client first resolves the matching
+    /// subreddits through PushShift's subreddit search, then queries each of them
+    /// (replacing any [`Filter::subreddit`]).  Items from different subreddits are
+    /// interleaved as they arrive.  Use [`Client::get_posts_matching`] to find out
+    /// which subreddit each item came from.  If the subreddit search fails, nothing is
+    /// fetched and the query's whole date range is reported as a
+    /// [`FailedRange`](crate::FailedRange).
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new().subreddit_matching("ask*").last_days(1);
+    /// assert_eq!(filter.subreddit_pattern.as_deref(), Some("ask*"));
+    /// ```
+    ///
+    /// [`Client::get_posts_matching`]: crate::Client::get_posts_matching
+    #[must_use]
+    pub fn subreddit_matching<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.subreddit_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Restricts results by score, e.g. `">50"` for items with a score greater than 50.
+    #[must_use]
+    pub fn score<S: Into<String>>(mut self, score: S) -> Self {
+        self.score = Some(score.into());
+        self
+    }
+
+    /// Restricts results to items whose text matches the given search terms, which can
+    /// be built with a [`Query`](crate::Query).
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{Filter, Query};
+    ///
+    /// let query = Query::term("rust").and(Query::phrase("memory safety"));
+    /// let filter = Filter::new().subreddit("programming").query(query);
+    /// ```
+    #[must_use]
+    pub fn query<S: Into<String>>(mut self, terms: S) -> Self {
+        self.query = Some(terms.into());
+        self
+    }
+
+    /// Restricts results to posts whose title matches the given search terms.
+    ///
+    /// Unlike a general search, this doesn't match the text of self-posts.  Comments
+    /// don't have a title, so this should only be used when searching for posts.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new().subreddit("gaming").title_contains("giveaway");
+    /// ```
+    #[must_use]
+    pub fn title_contains<S: Into<String>>(mut self, terms: S) -> Self {
+        self.title = Some(terms.into());
+        self
+    }
+
+    /// Restricts results to self-posts whose text matches the given search terms.
+    ///
+    /// This should only be used when searching for posts.
+    #[must_use]
+    pub fn selftext_contains<S: Into<String>>(mut self, terms: S) -> Self {
+        self.selftext = Some(terms.into());
+        self
+    }
+
+    /// Excludes items matching the given search terms, e.g. to suppress noisy keywords.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new().subreddit("investing").query_not("crypto");
+    /// ```
+    #[must_use]
+    pub fn query_not<S: Into<String>>(mut self, terms: S) -> Self {
+        self.query_not = Some(terms.into());
+        self
+    }
+
+    /// Restricts results to posts with the given link flair, e.g. `"Megathread"`.
+    ///
+    /// This should only be used when searching for posts.
+    #[must_use]
+    pub fn link_flair<S: Into<String>>(mut self, flair: S) -> Self {
+        self.link_flair_text = Some(flair.into());
+        self
+    }
+
+    /// Restricts results to posts or comments whose author has the given flair.
+    #[must_use]
+    pub fn author_flair<S: Into<String>>(mut self, flair: S) -> Self {
+        self.author_flair_text = Some(flair.into());
+        self
+    }
+
+    /// Restricts results to posts or comments whose author has a flair with the given
+    /// CSS class, which some subreddits use to mark user roles.
+    #[must_use]
+    pub fn author_flair_css_class<S: Into<String>>(mut self, class: S) -> Self {
+        self.author_flair_css_class = Some(class.into());
+        self
+    }
+
+    /// Restricts results to self-posts, i.e. text posts.
+    ///
+    /// This should only be used when searching for posts.
+    #[must_use]
+    pub fn self_posts_only(mut self) -> Self {
+        self.is_self = Some(true);
+        self
+    }
+
+    /// Restricts results to link posts, i.e. posts which aren't self-posts.
+    ///
+    /// This should only be used when searching for posts.
     #[must_use]
-    pub fn before(mut self, before: DateTime<Utc>) -> Self {
-        self.before = Some(before);
+    pub fn link_posts_only(mut self) -> Self {
+        self.is_self = Some(false);
         self
     }
 
+    /// Sets whether posts marked NSFW are included in the results, which they are by
+    /// default.
+    ///
+    /// This should only be used when searching for posts.
     #[must_use]
-    pub fn after(mut self, after: DateTime<Utc>) -> Self {
-        self.after = Some(after);
+    pub fn include_nsfw(mut self, include: bool) -> Self {
+        self.over_18 = if include { None } else { Some(false) };
         self
     }
 
+    /// Restricts results to posts marked NSFW.
+    ///
+    /// This should only be used when searching for posts.
+    #[must_use]
+    pub fn nsfw_only(mut self) -> Self {
+        self.over_18 = Some(true);
+        self
+    }
+
+    /// Restricts results to items which are (or, if `false`, aren't) stickied, e.g. to
+    /// leave pinned megathreads out of statistics.
+    #[must_use]
+    pub fn stickied(mut self, stickied: bool) -> Self {
+        self.stickied = Some(stickied);
+        self
+    }
+
+    /// Restricts results to posts which are (or, if `false`, aren't) locked.
+    ///
+    /// This should only be used when searching for posts.
+    #[must_use]
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = Some(locked);
+        self
+    }
+
+    /// Restricts results to items which were (or, if `false`, weren't) removed by a
+    /// moderator.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// // Everything the moderators of /r/news removed in the last week.
+    /// let filter = Filter::new()
+    ///     .subreddit("news")
+    ///     .mod_removed(true)
+    ///     .last_days(7);
+    /// ```
+    #[must_use]
+    pub fn mod_removed(mut self, removed: bool) -> Self {
+        self.mod_removed = Some(removed);
+        self
+    }
+
+    /// Restricts results to items which were (or, if `false`, weren't) deleted by their
+    /// author.
+    #[must_use]
+    pub fn user_removed(mut self, removed: bool) -> Self {
+        self.user_removed = Some(removed);
+        self
+    }
+
+    /// Restricts results to items distinguished in the given way, i.e. `"moderator"`
+    /// or `"admin"`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// // Comments made by moderators speaking as moderators.
+    /// let filter = Filter::new().subreddit("rust").distinguished("moderator");
+    /// ```
+    #[must_use]
+    pub fn distinguished<S: Into<String>>(mut self, distinguished: S) -> Self {
+        self.distinguished = Some(distinguished.into());
+        self
+    }
+
+    #[must_use]
+    pub fn before<D: IntoDateTime>(mut self, before: D) -> Self {
+        self.before = Some(before.into_date_time());
+        self.before_relative = None;
+        self
+    }
+
+    #[must_use]
+    pub fn after<D: IntoDateTime>(mut self, after: D) -> Self {
+        self.after = Some(after.into_date_time());
+        self.after_relative = None;
+        self
+    }
+
+    /// Restricts results to items with an ID lower than the given one, i.e. items created
+    /// before it, on backends which support ID pagination.
+    ///
+    /// IDs are given in base 36, with or without their `t1_` or `t3_` prefix.  Unlike a
+    /// date, an ID is exclusive and unique, so a stream can be resumed from exactly the
+    /// last item it returned; see [`ClientBuilder::id_pagination`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// // Carry on from the last comment stored by a previous run.
+    /// let filter = Filter::new().subreddit("rust").before_id("t1_k3e9xq2");
+    /// assert_eq!(filter.before_id.as_deref(), Some("k3e9xq2"));
+    /// ```
+    ///
+    /// [`ClientBuilder::id_pagination`]: crate::ClientBuilder::id_pagination
+    #[must_use]
+    pub fn before_id<S: AsRef<str>>(mut self, id: S) -> Self {
+        self.before_id = Some(strip_kind(id.as_ref()).to_string());
+        self
+    }
+
+    /// Restricts results to items with an ID higher than the given one, i.e. items
+    /// created after it, on backends which support ID pagination.
+    ///
+    /// See [`Filter::before_id`].
+    #[must_use]
+    pub fn after_id<S: AsRef<str>>(mut self, id: S) -> Self {
+        self.after_id = Some(strip_kind(id.as_ref()).to_string());
+        self
+    }
+
+    /// Restricts results to those created between `after` and `before`.
+    #[must_use]
+    pub fn between<A: IntoDateTime, B: IntoDateTime>(self, after: A, before: B) -> Self {
+        self.after(after).before(before)
+    }
+
+    /// Restricts results to those created within the given duration of now.
+    ///
+    /// # Example
+    /// ```rust
+    /// use chrono::Duration;
+    /// use pullcaps::Filter;
+    ///
+    /// // Everything from the past week.
+    /// let filter = Filter::new().subreddit("rust").last(Duration::weeks(1));
+    /// ```
+    #[must_use]
+    pub fn last(self, duration: Duration) -> Self {
+        self.after(duration)
+    }
+
+    /// Restricts results to those created within the past `days` days.
+    #[must_use]
+    pub fn last_days(self, days: i64) -> Self {
+        self.last(Duration::days(days))
+    }
+
+    /// Restricts results to those created before the given time ago, written the way
+    /// PushShift understands it, e.g. `"7d"`, `"24h"`, `"90m"` or `"30s"`.
+    ///
+    /// The time is sent to PushShift as it's written, replacing any `before` date.  Once
+    /// a stream has paginated past the bound (or split a chunked query into buckets), it
+    /// moves on to dates of its own.
+    ///
+    /// Returns an error if the time isn't a whole number followed by one of `d`, `h`, `m`
+    /// or `s`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// // Everything from last week, but not the past day.
+    /// let filter = Filter::new().after_relative("7d")?.before_relative("24h")?;
+    /// # Ok::<(), pullcaps::ParseFilterError>(())
+    /// ```
+    pub fn before_relative<S: AsRef<str>>(mut self, ago: S) -> Result<Self, ParseFilterError> {
+        self.before_relative = Some(relative(ago.as_ref())?);
+        self.before = None;
+        Ok(self)
+    }
+
+    /// Restricts results to those created after the given time ago, e.g. `"7d"`.
+    ///
+    /// See [`Filter::before_relative`].
+    pub fn after_relative<S: AsRef<str>>(mut self, ago: S) -> Result<Self, ParseFilterError> {
+        self.after_relative = Some(relative(ago.as_ref())?);
+        self.after = None;
+        Ok(self)
+    }
+
+    /// Returns the `before` bound as a date, working out a relative one from now.
+    pub(crate) fn before_date(&self) -> Option<DateTime<Utc>> {
+        self.before.or_else(|| {
+            let ago = parse_relative(self.before_relative.as_deref()?)?;
+            Some(ago.into_date_time())
+        })
+    }
+
+    /// Returns the `after` bound as a date, working out a relative one from now.
+    pub(crate) fn after_date(&self) -> Option<DateTime<Utc>> {
+        self.after.or_else(|| {
+            let ago = parse_relative(self.after_relative.as_deref()?)?;
+            Some(ago.into_date_time())
+        })
+    }
+
     #[must_use]
     pub fn sort_type(mut self, sort_type: SortType) -> Self {
         self.sort_type = sort_type;
         self
     }
 
+    /// Restricts the fields returned for each item, reducing the size of each response.
+    ///
+    /// Fields of the [models](crate::models) which aren't requested take their default
+    /// values.  The `id` and `created_utc` fields are always requested, since they're
+    /// required to paginate through results.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new().subreddit("rust").fields(["body"]);
+    /// ```
+    #[must_use]
+    pub fn fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut fields: Vec<String> = fields.into_iter().map(Into::into).collect();
+        for required in ["id", "created_utc"] {
+            if !fields.iter().any(|field| field == required) {
+                fields.push(required.to_string());
+            }
+        }
+        self.fields = Some(fields);
+        self
+    }
+
+    /// Sets the direction results are sorted in, by the attribute given by
+    /// [`Filter::sort_type`].  By default results are sorted in descending order.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{Filter, SortDirection};
+    ///
+    /// // The oldest comments first.
+    /// let filter = Filter::new().author("reddit").sort(SortDirection::Asc);
+    /// ```
+    #[must_use]
+    pub fn sort(mut self, sort: SortDirection) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Limits the number of results returned.
+    ///
+    /// Setting a limit disables chunking, so the results are the first `limit` items
+    /// in sorted order - a small limit only costs a single request.
     #[must_use]
     pub fn limit(mut self, limit: i64) -> Self {
         self.limit = Some(limit);
         self
     }
+
+    /// Requests that results are returned in order of creation date, in the direction
+    /// given by [`Filter::sort`].
+    ///
+    /// By default chunked queries return results in an arbitrary order; setting this
+    /// merges the chunks by creation date instead, at the cost of some parallelism.
+    #[must_use]
+    pub fn ordered(mut self) -> Self {
+        self.ordered = true;
+        self
+    }
+
+    /// Requests that the most recent results are delivered first, sorting results in
+    /// descending order of creation date.
+    ///
+    /// Unlike [`Filter::ordered`], the buckets of a chunked query are still fetched
+    /// concurrently, but the newest buckets are started first: the newest bucket's
+    /// results are delivered as they're fetched, while the next few buckets (see
+    /// [`ClientBuilder::max_concurrent_buckets`](crate::ClientBuilder::max_concurrent_buckets))
+    /// are held back until every newer bucket has been delivered.  This suits showing
+    /// recent content straight away while older history loads in the background.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new().subreddit("rust").newest_first();
+    /// ```
+    #[must_use]
+    pub fn newest_first(mut self) -> Self {
+        self.sort = SortDirection::Desc;
+        self.newest_first = true;
+        self
+    }
+
+    /// Requests that results are delivered in an order shuffled with the given seed, so
+    /// that running the same query over history which doesn't change again (with the
+    /// same client settings) returns the same results in the same order.
+    ///
+    /// By default chunked queries return results in whichever order their requests
+    /// happen to complete.  With a seed, the buckets of a chunked query are delivered
+    /// whole, in a shuffled order, with the results in each bucket shuffled too, which
+    /// makes the first few results of the stream a reproducible sample.  Queries which
+    /// aren't chunked are still returned in sorted order, which is deterministic
+    /// already, as is the order of [`Filter::ordered`] queries.  The same seed gives the
+    /// same order on every platform.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let filter = Filter::new().subreddit("rust").shuffled(42);
+    /// ```
+    #[must_use]
+    pub fn shuffled(mut self, seed: u64) -> Self {
+        self.shuffle_seed = Some(seed);
+        self
+    }
+
+    /// Splits the query into the given `(after, before)` date ranges, rather than the
+    /// buckets the client would plan for it.
+    ///
+    /// Planning a chunked query takes a few requests to find out how many items match
+    /// and how they're distributed over time.  Windows derived from a previous run
+    /// (see [`QueryPlan::windows`]) or from density estimates of your own skip those
+    /// requests entirely, and are used even if the client's chunking is disabled.  Each
+    /// window is fetched as a bucket of its own, replacing the filter's date range, so
+    /// items outside every window aren't returned.  Windows only apply to queries
+    /// sorted by creation date without a [limit](Filter::limit).
+    ///
+    /// # Example
+    /// ```rust
+    /// use chrono::{TimeZone, Utc};
+    /// use pullcaps::Filter;
+    ///
+    /// let date = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+    /// let filter = Filter::new()
+    ///     .subreddit("rust")
+    ///     .with_windows([(date(2020), date(2021)), (date(2021), date(2023))]);
+    /// ```
+    ///
+    /// [`QueryPlan::windows`]: crate::QueryPlan::windows
+    #[must_use]
+    pub fn with_windows<I, A, B>(mut self, windows: I) -> Self
+    where
+        I: IntoIterator<Item = (A, B)>,
+        A: IntoDateTime,
+        B: IntoDateTime,
+    {
+        let mut windows: Vec<_> = windows
+            .into_iter()
+            .map(|(after, before)| (after.into_date_time(), before.into_date_time()))
+            .collect();
+        windows.sort();
+        self.windows = Some(windows);
+        self
+    }
+
+    /// Sets the priority of this query's requests, so that e.g. interactive queries
+    /// aren't held up by bulk ones made with the same client (or its clones).
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{Filter, Priority};
+    ///
+    /// let filter = Filter::new().author("reddit").priority(Priority::Interactive);
+    /// ```
+    #[must_use]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets what happens to items whose body (or a post's self text) is the `[removed]`
+    /// or `[deleted]` placeholder PushShift archived in place of the original text.
+    ///
+    /// This is applied by the client as pages arrive, so unlike [`Filter::mod_removed`]
+    /// and [`Filter::user_removed`] it catches content PushShift didn't flag as removed,
+    /// but dropped items still count against the rate limit.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::{Filter, RemovedText};
+    ///
+    /// let filter = Filter::new().subreddit("rust").removed_text(RemovedText::Drop);
+    /// ```
+    #[must_use]
+    pub fn removed_text(mut self, removed_text: RemovedText) -> Self {
+        self.removed_text = removed_text;
+        self
+    }
+
+    /// Returns the query parameters PushShift is sent for this filter, using the
+    /// parameter names of the (legacy) PushShift API.
+    ///
+    /// These are the inverse of parsing a filter from a query string.  The page size and
+    /// any parameter names specific to the configured [`ApiVersion`] are added by the
+    /// client, see [`Client::url_for`](crate::Client::url_for).
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::Filter;
+    ///
+    /// let pairs = Filter::new().subreddit("rust").to_query_pairs();
+    /// assert!(pairs.contains(&("subreddit".to_string(), "rust".to_string())));
+    /// ```
+    pub fn to_query_pairs(&self) -> Vec<(String, String)> {
+        query_pairs(self, ApiVersion::Legacy)
+    }
+}
+
+/// Checks a relative time such as `"7d"`, returning it as it's sent to PushShift.
+fn relative(ago: &str) -> Result<String, ParseFilterError> {
+    match parse_relative(ago) {
+        Some(_) => Ok(ago.trim().to_string()),
+        None => Err(ParseFilterError(serde::de::Error::custom(format!(
+            "invalid relative time: {:?}",
+            ago
+        )))),
+    }
+}
+
+/// Parses a relative time such as `"7d"` (as understood by PushShift) into a duration.
+fn parse_relative(ago: &str) -> Option<Duration> {
+    let ago = ago.trim();
+    let unit = ago.chars().last()?;
+    let amount = &ago[..ago.len() - unit.len_utf8()];
+    if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: i64 = amount.parse().ok()?;
+    match unit {
+        'd' => Duration::try_days(amount),
+        'h' => Duration::try_hours(amount),
+        'm' => Duration::try_minutes(amount),
+        's' => Duration::try_seconds(amount),
+        _ => None,
+    }
+}
+
+/// Serializes a date bound as a Unix timestamp.
+mod date_bound {
+    use chrono::serde::ts_seconds_option;
+    use chrono::{DateTime, Utc};
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ts_seconds_option::serialize(value, serializer)
+    }
+}
+
+/// A date bound as it's written when deserializing a [`Filter`]: either a Unix timestamp,
+/// or a relative time (e.g. `"7d"`) which is kept as it's written.
+enum DateBound {
+    Date(DateTime<Utc>),
+    Relative(String),
+}
+
+impl<'de> Deserialize<'de> for DateBound {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Timestamp(i64),
+            Text(String),
+        }
+
+        let timestamp = match Raw::deserialize(deserializer)? {
+            Raw::Timestamp(timestamp) => timestamp,
+            Raw::Text(text) => match text.parse() {
+                Ok(timestamp) => timestamp,
+                Err(_) => {
+                    return relative(&text)
+                        .map(DateBound::Relative)
+                        .map_err(|_| D::Error::custom(format!("invalid date bound: {:?}", text)))
+                }
+            },
+        };
+
+        Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .map(DateBound::Date)
+            .ok_or_else(|| D::Error::custom(format!("invalid timestamp: {}", timestamp)))
+    }
+}
+
+impl Serialize for Filter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Filter::serialize(self, serializer)
+    }
+}
+
+/// The `before` and `after` bounds are deserialized separately from the rest of the
+/// filter, so that a relative time ends up in `before_relative` or `after_relative`
+/// rather than being turned into a date.
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(FilterVisitor)
+    }
+}
+
+struct FilterVisitor;
+
+impl<'de> Visitor<'de> for FilterVisitor {
+    type Value = Filter;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a filter")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Filter, A::Error> {
+        let mut bounds = DateBounds {
+            map,
+            before: None,
+            after: None,
+        };
+        let mut filter = Filter::deserialize(MapAccessDeserializer::new(&mut bounds))?;
+        match bounds.before {
+            Some(DateBound::Date(date)) => filter.before = Some(date),
+            Some(DateBound::Relative(ago)) => filter.before_relative = Some(ago),
+            None => {}
+        }
+        match bounds.after {
+            Some(DateBound::Date(date)) => filter.after = Some(date),
+            Some(DateBound::Relative(ago)) => filter.after_relative = Some(ago),
+            None => {}
+        }
+        Ok(filter)
+    }
+}
+
+/// Passes the entries of a map through to the rest of a [`Filter`], except for its date
+/// bounds, which are held on to.
+struct DateBounds<A> {
+    map: A,
+    before: Option<DateBound>,
+    after: Option<DateBound>,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for &mut DateBounds<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        while let Some(key) = self.map.next_key::<String>()? {
+            match key.as_str() {
+                "before" => self.before = self.map.next_value()?,
+                "after" => self.after = self.map.next_value()?,
+                _ => return seed.deserialize(key.into_deserializer()).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        self.map.next_value_seed(seed)
+    }
+}
+
+/// Adds a negated term (e.g. `!AutoModerator`) to a comma-separated filter value.
+fn exclude(value: Option<String>, term: &str) -> String {
+    match value {
+        Some(value) if !value.is_empty() => format!("{},!{}", value, term),
+        _ => format!("!{}", term),
+    }
+}
+
+/// Serializes query parameters into pairs, using the parameter names expected by the
+/// given API version.
+pub(crate) fn query_pairs<T: Serialize>(params: &T, version: ApiVersion) -> Vec<(String, String)> {
+    let map = match serde_json::to_value(params) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return Vec::new(),
+    };
+
+    map.into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::Null => return None,
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            Some((version.param_name(&key).to_string(), value))
+        })
+        .collect()
+}
+
+/// (De)serializes a list of strings as a single comma-separated string.
+mod comma_separated {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Vec<String>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(values) => serializer.serialize_some(&values.join(",")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<String>>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum CommaSeparated {
+            Joined(String),
+            List(Vec<String>),
+        }
+
+        Ok(
+            Option::<CommaSeparated>::deserialize(deserializer)?.map(|value| match value {
+                CommaSeparated::Joined(joined) => {
+                    joined.split(',').map(|s| s.trim().to_string()).collect()
+                }
+                CommaSeparated::List(list) => list,
+            }),
+        )
+    }
+}
+
+impl FromStr for Filter {
+    type Err = ParseFilterError;
+
+    /// Parses a filter from a query string, using the same parameter names as the
+    /// (legacy) PushShift API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_urlencoded::from_str(s.trim_start_matches('?')).map_err(ParseFilterError)
+    }
+}
+
+/// The error returned when a [`Filter`] can't be parsed from a query string.
+#[derive(Debug)]
+pub struct ParseFilterError(serde_urlencoded::de::Error);
+
+impl fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid filter: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFilterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// A value which can be used as a date bound of a [`Filter`].
+pub trait IntoDateTime {
+    fn into_date_time(self) -> DateTime<Utc>;
+}
+
+/// A date in any timezone is converted to UTC.
+impl<Tz: TimeZone> IntoDateTime for DateTime<Tz> {
+    fn into_date_time(self) -> DateTime<Utc> {
+        self.with_timezone(&Utc)
+    }
+}
+
+/// A date and time without a timezone is interpreted as UTC.
+impl IntoDateTime for NaiveDateTime {
+    fn into_date_time(self) -> DateTime<Utc> {
+        self.and_utc()
+    }
+}
+
+/// A date is interpreted as midnight UTC at the start of that day.
+impl IntoDateTime for NaiveDate {
+    fn into_date_time(self) -> DateTime<Utc> {
+        self.and_hms_opt(0, 0, 0).unwrap().and_utc()
+    }
+}
+
+/// A duration is interpreted as that long before now.
+impl IntoDateTime for Duration {
+    fn into_date_time(self) -> DateTime<Utc> {
+        Utc::now() - self
+    }
 }
 
 /// Indicates how a particular query should be sorted.
+///
+/// Large queries sorted by score or number of comments are chunked into ranges of
+/// values, each of which is fetched by date and then sorted, so every matching item can
+/// be retrieved rather than just the first page or so.  A value shared by a bucket's
+/// worth of items gets a bucket of its own, which is passed along as it's fetched rather
+/// than sorted in memory.  Use [`Filter::limit`] when only
+/// the top few items are wanted, to skip planning the chunks.
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SortType {
     /// Sort by creation date.
     #[serde(rename = "created_utc")]
@@ -95,3 +1104,315 @@ impl Default for SortType {
         Self::new()
     }
 }
+
+/// Indicates the direction in which a particular query should be sorted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortDirection {
+    /// Sort in ascending order, e.g. from oldest to newest.
+    #[serde(rename = "asc")]
+    Asc,
+    /// Sort in descending order, e.g. from newest to oldest.
+    #[serde(rename = "desc")]
+    Desc,
+}
+
+impl SortDirection {
+    pub fn new() -> Self {
+        Self::Desc
+    }
+}
+
+impl Default for SortDirection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Strips the `t1_` or `t3_` prefix from an ID, if it has one.
+fn strip_kind(id: &str) -> &str {
+    id.strip_prefix("t1_")
+        .or_else(|| id.strip_prefix("t3_"))
+        .unwrap_or(id)
+}
+
+/// What happens to items whose text is a `[removed]` or `[deleted]` placeholder, see
+/// [`Filter::removed_text`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemovedText {
+    /// Items are returned as they were archived, placeholder and all.  This is the
+    /// default.
+    #[default]
+    Keep,
+    /// Items are skipped, e.g. for text analysis which only wants content which is
+    /// still visible.
+    Drop,
+    /// Items are returned with their placeholder text emptied, and tagged with how
+    /// they were removed in [`Attrs::removed`](crate::models::Attrs::removed).
+    Tag,
+}
+
+impl RemovedText {
+    /// Drops or tags the items of a page with placeholder text.
+    pub(crate) fn apply<T: AsAttrs>(self, page: &mut Vec<T>) {
+        match self {
+            Self::Keep => {}
+            Self::Drop => page.retain(|item| !item.has_removed_text()),
+            Self::Tag => page.iter_mut().for_each(T::tag_removed_text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Query;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn test_date_bounds() {
+        let date = NaiveDate::from_ymd_opt(2021, 3, 14).unwrap();
+        let filter = Filter::new().between(date, Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+        assert_eq!(filter.after.unwrap().timestamp(), 1_615_680_000);
+        assert_eq!(filter.before.unwrap().timestamp(), 1_700_000_000);
+
+        let offset = FixedOffset::east_opt(10 * 3600).unwrap();
+        let local = offset.with_ymd_and_hms(2021, 3, 14, 10, 0, 0).unwrap();
+        let filter = Filter::new()
+            .after(local)
+            .before(date.and_hms_opt(12, 0, 0).unwrap());
+        assert_eq!(filter.after.unwrap().timestamp(), 1_615_680_000);
+        assert_eq!(
+            filter.before.unwrap().timestamp(),
+            1_615_680_000 + 12 * 3600
+        );
+
+        let filter = Filter::new().last_days(7);
+        let elapsed = Utc::now() - filter.after.unwrap();
+        assert!(elapsed >= Duration::days(7) && elapsed < Duration::days(7) + Duration::minutes(1));
+    }
+
+    #[test]
+    fn test_from_str() {
+        let filter: Filter = "?subreddit=rust&after=1600000000&score=>50&sort=asc&limit=10"
+            .parse()
+            .unwrap();
+        assert_eq!(filter.subreddit.as_deref(), Some("rust"));
+        assert_eq!(filter.after.unwrap().timestamp(), 1_600_000_000);
+        assert_eq!(filter.score.as_deref(), Some(">50"));
+        assert_eq!(filter.sort, SortDirection::Asc);
+        assert_eq!(filter.limit, Some(10));
+        assert!(filter.author.is_none());
+
+        assert!("sort_type=sideways".parse::<Filter>().is_err());
+
+        let filter: Filter = "fields=id,body".parse().unwrap();
+        assert_eq!(filter.fields.unwrap(), vec!["id", "body"]);
+    }
+
+    #[test]
+    fn test_text_search() {
+        let filter = Filter::new().title_contains("giveaway");
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("title=giveaway"));
+        assert!(!query.contains("selftext"));
+
+        let filter: Filter = query.parse().unwrap();
+        assert_eq!(filter.title.as_deref(), Some("giveaway"));
+    }
+
+    #[test]
+    fn test_flair() {
+        let filter = Filter::new()
+            .link_flair("Megathread")
+            .author_flair("Mod")
+            .author_flair_css_class("moderator");
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("link_flair_text=Megathread"));
+        assert!(query.contains("author_flair_text=Mod"));
+        assert!(query.contains("author_flair_css_class=moderator"));
+    }
+
+    #[test]
+    fn test_post_kind() {
+        let filter: Filter = "is_self=true".parse().unwrap();
+        assert_eq!(filter.is_self, Some(true));
+
+        let filter = Filter::new().self_posts_only().link_posts_only();
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("is_self=false"));
+    }
+
+    #[test]
+    fn test_nsfw() {
+        assert_eq!(Filter::new().include_nsfw(false).over_18, Some(false));
+        assert_eq!(Filter::new().nsfw_only().over_18, Some(true));
+        assert_eq!(Filter::new().nsfw_only().include_nsfw(true).over_18, None);
+    }
+
+    #[test]
+    fn test_moderation_status() {
+        let filter = Filter::new()
+            .stickied(false)
+            .locked(true)
+            .distinguished("moderator");
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("stickied=false"));
+        assert!(query.contains("locked=true"));
+        assert!(query.contains("distinguished=moderator"));
+
+        let filter = Filter::new().mod_removed(true).user_removed(false);
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("mod_removed=true"));
+        assert!(query.contains("user_removed=false"));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let filter: Filter = serde_json::from_value(serde_json::json!({
+            "author": "reddit",
+            "sort_type": "score",
+            "ordered": true,
+            "fields": ["id", "created_utc"],
+        }))
+        .unwrap();
+        assert_eq!(filter.author.as_deref(), Some("reddit"));
+        assert!(matches!(filter.sort_type, SortType::Score));
+        assert!(filter.ordered);
+        assert_eq!(filter.fields.unwrap(), vec!["id", "created_utc"]);
+    }
+
+    #[test]
+    fn test_newest_first_sorts_descending() {
+        let filter = Filter::new().sort(SortDirection::Asc).newest_first();
+        assert!(filter.newest_first);
+        assert_eq!(filter.sort, SortDirection::Desc);
+    }
+
+    #[test]
+    fn test_shuffled_isnt_sent() {
+        let filter = Filter::new().subreddit("rust").shuffled(42);
+        assert_eq!(filter.shuffle_seed, Some(42));
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(!query.contains("shuffle"));
+    }
+
+    #[test]
+    fn test_windows_arent_sent() {
+        let date = |ts| Utc.timestamp_opt(ts, 0).unwrap();
+        let filter = Filter::new().with_windows([(date(200), date(300)), (date(100), date(200))]);
+        assert_eq!(
+            filter.windows,
+            Some(vec![(date(100), date(200)), (date(200), date(300))])
+        );
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(!query.contains("100"));
+    }
+
+    #[test]
+    fn test_id_bounds() {
+        let filter = Filter::new().before_id("t1_abc").after_id("a12");
+        assert_eq!(filter.before_id.as_deref(), Some("abc"));
+        let pairs = filter.to_query_pairs();
+        assert!(pairs.contains(&("before_id".to_string(), "abc".to_string())));
+        assert!(pairs.contains(&("after_id".to_string(), "a12".to_string())));
+    }
+
+    #[test]
+    fn test_fields_include_pagination_fields() {
+        let filter = Filter::new().fields(["body", "id"]);
+        assert_eq!(filter.fields.unwrap(), vec!["body", "id", "created_utc"]);
+    }
+
+    #[test]
+    fn test_to_query_pairs_round_trip() {
+        let filter = Filter::new()
+            .subreddit("rust")
+            .after(Utc.timestamp_opt(1_600_000_000, 0).unwrap())
+            .fields(["id", "title"]);
+        let pairs = filter.to_query_pairs();
+        assert!(pairs.contains(&("after".to_string(), "1600000000".to_string())));
+        assert!(!pairs.iter().any(|(key, _)| key == "before"));
+
+        let query = serde_urlencoded::to_string(&pairs).unwrap();
+        let parsed: Filter = query.parse().unwrap();
+        assert_eq!(parsed.subreddit.as_deref(), Some("rust"));
+        assert_eq!(parsed.after, filter.after);
+        assert_eq!(parsed.fields, filter.fields);
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let filter = Filter::new()
+            .exclude_author("AutoModerator")
+            .exclude_author("RemindMeBot")
+            .subreddit("rust")
+            .exclude_subreddit("memes");
+        assert_eq!(
+            filter.author.as_deref(),
+            Some("!AutoModerator,!RemindMeBot")
+        );
+        assert_eq!(filter.subreddit.as_deref(), Some("rust,!memes"));
+
+        let filter = Filter::new().exclude_author("bot").author("reddit");
+        assert_eq!(filter.author.as_deref(), Some("reddit"));
+    }
+
+    #[test]
+    fn test_query() {
+        let query = Query::term("rust").not(Query::term("game"));
+        let filter = Filter::new().query(query);
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("q=rust%2B-game"));
+
+        let parsed: Filter = query.parse().unwrap();
+        assert_eq!(parsed.query.as_deref(), Some("rust+-game"));
+    }
+
+    #[test]
+    fn test_query_not() {
+        let filter = Filter::new().query_not("crypto");
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("q%3Anot=crypto"));
+
+        let parsed: Filter = query.parse().unwrap();
+        assert_eq!(parsed.query_not.as_deref(), Some("crypto"));
+    }
+
+    #[test]
+    fn test_relative_bounds() {
+        let filter = Filter::new()
+            .after_relative("7d")
+            .unwrap()
+            .before_relative(" 90m")
+            .unwrap();
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("after=7d") && query.contains("before=90m"));
+        let after = filter.after_date().unwrap();
+        let after = Utc::now() - after;
+        assert!(after >= Duration::days(7) && after < Duration::days(7) + Duration::minutes(1));
+
+        // A date replaces a relative bound.
+        let filter = filter.before(Utc.timestamp_opt(1_600_000_000, 0).unwrap());
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("before=1600000000") && !query.contains("90m"));
+
+        assert!(Filter::new().after_relative("7w").is_err());
+        assert!(Filter::new().before_relative("-5d").is_err());
+
+        // A relative time is kept as it's written when parsing a filter, too.
+        let filter: Filter = "after=24h&before=1600000000".parse().unwrap();
+        assert_eq!(filter.after, None);
+        assert_eq!(filter.after_relative.as_deref(), Some("24h"));
+        assert_eq!(filter.before.unwrap().timestamp(), 1_600_000_000);
+        let query = serde_urlencoded::to_string(&filter).unwrap();
+        assert!(query.contains("after=24h") && query.contains("before=1600000000"));
+        assert!("after=24w".parse::<Filter>().is_err());
+
+        assert_eq!(parse_relative("30s"), Some(Duration::seconds(30)));
+        assert_eq!(parse_relative("7w"), None);
+        assert_eq!(parse_relative("d"), None);
+        assert!("after=yesterday".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use crate::limiter::Meta;
+use crate::models::Shards;
+use std::time::Duration;
+
+/// The health of the backend a [`Client`](crate::Client) makes requests to, see
+/// [`Client::ping`](crate::Client::ping).
+#[derive(Clone, Debug)]
+pub struct Health {
+    /// How long the backend took to answer the check.
+    pub latency: Duration,
+
+    /// How many of the shards holding PushShift's data are available, if the backend
+    /// reports them.
+    pub shards: Option<Shards>,
+
+    /// The number of requests per minute the backend says it allows, if it announces a
+    /// rate limit.
+    pub rate_limit_per_minute: Option<u32>,
+}
+
+impl Health {
+    pub(crate) fn new(meta: Meta, latency: Duration) -> Self {
+        Self {
+            latency,
+            shards: meta.shards,
+            rate_limit_per_minute: meta.server_ratelimit_per_minute.filter(|&n| n > 0),
+        }
+    }
+
+    /// Returns whether every shard is available, assuming so if the backend doesn't
+    /// report its shards.
+    pub fn is_healthy(&self) -> bool {
+        self.shards.as_ref().is_none_or(Shards::is_complete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health() {
+        let meta: Meta = serde_json::from_str(
+            r#"{"server_ratelimit_per_minute": 120, "_shards": {"total": 8, "successful": 6}}"#,
+        )
+        .unwrap();
+        let health = Health::new(meta, Duration::from_millis(80));
+        assert_eq!(health.rate_limit_per_minute, Some(120));
+        assert!(!health.is_healthy());
+
+        let meta: Meta = serde_json::from_str(r#"{"server_ratelimit_per_minute": 0}"#).unwrap();
+        let health = Health::new(meta, Duration::from_millis(80));
+        assert_eq!(health.rate_limit_per_minute, None);
+        assert!(health.is_healthy());
+    }
+}
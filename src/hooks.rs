@@ -0,0 +1,69 @@
+use crate::{Error, PageTiming, Skipped};
+use reqwest::{StatusCode, Url};
+use std::time::Duration;
+
+/// Observes the requests made by a [`Client`], e.g. to log, count or alert on them.
+///
+/// Every method has an empty default implementation, so implementations only need to
+/// override the events they're interested in.  Hooks are called inline while requests
+/// are being made, so they should return quickly.
+///
+/// With the `metrics` feature enabled, clients also record counters and histograms of
+/// their requests, retries, latency, items and rate limit waits with the [`metrics`](https://docs.rs/metrics)
+/// crate, so they can be exported without implementing hooks.
+///
+/// # Example
+/// ```rust
+/// use pullcaps::{Client, Error, Hooks};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// #[derive(Default)]
+/// struct CountErrors(AtomicUsize);
+///
+/// impl Hooks for CountErrors {
+///     fn on_error(&self, _error: &Error) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let client = Client::builder().hooks(CountErrors::default()).build();
+/// ```
+///
+/// [`Client`]: crate::Client
+pub trait Hooks: Send + Sync {
+    /// Called before a request is sent to PushShift.
+    ///
+    /// Requests which are answered from the [cache](crate::ClientBuilder::cache), or
+    /// which share the response of an identical request already in flight, aren't sent.
+    fn on_request(&self, _url: &Url) {}
+
+    /// Called when PushShift responds to a request, successfully or otherwise.
+    fn on_response(&self, _url: &Url, _status: StatusCode, _elapsed: Duration) {}
+
+    /// Called when the body of a successful response has been downloaded, with its size
+    /// as transferred and once decompressed (with the `compression` feature).
+    fn on_body(&self, _url: &Url, _transferred: usize, _decoded: usize) {}
+
+    /// Called when a page of a query has been fetched, with how long it took to fetch.
+    fn on_page(&self, _timing: &PageTiming) {}
+
+    /// Called before a request is retried, with the error which caused the retry.
+    ///
+    /// `attempt` is the number of the retry, starting from 1.
+    fn on_retry(&self, _url: &Url, _attempt: usize, _error: &Error) {}
+
+    /// Called when a single item in a response is skipped or flagged, i.e. with an
+    /// [`Error::InvalidItem`] or [`Error::UnknownFields`].
+    fn on_item_error(&self, _error: &Error) {}
+
+    /// Called with every other [`Error`] encountered by the client.
+    fn on_error(&self, _error: &Error) {}
+
+    /// Called when the client fails over from one backend to the next, see
+    /// [`ClientBuilder::failover`](crate::ClientBuilder::failover).
+    fn on_failover(&self, _from: &Url, _to: &Url) {}
+
+    /// Called when the client skips over data, see
+    /// [`ClientBuilder::on_skipped`](crate::ClientBuilder::on_skipped).
+    fn on_skipped(&self, _skipped: &Skipped) {}
+}
@@ -0,0 +1,373 @@
+//! Refreshes content archived by PushShift with live data from reddit.
+//!
+//! PushShift scores and comment counts are a snapshot taken when the content was
+//! archived.  A [`Hydrator`] takes a stream of [`Post`]'s, [`Comment`]'s or
+//! [`Content`] and looks each item up on reddit.com (in batches, via `/api/info`),
+//! yielding the original item together with its current state.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() {
+//! use futures::StreamExt;
+//! use pullcaps::hydrate::Hydrator;
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::new();
+//! let hydrator = Hydrator::new();
+//!
+//! let posts = client.get_posts(Filter::new().subreddit("rust")).await.take(100);
+//! let mut posts = hydrator.hydrate(posts);
+//!
+//! while let Some(hydrated) = posts.next().await {
+//!     if let Some(live) = hydrated.live {
+//!         println!("score: {} -> {}", hydrated.item.attrs.score, live.score);
+//!     }
+//! }
+//! # }
+//! ```
+use crate::diff::{Delta, Snapshot};
+use crate::limiter::{parse_retry_after, Limiter, Priority, RateLimitMode};
+use crate::models::{Comment, Content, Post, RemovalStatus, DELETED_AUTHOR};
+use futures::stream::{self, Stream, StreamExt};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// The most items reddit will look up in a single `/api/info` request.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// The number of times a request rejected with `429 Too Many Requests` is retried.
+const MAX_RATE_LIMIT_RETRIES: usize = 5;
+
+const INFO_URL: &str = "https://www.reddit.com/api/info.json";
+
+/// Content which can be looked up on reddit by its fullname.
+pub trait Hydratable {
+    /// Returns the reddit fullname of this content, e.g. `t3_abc123`.
+    fn fullname(&self) -> String;
+
+    /// Returns whether, and how, this content had been removed when it was archived.
+    fn removal_status(&self) -> RemovalStatus;
+}
+
+impl Hydratable for Post {
+    fn fullname(&self) -> String {
+        self.id().fullname()
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        Post::removal_status(self)
+    }
+}
+
+impl Hydratable for Comment {
+    fn fullname(&self) -> String {
+        self.id().fullname()
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        Comment::removal_status(self)
+    }
+}
+
+impl Hydratable for Content {
+    fn fullname(&self) -> String {
+        match self {
+            Self::Post(post) => post.fullname(),
+            Self::Comment(comment) => comment.fullname(),
+        }
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        Content::removal_status(self)
+    }
+}
+
+/// The current state of a post or comment on reddit.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LiveData {
+    /// The current score.
+    pub score: i32,
+
+    /// The current number of comments, if a post.
+    pub num_comments: Option<i64>,
+
+    /// Why the content was removed, if it was.
+    pub removed_by_category: Option<String>,
+
+    #[serde(rename = "author")]
+    author_name: Option<String>,
+    body: Option<String>,
+    selftext: Option<String>,
+}
+
+impl LiveData {
+    /// Returns whether, and how, the content has since been removed.
+    pub fn removal_status(&self) -> RemovalStatus {
+        RemovalStatus::classify(
+            self.removed_by_category.as_deref(),
+            self.body.as_deref().or(self.selftext.as_deref()),
+            self.author_name.as_deref() == Some(DELETED_AUTHOR),
+        )
+    }
+
+    /// Returns whether the content has been removed by a moderator or reddit.
+    pub fn is_removed(&self) -> bool {
+        !matches!(
+            self.removal_status(),
+            RemovalStatus::Visible | RemovalStatus::Deleted
+        )
+    }
+
+    /// Returns whether the content has been deleted by its author.
+    pub fn is_deleted(&self) -> bool {
+        self.removal_status() == RemovalStatus::Deleted
+    }
+}
+
+impl Snapshot for LiveData {
+    fn score(&self) -> i32 {
+        self.score
+    }
+
+    fn num_comments(&self) -> Option<i64> {
+        self.num_comments
+    }
+
+    fn removal_status(&self) -> RemovalStatus {
+        LiveData::removal_status(self)
+    }
+}
+
+/// An item together with its live state on reddit.
+#[derive(Clone, Debug)]
+pub struct Hydrated<T> {
+    pub item: T,
+
+    /// The current state of the item, or `None` if reddit didn't return it.
+    pub live: Option<LiveData>,
+}
+
+impl<T: Hydratable> Hydrated<T> {
+    /// Returns whether this item was visible when archived by PushShift, but has since
+    /// been removed or deleted on reddit (or is no longer returned by reddit at all).
+    pub fn removed_since_archive(&self) -> bool {
+        self.item.removal_status().is_visible()
+            && !self
+                .live
+                .as_ref()
+                .is_some_and(|live| live.removal_status().is_visible())
+    }
+}
+
+impl<T: Snapshot> Hydrated<T> {
+    /// Returns how the item has changed since it was archived, or `None` if reddit
+    /// didn't return it.
+    pub fn delta(&self) -> Option<Delta> {
+        let live = self.live.as_ref()?;
+        Some(Delta::between(&self.item, live))
+    }
+}
+
+#[derive(Deserialize)]
+struct Listing {
+    data: ListingData,
+}
+
+#[derive(Deserialize)]
+struct ListingData {
+    children: Vec<Thing>,
+}
+
+#[derive(Deserialize)]
+struct Thing {
+    data: ThingData,
+}
+
+#[derive(Deserialize)]
+struct ThingData {
+    name: String,
+    #[serde(flatten)]
+    live: LiveData,
+}
+
+/// Looks up live data for archived content on reddit.com.
+///
+/// Requests are made at most once per second, and slowed down further if reddit
+/// responds with `429 Too Many Requests`.
+#[derive(Clone)]
+pub struct Hydrator {
+    client: reqwest::Client,
+    limiter: Arc<Limiter>,
+}
+
+impl Hydrator {
+    /// Creates a new hydrator.
+    pub fn new() -> Self {
+        // Reddit rejects requests which don't identify themselves.
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("pullcaps/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .unwrap_or_default();
+        Self::with_client(client)
+    }
+
+    /// Creates a new hydrator with the given backing [`reqwest::Client`].
+    ///
+    /// Reddit requires a descriptive `User-Agent`, so the client should be configured
+    /// with one.
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            limiter: RateLimitMode::PerClient.limiter(),
+        }
+    }
+
+    /// Returns a [`Stream`] yielding each item in `items` together with its live data.
+    ///
+    /// [`Stream`]: futures::Stream
+    pub fn hydrate<'a, S, T>(&'a self, items: S) -> Pin<Box<dyn Stream<Item = Hydrated<T>> + 'a>>
+    where
+        S: Stream<Item = T> + 'a,
+        T: Hydratable + 'a,
+    {
+        Box::pin(
+            items
+                .chunks(MAX_BATCH_SIZE)
+                .then(move |batch| self.hydrate_batch(batch))
+                .flat_map(stream::iter),
+        )
+    }
+
+    async fn hydrate_batch<T: Hydratable>(&self, batch: Vec<T>) -> Vec<Hydrated<T>> {
+        let fullnames: Vec<String> = batch.iter().map(Hydratable::fullname).collect();
+        let mut live = self.get_info(&fullnames.join(",")).await;
+
+        batch
+            .into_iter()
+            .zip(fullnames)
+            .map(|(item, fullname)| Hydrated {
+                item,
+                live: live.remove(&fullname),
+            })
+            .collect()
+    }
+
+    /// Looks up the given comma-separated fullnames, returning live data keyed by fullname.
+    async fn get_info(&self, ids: &str) -> HashMap<String, LiveData> {
+        for _ in 0..=MAX_RATE_LIMIT_RETRIES {
+            self.limiter.until_ready(Priority::Bulk).await;
+            let _in_flight = crate::concurrency::acquire().await;
+            let response = match self
+                .client
+                .get(INFO_URL)
+                .query(&[("id", ids), ("raw_json", "1")])
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(_) => break,
+            };
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after);
+                self.limiter.throttle(retry_after);
+                continue;
+            }
+
+            self.limiter.record_success();
+            if let Ok(listing) = response.json::<Listing>().await {
+                return listing
+                    .data
+                    .children
+                    .into_iter()
+                    .map(|thing| (thing.data.name, thing.data.live))
+                    .collect();
+            }
+            break;
+        }
+
+        HashMap::new()
+    }
+}
+
+impl Default for Hydrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() {
+        let listing: Listing = serde_json::from_value(serde_json::json!({
+            "kind": "Listing",
+            "data": {
+                "children": [
+                    {
+                        "kind": "t1",
+                        "data": {
+                            "name": "t1_abc",
+                            "score": 12,
+                            "author": "[deleted]",
+                            "body": "[removed]",
+                        }
+                    },
+                    {
+                        "kind": "t3",
+                        "data": {
+                            "name": "t3_def",
+                            "score": 40,
+                            "num_comments": 3,
+                            "removed_by_category": "deleted",
+                            "selftext": "[deleted]",
+                        }
+                    }
+                ]
+            }
+        }))
+        .unwrap();
+
+        let comment = &listing.data.children[0].data;
+        assert_eq!(comment.name, "t1_abc");
+        assert!(comment.live.is_removed());
+        assert!(!comment.live.is_deleted());
+
+        let post = &listing.data.children[1].data;
+        assert_eq!(post.live.num_comments, Some(3));
+        assert!(!post.live.is_removed());
+        assert!(post.live.is_deleted());
+    }
+
+    #[test]
+    fn test_removed_since_archive() {
+        let live: LiveData = serde_json::from_value(serde_json::json!({
+            "score": 3,
+            "author": "reddit",
+            "body": "[removed]",
+        }))
+        .unwrap();
+
+        let hydrated = Hydrated {
+            item: crate::test_util::comment("abc", 0),
+            live: Some(live),
+        };
+        assert!(hydrated.removed_since_archive());
+
+        let hydrated = Hydrated {
+            item: crate::test_util::comment("abc", 0),
+            live: None,
+        };
+        assert!(hydrated.removed_since_archive());
+    }
+}
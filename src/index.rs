@@ -0,0 +1,461 @@
+//! A local full-text index over fetched posts and comments.
+//!
+//! Pulling a large archive from PushShift can take hours, so a [`ContentIndex`] keeps
+//! what's been fetched (on disk, or in memory) and answers keyword, author and date
+//! queries over it without making any further requests.  Indexing the same item again
+//! replaces it, so an archive can be refreshed by ingesting it again.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() -> Result<(), pullcaps::index::IndexError> {
+//! use pullcaps::index::{ContentIndex, IndexQuery};
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::new();
+//! let index = ContentIndex::open("rust-archive")?;
+//!
+//! let comments = client.get_comments(Filter::new().subreddit("rust")).await;
+//! index.ingest(comments).await?;
+//!
+//! for content in index.search(&IndexQuery::new().text("borrow checker").author("dcchut"))? {
+//!     println!("{}", content.attrs().id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::filter::IntoDateTime;
+use crate::models::{Author, Comment, Content, Post};
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use std::fmt;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, Value, FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::{doc, Index, IndexReader, IndexWriter, Order, TantivyDocument, Term};
+
+/// The memory used by the index writer to buffer new documents.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// The number of results returned by a query, unless it says otherwise.
+const DEFAULT_LIMIT: usize = 100;
+
+/// A full-text index of posts and comments, see the [module documentation](self).
+pub struct ContentIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+/// The fields of each indexed document.
+struct Fields {
+    /// The fullname of the item, e.g. `t1_abc123`, which identifies it in the index.
+    id: Field,
+    /// The text of the item: a comment's body, or a post's title and text.
+    text: Field,
+    /// The lowercase username of the item's author.
+    author: Field,
+    /// The lowercase name of the item's subreddit.
+    subreddit: Field,
+    /// The creation date of the item, as a Unix timestamp.
+    created_utc: Field,
+    /// The item itself, as JSON.
+    content: Field,
+}
+
+impl ContentIndex {
+    /// Opens the index in the given directory, creating it if necessary.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, IndexError> {
+        std::fs::create_dir_all(dir.as_ref()).map_err(tantivy::TantivyError::from)?;
+        let directory = tantivy::directory::MmapDirectory::open(dir.as_ref())?;
+        Self::new(Index::open_or_create(directory, schema())?)
+    }
+
+    /// Creates an index which is kept in memory, and lost when it's dropped.
+    pub fn in_memory() -> Result<Self, IndexError> {
+        Self::new(Index::create_in_ram(schema()))
+    }
+
+    fn new(index: Index) -> Result<Self, IndexError> {
+        let schema = index.schema();
+        let field = |name| schema.get_field(name);
+        let fields = Fields {
+            id: field("id")?,
+            text: field("text")?,
+            author: field("author")?,
+            subreddit: field("subreddit")?,
+            created_utc: field("created_utc")?,
+            content: field("content")?,
+        };
+
+        Ok(Self {
+            reader: index
+                .reader_builder()
+                .reload_policy(tantivy::ReloadPolicy::Manual)
+                .try_into()?,
+            writer: Mutex::new(index.writer(WRITER_MEMORY)?),
+            index,
+            fields,
+        })
+    }
+
+    /// Adds a post or comment to the index, replacing it if it's already there.
+    ///
+    /// Items aren't searchable until they've been [committed](ContentIndex::commit).
+    pub fn add<C: Into<Content>>(&self, content: C) -> Result<(), IndexError> {
+        let content = content.into();
+        let (fullname, text, json) = match &content {
+            Content::Post(post) => (
+                post.id().fullname(),
+                match &post.self_text {
+                    Some(self_text) => format!("{}\n\n{}", post.title, self_text),
+                    None => post.title.clone(),
+                },
+                serde_json::to_string(post),
+            ),
+            Content::Comment(comment) => (
+                comment.id().fullname(),
+                comment.body.clone(),
+                serde_json::to_string(comment),
+            ),
+        };
+        let json = json.map_err(IndexError::Content)?;
+
+        let author = match content.author() {
+            Author::Known { name, .. } => name.to_lowercase(),
+            Author::Deleted => String::new(),
+        };
+
+        let writer = self.writer.lock().unwrap();
+        writer.delete_term(Term::from_field_text(self.fields.id, &fullname));
+        writer.add_document(doc!(
+            self.fields.id => fullname,
+            self.fields.text => text,
+            self.fields.author => author,
+            self.fields.subreddit => content.subreddit().name.to_lowercase(),
+            self.fields.created_utc => content.attrs().date.timestamp(),
+            self.fields.content => json,
+        ))?;
+        Ok(())
+    }
+
+    /// Makes the items added since the last commit searchable, and (for an index on
+    /// disk) persists them.
+    pub fn commit(&self) -> Result<(), IndexError> {
+        self.writer.lock().unwrap().commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    /// Adds every item of a stream to the index, committing them once the stream ends.
+    /// Returns the number of items added.
+    pub async fn ingest<S, C>(&self, items: S) -> Result<usize, IndexError>
+    where
+        S: Stream<Item = C>,
+        C: Into<Content>,
+    {
+        let mut items = Box::pin(items);
+        let mut count = 0;
+        while let Some(item) = items.next().await {
+            self.add(item)?;
+            count += 1;
+        }
+        self.commit()?;
+        Ok(count)
+    }
+
+    /// Returns the number of items which have been committed to the index.
+    pub fn len(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Returns whether no items have been committed to the index.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the items matching the given query.
+    ///
+    /// Results are ordered by relevance if the query searches for text, and from
+    /// newest to oldest otherwise.
+    pub fn search(&self, query: &IndexQuery) -> Result<Vec<Content>, IndexError> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+        if let Some(text) = &query.text {
+            let parser = QueryParser::for_index(&self.index, vec![self.fields.text]);
+            clauses.push((Occur::Must, parser.parse_query(text)?));
+        }
+        for (field, value) in [
+            (self.fields.author, &query.author),
+            (self.fields.subreddit, &query.subreddit),
+        ] {
+            if let Some(value) = value {
+                let term = Term::from_field_text(field, &value.to_lowercase());
+                let query = TermQuery::new(term, IndexRecordOption::Basic);
+                clauses.push((Occur::Must, Box::new(query)));
+            }
+        }
+        if query.after.is_some() || query.before.is_some() {
+            let bound = |date: Option<DateTime<Utc>>| {
+                date.map_or(Bound::Unbounded, |date| Bound::Included(date.timestamp()))
+            };
+            let range = RangeQuery::new_i64_bounds(
+                "created_utc".to_string(),
+                bound(query.after),
+                bound(query.before),
+            );
+            clauses.push((Occur::Must, Box::new(range)));
+        }
+
+        let combined: Box<dyn Query> = if clauses.is_empty() {
+            Box::new(AllQuery)
+        } else {
+            Box::new(BooleanQuery::new(clauses))
+        };
+
+        let searcher = self.reader.searcher();
+        let limit = TopDocs::with_limit(query.limit.unwrap_or(DEFAULT_LIMIT).max(1));
+        let addresses: Vec<_> = if query.text.is_some() {
+            searcher
+                .search(&combined, &limit)?
+                .into_iter()
+                .map(|(_, address)| address)
+                .collect()
+        } else {
+            let newest = limit.order_by_fast_field::<i64>("created_utc", Order::Desc);
+            searcher
+                .search(&combined, &newest)?
+                .into_iter()
+                .map(|(_, address)| address)
+                .collect()
+        };
+
+        addresses
+            .into_iter()
+            .map(|address| {
+                let document: TantivyDocument = searcher.doc(address)?;
+                let json = document
+                    .get_first(self.fields.content)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default();
+                parse_content(json)
+            })
+            .collect()
+    }
+}
+
+/// Returns the schema of the index.
+fn schema() -> Schema {
+    let mut schema = Schema::builder();
+    schema.add_text_field("id", STRING | STORED);
+    schema.add_text_field("text", TEXT);
+    schema.add_text_field("author", STRING);
+    schema.add_text_field("subreddit", STRING);
+    schema.add_i64_field("created_utc", INDEXED | FAST);
+    schema.add_text_field("content", STORED);
+    schema.build()
+}
+
+/// Parses a stored item, which is a comment if it has a body and a post otherwise.
+fn parse_content(json: &str) -> Result<Content, IndexError> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(IndexError::Content)?;
+    let content = if value.get("body").is_some() {
+        Content::Comment(serde_json::from_value::<Comment>(value).map_err(IndexError::Content)?)
+    } else {
+        Content::Post(serde_json::from_value::<Post>(value).map_err(IndexError::Content)?)
+    };
+    Ok(content)
+}
+
+/// A query over a [`ContentIndex`].
+///
+/// Every condition which is set must match.
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct IndexQuery {
+    text: Option<String>,
+    author: Option<String>,
+    subreddit: Option<String>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+}
+
+impl IndexQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches items whose text (a comment's body, or a post's title and text) matches the
+    /// given keywords.
+    ///
+    /// This supports tantivy's query syntax, e.g. `"borrow checker"` for a phrase,
+    /// or `async AND -tokio`.
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Matches items by the given author (ignoring case).
+    pub fn author<S: Into<String>>(mut self, author: S) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Matches items in the given subreddit (ignoring case).
+    pub fn subreddit<S: Into<String>>(mut self, subreddit: S) -> Self {
+        self.subreddit = Some(subreddit.into());
+        self
+    }
+
+    /// Matches items created at or after the given date.
+    pub fn after<D: IntoDateTime>(mut self, after: D) -> Self {
+        self.after = Some(after.into_date_time());
+        self
+    }
+
+    /// Matches items created at or before the given date.
+    pub fn before<D: IntoDateTime>(mut self, before: D) -> Self {
+        self.before = Some(before.into_date_time());
+        self
+    }
+
+    /// Limits the number of results returned, which defaults to 100.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// An error encountered while using a [`ContentIndex`].
+#[derive(Debug)]
+pub enum IndexError {
+    /// The index couldn't be read or written.
+    Index(tantivy::TantivyError),
+    /// A query's text couldn't be parsed.
+    Query(tantivy::query::QueryParserError),
+    /// An item couldn't be stored in, or read back from, the index.
+    Content(serde_json::Error),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(error) => write!(f, "index error: {}", error),
+            Self::Query(error) => write!(f, "invalid query: {}", error),
+            Self::Content(error) => write!(f, "invalid indexed item: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Index(error) => Some(error),
+            Self::Query(error) => Some(error),
+            Self::Content(error) => Some(error),
+        }
+    }
+}
+
+impl From<tantivy::TantivyError> for IndexError {
+    fn from(error: tantivy::TantivyError) -> Self {
+        Self::Index(error)
+    }
+}
+
+impl From<tantivy::directory::error::OpenDirectoryError> for IndexError {
+    fn from(error: tantivy::directory::error::OpenDirectoryError) -> Self {
+        Self::Index(error.into())
+    }
+}
+
+impl From<tantivy::query::QueryParserError> for IndexError {
+    fn from(error: tantivy::query::QueryParserError) -> Self {
+        Self::Query(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::comment;
+
+    fn post(id: &str, created_utc: i64, text: &str) -> Post {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "created_utc": created_utc,
+            "author": "Ferris",
+            "subreddit": "rust",
+            "title": format!("Post {}", id),
+            "selftext": text,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_search() {
+        let index = ContentIndex::in_memory().unwrap();
+        let mut reply = comment("c1", 200);
+        reply.body = "Fighting the borrow checker again".to_string();
+        index.add(reply).unwrap();
+        index
+            .add(post("p1", 100, "Announcing a new borrow checker"))
+            .unwrap();
+        index.add(post("p2", 300, "Async traits are here")).unwrap();
+        index.commit().unwrap();
+        assert_eq!(index.len(), 3);
+
+        let ids = |query: IndexQuery| -> Vec<String> {
+            let results = index.search(&query).unwrap();
+            results.iter().map(|c| c.attrs().id.clone()).collect()
+        };
+
+        let mut matches = ids(IndexQuery::new().text("borrow"));
+        matches.sort();
+        assert_eq!(matches, vec!["c1", "p1"]);
+        assert_eq!(
+            ids(IndexQuery::new().text("borrow").author("ferris")),
+            vec!["p1"]
+        );
+        assert_eq!(
+            ids(IndexQuery::new().author("Ferris").after(chrono_date(150))),
+            vec!["p2"]
+        );
+
+        // Posts' titles are searched as well as their text.
+        assert_eq!(ids(IndexQuery::new().text("p2")), vec!["p2"]);
+
+        // Without any text, the newest items come first.
+        assert_eq!(ids(IndexQuery::new().limit(2)), vec!["p2", "c1"]);
+        assert!(matches!(
+            index.search(&IndexQuery::new().text("borrow AND (")),
+            Err(IndexError::Query(_))
+        ));
+    }
+
+    #[test]
+    fn test_reindexing_replaces_items() {
+        let index = ContentIndex::in_memory().unwrap();
+        index.add(post("p1", 100, "first draft")).unwrap();
+        index.commit().unwrap();
+        index.add(post("p1", 100, "final version")).unwrap();
+        index.commit().unwrap();
+
+        assert_eq!(index.len(), 1);
+        assert!(index
+            .search(&IndexQuery::new().text("draft"))
+            .unwrap()
+            .is_empty());
+    }
+
+    fn chrono_date(ts: i64) -> DateTime<Utc> {
+        use chrono::TimeZone;
+        Utc.timestamp_opt(ts, 0).unwrap()
+    }
+}
@@ -51,10 +51,99 @@
 //! # }
 //! ```
 
+pub mod backfill;
+pub mod cache;
+pub mod diff;
 pub mod models;
+pub mod partition;
+pub mod sync;
 
+#[cfg(feature = "archive")]
+pub mod archive;
+
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+
+#[cfg(feature = "feed")]
+pub mod feed;
+
+#[cfg(feature = "hydrate")]
+pub mod hydrate;
+
+#[cfg(feature = "index")]
+pub mod index;
+
+#[cfg(feature = "manifest")]
+pub mod manifest;
+
+#[cfg(feature = "text")]
+pub mod text;
+
+#[cfg(feature = "warc")]
+pub mod warc;
+
+mod aggregations;
+mod ancestors;
+mod api;
+mod auth;
+mod authors;
+mod batch;
+mod breaker;
+mod budget;
+mod buffer;
+mod chunking;
 mod client;
+mod concurrency;
+mod control;
+mod error;
+mod ext;
+mod failover;
 mod filter;
+mod health;
+mod hooks;
+mod limiter;
+mod overview;
+mod pagination;
+mod plan;
+mod pool;
+mod progress;
+mod query;
+mod response;
+mod sample;
+mod search;
+mod skipped;
+mod stream;
+mod subreddits;
+#[cfg(feature = "metrics")]
+mod telemetry;
+#[cfg(test)]
+mod test_util;
+mod timing;
+mod transfer;
 
-pub use client::Client;
-pub use filter::{Filter, SortType};
+pub use aggregations::{AuthorActivity, AuthorSummary, SubredditActivity};
+pub use ancestors::Ancestors;
+pub use api::{ApiVersion, Endpoint};
+pub use batch::{BatchQuery, BatchResult};
+pub use breaker::CircuitState;
+pub use chunking::Chunking;
+pub use client::{Client, ClientBuilder};
+pub use concurrency::{in_flight_requests, set_max_in_flight_requests};
+pub use control::StreamControl;
+pub use error::Error;
+pub use ext::{GroupByPeriod, Period, PullcapsStreamExt};
+pub use filter::{Filter, IntoDateTime, ParseFilterError, RemovedText, SortDirection, SortType};
+pub use health::Health;
+pub use hooks::Hooks;
+#[cfg(feature = "redis")]
+pub use limiter::RedisRateLimiter;
+pub use limiter::{Priority, RateLimitBackend, RateLimitMode, RateLimitStatus};
+pub use overview::{AuthorStatus, UserOverview};
+pub use plan::QueryPlan;
+pub use progress::Progress;
+pub use query::Query;
+pub use skipped::{SkipReason, Skipped};
+pub use stream::{CommentStream, FailedRange, ItemStream, PostStream};
+pub use timing::PageTiming;
+pub use transfer::TransferStats;
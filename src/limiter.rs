@@ -0,0 +1,549 @@
+use crate::models::Shards;
+use futures::future::BoxFuture;
+use governor::{Quota, RateLimiter};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{MutexGuard, Notify};
+
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisRateLimiter;
+
+type PSRateLimiter = RateLimiter<
+    governor::state::NotKeyed,
+    governor::state::InMemoryState,
+    governor::clock::DefaultClock,
+    governor::middleware::NoOpMiddleware,
+>;
+
+/// The interval between requests permitted by PushShift's rate limit, until the
+/// actual limit has been discovered.
+pub(crate) const REQUEST_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long a discovered rate limit is used before it's discovered again.
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The largest factor by which throttling slows down the base quota.
+const MAX_SLOWDOWN: u32 = 16;
+
+/// The number of consecutive successful requests required before a throttled
+/// limiter speeds back up.
+const RECOVERY_REQUESTS: u32 = 10;
+
+/// Determines which rate limiter a [`Client`] uses to throttle its requests.
+///
+/// [`Client`]: crate::Client
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitMode {
+    /// The client (together with its clones) owns its own rate limiter.
+    PerClient,
+    /// The client shares a single process-wide rate limiter with every other
+    /// client constructed using this mode.
+    Shared,
+}
+
+impl RateLimitMode {
+    pub fn new() -> Self {
+        Self::PerClient
+    }
+
+    /// Returns a rate limiter appropriate for this mode.
+    pub(crate) fn limiter(&self) -> Arc<Limiter> {
+        match self {
+            Self::PerClient => Arc::new(Limiter::new()),
+            Self::Shared => shared_limiter(),
+        }
+    }
+}
+
+impl Default for RateLimitMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The priority of a query's requests when they wait on the rate limiter, see
+/// [`Filter::priority`].
+///
+/// Interactive requests are permitted before any bulk requests which are waiting, so
+/// that e.g. a query from a user interface isn't stuck behind a large backfill.
+/// Requests of the same priority are permitted in the order they were made.
+///
+/// [`Filter::priority`]: crate::Filter::priority
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// A query which someone is waiting on.
+    Interactive,
+    /// A query which can wait, e.g. a backfill.  This is the default.
+    #[default]
+    Bulk,
+}
+
+/// A rate limiter whose quota is kept outside of the process, so that it can be shared
+/// by several processes; see [`ClientBuilder::rate_limit_backend`].
+///
+/// [`ClientBuilder::rate_limit_backend`]: crate::ClientBuilder::rate_limit_backend
+pub trait RateLimitBackend: Send + Sync {
+    /// Reserves the next slot for a request, where consecutive requests (across every
+    /// process sharing the quota) must be at least `period` apart.  Returns how long to
+    /// wait before the request may be made.
+    ///
+    /// Returns `None` if the backend is unavailable, in which case the client falls
+    /// back to its in-process rate limiter.
+    fn acquire(&self, period: Duration) -> BoxFuture<'_, Option<Duration>>;
+}
+
+/// A snapshot of a [`Client`]'s rate limiter, see [`Client::rate_limit_status`].
+///
+/// [`Client`]: crate::Client
+/// [`Client::rate_limit_status`]: crate::Client::rate_limit_status
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// The number of requests which could be made right now without waiting.
+    pub remaining: u32,
+    /// The estimated time until a new request would be permitted, behind any requests
+    /// which are already waiting.
+    pub wait: Duration,
+    /// The number of requests currently waiting for a permit.
+    pub queued: usize,
+    /// The interval between requests, which is longer than PushShift's rate limit
+    /// while the client is backing off after being throttled.
+    pub period: Duration,
+}
+
+/// The response of PushShift's meta endpoint, as far as it concerns the rate limit and
+/// the health of the server.
+#[derive(Deserialize)]
+pub(crate) struct Meta {
+    pub(crate) server_ratelimit_per_minute: Option<u32>,
+    #[serde(default, alias = "_shards")]
+    pub(crate) shards: Option<Shards>,
+}
+
+impl Meta {
+    /// Returns the interval between requests permitted by the advertised rate limit.
+    pub(crate) fn period(&self) -> Option<Duration> {
+        match self.server_ratelimit_per_minute? {
+            0 => None,
+            per_minute => Some(Duration::from_secs(60) / per_minute),
+        }
+    }
+}
+
+/// A rate limiter which limits PS API queries to the rate PushShift advertises (or 1
+/// per second, until it's been discovered), and which can be slowed down further when
+/// the server tells us we're making too many requests.
+pub(crate) struct Limiter {
+    /// The interval between requests when not throttled.
+    period: Mutex<Duration>,
+    /// When the rate limit was last discovered (or an attempt was made to), held while
+    /// a discovery is in progress so that requests wait for it.
+    discovered_at: tokio::sync::Mutex<Option<Instant>>,
+    inner: RwLock<Arc<PSRateLimiter>>,
+    /// The factor by which the base period is currently stretched.
+    slowdown: AtomicU32,
+    /// Consecutive successful requests since the last change to `slowdown`.
+    successes: AtomicU32,
+    /// No requests should be made before this instant.
+    blocked_until: Mutex<Option<Instant>>,
+    /// Requests wait their turn here, so that they're permitted in the order they were
+    /// made and no single stream can starve the others.
+    queue: tokio::sync::Mutex<()>,
+    /// The quota shared with other processes, if any.
+    backend: Option<Arc<dyn RateLimitBackend>>,
+    /// The number of requests waiting in `until_ready`.
+    queued: AtomicUsize,
+    /// The number of interactive requests waiting for their turn.
+    interactive: AtomicUsize,
+    /// Notified when there are no longer any interactive requests waiting.
+    no_interactive: Notify,
+    /// The earliest the next request is expected to be permitted, if one has been made.
+    next_permit: Mutex<Option<Instant>>,
+}
+
+impl Limiter {
+    fn new() -> Self {
+        let period = REQUEST_PERIOD;
+        Self {
+            period: Mutex::new(period),
+            discovered_at: tokio::sync::Mutex::new(None),
+            inner: RwLock::new(Arc::new(RateLimiter::direct(
+                Quota::with_period(period).unwrap(),
+            ))),
+            slowdown: AtomicU32::new(1),
+            successes: AtomicU32::new(0),
+            blocked_until: Mutex::new(None),
+            queue: tokio::sync::Mutex::new(()),
+            backend: None,
+            queued: AtomicUsize::new(0),
+            interactive: AtomicUsize::new(0),
+            no_interactive: Notify::new(),
+            next_permit: Mutex::new(None),
+        }
+    }
+
+    /// Creates a rate limiter which takes its quota from the given backend.
+    pub(crate) fn with_backend(backend: Arc<dyn RateLimitBackend>) -> Self {
+        Self {
+            backend: Some(backend),
+            ..Self::new()
+        }
+    }
+
+    /// Discovers the rate limit with `discover`, which returns the interval between
+    /// requests, unless it's been discovered recently.
+    ///
+    /// Requests wait for a discovery in progress, so that they use the discovered
+    /// rate limit.  If it can't be discovered the current rate limit is kept, and
+    /// discovery isn't attempted again until the next interval.
+    pub(crate) async fn discover<F>(&self, discover: F)
+    where
+        F: Future<Output = Option<Duration>>,
+    {
+        let mut discovered_at = self.discovered_at.lock().await;
+        if discovered_at.is_some_and(|at| at.elapsed() < DISCOVERY_INTERVAL) {
+            return;
+        }
+        if let Some(period) = discover.await {
+            self.set_period(period);
+        }
+        *discovered_at = Some(Instant::now());
+    }
+
+    /// Waits until a request of the given priority is permitted.
+    pub(crate) async fn until_ready(&self, priority: Priority) {
+        let _queued = Queued::new(&self.queued);
+        let _turn = match priority {
+            Priority::Interactive => {
+                let _waiting = Interactive::new(self);
+                self.queue.lock().await
+            }
+            Priority::Bulk => self.bulk_turn().await,
+        };
+        self.wait_for_permit().await;
+        *self.next_permit.lock().unwrap() = Some(Instant::now() + self.current_period());
+    }
+
+    /// Waits for a bulk request's turn, letting any interactive requests go first.
+    async fn bulk_turn(&self) -> MutexGuard<'_, ()> {
+        loop {
+            let no_interactive = self.no_interactive.notified();
+            if self.interactive.load(Ordering::SeqCst) > 0 {
+                no_interactive.await;
+                continue;
+            }
+
+            let turn = self.queue.lock().await;
+            // An interactive request may have started waiting while we queued.
+            if self.interactive.load(Ordering::SeqCst) == 0 {
+                return turn;
+            }
+        }
+    }
+
+    async fn wait_for_permit(&self) {
+        let blocked_until = *self.blocked_until.lock().unwrap();
+        if let Some(blocked_until) = blocked_until {
+            let now = Instant::now();
+            if blocked_until > now {
+                tokio::time::sleep(blocked_until - now).await;
+            }
+        }
+
+        if let Some(backend) = &self.backend {
+            if let Some(wait) = backend.acquire(self.current_period()).await {
+                tokio::time::sleep(wait).await;
+                return;
+            }
+        }
+
+        let inner = self.inner.read().unwrap().clone();
+        inner.until_ready().await;
+    }
+
+    /// Returns the interval between requests, including any slowdown.
+    fn current_period(&self) -> Duration {
+        self.period() * self.slowdown.load(Ordering::SeqCst)
+    }
+
+    /// Returns the interval between requests when not throttled.
+    fn period(&self) -> Duration {
+        *self.period.lock().unwrap()
+    }
+
+    /// Estimates how soon a new request would be permitted.
+    ///
+    /// This is based on this process's requests only, so it doesn't account for other
+    /// processes sharing a [`RateLimitBackend`].
+    pub(crate) fn status(&self) -> RateLimitStatus {
+        let now = Instant::now();
+        let period = self.current_period();
+        let queued = self.queued.load(Ordering::SeqCst);
+
+        let blocked_until = *self.blocked_until.lock().unwrap();
+        let next_permit = *self.next_permit.lock().unwrap();
+        let ready_at = blocked_until.max(next_permit).unwrap_or(now);
+        let wait = ready_at.saturating_duration_since(now) + period * queued as u32;
+
+        RateLimitStatus {
+            remaining: u32::from(wait.is_zero()),
+            wait,
+            queued,
+            period,
+        }
+    }
+
+    /// Records that the server rejected a request for exceeding its rate limit.
+    ///
+    /// No further requests are permitted until `retry_after` has elapsed (or a
+    /// backoff proportional to the current slowdown, if the server didn't say), and
+    /// the quota is halved so that we're less likely to be throttled again.
+    pub(crate) fn throttle(&self, retry_after: Option<Duration>) {
+        let slowdown = (self.slowdown.load(Ordering::SeqCst) * 2).min(MAX_SLOWDOWN);
+        self.set_slowdown(slowdown);
+
+        let wait = retry_after.unwrap_or(self.period() * slowdown);
+        let until = Instant::now() + wait;
+        let mut blocked_until = self.blocked_until.lock().unwrap();
+        if blocked_until.is_none_or(|current| current < until) {
+            *blocked_until = Some(until);
+        }
+    }
+
+    /// Records a successful request, gradually restoring the original quota after
+    /// the limiter has been throttled.
+    pub(crate) fn record_success(&self) {
+        let slowdown = self.slowdown.load(Ordering::SeqCst);
+        if slowdown > 1 && self.successes.fetch_add(1, Ordering::SeqCst) + 1 >= RECOVERY_REQUESTS {
+            self.set_slowdown(slowdown / 2);
+        }
+    }
+
+    fn set_slowdown(&self, slowdown: u32) {
+        self.slowdown.store(slowdown, Ordering::SeqCst);
+        self.successes.store(0, Ordering::SeqCst);
+        self.update_quota();
+    }
+
+    /// Changes the interval between requests when not throttled.
+    fn set_period(&self, period: Duration) {
+        *self.period.lock().unwrap() = period;
+        self.update_quota();
+    }
+
+    fn update_quota(&self) {
+        let quota = Quota::with_period(self.current_period()).unwrap();
+        *self.inner.write().unwrap() = Arc::new(RateLimiter::direct(quota));
+    }
+}
+
+/// Counts a request as queued for as long as it's waiting for a permit, including if
+/// it's cancelled.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn new(queued: &'a AtomicUsize) -> Self {
+        queued.fetch_add(1, Ordering::SeqCst);
+        Self(queued)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts an interactive request as waiting until it gets its turn, including if it's
+/// cancelled, then lets bulk requests continue.
+struct Interactive<'a>(&'a Limiter);
+
+impl<'a> Interactive<'a> {
+    fn new(limiter: &'a Limiter) -> Self {
+        limiter.interactive.fetch_add(1, Ordering::SeqCst);
+        Self(limiter)
+    }
+}
+
+impl Drop for Interactive<'_> {
+    fn drop(&mut self) {
+        if self.0.interactive.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.no_interactive.notify_waiters();
+        }
+    }
+}
+
+/// A global rate limiter, used by all clients in [`RateLimitMode::Shared`].
+fn shared_limiter() -> Arc<Limiter> {
+    static PS_RATE_LIMITER: OnceCell<Arc<Limiter>> = OnceCell::new();
+    PS_RATE_LIMITER
+        .get_or_init(|| Arc::new(Limiter::new()))
+        .clone()
+}
+
+/// Parses the value of a `Retry-After` header given in seconds.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_mode_shares_limiter() {
+        let a = RateLimitMode::Shared.limiter();
+        let b = RateLimitMode::Shared.limiter();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let c = RateLimitMode::PerClient.limiter();
+        let d = RateLimitMode::PerClient.limiter();
+        assert!(!Arc::ptr_eq(&c, &d));
+    }
+
+    #[test]
+    fn test_throttle_and_recover() {
+        let limiter = Limiter::new();
+        limiter.throttle(Some(Duration::from_secs(5)));
+        limiter.throttle(None);
+        assert_eq!(limiter.slowdown.load(Ordering::SeqCst), 4);
+        assert!(limiter.blocked_until.lock().unwrap().is_some());
+
+        for _ in 0..RECOVERY_REQUESTS {
+            limiter.record_success();
+        }
+        assert_eq!(limiter.slowdown.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_backend_sets_the_pace() {
+        struct Fixed(Mutex<Vec<Duration>>);
+
+        impl RateLimitBackend for Fixed {
+            fn acquire(&self, period: Duration) -> BoxFuture<'_, Option<Duration>> {
+                self.0.lock().unwrap().push(period);
+                Box::pin(async { Some(Duration::ZERO) })
+            }
+        }
+
+        let backend = Arc::new(Fixed(Mutex::new(Vec::new())));
+        let limiter = Limiter::with_backend(backend.clone());
+
+        // The in-process limiter would make the second request wait a second.
+        let start = Instant::now();
+        limiter.until_ready(Priority::Bulk).await;
+        limiter.until_ready(Priority::Bulk).await;
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        limiter.set_slowdown(2);
+        limiter.until_ready(Priority::Bulk).await;
+        assert_eq!(
+            *backend.0.lock().unwrap(),
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(1),
+                Duration::from_secs(2)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let limiter = Limiter::new();
+        let status = limiter.status();
+        assert_eq!(
+            (status.remaining, status.wait, status.queued),
+            (1, Duration::ZERO, 0)
+        );
+
+        limiter.until_ready(Priority::Bulk).await;
+        let status = limiter.status();
+        assert_eq!(status.remaining, 0);
+        assert!(status.wait > Duration::ZERO && status.wait <= REQUEST_PERIOD);
+
+        limiter.throttle(Some(Duration::from_secs(30)));
+        let status = limiter.status();
+        assert_eq!(status.period, REQUEST_PERIOD * 2);
+        assert!(status.wait > Duration::from_secs(29));
+    }
+
+    #[tokio::test]
+    async fn test_interactive_requests_go_first() {
+        struct Paced;
+
+        impl RateLimitBackend for Paced {
+            fn acquire(&self, _period: Duration) -> BoxFuture<'_, Option<Duration>> {
+                Box::pin(async { Some(Duration::from_millis(50)) })
+            }
+        }
+
+        let limiter = Arc::new(Limiter::with_backend(Arc::new(Paced)));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let request = |name: &'static str, priority: Priority| {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            tokio::spawn(async move {
+                limiter.until_ready(priority).await;
+                order.lock().unwrap().push(name);
+            })
+        };
+
+        let mut requests = vec![
+            request("bulk-1", Priority::Bulk),
+            request("bulk-2", Priority::Bulk),
+            request("bulk-3", Priority::Bulk),
+        ];
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        requests.push(request("interactive", Priority::Interactive));
+        for request in requests {
+            request.await.unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["bulk-1", "interactive", "bulk-2", "bulk-3"]
+        );
+    }
+
+    #[test]
+    fn test_meta_period() {
+        let meta = |json| serde_json::from_str::<Meta>(json).unwrap().period();
+        assert_eq!(
+            meta(r#"{"server_ratelimit_per_minute": 120}"#),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(meta(r#"{"server_ratelimit_per_minute": 0}"#), None);
+        assert_eq!(meta(r#"{"client_accepts_json": true}"#), None);
+    }
+
+    #[tokio::test]
+    async fn test_discover() {
+        let limiter = Limiter::new();
+        limiter.throttle(None);
+        limiter
+            .discover(async { Some(Duration::from_millis(500)) })
+            .await;
+        assert_eq!(limiter.status().period, Duration::from_secs(1));
+
+        // The rate limit isn't discovered again until the interval has passed.
+        limiter
+            .discover(async { Some(Duration::from_secs(5)) })
+            .await;
+        assert_eq!(limiter.period(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
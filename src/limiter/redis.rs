@@ -0,0 +1,77 @@
+use super::RateLimitBackend;
+use futures::future::BoxFuture;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult, Script};
+use std::time::Duration;
+
+const DEFAULT_KEY: &str = "pullcaps:rate-limit";
+
+/// Reserves the next request slot, returning how many microseconds to wait for it.
+///
+/// The key holds the earliest time (in microseconds, by the Redis server's clock) at
+/// which the next request may be made, and expires once that time has passed.
+const ACQUIRE: &str = r"
+local now = redis.call('TIME')
+local now = tonumber(now[1]) * 1000000 + tonumber(now[2])
+local period = tonumber(ARGV[1])
+local slot = math.max(tonumber(redis.call('GET', KEYS[1]) or now), now)
+local expiry = math.ceil((slot - now + period) / 1000) + 1000
+redis.call('SET', KEYS[1], slot + period, 'PX', expiry)
+return slot - now
+";
+
+/// A [`RateLimitBackend`] which keeps its quota in Redis, so that every process using
+/// the same Redis server (and key) collectively respects PushShift's rate limit.
+///
+/// If Redis can't be reached, each client falls back to its own in-process rate
+/// limiter until it can be again.
+///
+/// # Example
+/// ```rust,no_run
+/// # async fn example() {
+/// use pullcaps::{Client, RedisRateLimiter};
+///
+/// let limiter = RedisRateLimiter::new("redis://127.0.0.1/").await.unwrap();
+/// let client = Client::builder().rate_limit_backend(limiter).build();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedisRateLimiter {
+    connection: ConnectionManager,
+    key: String,
+    script: Script,
+}
+
+impl RedisRateLimiter {
+    /// Connects to the Redis server at the given URL, e.g. `redis://127.0.0.1/`.
+    pub async fn new(url: &str) -> RedisResult<Self> {
+        let connection = ConnectionManager::new(Client::open(url)?).await?;
+        Ok(Self {
+            connection,
+            key: DEFAULT_KEY.to_string(),
+            script: Script::new(ACQUIRE),
+        })
+    }
+
+    /// Sets the key under which the quota is stored, which defaults to
+    /// `pullcaps:rate-limit`.  Processes only share a quota if they use the same key.
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.key = key.into();
+        self
+    }
+}
+
+impl RateLimitBackend for RedisRateLimiter {
+    fn acquire(&self, period: Duration) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let mut connection = self.connection.clone();
+            let wait: RedisResult<u64> = self
+                .script
+                .key(&self.key)
+                .arg(period.as_micros() as u64)
+                .invoke_async(&mut connection)
+                .await;
+            wait.ok().map(Duration::from_micros)
+        })
+    }
+}
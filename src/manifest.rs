@@ -0,0 +1,384 @@
+//! Manifests describing exported datasets, so that they can be published and verified.
+//!
+//! A [`ManifestWriter`] counts the items of the streams passed through it, recording the
+//! query each came from, and then writes a JSON manifest listing the queries, how many
+//! items each returned (in total, for each day and for each bucket the query was chunked
+//! into), the date ranges which failed to fetch, the version of pullcaps used, and the
+//! size and SHA-256 checksum of every file exported.  It works alongside any sink,
+//! e.g. an `ArchiveWriter` or an [`NdjsonPartitions`].
+//!
+//! Manifests are stable: queries are listed in the order they were tracked, and files
+//! and days in sorted order, so exporting the same data twice produces the same
+//! manifest, apart from its creation date.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use pullcaps::manifest::ManifestWriter;
+//! use pullcaps::partition::NdjsonPartitions;
+//! use pullcaps::{Client, Endpoint, Filter};
+//!
+//! let client = Client::new();
+//! let filter = Filter::new().author("reddit").last_days(30);
+//!
+//! let mut manifest = ManifestWriter::new();
+//! let comments = client.get_comments(filter.clone()).await;
+//! let comments = manifest.track(&client, Endpoint::Comments, &filter, comments);
+//!
+//! let mut partitions = NdjsonPartitions::new("export");
+//! partitions.write_all(comments).await?;
+//! let files = partitions.finish()?;
+//!
+//! manifest.write("export/manifest.json", files)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`NdjsonPartitions`]: crate::partition::NdjsonPartitions
+use crate::models::{AsAttrs, Thing};
+use crate::stream::{ItemStream, Tracking};
+use crate::{Client, Endpoint, FailedRange, Filter};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A description of an exported dataset, see the [module documentation](self).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    /// The version of pullcaps the dataset was exported with.
+    pub pullcaps_version: String,
+
+    /// When the manifest was written.
+    pub created: DateTime<Utc>,
+
+    /// The queries whose items were exported.
+    pub queries: Vec<ManifestQuery>,
+
+    /// The files the items were exported to.
+    pub files: Vec<ManifestFile>,
+}
+
+/// A query whose items were exported, as recorded in a [`Manifest`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ManifestQuery {
+    /// The backend the query was made to, e.g. `https://api.pushshift.io/`.
+    pub backend: String,
+
+    /// The path of the endpoint the query was made to.
+    pub endpoint: String,
+
+    /// The query parameters of the filter used, as they were sent to the backend.
+    pub params: Vec<(String, String)>,
+
+    /// The start of the date range the filter asked for, if it had one.
+    pub after: Option<DateTime<Utc>>,
+
+    /// The end of the date range the filter asked for, if it had one.
+    pub before: Option<DateTime<Utc>>,
+
+    /// The creation date of the oldest item exported, if any were.
+    pub earliest: Option<DateTime<Utc>>,
+
+    /// The creation date of the newest item exported, if any were.
+    pub latest: Option<DateTime<Utc>>,
+
+    /// The number of items exported.
+    pub items: u64,
+
+    /// The number of items exported which were created on each day (in UTC).
+    pub days: BTreeMap<NaiveDate, u64>,
+
+    /// The date ranges the query was chunked into, if it was, with the number of items
+    /// exported from each.
+    #[serde(default)]
+    pub buckets: Vec<ManifestBucket>,
+
+    /// The date ranges the query failed to fetch, whose items are missing from the
+    /// export, see [`CommentStream::failed_ranges`](crate::CommentStream::failed_ranges).
+    #[serde(default)]
+    pub failed_ranges: Vec<FailedRange>,
+}
+
+/// A bucket a query was chunked into, as recorded in a [`ManifestQuery`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ManifestBucket {
+    /// The start of the bucket's date range.
+    pub after: DateTime<Utc>,
+
+    /// The end of the bucket's date range.
+    pub before: DateTime<Utc>,
+
+    /// The number of items exported from the bucket.
+    pub items: u64,
+}
+
+impl ManifestQuery {
+    fn new(client: &Client, endpoint: Endpoint, filter: &Filter) -> Self {
+        let url = client.url_for(endpoint, filter);
+        Self {
+            backend: client.backend().to_string(),
+            endpoint: url.path().to_string(),
+            params: url.query_pairs().into_owned().collect(),
+            after: filter.after_date(),
+            before: filter.before_date(),
+            earliest: None,
+            latest: None,
+            items: 0,
+            days: BTreeMap::new(),
+            buckets: Vec::new(),
+            failed_ranges: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, date: DateTime<Utc>, tracking: &Tracking) {
+        self.items += 1;
+        *self.days.entry(date.date_naive()).or_default() += 1;
+        self.earliest = Some(self.earliest.map_or(date, |earliest| earliest.min(date)));
+        self.latest = Some(self.latest.map_or(date, |latest| latest.max(date)));
+
+        // Buckets are planned before any of their items are fetched, so catching up
+        // with those planned so far is enough to count this item.
+        let planned = tracking.buckets.lock().unwrap();
+        for (after, before) in &planned[self.buckets.len()..] {
+            self.buckets.push(ManifestBucket {
+                after: *after,
+                before: *before,
+                items: 0,
+            });
+        }
+        if let Some(bucket) = self
+            .buckets
+            .iter_mut()
+            .find(|bucket| bucket.after <= date && date <= bucket.before)
+        {
+            bucket.items += 1;
+        }
+    }
+}
+
+/// A file the items of a dataset were exported to, as recorded in a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ManifestFile {
+    /// The path of the file, relative to the manifest if it's in the same directory (or
+    /// one below it).
+    pub path: PathBuf,
+
+    /// The size of the file in bytes.
+    pub bytes: u64,
+
+    /// The SHA-256 checksum of the file, in lowercase hex.
+    pub sha256: String,
+}
+
+impl ManifestFile {
+    /// Describes the file at the given path, reading it to compute its checksum.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut hasher = Sha256::new();
+        let bytes = io::copy(&mut File::open(path)?, &mut hasher)?;
+        let hash = hasher.finalize();
+        Ok(Self {
+            path: path.to_path_buf(),
+            bytes,
+            sha256: hash.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        })
+    }
+
+    /// Checks that the file, resolved against `dir` (the manifest's directory), still
+    /// has the recorded size and checksum.
+    pub fn verify<P: AsRef<Path>>(&self, dir: P) -> io::Result<bool> {
+        let actual = Self::read(dir.as_ref().join(&self.path))?;
+        Ok(actual.bytes == self.bytes && actual.sha256 == self.sha256)
+    }
+}
+
+/// Records the queries and items of an export, then writes its [`Manifest`], see the
+/// [module documentation](self).
+#[derive(Default)]
+pub struct ManifestWriter {
+    queries: Vec<(Arc<Mutex<ManifestQuery>>, Tracking)>,
+}
+
+impl ManifestWriter {
+    /// Creates a writer which hasn't tracked any queries yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the query a stream was created for, and counts its items as they pass
+    /// through the returned stream.
+    ///
+    /// `endpoint` and `filter` should be those the stream was created with, so that the
+    /// manifest describes the query which was made.  The buckets the query is chunked
+    /// into and the date ranges it fails to fetch are taken from the stream itself.
+    pub fn track<S>(
+        &mut self,
+        client: &Client,
+        endpoint: Endpoint,
+        filter: &Filter,
+        items: S,
+    ) -> impl Stream<Item = S::Item>
+    where
+        S: ItemStream,
+        S::Item: Thing,
+    {
+        let query = Arc::new(Mutex::new(ManifestQuery::new(client, endpoint, filter)));
+        let tracking = items.tracking().clone();
+        self.queries.push((query.clone(), tracking.clone()));
+        items.inspect(move |item| query.lock().unwrap().record(item.attrs().date, &tracking))
+    }
+
+    /// Returns the manifest of the export so far, describing the given files.
+    pub fn manifest<I, P>(&self, files: I) -> io::Result<Manifest>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut files = files
+            .into_iter()
+            .map(ManifestFile::read)
+            .collect::<io::Result<Vec<_>>>()?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Manifest {
+            pullcaps_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: Utc::now(),
+            queries: self
+                .queries
+                .iter()
+                .map(|(query, tracking)| ManifestQuery {
+                    failed_ranges: tracking.failed.lock().unwrap().clone(),
+                    ..query.lock().unwrap().clone()
+                })
+                .collect(),
+            files,
+        })
+    }
+
+    /// Writes the manifest of the export to `path`, describing the given files, and
+    /// returns it.
+    ///
+    /// Files in the manifest's directory (or below it) are listed relative to it, so the
+    /// export can be moved as a whole.
+    pub fn write<P, I, F>(&self, path: P, files: I) -> io::Result<Manifest>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = F>,
+        F: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut manifest = self.manifest(files)?;
+        for file in &mut manifest.files {
+            if let Ok(relative) = file.path.strip_prefix(dir) {
+                file.path = relative.to_path_buf();
+            }
+        }
+        manifest.files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &manifest)?;
+        Ok(manifest)
+    }
+}
+
+impl Manifest {
+    /// Reads a manifest written by a [`ManifestWriter`].
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Checks every file in the manifest, whose paths are resolved against `dir` (the
+    /// manifest's directory), returning those which are missing or have changed.
+    pub fn verify<P: AsRef<Path>>(&self, dir: P) -> Vec<&ManifestFile> {
+        let dir = dir.as_ref();
+        self.files
+            .iter()
+            .filter(|file| !file.verify(dir).unwrap_or(false))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[tokio::test]
+    async fn test_manifest() {
+        use chrono::TimeZone;
+
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path().join("rust.ndjson");
+        fs::write(&data, "abc").unwrap();
+
+        let date = |ts| Utc.timestamp_opt(ts, 0).unwrap();
+        let page = r#"{"data": [
+            {"id": "a", "created_utc": 86405},
+            {"id": "b", "created_utc": 86401},
+            {"id": "c", "created_utc": 1}
+        ]}"#;
+        // The first bucket is fetched, but the second fails.
+        let url = crate::test_util::serve(vec![(200, page), (500, "")]).await;
+        let client = Client::builder()
+            .failover([url.clone()], 1)
+            .max_concurrent_buckets(1)
+            .discover_rate_limit(false)
+            .build();
+        let filter = Filter::new()
+            .subreddit("rust")
+            .with_windows([(date(0), date(2 * DAY)), (date(2 * DAY), date(3 * DAY))]);
+
+        let mut writer = ManifestWriter::new();
+        let comments = client.get_comments(filter.clone()).await;
+        let tracked = writer.track(&client, Endpoint::Comments, &filter, comments);
+        assert_eq!(tracked.count().await, 3);
+
+        let path = dir.path().join("manifest.json");
+        writer.write(&path, [&data]).unwrap();
+        let manifest = Manifest::read(&path).unwrap();
+
+        let query = &manifest.queries[0];
+        assert_eq!(query.backend, url.as_str());
+        assert!(query
+            .params
+            .contains(&("subreddit".to_string(), "rust".to_string())));
+        assert_eq!(query.items, 3);
+        assert_eq!(query.days.values().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(query.earliest.unwrap().timestamp(), 1);
+        assert_eq!(query.latest.unwrap().timestamp(), DAY + 5);
+        let buckets: Vec<_> = query.buckets.iter().map(|bucket| bucket.items).collect();
+        assert_eq!(buckets, vec![3, 0]);
+        assert_eq!(
+            query.failed_ranges,
+            vec![FailedRange {
+                after: Some(date(2 * DAY)),
+                before: Some(date(3 * DAY)),
+                values: None,
+            }]
+        );
+
+        assert_eq!(
+            manifest.files,
+            vec![ManifestFile {
+                path: PathBuf::from("rust.ndjson"),
+                bytes: 3,
+                sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+                    .to_string(),
+            }]
+        );
+        assert!(manifest.verify(dir.path()).is_empty());
+
+        fs::write(&data, "abd").unwrap();
+        assert_eq!(manifest.verify(dir.path()).len(), 1);
+    }
+}
@@ -1,31 +1,176 @@
 //! The data model underlying the PushShift API.
-use chrono::serde::ts_seconds;
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
-pub(crate) trait AsAttrs {
-    fn attrs(&self) -> &Attrs;
+mod compat;
+mod id;
+mod media;
+mod poll;
+
+pub(crate) use compat::canonical_field;
+pub use id::{CommentId, ParentId, ParseIdError, PostId, SubredditId};
+pub use media::{
+    GalleryData, GalleryItem, Image, Media, MediaMetadata, MediaSource, Oembed, Preview,
+    PreviewImage, RedditVideo,
+};
+pub use poll::{PollData, PollOption};
+
+const REDDIT_URL: &str = "https://www.reddit.com";
+
+pub(crate) use sealed::AsAttrs;
+
+mod sealed {
+    use super::Attrs;
+
+    /// Access to the attributes of anything a page of results is made of.
+    ///
+    /// This is public so that it can be a supertrait of [`Thing`](super::Thing), but lives
+    /// in a private module so that it can't be named, or implemented, outside the crate.
+    pub trait AsAttrs {
+        /// Returns the attributes common to posts and comments.
+        fn attrs(&self) -> &Attrs;
+
+        /// Returns the number of comments, if a post.
+        fn num_comments(&self) -> Option<i64> {
+            None
+        }
+
+        /// Returns whether the text is a `[removed]` or `[deleted]` placeholder.
+        fn has_removed_text(&self) -> bool {
+            false
+        }
+
+        /// Empties placeholder text, tagging the item with how it was removed.
+        fn tag_removed_text(&mut self) {}
+    }
+}
+
+/// Returns whether text is the placeholder left in place of removed or deleted content.
+fn is_placeholder(text: &str) -> bool {
+    matches!(text, "[removed]" | "[deleted]")
+}
+
+/// A post or comment, which reddit calls a "thing".
+///
+/// This is implemented by [`Post`], [`Comment`] and [`Content`], so that streams of any
+/// of them can be refined with [`PullcapsStreamExt`](crate::PullcapsStreamExt). Their
+/// attributes are available through `attrs()` on any `T: Thing`; the trait is sealed,
+/// and can't be implemented outside the crate.
+pub trait Thing: AsAttrs {
+    /// Returns the author of this content.
+    fn author(&self) -> &Author;
+
+    /// Returns the subreddit this content was posted in.
+    fn subreddit(&self) -> &SubReddit;
 }
 
 /// Common attributes between  [`Post`]'s and [`Comment`]'s.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Attrs {
     /// A unique ID identify the content.
     pub id: String,
 
     /// The score of this content.
+    #[serde(default)]
     pub score: i32,
 
     /// A permalink to this content.
     pub permalink: Option<String>,
 
     /// The date at which this content was created.
-    #[serde(rename = "created_utc", with = "ts_seconds")]
+    #[serde(rename = "created_utc", with = "compat::timestamp")]
     pub date: DateTime<Utc>,
+
+    /// The date at which this content was last edited, if it has been.
+    ///
+    /// Some old content is marked as edited without saying when; this is `None` for
+    /// that content too.
+    #[serde(default, with = "edited")]
+    pub edited: Option<DateTime<Utc>>,
+
+    /// Whether the content had been removed by a moderator, if PushShift recorded it.
+    #[serde(default)]
+    pub mod_removed: Option<bool>,
+
+    /// Whether the content had been deleted by its author, if PushShift recorded it.
+    #[serde(default)]
+    pub user_removed: Option<bool>,
+
+    /// How the content had been removed, if its placeholder text was emptied by
+    /// [`RemovedText::Tag`](crate::RemovedText::Tag).  It's kept when the item is
+    /// serialized, e.g. into an archive, so that the tag survives a round trip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<RemovalStatus>,
+}
+
+impl Attrs {
+    /// Returns the key posts and comments are ordered by: their creation date, then
+    /// their ID.
+    ///
+    /// PushShift only records creation dates to the second, so many items can share a
+    /// date; breaking ties by ID gives a total order over items.  This is the order
+    /// used by [`Filter::ordered`](crate::Filter::ordered) streams, and by the `Ord`
+    /// implementations of [`Post`] and [`Comment`].
+    pub fn sort_key(&self) -> SortKey<'_> {
+        SortKey {
+            date: self.date,
+            id: &self.id,
+        }
+    }
+
+    /// Returns the permalink as an absolute URL.
+    fn url(&self) -> Option<String> {
+        let permalink = self.permalink.as_deref().filter(|p| !p.is_empty())?;
+        if permalink.starts_with("http") {
+            Some(permalink.to_string())
+        } else {
+            Some(format!("{}{}", REDDIT_URL, permalink))
+        }
+    }
+}
+
+/// The key posts and comments are ordered by, see [`Attrs::sort_key`].
+///
+/// Keys are ordered by date, then by ID.  IDs are compared as the base 36 numbers they
+/// are, so `"z"` comes before `"10"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SortKey<'a> {
+    pub date: DateTime<Utc>,
+    pub id: &'a str,
+}
+
+impl PartialOrd for SortKey<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortKey<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.date
+            .cmp(&other.date)
+            .then_with(|| cmp_ids(self.id, other.id))
+    }
+}
+
+/// Compares two base 36 IDs numerically.
+///
+/// Reddit's IDs have no leading zeros, so a shorter ID is always the smaller one.
+pub(crate) fn cmp_ids(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
 }
 
 /// A single comment on a reddit [`Post`].
-#[derive(Clone, Debug, Deserialize)]
+///
+/// Comments are equal (and hash the same) if they have the same ID, and are ordered by
+/// creation date, then ID.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Comment {
     #[serde(flatten)]
     pub author: Author,
@@ -36,18 +181,110 @@ pub struct Comment {
     #[serde(flatten)]
     pub attrs: Attrs,
 
+    #[serde(default)]
     pub body: String,
-    pub parent_id: String,
+
+    /// The comment or post this comment replies to.
+    #[serde(default, deserialize_with = "id::lenient")]
+    pub parent_id: Option<ParentId>,
+
+    /// The post this comment was made on.
+    #[serde(default, deserialize_with = "id::lenient")]
+    pub link_id: Option<PostId>,
+
+    /// Whether the comment's score was hidden when it was archived, in which case the
+    /// score isn't meaningful.
+    #[serde(default)]
+    pub score_hidden: Option<bool>,
+
+    /// The text of the author's flair in the subreddit, if any.
+    #[serde(default)]
+    pub author_flair_text: Option<String>,
+
+    /// The CSS class of the author's flair in the subreddit, if any.  Some subreddits
+    /// use this to mark user roles, as it's more stable than the flair's text.
+    #[serde(default)]
+    pub author_flair_css_class: Option<String>,
+}
+
+impl Comment {
+    /// Returns the ID of this comment.
+    pub fn id(&self) -> CommentId {
+        CommentId::new_unchecked(&self.attrs.id)
+    }
+
+    /// Returns whether, and how, this comment had been removed when it was archived.
+    pub fn removal_status(&self) -> RemovalStatus {
+        if let Some(status) = self.attrs.removed {
+            return status;
+        }
+        RemovalStatus::classify(None, Some(&self.body), self.author.is_deleted())
+    }
+
+    /// Returns the body of this comment with HTML entities (such as `&amp;`) decoded.
+    pub fn body_decoded(&self) -> Cow<'_, str> {
+        decode_html_entities(&self.body)
+    }
+
+    /// Returns the post this comment was made on, if known.
+    pub fn post_id(&self) -> Option<PostId> {
+        match (&self.link_id, &self.parent_id) {
+            (Some(link_id), _) => Some(link_id.clone()),
+            (None, Some(ParentId::Post(post_id))) => Some(post_id.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns a link to this comment on reddit.
+    ///
+    /// This is built from the comment's permalink, or if that's missing, from the post
+    /// it was made on.  Returns `None` if neither is known.
+    pub fn url(&self) -> Option<String> {
+        if let Some(url) = self.attrs.url() {
+            return Some(url);
+        }
+        let post_id = self.post_id()?;
+        Some(format!(
+            "{}{}_/{}/",
+            REDDIT_URL,
+            self.subreddit.comments_path(&post_id),
+            self.attrs.id
+        ))
+    }
 }
 
 impl AsAttrs for Comment {
     fn attrs(&self) -> &Attrs {
         &self.attrs
     }
+
+    fn has_removed_text(&self) -> bool {
+        is_placeholder(&self.body)
+    }
+
+    fn tag_removed_text(&mut self) {
+        if self.has_removed_text() {
+            self.attrs.removed = Some(self.removal_status().or_deleted());
+            self.body.clear();
+        }
+    }
+}
+
+impl Thing for Comment {
+    fn author(&self) -> &Author {
+        &self.author
+    }
+
+    fn subreddit(&self) -> &SubReddit {
+        &self.subreddit
+    }
 }
 
 /// A single reddit post.
-#[derive(Clone, Debug, Deserialize)]
+///
+/// Posts are equal (and hash the same) if they have the same ID, and are ordered by
+/// creation date, then ID.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Post {
     #[serde(flatten)]
     pub author: Author,
@@ -58,39 +295,1172 @@ pub struct Post {
     #[serde(flatten)]
     pub attrs: Attrs,
 
+    /// The title of this post.
+    #[serde(default)]
+    pub title: String,
+
     /// URL of the linked content.
-    #[serde(rename = "url")]
+    #[serde(rename = "url", default)]
     pub content_url: String,
 
     /// URL to the comment page for this post.
-    #[serde(rename = "full_link")]
+    ///
+    /// This is empty if the backend doesn't return it, as pullpush.io and the dump files
+    /// don't; [`Post::url`] falls back to the post's permalink.
+    #[serde(rename = "full_link", default)]
     pub comment_url: String,
 
     /// The text of this post, if a self-post.
-    #[serde(rename = "selftext")]
+    #[serde(rename = "selftext", alias = "self_text")]
     pub self_text: Option<String>,
+
+    /// Whether this is a self-post, i.e. a text post rather than a link.
+    #[serde(default)]
+    pub is_self: Option<bool>,
+
+    /// Whether this post is a video hosted by reddit.
+    #[serde(default)]
+    pub is_video: Option<bool>,
+
+    /// Whether this post is a gallery of images.
+    #[serde(default)]
+    pub is_gallery: Option<bool>,
+
+    /// Reddit's guess at what the post links to, e.g. `"image"` or `"hosted:video"`.
+    #[serde(default)]
+    pub post_hint: Option<String>,
+
+    /// The number of comments on this post.
+    #[serde(default)]
+    pub num_comments: Option<i64>,
+
+    /// Why this post was removed, if it was.
+    #[serde(default)]
+    pub removed_by_category: Option<String>,
+
+    /// The text of the author's flair in the subreddit, if any.
+    #[serde(default)]
+    pub author_flair_text: Option<String>,
+
+    /// The CSS class of the author's flair in the subreddit, if any.  Some subreddits
+    /// use this to mark user roles, as it's more stable than the flair's text.
+    #[serde(default)]
+    pub author_flair_css_class: Option<String>,
+
+    /// Whether this post is marked NSFW.
+    #[serde(default)]
+    pub over_18: Option<bool>,
+
+    /// The fraction of votes on this post which are upvotes, between 0 and 1.
+    #[serde(default)]
+    pub upvote_ratio: Option<f64>,
+
+    /// The embedded media of this post, e.g. a video.
+    #[serde(default)]
+    pub media: Option<Box<Media>>,
+
+    /// The preview images reddit generated for this post.
+    #[serde(default)]
+    pub preview: Option<Preview>,
+
+    /// The order of the images in this post, if it's a gallery.
+    #[serde(default)]
+    pub gallery_data: Option<GalleryData>,
+
+    /// Details of the images in a gallery (or inline in a self-post), keyed by their
+    /// media ID.
+    #[serde(default)]
+    pub media_metadata: Option<HashMap<String, MediaMetadata>>,
+
+    /// The poll attached to this post, if any.
+    #[serde(default)]
+    pub poll_data: Option<Box<PollData>>,
+
+    /// The ID of the post this post is a crosspost of, if any.
+    #[serde(default, deserialize_with = "id::lenient")]
+    pub crosspost_parent: Option<PostId>,
+
+    /// The post this post is a crosspost of, as it was when this post was archived.
+    ///
+    /// Reddit returns this as a list, which only ever contains the direct parent; see
+    /// [`Post::crosspost_parent_post`].
+    #[serde(default)]
+    pub crosspost_parent_list: Vec<Post>,
+}
+
+impl Post {
+    /// Returns the ID of this post.
+    pub fn id(&self) -> PostId {
+        PostId::new_unchecked(&self.attrs.id)
+    }
+
+    /// Returns a link to the comment page of this post on reddit.
+    pub fn url(&self) -> String {
+        self.attrs.url().unwrap_or_else(|| {
+            format!("{}{}", REDDIT_URL, self.subreddit.comments_path(&self.id()))
+        })
+    }
+
+    /// Returns the text of this post with HTML entities (such as `&amp;`) decoded, if a
+    /// self-post.
+    pub fn self_text_decoded(&self) -> Option<Cow<'_, str>> {
+        self.self_text.as_deref().map(decode_html_entities)
+    }
+
+    /// Returns a short link to this post, e.g. `https://redd.it/abc123`.
+    pub fn short_url(&self) -> String {
+        format!("https://redd.it/{}", self.attrs.id)
+    }
+
+    /// Returns the URLs of the images in this post at their original size, in order,
+    /// if it's a gallery.
+    pub fn gallery_urls(&self) -> Vec<String> {
+        let (gallery, metadata) = match (&self.gallery_data, &self.media_metadata) {
+            (Some(gallery), Some(metadata)) => (gallery, metadata),
+            _ => return Vec::new(),
+        };
+
+        gallery
+            .items
+            .iter()
+            .filter_map(|item| metadata.get(&item.media_id)?.source.as_ref()?.url_decoded())
+            .collect()
+    }
+
+    /// Returns the post this post is a crosspost of, if it's a crosspost and the parent
+    /// was archived along with it.
+    ///
+    /// The parent may itself be a crosspost, so chains of crossposts can be followed
+    /// as far as they were archived.
+    pub fn crosspost_parent_post(&self) -> Option<&Post> {
+        self.crosspost_parent_list.first()
+    }
+
+    /// Returns whether, and how, this post had been removed when it was archived.
+    pub fn removal_status(&self) -> RemovalStatus {
+        if let Some(status) = self.attrs.removed {
+            return status;
+        }
+        RemovalStatus::classify(
+            self.removed_by_category.as_deref(),
+            self.self_text.as_deref(),
+            self.author.is_deleted(),
+        )
+    }
+
+    /// Returns what kind of post this is, e.g. a self-post or an image.
+    ///
+    /// A crosspost is always [`SubmissionKind::Crosspost`], whatever it links to; see
+    /// [`Post::crosspost_parent_post`] for the kind of the original post.
+    pub fn kind(&self) -> SubmissionKind {
+        if self.crosspost_parent.is_some() || !self.crosspost_parent_list.is_empty() {
+            return SubmissionKind::Crosspost;
+        }
+        if self.poll_data.is_some() {
+            return SubmissionKind::Poll;
+        }
+        if self.is_gallery == Some(true) || self.gallery_data.is_some() {
+            return SubmissionKind::Gallery;
+        }
+        if self.is_self.unwrap_or_else(|| self.links_to_itself()) {
+            return SubmissionKind::SelfPost;
+        }
+
+        let is_reddit_video = self
+            .media
+            .as_ref()
+            .is_some_and(|media| media.reddit_video.is_some());
+        match self.post_hint.as_deref() {
+            Some("image") => SubmissionKind::Image,
+            Some("hosted:video" | "rich:video") => SubmissionKind::Video,
+            _ if self.is_video == Some(true) || is_reddit_video => SubmissionKind::Video,
+            Some(_) => SubmissionKind::Link,
+            None if has_image_extension(&self.content_url) => SubmissionKind::Image,
+            None => SubmissionKind::Link,
+        }
+    }
+
+    /// Returns whether this post's URL is its own comment page, as it is for
+    /// self-posts, for posts archived without `is_self`.
+    fn links_to_itself(&self) -> bool {
+        self.content_url.is_empty()
+            || self
+                .content_url
+                .contains(&format!("/comments/{}/", self.attrs.id))
+    }
+}
+
+/// What kind of content a [`Post`] is, see [`Post::kind`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SubmissionKind {
+    /// A text post.
+    SelfPost,
+    /// A link to another site.
+    Link,
+    /// A single image.
+    Image,
+    /// A video, either hosted by reddit or embedded from another site.
+    Video,
+    /// A gallery of images.
+    Gallery,
+    /// A poll.
+    Poll,
+    /// A crosspost of another post.
+    Crosspost,
+}
+
+/// Returns whether a URL looks like it points directly at an image.
+fn has_image_extension(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    matches!(
+        extension.as_deref(),
+        Some("jpg" | "jpeg" | "png" | "gif" | "webp")
+    )
 }
 
 impl AsAttrs for Post {
     fn attrs(&self) -> &Attrs {
         &self.attrs
     }
+
+    fn num_comments(&self) -> Option<i64> {
+        self.num_comments
+    }
+
+    fn has_removed_text(&self) -> bool {
+        self.self_text.as_deref().is_some_and(is_placeholder)
+    }
+
+    fn tag_removed_text(&mut self) {
+        if self.has_removed_text() {
+            self.attrs.removed = Some(self.removal_status().or_deleted());
+            self.self_text = None;
+        }
+    }
+}
+
+impl Thing for Post {
+    fn author(&self) -> &Author {
+        &self.author
+    }
+
+    fn subreddit(&self) -> &SubReddit {
+        &self.subreddit
+    }
+}
+
+/// Either a [`Post`] or a [`Comment`].
+///
+/// Content serializes as the post or comment itself, and can be deserialized from the
+/// JSON of either, e.g. a line of a dump mixing posts and comments; see
+/// [`Content::from_json`].
+// Posts are larger than comments, but boxing them would make matching on content
+// more awkward for little benefit.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Content {
+    Post(Post),
+    Comment(Comment),
+}
+
+impl Content {
+    /// Returns the attributes common to posts and comments.
+    pub fn attrs(&self) -> &Attrs {
+        match self {
+            Self::Post(post) => &post.attrs,
+            Self::Comment(comment) => &comment.attrs,
+        }
+    }
+
+    /// Returns the author of this content.
+    pub fn author(&self) -> &Author {
+        match self {
+            Self::Post(post) => &post.author,
+            Self::Comment(comment) => &comment.author,
+        }
+    }
+
+    /// Returns the subreddit this content was posted in.
+    pub fn subreddit(&self) -> &SubReddit {
+        match self {
+            Self::Post(post) => &post.subreddit,
+            Self::Comment(comment) => &comment.subreddit,
+        }
+    }
+
+    /// Returns the text of the author's flair in the subreddit, if any.
+    pub fn author_flair_text(&self) -> Option<&str> {
+        match self {
+            Self::Post(post) => post.author_flair_text.as_deref(),
+            Self::Comment(comment) => comment.author_flair_text.as_deref(),
+        }
+    }
+}
+
+impl Content {
+    /// Parses a post or comment from its JSON, as returned by PushShift or reddit.
+    ///
+    /// Whether the object is a post or a comment is worked out from its fullname if it
+    /// has one, and otherwise from its fields: comments have a `body`, posts a `title`.
+    /// Objects wrapped in reddit's `{"kind": ..., "data": ...}` envelope are unwrapped.
+    ///
+    /// # Example
+    /// ```rust
+    /// use pullcaps::models::Content;
+    ///
+    /// let line = r#"{"id": "abc", "created_utc": 1600000000, "author": "reddit",
+    ///     "subreddit": "rust", "title": "Hello"}"#;
+    /// let content: Content = line.parse().unwrap();
+    /// assert!(matches!(content, Content::Post(_)));
+    /// ```
+    pub fn from_json(mut value: serde_json::Value) -> Result<Self, serde_json::Error> {
+        if value_is_envelope(&value) {
+            value = value["data"].take();
+        }
+
+        let object = value
+            .as_object()
+            .ok_or_else(|| serde_json::Error::custom("expected a post or comment object"))?;
+        let kind = object
+            .get("name")
+            .and_then(|name| name.as_str())
+            .and_then(|name| name.split_once('_'))
+            .map(|(kind, _)| kind);
+        let is_comment = match kind {
+            Some("t1") => true,
+            Some("t3") => false,
+            _ => object.contains_key("body") && !object.contains_key("title"),
+        };
+
+        if is_comment {
+            serde_json::from_value(value).map(Self::Comment)
+        } else {
+            serde_json::from_value(value).map(Self::Post)
+        }
+    }
+
+    /// Returns whether, and how, this content had been removed when it was archived.
+    pub fn removal_status(&self) -> RemovalStatus {
+        match self {
+            Self::Post(post) => post.removal_status(),
+            Self::Comment(comment) => comment.removal_status(),
+        }
+    }
+}
+
+/// Returns whether a value is a reddit "thing" envelope, e.g. `{"kind": "t1", "data": {...}}`.
+fn value_is_envelope(value: &serde_json::Value) -> bool {
+    value.get("kind").is_some_and(|kind| kind.is_string())
+        && value.get("data").is_some_and(|data| data.is_object())
+}
+
+impl<'de> Deserialize<'de> for Content {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Self::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+impl FromStr for Content {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json(serde_json::from_str(s)?)
+    }
+}
+
+impl AsAttrs for Content {
+    fn attrs(&self) -> &Attrs {
+        Content::attrs(self)
+    }
+
+    fn num_comments(&self) -> Option<i64> {
+        match self {
+            Self::Post(post) => post.num_comments,
+            Self::Comment(_) => None,
+        }
+    }
+
+    fn has_removed_text(&self) -> bool {
+        match self {
+            Self::Post(post) => post.has_removed_text(),
+            Self::Comment(comment) => comment.has_removed_text(),
+        }
+    }
+
+    fn tag_removed_text(&mut self) {
+        match self {
+            Self::Post(post) => post.tag_removed_text(),
+            Self::Comment(comment) => comment.tag_removed_text(),
+        }
+    }
+}
+
+impl Thing for Content {
+    fn author(&self) -> &Author {
+        Content::author(self)
+    }
+
+    fn subreddit(&self) -> &SubReddit {
+        Content::subreddit(self)
+    }
+}
+
+impl From<Post> for Content {
+    fn from(post: Post) -> Self {
+        Self::Post(post)
+    }
+}
+
+impl From<Comment> for Content {
+    fn from(comment: Comment) -> Self {
+        Self::Comment(comment)
+    }
+}
+
+/// Implements identity and ordering for a model keyed on its ID.
+///
+/// Two items are equal if they have the same ID, regardless of when each was archived,
+/// and are ordered by creation date and then ID.  An item's creation date never changes,
+/// so this ordering is consistent with equality.
+macro_rules! keyed_on_id {
+    ($model:ty) => {
+        impl PartialEq for $model {
+            fn eq(&self, other: &Self) -> bool {
+                self.attrs.id == other.attrs.id
+            }
+        }
+
+        impl Eq for $model {}
+
+        impl Hash for $model {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.attrs.id.hash(state);
+            }
+        }
+
+        impl PartialOrd for $model {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $model {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.attrs.sort_key().cmp(&other.attrs.sort_key())
+            }
+        }
+    };
+}
+
+keyed_on_id!(Post);
+keyed_on_id!(Comment);
+
+/// Whether, and how, a [`Post`] or [`Comment`] has been removed.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalStatus {
+    /// The content is visible.
+    Visible,
+    /// The content was deleted by its author.
+    Deleted,
+    /// The content was removed by the subreddit's moderators (or AutoModerator).
+    RemovedByModerator,
+    /// The content was removed by reddit, e.g. by its spam filters or admins.
+    RemovedByReddit,
+    /// The content was removed, but it isn't known by whom.
+    Removed,
+}
+
+impl RemovalStatus {
+    /// Classifies content from its `removed_by_category`, its text and whether its
+    /// author has been deleted.
+    pub(crate) fn classify(
+        removed_by_category: Option<&str>,
+        text: Option<&str>,
+        author_deleted: bool,
+    ) -> Self {
+        match removed_by_category {
+            Some("deleted" | "author") => return Self::Deleted,
+            Some("moderator" | "automod_filtered" | "community_ops") => {
+                return Self::RemovedByModerator
+            }
+            Some(_) => return Self::RemovedByReddit,
+            None => {}
+        }
+
+        match text {
+            Some("[removed]") => Self::Removed,
+            Some("[deleted]") if author_deleted => Self::Deleted,
+            _ => Self::Visible,
+        }
+    }
+
+    /// Returns whether the content is still visible.
+    pub fn is_visible(&self) -> bool {
+        matches!(self, Self::Visible)
+    }
+
+    /// Treats content whose text is a placeholder, but which otherwise looks visible,
+    /// as deleted.
+    fn or_deleted(self) -> Self {
+        match self {
+            Self::Visible => Self::Deleted,
+            status => status,
+        }
+    }
 }
 
 /// The author of a [`Post`] or [`Comment`].
-#[derive(Clone, Debug, Deserialize)]
-pub struct Author {
+///
+/// Reddit replaces the name of authors who have deleted their account (or their
+/// content) with `[deleted]`, which is represented as [`Author::Deleted`] so that it
+/// can't be mistaken for a real username.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "RawAuthor", into = "RawAuthor")]
+pub enum Author {
+    Known {
+        /// The fullname of the author, e.g. `t2_abc123`, if known.
+        id: Option<String>,
+        name: String,
+    },
+    Deleted,
+}
+
+impl Author {
+    /// Returns the author's username, unless they've been deleted.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Known { name, .. } => Some(name),
+            Self::Deleted => None,
+        }
+    }
+
+    /// Returns the author's fullname, if known.
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Self::Known { id, .. } => id.as_deref(),
+            Self::Deleted => None,
+        }
+    }
+
+    /// Returns whether the author has been deleted.
+    pub fn is_deleted(&self) -> bool {
+        matches!(self, Self::Deleted)
+    }
+}
+
+/// The representation of an [`Author`] used by the PushShift API.
+#[derive(Clone, Deserialize, Serialize)]
+struct RawAuthor {
     #[serde(rename = "author_fullname")]
-    pub id: Option<String>,
-    #[serde(rename = "author")]
-    pub name: String,
+    id: Option<String>,
+    #[serde(rename = "author", default)]
+    name: String,
+}
+
+impl From<RawAuthor> for Author {
+    fn from(raw: RawAuthor) -> Self {
+        if raw.name == DELETED_AUTHOR {
+            Self::Deleted
+        } else {
+            Self::Known {
+                id: raw.id,
+                name: raw.name,
+            }
+        }
+    }
+}
+
+impl From<Author> for RawAuthor {
+    fn from(author: Author) -> Self {
+        match author {
+            Author::Known { id, name } => Self { id, name },
+            Author::Deleted => Self {
+                id: None,
+                name: DELETED_AUTHOR.to_string(),
+            },
+        }
+    }
 }
 
+/// The name reddit gives to authors who have been deleted.
+pub(crate) const DELETED_AUTHOR: &str = "[deleted]";
+
 /// The subreddit associated to a [`Post`] or [`Comment`]
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SubReddit {
-    #[serde(rename = "subreddit_id")]
-    pub id: String,
-    #[serde(rename = "subreddit")]
+    #[serde(rename = "subreddit_id", default, deserialize_with = "id::lenient")]
+    pub id: Option<SubredditId>,
+    #[serde(rename = "subreddit", default)]
     pub name: String,
+
+    /// The number of subscribers the subreddit had when the item was archived.
+    #[serde(rename = "subreddit_subscribers", default)]
+    pub subscribers: Option<i64>,
+
+    /// Who can view and post in the subreddit, e.g. `public`, `restricted` or `user`
+    /// for a user's profile.
+    #[serde(rename = "subreddit_type", default)]
+    pub kind: Option<String>,
+}
+
+impl SubReddit {
+    /// Returns the path of the comment page of the given post in this subreddit.
+    fn comments_path(&self, post_id: &PostId) -> String {
+        if self.name.is_empty() {
+            format!("/comments/{}/", post_id.as_str())
+        } else {
+            format!("/r/{}/comments/{}/", self.name, post_id.as_str())
+        }
+    }
+}
+
+/// Information about how PushShift executed a query.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Metadata {
+    /// The total number of items matching the query.
+    pub total_results: i64,
+
+    /// How many of the shards holding PushShift's data answered the query.
+    #[serde(default)]
+    pub shards: Option<Shards>,
+
+    /// Whether the query timed out before every shard had answered.
+    #[serde(default)]
+    pub timed_out: Option<bool>,
+
+    #[serde(default)]
+    execution_time_milliseconds: Option<f64>,
+}
+
+impl Metadata {
+    /// Returns how long PushShift took to execute the query.
+    pub fn execution_time(&self) -> Option<std::time::Duration> {
+        self.execution_time_milliseconds
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+            .map(|ms| std::time::Duration::from_secs_f64(ms / 1000.0))
+    }
+
+    /// Returns whether every shard answered the query, so that the results (and
+    /// [`total_results`](Metadata::total_results)) are complete.
+    pub fn is_complete(&self) -> bool {
+        self.timed_out != Some(true) && self.shards.as_ref().is_none_or(Shards::is_complete)
+    }
+}
+
+/// The number of shards which answered a query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Shards {
+    pub total: i64,
+    pub successful: i64,
+    #[serde(default)]
+    pub skipped: i64,
+    #[serde(default)]
+    pub failed: i64,
+}
+
+impl Shards {
+    /// Returns whether every shard answered the query.
+    pub fn is_complete(&self) -> bool {
+        self.successful + self.skipped >= self.total
+    }
+}
+
+/// (De)serializes reddit's `edited` field, which is either `false` or the timestamp at
+/// which the content was edited.
+mod edited {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::de::IgnoredAny;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        edited: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match edited {
+            Some(date) => serializer.serialize_f64(date.timestamp_millis() as f64 / 1000.0),
+            None => serializer.serialize_bool(false),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Edited {
+            Timestamp(f64),
+            // `false`, or `true` for old content edited at an unknown time.
+            Flag(IgnoredAny),
+        }
+
+        Ok(match Option::<Edited>::deserialize(deserializer)? {
+            Some(Edited::Timestamp(ts)) => Utc.timestamp_millis_opt((ts * 1000.0) as i64).single(),
+            Some(Edited::Flag(_)) | None => None,
+        })
+    }
+}
+
+/// Decodes the HTML entities reddit uses to escape text.
+pub(crate) fn decode_html_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest
+            .find(';')
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    Cow::Owned(decoded)
+}
+
+/// Decodes the name of a single entity, e.g. `amp` or `#39`.
+fn decode_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            return char::from_u32(code);
+        }
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removal_status() {
+        assert_eq!(
+            RemovalStatus::classify(None, Some("hello"), false),
+            RemovalStatus::Visible
+        );
+        assert_eq!(
+            RemovalStatus::classify(None, Some("[removed]"), false),
+            RemovalStatus::Removed
+        );
+        assert_eq!(
+            RemovalStatus::classify(None, Some("[deleted]"), true),
+            RemovalStatus::Deleted
+        );
+        assert_eq!(
+            RemovalStatus::classify(Some("moderator"), Some("[removed]"), false),
+            RemovalStatus::RemovedByModerator
+        );
+        assert_eq!(
+            RemovalStatus::classify(Some("anti_evil_ops"), None, false),
+            RemovalStatus::RemovedByReddit
+        );
+    }
+
+    #[test]
+    fn test_tag_removed_text() {
+        let mut comment = crate::test_util::comment("a", 1);
+        assert!(!comment.has_removed_text());
+        comment.body = "[removed]".to_string();
+        assert!(comment.has_removed_text());
+
+        comment.tag_removed_text();
+        assert_eq!(comment.body, "");
+        assert_eq!(comment.attrs.removed, Some(RemovalStatus::Removed));
+        assert_eq!(comment.removal_status(), RemovalStatus::Removed);
+
+        // The tag survives a round trip, and untagged items don't gain a field.
+        let json = serde_json::to_value(&comment).unwrap();
+        assert_eq!(json["removed"], "removed");
+        let comment: Comment = serde_json::from_value(json).unwrap();
+        assert_eq!(comment.attrs.removed, Some(RemovalStatus::Removed));
+        let json = serde_json::to_value(crate::test_util::comment("c", 1)).unwrap();
+        assert!(json.get("removed").is_none());
+
+        // The author of a deleted comment isn't always recorded as deleted.
+        let mut comment = crate::test_util::comment("b", 1);
+        comment.body = "[deleted]".to_string();
+        comment.tag_removed_text();
+        assert_eq!(comment.removal_status(), RemovalStatus::Deleted);
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let attrs = |id: &str, created_utc: i64| -> Attrs {
+            serde_json::from_value(serde_json::json!({"id": id, "created_utc": created_utc}))
+                .unwrap()
+        };
+        let (a, b, c) = (attrs("b", 10), attrs("a", 20), attrs("c", 20));
+        assert!(a.sort_key() < b.sort_key());
+        assert!(b.sort_key() < c.sort_key());
+        assert_eq!(c.sort_key().id, "c");
+
+        // IDs are compared as numbers rather than strings.
+        let (d, e) = (attrs("zz", 30), attrs("100", 30));
+        assert!(d.sort_key() < e.sort_key());
+    }
+
+    #[test]
+    fn test_content_from_json() {
+        let comment = serde_json::json!({
+            "id": "c",
+            "created_utc": 1_600_000_000,
+            "author": "reddit",
+            "subreddit": "rust",
+            "body": "hello",
+        });
+        let post = serde_json::json!({
+            "id": "p",
+            "created_utc": 1_600_000_000,
+            "author": "reddit",
+            "subreddit": "rust",
+            "title": "hello",
+        });
+
+        let content = Content::from_json(comment.clone()).unwrap();
+        assert!(matches!(content, Content::Comment(_)));
+        let content: Content = post.to_string().parse().unwrap();
+        assert!(matches!(content, Content::Post(_)));
+
+        // Reddit's envelopes are unwrapped.
+        let wrapped = serde_json::json!({"kind": "t1", "data": comment});
+        let content: Content = serde_json::from_value(wrapped).unwrap();
+        assert_eq!(content.attrs().id, "c");
+
+        // Content serializes as the item itself, so it round-trips.
+        let content = Content::from_json(post).unwrap();
+        let json = serde_json::to_value(&content).unwrap();
+        assert!(matches!(Content::from_json(json), Ok(Content::Post(_))));
+
+        assert!(Content::from_json(serde_json::json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_trimmed_payload() {
+        let comment: Comment = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "body": "hello",
+        }))
+        .unwrap();
+        assert_eq!(comment.body, "hello");
+        assert_eq!(comment.attrs.score, 0);
+        assert_eq!(comment.author.name(), Some(""));
+
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "def",
+            "created_utc": 1_600_000_000,
+        }))
+        .unwrap();
+        assert!(post.self_text.is_none());
+        assert!(post.over_18.is_none());
+        assert!(post.upvote_ratio.is_none());
+    }
+
+    #[test]
+    fn test_media() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "gallery_data": {"items": [{"media_id": "b"}, {"media_id": "a"}]},
+            "media_metadata": {
+                "a": {"status": "valid", "e": "Image", "s": {"u": "https://i.redd.it/a.jpg?x=1&amp;y=2", "x": 10, "y": 20}},
+                "b": {"status": "valid", "e": "AnimatedImage", "s": {"gif": "https://i.redd.it/b.gif"}},
+            },
+            "preview": {"images": [{"source": {"url": "https://preview.redd.it/c.jpg", "width": 5, "height": 5}}]},
+            "media": {"reddit_video": {"fallback_url": "https://v.redd.it/d", "duration": 12}},
+        }))
+        .unwrap();
+
+        assert_eq!(
+            post.gallery_urls(),
+            vec!["https://i.redd.it/b.gif", "https://i.redd.it/a.jpg?x=1&y=2"]
+        );
+        assert_eq!(post.preview.unwrap().images[0].source.width, 5);
+        assert_eq!(post.media.unwrap().reddit_video.unwrap().duration, Some(12));
+    }
+
+    #[test]
+    fn test_poll_data() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "poll_data": {
+                "options": [
+                    {"id": "1", "text": "tabs", "vote_count": 10},
+                    {"id": "2", "text": "spaces", "vote_count": 12},
+                ],
+                "total_vote_count": 22,
+                "voting_end_timestamp": 1_600_100_000_000_i64,
+            },
+        }))
+        .unwrap();
+
+        let poll = post.poll_data.unwrap();
+        assert_eq!(poll.winner().unwrap().text, "spaces");
+        assert_eq!(poll.voting_ends.unwrap().timestamp(), 1_600_100_000);
+    }
+
+    #[test]
+    fn test_kind() {
+        let kind = |json: serde_json::Value| {
+            let mut post = serde_json::json!({"id": "abc", "created_utc": 1_600_000_000});
+            post.as_object_mut()
+                .unwrap()
+                .extend(json.as_object().unwrap().clone());
+            serde_json::from_value::<Post>(post).unwrap().kind()
+        };
+
+        assert_eq!(
+            kind(serde_json::json!({"is_self": true, "selftext": "hi"})),
+            SubmissionKind::SelfPost
+        );
+        assert_eq!(
+            kind(serde_json::json!({"url": "https://www.reddit.com/r/rust/comments/abc/hi/"})),
+            SubmissionKind::SelfPost
+        );
+        assert_eq!(
+            kind(serde_json::json!({"is_self": false, "url": "https://blog.rust-lang.org"})),
+            SubmissionKind::Link
+        );
+        assert_eq!(
+            kind(serde_json::json!({"is_self": false, "url": "https://i.redd.it/a.JPG?x=1"})),
+            SubmissionKind::Image
+        );
+        assert_eq!(
+            kind(serde_json::json!({"is_self": false, "post_hint": "hosted:video"})),
+            SubmissionKind::Video
+        );
+        assert_eq!(
+            kind(serde_json::json!({"is_self": false, "is_gallery": true})),
+            SubmissionKind::Gallery
+        );
+        assert_eq!(
+            kind(serde_json::json!({"is_self": true, "poll_data": {"options": []}})),
+            SubmissionKind::Poll
+        );
+        assert_eq!(
+            kind(serde_json::json!({"is_self": false, "crosspost_parent": "t3_def"})),
+            SubmissionKind::Crosspost
+        );
+    }
+
+    #[test]
+    fn test_crosspost_parent() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "crosspost_parent": "t3_def",
+            "crosspost_parent_list": [{
+                "id": "def",
+                "created_utc": 1_500_000_000,
+                "crosspost_parent": "t3_ghi",
+            }],
+        }))
+        .unwrap();
+
+        assert_eq!(post.crosspost_parent.as_ref().unwrap().as_str(), "def");
+        let parent = post.crosspost_parent_post().unwrap();
+        assert_eq!(parent.id(), "def".parse().unwrap());
+        assert_eq!(parent.crosspost_parent.as_ref().unwrap().as_str(), "ghi");
+        assert!(parent.crosspost_parent_post().is_none());
+    }
+
+    #[test]
+    fn test_subreddit_metadata() {
+        let comment: Comment = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "subreddit": "rust",
+            "subreddit_subscribers": 250_000,
+            "subreddit_type": "public",
+        }))
+        .unwrap();
+        assert_eq!(comment.subreddit.subscribers, Some(250_000));
+        assert_eq!(comment.subreddit.kind.as_deref(), Some("public"));
+    }
+
+    #[test]
+    fn test_author_flair() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "author_flair_text": "Verified Doctor",
+            "author_flair_css_class": "doctor",
+        }))
+        .unwrap();
+        assert_eq!(post.author_flair_text.as_deref(), Some("Verified Doctor"));
+        assert_eq!(post.author_flair_css_class.as_deref(), Some("doctor"));
+        assert_eq!(
+            Content::from(post).author_flair_text(),
+            Some("Verified Doctor")
+        );
+    }
+
+    #[test]
+    fn test_edited() {
+        let edited = |value: serde_json::Value| {
+            let comment: Comment = serde_json::from_value(serde_json::json!({
+                "id": "abc",
+                "created_utc": 1_600_000_000,
+                "edited": value,
+            }))
+            .unwrap();
+            comment.attrs.edited.map(|date| date.timestamp_millis())
+        };
+
+        assert_eq!(edited(serde_json::json!(false)), None);
+        assert_eq!(edited(serde_json::json!(true)), None);
+        assert_eq!(
+            edited(serde_json::json!(1_600_000_100)),
+            Some(1_600_000_100_000)
+        );
+        assert_eq!(
+            edited(serde_json::json!(1_600_000_100.5)),
+            Some(1_600_000_100_500)
+        );
+    }
+
+    #[test]
+    fn test_deleted_author() {
+        let comment: Comment = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "author": "[deleted]",
+            "body": "[deleted]",
+        }))
+        .unwrap();
+        assert_eq!(comment.author, Author::Deleted);
+        assert_eq!(comment.author.name(), None);
+        assert_eq!(comment.removal_status(), RemovalStatus::Deleted);
+
+        let value = serde_json::to_value(&comment).unwrap();
+        assert_eq!(value["author"], "[deleted]");
+    }
+
+    #[test]
+    fn test_urls() {
+        let mut comment = crate::test_util::comment("def", 0);
+        assert_eq!(
+            comment.url().unwrap(),
+            "https://www.reddit.com/r/rust/comments/abc/_/def/"
+        );
+        comment.attrs.permalink = Some("/r/rust/comments/abc/title/def/".to_string());
+        assert_eq!(
+            comment.url().unwrap(),
+            "https://www.reddit.com/r/rust/comments/abc/title/def/"
+        );
+
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "subreddit": "rust",
+        }))
+        .unwrap();
+        assert_eq!(post.url(), "https://www.reddit.com/r/rust/comments/abc/");
+        assert_eq!(post.short_url(), "https://redd.it/abc");
+    }
+
+    #[test]
+    fn test_decode_html_entities() {
+        assert_eq!(
+            decode_html_entities("a &amp; b &lt;3 &gt; &#39;c&#x27; &foo; & d"),
+            "a & b <3 > 'c' &foo; & d"
+        );
+        assert!(matches!(decode_html_entities("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata: Metadata = serde_json::from_value(serde_json::json!({
+            "total_results": 1234,
+            "execution_time_milliseconds": 12.5,
+            "shards": {"total": 74, "successful": 70, "skipped": 0, "failed": 4},
+            "timed_out": false,
+        }))
+        .unwrap();
+        assert_eq!(
+            metadata.execution_time(),
+            Some(std::time::Duration::from_micros(12500))
+        );
+        assert!(!metadata.is_complete());
+    }
+
+    #[test]
+    fn test_typed_ids() {
+        let comment = crate::test_util::comment("abc", 0);
+        assert_eq!(comment.id().fullname(), "t1_abc");
+        assert_eq!(comment.subreddit.id.unwrap().as_str(), "2s7lj");
+        assert_eq!(
+            comment.parent_id,
+            Some(ParentId::Post("abc".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_malformed_ids_are_dropped() {
+        let comment: Comment = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 0,
+            "parent_id": "",
+            "link_id": "t3_",
+            "subreddit_id": null,
+        }))
+        .unwrap();
+        assert_eq!(comment.parent_id, None);
+        assert_eq!(comment.link_id, None);
+        assert_eq!(comment.subreddit.id, None);
+    }
+
+    #[test]
+    fn test_keyed_on_id() {
+        use crate::test_util::comment;
+        use std::collections::{BTreeSet, HashSet};
+
+        let mut edited = comment("a", 10);
+        edited.body = "edited".to_string();
+        let comments = vec![comment("b", 10), comment("a", 10), edited, comment("c", 5)];
+
+        let unique: HashSet<_> = comments.iter().cloned().collect();
+        assert_eq!(unique.len(), 3);
+
+        let ordered: Vec<_> = comments
+            .into_iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|comment| comment.attrs.id)
+            .collect();
+        assert_eq!(ordered, vec!["c", "a", "b"]);
+    }
 }
@@ -0,0 +1,104 @@
+//! Compatibility with the different ways backends spell the same data.
+//!
+//! PushShift, its mirrors (such as pullpush.io) and the monthly dump files mostly agree
+//! on field names, but not entirely: some fields are renamed, some are missing, and old
+//! dumps store timestamps as strings.  Renamed fields are accepted through serde aliases
+//! on the models, which are listed here too so that [strict mode] doesn't report them
+//! as unknown.
+//!
+//! [strict mode]: crate::ClientBuilder::strict
+
+/// Alternative spellings of fields accepted by the models, with the name each is
+/// serialized under.
+const FIELD_ALIASES: &[(&str, &str)] = &[("self_text", "selftext")];
+
+/// Returns the name a field is serialized under, given any of its accepted spellings.
+pub(crate) fn canonical_field(name: &str) -> &str {
+    FIELD_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, canonical)| canonical)
+}
+
+/// (De)serializes a creation date as a Unix timestamp in seconds, which backends give
+/// as an integer, a float, or (in old dump files) a string.
+pub(crate) mod timestamp {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        date: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(date.timestamp())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Timestamp {
+            Integer(i64),
+            Float(f64),
+            Text(String),
+        }
+
+        let seconds = match Timestamp::deserialize(deserializer)? {
+            Timestamp::Integer(seconds) => seconds,
+            Timestamp::Float(seconds) => seconds as i64,
+            Timestamp::Text(text) => match text.parse::<i64>() {
+                Ok(seconds) => seconds,
+                Err(_) => text
+                    .parse::<f64>()
+                    .map_err(|_| D::Error::custom(format!("invalid timestamp {:?}", text)))?
+                    as i64,
+            },
+        };
+        Utc.timestamp_opt(seconds, 0)
+            .single()
+            .ok_or_else(|| D::Error::custom(format!("timestamp {} out of range", seconds)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Comment, Post};
+
+    #[test]
+    fn test_timestamps() {
+        for created_utc in [
+            serde_json::json!(1_134_365_188),
+            serde_json::json!(1_134_365_188.0),
+            serde_json::json!("1134365188"),
+        ] {
+            let comment: Comment = serde_json::from_value(serde_json::json!({
+                "id": "c0001",
+                "created_utc": created_utc,
+            }))
+            .unwrap();
+            assert_eq!(comment.attrs.date.timestamp(), 1_134_365_188);
+        }
+
+        let result = serde_json::from_value::<Comment>(serde_json::json!({
+            "id": "c0001",
+            "created_utc": "yesterday",
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "self_text": "hello",
+        }))
+        .unwrap();
+        assert_eq!(post.self_text.as_deref(), Some("hello"));
+        assert_eq!(canonical_field("self_text"), "selftext");
+        assert_eq!(canonical_field("title"), "title");
+    }
+}
@@ -0,0 +1,246 @@
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Defines a newtype around the base36 ID of a kind of reddit content, whose fullname
+/// is the ID with the given prefix.
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident, $prefix:literal) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            /// The prefix of fullnames of this kind of content.
+            pub const PREFIX: &'static str = $prefix;
+
+            /// Returns the ID in base36, without the fullname prefix.
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Returns the fullname of this ID, e.g. `t3_abc123` for a post.
+            pub fn fullname(&self) -> String {
+                format!("{}{}", Self::PREFIX, self.0)
+            }
+
+            /// Returns the numeric value of this ID, if it fits in a `u64`.
+            pub fn to_u64(&self) -> Option<u64> {
+                u64::from_str_radix(&self.0, 36).ok()
+            }
+
+            /// Creates an ID from its numeric value.
+            pub fn from_u64(id: u64) -> Self {
+                Self(to_base36(id))
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIdError;
+
+            /// Parses either a fullname or a bare base36 ID.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let id = s.strip_prefix(Self::PREFIX).unwrap_or(s);
+                if id.is_empty() || !id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                    return Err(ParseIdError(s.to_string()));
+                }
+                Ok(Self(id.to_ascii_lowercase()))
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = ParseIdError;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.fullname()
+            }
+        }
+
+        /// IDs are displayed as their fullname, as they're serialized.
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}{}", Self::PREFIX, self.0)
+            }
+        }
+    };
+}
+
+id_type!(
+    /// The ID of a [`Post`](super::Post), with fullname prefix `t3_`.
+    PostId,
+    "t3_"
+);
+id_type!(
+    /// The ID of a [`Comment`](super::Comment), with fullname prefix `t1_`.
+    CommentId,
+    "t1_"
+);
+id_type!(
+    /// The ID of a [`SubReddit`](super::SubReddit), with fullname prefix `t5_`.
+    SubredditId,
+    "t5_"
+);
+
+impl PostId {
+    /// Creates an ID from a trusted base36 value, without validating it.
+    pub(crate) fn new_unchecked(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl CommentId {
+    /// Creates an ID from a trusted base36 value, without validating it.
+    pub(crate) fn new_unchecked(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+/// The parent of a [`Comment`](super::Comment): either the comment it replies to, or
+/// the post it was made on if it's a top level comment.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ParentId {
+    Comment(CommentId),
+    Post(PostId),
+}
+
+impl ParentId {
+    /// Returns the fullname of the parent.
+    pub fn fullname(&self) -> String {
+        match self {
+            Self::Comment(id) => id.fullname(),
+            Self::Post(id) => id.fullname(),
+        }
+    }
+}
+
+impl FromStr for ParentId {
+    type Err = ParseIdError;
+
+    /// Parses a fullname, which must be prefixed with `t1_` or `t3_`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(CommentId::PREFIX) {
+            s.parse().map(Self::Comment)
+        } else if s.starts_with(PostId::PREFIX) {
+            s.parse().map(Self::Post)
+        } else {
+            Err(ParseIdError(s.to_string()))
+        }
+    }
+}
+
+impl TryFrom<String> for ParentId {
+    type Error = ParseIdError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ParentId> for String {
+    fn from(id: ParentId) -> Self {
+        id.fullname()
+    }
+}
+
+impl fmt::Display for ParentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.fullname())
+    }
+}
+
+/// The error returned when an ID or fullname can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdError(String);
+
+impl fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid reddit ID: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseIdError {}
+
+/// Deserializes an optional ID, treating one which is missing, empty or malformed (as
+/// some archived items' are) as `None` rather than failing the whole item.
+pub(crate) fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawId {
+        Text(String),
+        Other(IgnoredAny),
+    }
+
+    Ok(match Option::<RawId>::deserialize(deserializer)? {
+        Some(RawId::Text(id)) => id.parse().ok(),
+        Some(RawId::Other(_)) | None => None,
+    })
+}
+
+fn to_base36(mut n: u64) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut digits = Vec::new();
+    loop {
+        digits.push(DIGITS[(n % 36) as usize]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ids() {
+        let id: PostId = "t3_abc123".parse().unwrap();
+        assert_eq!(id.as_str(), "abc123");
+        assert_eq!(id.fullname(), "t3_abc123");
+        assert_eq!(id.to_string(), String::from(id.clone()));
+        assert_eq!("abc123".parse::<PostId>().unwrap(), id);
+
+        assert!("t3_".parse::<PostId>().is_err());
+        assert!("t1_abc".parse::<PostId>().is_err());
+    }
+
+    #[test]
+    fn test_base36() {
+        let id = CommentId::from_u64(36 * 36 + 35);
+        assert_eq!(id.as_str(), "10z");
+        assert_eq!(id.to_u64(), Some(36 * 36 + 35));
+        assert_eq!(CommentId::from_u64(0).as_str(), "0");
+    }
+
+    #[test]
+    fn test_parent_id() {
+        assert_eq!(
+            "t1_abc".parse::<ParentId>().unwrap(),
+            ParentId::Comment("abc".parse().unwrap())
+        );
+        assert_eq!(
+            "t3_def".parse::<ParentId>().unwrap(),
+            ParentId::Post("def".parse().unwrap())
+        );
+        assert!("abc".parse::<ParentId>().is_err());
+
+        let parent: ParentId = serde_json::from_value(serde_json::json!("t1_abc")).unwrap();
+        assert_eq!(serde_json::to_value(parent).unwrap(), "t1_abc");
+    }
+}
@@ -0,0 +1,165 @@
+use super::decode_html_entities;
+use serde::{Deserialize, Serialize};
+
+/// The preview images reddit generates for a [`Post`](super::Post).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Preview {
+    #[serde(default)]
+    pub images: Vec<PreviewImage>,
+
+    /// Whether reddit shows the preview.
+    pub enabled: Option<bool>,
+}
+
+/// A single preview image, at its original size and several smaller resolutions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PreviewImage {
+    pub id: Option<String>,
+
+    /// The image at its original size.
+    pub source: Image,
+
+    /// The image scaled down to smaller widths, smallest first.
+    #[serde(default)]
+    pub resolutions: Vec<Image>,
+}
+
+/// An image hosted by reddit.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Image {
+    /// The URL of the image, with HTML entities escaped as they're returned by reddit.
+    pub url: String,
+
+    #[serde(default)]
+    pub width: u32,
+
+    #[serde(default)]
+    pub height: u32,
+}
+
+impl Image {
+    /// Returns the URL of the image with HTML entities (e.g. `&amp;`) decoded.
+    pub fn url_decoded(&self) -> String {
+        decode_html_entities(&self.url).into_owned()
+    }
+}
+
+/// The embedded media of a [`Post`](super::Post), e.g. a video.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Media {
+    /// The kind of media, e.g. `youtube.com`, if it's embedded from another site.
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+
+    /// A video hosted by reddit.
+    pub reddit_video: Option<RedditVideo>,
+
+    /// Media embedded from another site.
+    pub oembed: Option<Oembed>,
+}
+
+/// A video hosted by reddit.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RedditVideo {
+    /// A direct link to the video (without audio).
+    pub fallback_url: Option<String>,
+    pub hls_url: Option<String>,
+    pub dash_url: Option<String>,
+
+    #[serde(default)]
+    pub width: u32,
+
+    #[serde(default)]
+    pub height: u32,
+
+    /// The length of the video, in seconds.
+    pub duration: Option<u32>,
+
+    /// Whether the video was converted from a GIF.
+    #[serde(default)]
+    pub is_gif: bool,
+}
+
+/// Media embedded from another site, following the oEmbed format.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Oembed {
+    pub provider_name: Option<String>,
+    pub title: Option<String>,
+    pub author_name: Option<String>,
+    pub thumbnail_url: Option<String>,
+}
+
+/// The order (and captions) of the images in a gallery post.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct GalleryData {
+    #[serde(default)]
+    pub items: Vec<GalleryItem>,
+}
+
+/// A single image in a gallery, whose details are in the post's `media_metadata`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GalleryItem {
+    /// The key of the image in the post's `media_metadata`.
+    pub media_id: String,
+
+    pub caption: Option<String>,
+
+    /// A link attached to the image.
+    pub outbound_url: Option<String>,
+}
+
+/// Details of a single image or video in a gallery post (or inline in a self-post).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MediaMetadata {
+    /// Whether the media has been processed, i.e. `valid`.
+    pub status: Option<String>,
+
+    /// The kind of media, e.g. `Image` or `AnimatedImage`.
+    #[serde(rename = "e")]
+    pub kind: Option<String>,
+
+    /// The MIME type of the media, e.g. `image/jpg`.
+    #[serde(rename = "m")]
+    pub mime_type: Option<String>,
+
+    /// The media at its original size.
+    #[serde(rename = "s")]
+    pub source: Option<MediaSource>,
+
+    /// The media scaled down to smaller widths, smallest first.
+    #[serde(rename = "p", default)]
+    pub previews: Vec<MediaSource>,
+}
+
+/// A single rendition of an item of [`MediaMetadata`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MediaSource {
+    /// The URL of an image, with HTML entities escaped.
+    #[serde(rename = "u")]
+    pub url: Option<String>,
+
+    /// The URL of an animated image, as a GIF.
+    pub gif: Option<String>,
+
+    /// The URL of an animated image, as an MP4 video.
+    pub mp4: Option<String>,
+
+    #[serde(rename = "x", default)]
+    pub width: u32,
+
+    #[serde(rename = "y", default)]
+    pub height: u32,
+}
+
+impl MediaSource {
+    /// Returns the URL of this rendition (preferring an image, then a GIF, then a
+    /// video), with HTML entities decoded.
+    pub fn url_decoded(&self) -> Option<String> {
+        let url = self
+            .url
+            .as_deref()
+            .or(self.gif.as_deref())
+            .or(self.mp4.as_deref())?;
+        Some(decode_html_entities(url).into_owned())
+    }
+}
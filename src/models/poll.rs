@@ -0,0 +1,45 @@
+use chrono::serde::ts_milliseconds_option;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A poll attached to a [`Post`](super::Post).
+///
+/// Vote counts are only available once voting has ended.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PollData {
+    #[serde(default)]
+    pub options: Vec<PollOption>,
+
+    /// The total number of votes cast in the poll.
+    pub total_vote_count: Option<i64>,
+
+    /// The date at which voting ends (or ended).
+    #[serde(
+        rename = "voting_end_timestamp",
+        default,
+        with = "ts_milliseconds_option"
+    )]
+    pub voting_ends: Option<DateTime<Utc>>,
+}
+
+impl PollData {
+    /// Returns the option with the most votes, if voting has ended.
+    pub fn winner(&self) -> Option<&PollOption> {
+        self.options
+            .iter()
+            .filter(|option| option.vote_count.is_some())
+            .max_by_key(|option| option.vote_count)
+    }
+}
+
+/// A single option of a [`PollData`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PollOption {
+    pub id: String,
+
+    #[serde(default)]
+    pub text: String,
+
+    /// The number of votes for this option, if voting has ended.
+    pub vote_count: Option<i64>,
+}
@@ -0,0 +1,129 @@
+use crate::models::Content;
+use crate::stream::{merge_by_date, CommentStream, PostStream};
+use crate::SortDirection;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+
+/// Everything an author has posted, with totals, like reddit's "overview" page of a
+/// user; see [`Client::get_user_overview`].
+///
+/// [`Client::get_user_overview`]: crate::Client::get_user_overview
+#[must_use = "streams do nothing unless polled"]
+pub struct UserOverview {
+    /// The name of the author.
+    pub name: String,
+
+    /// The author's posts, most recent first.
+    pub posts: PostStream,
+
+    /// The author's comments, most recent first.
+    pub comments: CommentStream,
+
+    /// The total number of posts made by the author.
+    pub post_count: i64,
+
+    /// The total number of comments made by the author.
+    pub comment_count: i64,
+
+    /// The date of the author's oldest post or comment.
+    pub first_activity: Option<DateTime<Utc>>,
+
+    /// The date of the author's most recent post or comment.
+    pub last_activity: Option<DateTime<Utc>>,
+
+    /// Whether PushShift has any content by the author, and if not, what's known of
+    /// them.
+    pub status: AuthorStatus,
+}
+
+/// What PushShift knows of an author, see [`UserOverview::status`].
+///
+/// PushShift only indexes content, so an account which has never posted (or whose
+/// content was never archived, e.g. because it was shadowbanned) looks the same as one
+/// which doesn't exist.  An author is only known to be real if they have content, or if
+/// their fullname was resolved earlier by the client, e.g. with
+/// [`Client::resolve_author`](crate::Client::resolve_author).
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthorStatus {
+    /// PushShift has posts or comments by the author.
+    Active,
+    /// PushShift has nothing under this name, but has content under the author's
+    /// fullname with a different name, so they've been renamed.
+    Renamed {
+        /// The fullname of the author, e.g. `t2_1w72`.
+        id: String,
+        /// The author's most recent name.
+        name: String,
+    },
+    /// The author's fullname is known, but PushShift has none of their content, e.g.
+    /// because it was all deleted.
+    NoContent {
+        /// The fullname of the author, e.g. `t2_1w72`.
+        id: String,
+    },
+    /// PushShift has no content by the author, and they've never been resolved: the
+    /// account may not exist, may never have posted, or may be shadowbanned.
+    NotFound,
+}
+
+impl AuthorStatus {
+    /// Returns whether the author is known to exist.
+    pub fn exists(&self) -> bool {
+        !matches!(self, Self::NotFound)
+    }
+}
+
+impl UserOverview {
+    /// Returns the total number of posts and comments made by the author.
+    pub fn total(&self) -> i64 {
+        self.post_count + self.comment_count
+    }
+
+    /// Interleaves the author's posts and comments into a single stream, most recent
+    /// first, as on reddit's overview page.
+    pub fn content(self) -> impl Stream<Item = Content> + Send {
+        let posts = self.posts.map(Content::from).boxed();
+        let comments = self.comments.map(Content::from).boxed();
+        merge_by_date(vec![posts, comments], SortDirection::Desc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Post;
+    use crate::test_util::comment;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn test_content_is_interleaved() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": "p",
+            "created_utc": 20,
+            "author": "reddit",
+            "subreddit": "rust",
+        }))
+        .unwrap();
+        let comments = vec![comment("c2", 30), comment("c1", 10)];
+
+        let overview = UserOverview {
+            name: "reddit".to_string(),
+            posts: PostStream::new(stream::iter(vec![post])),
+            comments: CommentStream::new(stream::iter(comments)),
+            post_count: 1,
+            comment_count: 2,
+            first_activity: None,
+            last_activity: None,
+            status: AuthorStatus::Active,
+        };
+        assert_eq!(overview.total(), 3);
+
+        let ids: Vec<String> = overview
+            .content()
+            .map(|content| content.attrs().id.clone())
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["c2", "p", "c1"]);
+    }
+}
@@ -0,0 +1,511 @@
+use crate::models::{AsAttrs, SortKey};
+use crate::SortDirection;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Url;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Tracks how far through a query a paginated stream has progressed.
+///
+/// PushShift only lets us paginate by date, with second resolution, and its `before`
+/// and `after` parameters are inclusive.  Each page therefore overlaps the previous one
+/// by the items created in the same second as the last item seen, so the cursor
+/// remembers the IDs of those items and removes them from the next page.
+///
+/// When more items were created in a single second than fit in a page, the cursor pins
+/// the query to that second and pages through the rest of its items by ID, so that
+/// items are paginated by their date and then their ID.
+///
+/// Backends which support it can paginate by ID instead, whose bounds are exclusive, so
+/// that pages don't overlap at all.
+pub(crate) struct Cursor {
+    direction: SortDirection,
+    /// Whether the cursor moves the ID bound of the query rather than its date bound.
+    by_id: bool,
+    /// Whether the cursor has fallen back from moving the ID bound to the date bound.
+    fell_back: bool,
+    /// The ID of the furthest item seen so far.
+    last_id: Option<String>,
+    /// The value of `before` (or `after`, when ascending) for the next request.
+    bound: Option<DateTime<Utc>>,
+    /// The creation date of the furthest item seen so far, i.e. the earliest one (or the
+    /// latest one, when ascending) by [`SortKey`].
+    last: Option<DateTime<Utc>>,
+    /// The IDs of the items seen so far which were created at `last`.
+    seen: HashSet<String>,
+    /// Whether the query is pinned to the second `last`, moving through the items
+    /// created in it by ID.
+    pinned: bool,
+}
+
+impl Cursor {
+    pub(crate) fn new(direction: SortDirection) -> Self {
+        Self {
+            direction,
+            by_id: false,
+            fell_back: false,
+            last_id: None,
+            bound: None,
+            last: None,
+            seen: HashSet::new(),
+            pinned: false,
+        }
+    }
+
+    /// Creates a cursor which paginates by ID, for a query sorted by creation date.
+    pub(crate) fn by_id(direction: SortDirection) -> Self {
+        Self {
+            by_id: true,
+            ..Self::new(direction)
+        }
+    }
+
+    /// Removes any items in `page` that were already returned by a previous page,
+    /// and advances the cursor past the items in `page`.
+    pub(crate) fn advance<T: AsAttrs>(&mut self, page: Vec<T>) -> Vec<T> {
+        let page: Vec<T> = page
+            .into_iter()
+            .filter(|item| {
+                let attrs = item.attrs();
+                // Items from before the last one seen can only turn up again if the
+                // backend ignored the bound of the request.
+                let is_behind = match (self.last, self.direction) {
+                    (None, _) => false,
+                    (Some(last), SortDirection::Desc) => attrs.date > last,
+                    (Some(last), SortDirection::Asc) => attrs.date < last,
+                };
+                let is_seen = Some(attrs.date) == self.last && self.seen.contains(&attrs.id);
+                !is_behind && !is_seen
+            })
+            .collect();
+
+        for item in &page {
+            let attrs = item.attrs();
+            let is_further = self.last_key().is_none_or(|last| match self.direction {
+                SortDirection::Desc => attrs.sort_key() < last,
+                SortDirection::Asc => attrs.sort_key() > last,
+            });
+            if is_further {
+                if Some(attrs.date) != self.last {
+                    self.seen.clear();
+                }
+                self.last = Some(attrs.date);
+                self.last_id = Some(attrs.id.clone());
+            }
+            if Some(attrs.date) == self.last {
+                self.seen.insert(attrs.id.clone());
+            }
+        }
+        self.bound = self.last;
+        page
+    }
+
+    /// Returns the key of the furthest item seen so far.
+    fn last_key(&self) -> Option<SortKey<'_>> {
+        Some(SortKey {
+            date: self.last?,
+            id: self.last_id.as_deref()?,
+        })
+    }
+
+    /// Moves the cursor on after a full page which contained nothing new.
+    ///
+    /// When paginating by date, this happens when more items were created in a single
+    /// second than fit in a page.  The cursor then pins the query to that second, and
+    /// moves through the rest of its items by ID.  If that doesn't get anywhere either,
+    /// the backend must have ignored the ID bound: the remaining items from that second
+    /// can't be retrieved, but at least pagination can continue past it.
+    ///
+    /// ID bounds are exclusive, so when paginating by ID a full page of nothing new
+    /// instead means that the backend ignored the ID bound.  The cursor then falls back
+    /// to paginating by date from the last item seen, rather than requesting the same
+    /// page over and over.
+    ///
+    /// Returns the second the cursor moved past without fetching all of its items, if
+    /// it had to.
+    pub(crate) fn stalled(&mut self) -> Option<DateTime<Utc>> {
+        if self.by_id {
+            self.by_id = false;
+            self.fell_back = true;
+            self.bound = self.last;
+        } else if self.pinned || self.fell_back {
+            self.skip_second();
+            return self.last;
+        } else {
+            self.pinned = true;
+        }
+        None
+    }
+
+    /// Moves the cursor on after a page which wasn't full, returning whether there's
+    /// anything left to fetch.
+    ///
+    /// A query pinned to a single second has only run out of items from that second,
+    /// so the cursor moves past it.  Otherwise the query is finished.
+    pub(crate) fn next_second(&mut self) -> bool {
+        if !self.pinned {
+            return false;
+        }
+        self.skip_second();
+        true
+    }
+
+    /// Moves the cursor past the second in which the furthest item seen so far was
+    /// created.
+    fn skip_second(&mut self) {
+        self.pinned = false;
+        self.bound = self.last.map(|last| match self.direction {
+            SortDirection::Desc => last - Duration::seconds(1),
+            SortDirection::Asc => last + Duration::seconds(1),
+        });
+        self.seen.clear();
+    }
+
+    /// Restricts `query` to the items which haven't been reached by the cursor yet.
+    pub(crate) fn apply(&self, query: &mut PageQuery) {
+        query.tiebreak = None;
+        if self.pinned {
+            if let (Some(date), Some(id)) = (self.last, self.last_id.clone()) {
+                let (before_id, after_id) = match self.direction {
+                    SortDirection::Desc => (Some(id), None),
+                    SortDirection::Asc => (None, Some(id)),
+                };
+                query.tiebreak = Some(Tiebreak {
+                    date,
+                    before_id,
+                    after_id,
+                });
+            }
+            return;
+        }
+        if self.by_id && self.last_id.is_some() {
+            match self.direction {
+                SortDirection::Desc => query.before_id = self.last_id.clone(),
+                SortDirection::Asc => query.after_id = self.last_id.clone(),
+            }
+            return;
+        }
+        if self.fell_back {
+            // The ID bound the cursor set is no use to the backend.
+            match self.direction {
+                SortDirection::Desc => query.before_id = None,
+                SortDirection::Asc => query.after_id = None,
+            }
+        }
+        match (self.bound, self.direction) {
+            (None, _) => {}
+            (Some(bound), SortDirection::Desc) => query.before = Some(bound),
+            (Some(bound), SortDirection::Asc) => query.after = Some(bound),
+        }
+    }
+}
+
+/// Pins a query to the items created in a single second, beyond an exclusive ID bound.
+#[derive(Clone, Debug)]
+pub(crate) struct Tiebreak {
+    pub(crate) date: DateTime<Utc>,
+    pub(crate) before_id: Option<String>,
+    pub(crate) after_id: Option<String>,
+}
+
+/// The query parameters of a paginated request, serialized once so that only the date
+/// and ID bounds need to be filled in for each page.
+///
+/// The serialized parameters are shared between clones, so the buckets of a chunked
+/// query can each have a query of their own without serializing them again.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PageQuery {
+    /// The query pairs, with a placeholder value for each date and ID bound.
+    pairs: Arc<[(String, String)]>,
+    /// The positions of the `before` and `after` pairs in `pairs`.
+    before_index: Option<usize>,
+    after_index: Option<usize>,
+    /// The positions of the `before_id` and `after_id` pairs in `pairs`.
+    before_id_index: Option<usize>,
+    after_id_index: Option<usize>,
+    pub(crate) before: Option<DateTime<Utc>>,
+    pub(crate) after: Option<DateTime<Utc>>,
+    pub(crate) before_id: Option<String>,
+    pub(crate) after_id: Option<String>,
+    /// Relative date bounds such as `"7d"`, sent until the cursor sets a date bound.
+    before_relative: Option<String>,
+    after_relative: Option<String>,
+    /// Overrides the date bounds, and the ID bound in the direction of the query, while
+    /// the cursor moves through a single second.
+    pub(crate) tiebreak: Option<Tiebreak>,
+}
+
+impl PageQuery {
+    /// Creates a query from pairs which include a placeholder for each date bound,
+    /// under the names `before_name` and `after_name`, and for each ID bound.
+    pub(crate) fn new(
+        pairs: Vec<(String, String)>,
+        before_name: &str,
+        after_name: &str,
+        before: Option<DateTime<Utc>>,
+        after: Option<DateTime<Utc>>,
+    ) -> Self {
+        let position = |name: &str| pairs.iter().position(|(key, _)| key == name);
+        Self {
+            before_index: position(before_name),
+            after_index: position(after_name),
+            before_id_index: position("before_id"),
+            after_id_index: position("after_id"),
+            pairs: pairs.into(),
+            before,
+            after,
+            before_id: None,
+            after_id: None,
+            before_relative: None,
+            after_relative: None,
+            tiebreak: None,
+        }
+    }
+
+    /// Sets the ID bounds the query starts from.
+    pub(crate) fn with_ids(mut self, before_id: Option<String>, after_id: Option<String>) -> Self {
+        self.before_id = before_id;
+        self.after_id = after_id;
+        self
+    }
+
+    /// Sets the relative date bounds the query starts from.
+    pub(crate) fn with_relative(
+        mut self,
+        before_relative: Option<String>,
+        after_relative: Option<String>,
+    ) -> Self {
+        self.before_relative = before_relative;
+        self.after_relative = after_relative;
+        self
+    }
+
+    /// Returns a copy of the query restricted to the given date range.
+    pub(crate) fn bounded(&self, after: DateTime<Utc>, before: DateTime<Utc>) -> Self {
+        Self {
+            pairs: self.pairs.clone(),
+            before: Some(before),
+            after: Some(after),
+            before_id: self.before_id.clone(),
+            after_id: self.after_id.clone(),
+            before_relative: None,
+            after_relative: None,
+            tiebreak: None,
+            ..*self
+        }
+    }
+
+    /// Returns `base` with the query's parameters appended.
+    pub(crate) fn url(&self, base: &Url) -> Url {
+        let mut url = base.clone();
+        {
+            let mut query = url.query_pairs_mut();
+            let tiebreak = self.tiebreak.as_ref();
+            let (before, after) = match tiebreak {
+                Some(tiebreak) => (Some(tiebreak.date), Some(tiebreak.date)),
+                None => (self.before, self.after),
+            };
+            for (i, (key, value)) in self.pairs.iter().enumerate() {
+                let bound = if Some(i) == self.before_index {
+                    before
+                        .map(|bound| bound.timestamp().to_string())
+                        .or_else(|| self.before_relative.clone())
+                } else if Some(i) == self.after_index {
+                    after
+                        .map(|bound| bound.timestamp().to_string())
+                        .or_else(|| self.after_relative.clone())
+                } else if Some(i) == self.before_id_index {
+                    tiebreak
+                        .and_then(|tiebreak| tiebreak.before_id.clone())
+                        .or_else(|| self.before_id.clone())
+                } else if Some(i) == self.after_id_index {
+                    tiebreak
+                        .and_then(|tiebreak| tiebreak.after_id.clone())
+                        .or_else(|| self.after_id.clone())
+                } else {
+                    query.append_pair(key, value);
+                    continue;
+                };
+                if let Some(bound) = bound {
+                    query.append_pair(key, &bound);
+                }
+            }
+        }
+        url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Comment;
+    use crate::test_util::comment;
+    use chrono::TimeZone;
+
+    fn ids(page: &[Comment]) -> Vec<&str> {
+        page.iter()
+            .map(|comment| comment.attrs.id.as_str())
+            .collect()
+    }
+
+    fn date(ts: i64) -> Option<DateTime<Utc>> {
+        Some(Utc.timestamp_opt(ts, 0).unwrap())
+    }
+
+    fn applied(cursor: &Cursor) -> PageQuery {
+        let mut query = PageQuery::default();
+        cursor.apply(&mut query);
+        query
+    }
+
+    #[test]
+    fn test_cursor_keeps_items_sharing_a_timestamp() {
+        let mut cursor = Cursor::new(SortDirection::Desc);
+
+        // The page ends part way through the items created at t=10.
+        let page = cursor.advance(vec![comment("a", 11), comment("b", 10), comment("c", 10)]);
+        assert_eq!(ids(&page), vec!["a", "b", "c"]);
+        assert_eq!(applied(&cursor).before, date(10));
+
+        // The next page starts at t=10 again, and must only contain the unseen items.
+        let page = cursor.advance(vec![
+            comment("b", 10),
+            comment("c", 10),
+            comment("d", 10),
+            comment("e", 9),
+        ]);
+        assert_eq!(ids(&page), vec!["d", "e"]);
+        assert_eq!(applied(&cursor).before, date(9));
+    }
+
+    #[test]
+    fn test_cursor_accumulates_seen_items_across_pages() {
+        let mut cursor = Cursor::new(SortDirection::Desc);
+
+        let page = cursor.advance(vec![comment("a", 10), comment("b", 10)]);
+        assert_eq!(ids(&page), vec!["a", "b"]);
+        let page = cursor.advance(vec![comment("a", 10), comment("b", 10), comment("c", 10)]);
+        assert_eq!(ids(&page), vec!["c"]);
+        let page = cursor.advance(vec![comment("a", 10), comment("b", 10), comment("c", 10)]);
+        assert!(page.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_pins_overfull_second() {
+        let mut cursor = Cursor::new(SortDirection::Desc);
+        cursor.advance(vec![comment("c", 11), comment("b", 10), comment("a", 10)]);
+        // The next page starts at t=10, and has nothing new.
+        let page = cursor.advance(vec![comment("b", 10), comment("a", 10)]);
+        assert!(page.is_empty());
+
+        // The query is pinned to t=10, below the lowest ID seen in it.
+        cursor.stalled();
+        let pairs = ["before", "after", "before_id", "after_id", "q"]
+            .iter()
+            .map(|key| (key.to_string(), String::new()))
+            .collect();
+        let mut query = PageQuery::new(pairs, "before", "after", None, date(5));
+        cursor.apply(&mut query);
+        let url = query.url(&Url::parse("https://example.com/").unwrap());
+        assert_eq!(url.query(), Some("before=10&after=10&before_id=a&q="));
+
+        let page = cursor.advance(vec![comment("9", 10), comment("8", 10)]);
+        assert_eq!(ids(&page), vec!["9", "8"]);
+        assert_eq!(
+            applied(&cursor).tiebreak.unwrap().before_id.as_deref(),
+            Some("8")
+        );
+
+        // Once the second runs out, the query carries on from the second before it.
+        assert!(cursor.next_second());
+        cursor.apply(&mut query);
+        let url = query.url(&Url::parse("https://example.com/").unwrap());
+        assert_eq!(url.query(), Some("before=9&after=5&q="));
+        let page = cursor.advance(vec![comment("7", 9)]);
+        assert_eq!(ids(&page), vec!["7"]);
+        assert!(!cursor.next_second());
+    }
+
+    #[test]
+    fn test_cursor_skips_second_if_ids_are_ignored() {
+        let mut cursor = Cursor::new(SortDirection::Desc);
+        cursor.advance(vec![comment("a", 10), comment("b", 10)]);
+
+        assert_eq!(cursor.stalled(), None);
+        assert!(applied(&cursor).tiebreak.is_some());
+        // The backend ignored the ID bound, so the rest of the second is skipped.
+        cursor.advance(vec![comment("a", 10), comment("b", 10)]);
+        assert_eq!(cursor.stalled(), date(10));
+        let query = applied(&cursor);
+        assert!(query.tiebreak.is_none());
+        assert_eq!(query.before, date(9));
+
+        let page = cursor.advance(vec![comment("c", 9)]);
+        assert_eq!(ids(&page), vec!["c"]);
+    }
+
+    #[test]
+    fn test_cursor_ascending() {
+        let mut cursor = Cursor::new(SortDirection::Asc);
+
+        let page = cursor.advance(vec![comment("a", 9), comment("b", 10)]);
+        assert_eq!(ids(&page), vec!["a", "b"]);
+        assert_eq!(applied(&cursor).after, date(10));
+
+        let page = cursor.advance(vec![comment("b", 10), comment("c", 11)]);
+        assert_eq!(ids(&page), vec!["c"]);
+        assert_eq!(applied(&cursor).after, date(11));
+
+        cursor.stalled();
+        assert_eq!(
+            applied(&cursor).tiebreak.unwrap().after_id.as_deref(),
+            Some("c")
+        );
+        assert!(cursor.next_second());
+        assert_eq!(applied(&cursor).after, date(12));
+    }
+
+    #[test]
+    fn test_cursor_by_id() {
+        let mut cursor = Cursor::by_id(SortDirection::Desc);
+        assert_eq!(applied(&cursor).before_id, None);
+
+        cursor.advance(vec![comment("c", 11), comment("b", 10)]);
+        let query = applied(&cursor);
+        assert_eq!(query.before_id.as_deref(), Some("b"));
+        // The date bound is left alone.
+        assert_eq!(query.before, None);
+    }
+
+    #[test]
+    fn test_cursor_by_id_orders_ids_numerically() {
+        let mut cursor = Cursor::by_id(SortDirection::Desc);
+        cursor.advance(vec![comment("z", 10), comment("10", 10)]);
+        assert_eq!(applied(&cursor).before_id.as_deref(), Some("z"));
+    }
+
+    #[test]
+    fn test_cursor_by_id_falls_back_to_dates() {
+        let mut cursor = Cursor::by_id(SortDirection::Desc);
+        // The query is reused from page to page, as it is by a stream.
+        let mut query = PageQuery::default();
+        cursor.advance(vec![comment("c", 11), comment("b", 10)]);
+        cursor.apply(&mut query);
+        assert_eq!(query.before_id.as_deref(), Some("b"));
+
+        // A backend ignoring the ID bound returns the same page again.
+        let page = cursor.advance(vec![comment("c", 11), comment("b", 10)]);
+        assert!(page.is_empty());
+
+        cursor.stalled();
+        cursor.apply(&mut query);
+        assert_eq!(query.before_id, None);
+        assert_eq!(query.before, date(10));
+
+        let page = cursor.advance(vec![comment("b", 10), comment("a", 9)]);
+        assert_eq!(ids(&page), vec!["a"]);
+        cursor.apply(&mut query);
+        assert_eq!(query.before_id, None);
+        assert_eq!(query.before, date(9));
+    }
+}
@@ -0,0 +1,245 @@
+//! Splitting a stream which mixes several subreddits into an output for each subreddit.
+//!
+//! A [`Partitioner`] routes each item to a sink for its subreddit, opening sinks as new
+//! subreddits turn up, so items can be written to separate files, sent down separate
+//! channels, and so on.  [`NdjsonPartitions`] uses one to write each subreddit's items to
+//! its own newline-delimited JSON file.
+//!
+//! Subreddits are told apart by [name](crate::models::SubReddit::name), ignoring case.
+//! Items without a subreddit are routed to a partition named `_unknown`, which can't be
+//! the name of a real subreddit.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use pullcaps::partition::NdjsonPartitions;
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::new();
+//! let filter = Filter::new().author("reddit");
+//!
+//! let mut partitions = NdjsonPartitions::new("comments");
+//! partitions.write_all(client.get_comments(filter).await).await?;
+//!
+//! for path in partitions.finish()? {
+//!     println!("wrote {}", path.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::models::Thing;
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+/// The partition items without a subreddit are routed to.
+const UNKNOWN: &str = "_unknown";
+
+/// Returns the name of the partition an item belongs in.
+///
+/// Subreddit names only contain letters, digits and underscores, but anything else is
+/// replaced with an underscore all the same, so that a partition's name is always safe
+/// to use as a file name.
+fn partition_name<T: Thing>(item: &T) -> String {
+    let name = &item.subreddit().name;
+    if name.is_empty() {
+        return UNKNOWN.to_string();
+    }
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Routes posts and comments to a sink for their subreddit, see the
+/// [module documentation](self).
+///
+/// Sinks are opened with the given function the first time an item from their subreddit
+/// is routed, which is passed the (lowercase) name of the partition.
+///
+/// # Example
+/// Sending each subreddit's comments down its own channel:
+/// ```rust
+/// # fn example(comments: Vec<pullcaps::models::Comment>) {
+/// use futures::channel::mpsc;
+/// use pullcaps::partition::Partitioner;
+///
+/// let mut receivers = Vec::new();
+/// let mut partitioner = Partitioner::new(|subreddit: &str| {
+///     let (sender, receiver) = mpsc::unbounded();
+///     receivers.push((subreddit.to_string(), receiver));
+///     Ok::<_, std::convert::Infallible>(sender)
+/// });
+///
+/// for comment in comments {
+///     let sender = partitioner.route(&comment).unwrap();
+///     sender.unbounded_send(comment).unwrap();
+/// }
+/// # }
+/// ```
+pub struct Partitioner<S, F> {
+    open: F,
+    sinks: HashMap<String, S>,
+}
+
+impl<S, F, E> Partitioner<S, F>
+where
+    F: FnMut(&str) -> Result<S, E>,
+{
+    /// Creates a partitioner which opens sinks with the given function.
+    pub fn new(open: F) -> Self {
+        Self {
+            open,
+            sinks: HashMap::new(),
+        }
+    }
+
+    /// Returns the sink for the item's subreddit, opening it if it's the first item
+    /// from the subreddit.
+    pub fn route<T: Thing>(&mut self, item: &T) -> Result<&mut S, E> {
+        let name = partition_name(item);
+        if !self.sinks.contains_key(&name) {
+            let sink = (self.open)(&name)?;
+            self.sinks.insert(name.clone(), sink);
+        }
+        Ok(self.sinks.get_mut(&name).unwrap())
+    }
+
+    /// Returns the sinks opened so far, keyed by the name of their partition.
+    pub fn sinks(&self) -> &HashMap<String, S> {
+        &self.sinks
+    }
+
+    /// Returns the sinks opened, keyed by the name of their partition.
+    pub fn into_sinks(self) -> HashMap<String, S> {
+        self.sinks
+    }
+}
+
+/// A function opening the file for a partition.
+type OpenFile = Box<dyn FnMut(&str) -> io::Result<BufWriter<File>> + Send>;
+
+/// Writes posts and comments to a newline-delimited JSON file for each subreddit, named
+/// like `rust.ndjson`, see the [module documentation](self).
+///
+/// Items are appended to any files which already exist, so partitions can be extended
+/// by writing to them again.  Files are only complete once the writer is
+/// [finished](NdjsonPartitions::finish).
+pub struct NdjsonPartitions {
+    dir: PathBuf,
+    partitioner: Partitioner<BufWriter<File>, OpenFile>,
+}
+
+impl NdjsonPartitions {
+    /// Creates a writer for partitions in the given directory, which is created if
+    /// necessary when the first item is written.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        let dir = dir.into();
+        let files = dir.clone();
+        let open: OpenFile = Box::new(move |name| {
+            fs::create_dir_all(&files)?;
+            let path = files.join(format!("{}.ndjson", name));
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Ok(BufWriter::new(file))
+        });
+        Self {
+            dir,
+            partitioner: Partitioner::new(open),
+        }
+    }
+
+    /// Writes a post or comment to the file for its subreddit.
+    pub fn write<T: Thing + Serialize>(&mut self, item: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(item)?;
+        line.push(b'\n');
+        self.partitioner.route(item)?.write_all(&line)
+    }
+
+    /// Writes every item of a stream to the file for its subreddit, returning the
+    /// number of items written.
+    ///
+    /// The files aren't finished, so that more items can be written afterwards.
+    pub async fn write_all<S>(&mut self, items: S) -> io::Result<usize>
+    where
+        S: Stream,
+        S::Item: Thing + Serialize,
+    {
+        let mut items = Box::pin(items);
+        let mut count = 0;
+        while let Some(item) = items.next().await {
+            self.write(&item)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Flushes every file, returning the paths of the files written to, sorted by name.
+    pub fn finish(self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for (name, mut file) in self.partitioner.into_sinks() {
+            file.flush()?;
+            paths.push(self.dir.join(format!("{}.ndjson", name)));
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Comment;
+    use crate::test_util::comment;
+
+    fn in_subreddit(id: &str, subreddit: &str) -> Comment {
+        let mut comment = comment(id, 1);
+        comment.subreddit.name = subreddit.to_string();
+        comment
+    }
+
+    #[test]
+    fn test_partition_name() {
+        assert_eq!(partition_name(&in_subreddit("a", "Rust")), "rust");
+        assert_eq!(partition_name(&in_subreddit("a", "")), "_unknown");
+        assert_eq!(partition_name(&in_subreddit("a", "../etc")), "___etc");
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_partitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut partitions = NdjsonPartitions::new(dir.path());
+        let comments = vec![
+            in_subreddit("a", "rust"),
+            in_subreddit("b", "golang"),
+            in_subreddit("c", "Rust"),
+        ];
+        assert_eq!(
+            partitions
+                .write_all(futures::stream::iter(comments))
+                .await
+                .unwrap(),
+            3
+        );
+        let paths = partitions.finish().unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                dir.path().join("golang.ndjson"),
+                dir.path().join("rust.ndjson")
+            ]
+        );
+
+        let ids: Vec<_> = fs::read_to_string(&paths[1])
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Comment>(line).unwrap().attrs.id)
+            .collect();
+        assert_eq!(ids, vec!["a", "c"]);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::limiter::REQUEST_PERIOD;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use std::time::Duration;
+
+/// The requests a query would make, without fetching any of its results; see
+/// [`Client::plan_posts`].
+///
+/// [`Client::plan_posts`]: crate::Client::plan_posts
+#[derive(Clone, Debug)]
+pub struct QueryPlan {
+    /// The total number of items matching the query, if PushShift was asked.
+    pub total_results: Option<i64>,
+
+    /// The first request of each stream of pages the query is split into.  Later pages
+    /// are requested by moving the date bound of the request past the items seen so far.
+    pub requests: Vec<Url>,
+
+    /// The `(after, before)` date range of each bucket the query is split into, or
+    /// empty if it isn't chunked by date.  These can be passed to
+    /// [`Filter::with_windows`] to split a later run of the query the same way, without
+    /// planning it again.
+    ///
+    /// [`Filter::with_windows`]: crate::Filter::with_windows
+    pub windows: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+
+    /// An estimate of the number of requests needed to fetch every page.
+    pub estimated_requests: usize,
+}
+
+impl QueryPlan {
+    /// Returns an estimate of how long the query takes at PushShift's rate limit of one
+    /// request per second, assuming that the client isn't throttled.
+    pub fn estimated_duration(&self) -> Duration {
+        REQUEST_PERIOD * self.estimated_requests as u32
+    }
+}
+
+/// Returns the number of requests needed to fetch `items` items, `batch_size` at a time.
+pub(crate) fn requests_for(items: i64, batch_size: i64) -> usize {
+    let items = items.max(0);
+    // A full last page is followed by an empty one.
+    (items / batch_size.max(1) + 1) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_for() {
+        assert_eq!(requests_for(0, 100), 1);
+        assert_eq!(requests_for(99, 100), 1);
+        assert_eq!(requests_for(100, 100), 2);
+        assert_eq!(requests_for(250, 100), 3);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::limiter::Limiter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The clients a pooled [`Client`](crate::Client) spreads its requests across, see
+/// [`ClientBuilder::pool`](crate::ClientBuilder::pool).
+pub(crate) struct ClientPool {
+    members: Vec<Member>,
+    /// The index of the member the next request goes to, before wrapping around.
+    next: AtomicUsize,
+}
+
+/// The parts of a client which a pool uses to make requests.
+pub(crate) struct Member {
+    pub(crate) client: reqwest::Client,
+    pub(crate) limiter: Arc<Limiter>,
+}
+
+impl ClientPool {
+    /// Creates a pool of the given members, returning `None` if there aren't any.
+    pub(crate) fn new(members: Vec<Member>) -> Option<Self> {
+        if members.is_empty() {
+            return None;
+        }
+        Some(Self {
+            members,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the member which should make the next request, taking turns.
+    pub(crate) fn next(&self) -> &Member {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.members[i % self.members.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RateLimitMode;
+
+    #[test]
+    fn test_round_robin() {
+        let members: Vec<_> = (0..3)
+            .map(|_| Member {
+                client: reqwest::Client::new(),
+                limiter: RateLimitMode::PerClient.limiter(),
+            })
+            .collect();
+        let limiters: Vec<_> = members.iter().map(|m| m.limiter.clone()).collect();
+        let pool = ClientPool::new(members).unwrap();
+
+        for i in 0..7 {
+            assert!(Arc::ptr_eq(&pool.next().limiter, &limiters[i % 3]));
+        }
+        assert!(ClientPool::new(Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A handle to follow how far through its query a stream is, see
+/// [`CommentStream::progress`](crate::CommentStream::progress).
+///
+/// The total number of matching items is taken from PushShift's metadata when the query
+/// is planned, so it's only known for queries sorted by creation date.  Handles can be
+/// cloned, e.g. to log progress from another task.
+///
+/// # Example
+/// ```rust,no_run
+/// # async fn example() {
+/// use futures::StreamExt;
+/// use pullcaps::{Client, Filter};
+///
+/// let client = Client::new();
+/// let mut comments = client.get_comments(Filter::new().subreddit("rust")).await;
+/// let progress = comments.progress();
+///
+/// while let Some(comment) = comments.next().await {
+///     if let Some(eta) = progress.eta() {
+///         println!("done in ~{}m", eta.as_secs() / 60);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Progress(Arc<Mutex<ProgressState>>);
+
+#[derive(Debug)]
+struct ProgressState {
+    total: Option<i64>,
+    fetched: u64,
+    started: Instant,
+}
+
+impl Progress {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(Mutex::new(ProgressState {
+            total: None,
+            fetched: 0,
+            started: Instant::now(),
+        })))
+    }
+
+    /// Adds to the total number of items matching the query, e.g. for each of the
+    /// subreddits a query is split into.
+    pub(crate) fn add_total(&self, total: i64) {
+        let mut state = self.0.lock().unwrap();
+        state.total = Some(state.total.unwrap_or(0) + total);
+    }
+
+    /// Records that `n` more items have been fetched.
+    pub(crate) fn record(&self, n: usize) {
+        self.0.lock().unwrap().fetched += n as u64;
+    }
+
+    /// Returns the total number of items matching the query, if it's known.
+    pub fn total(&self) -> Option<i64> {
+        self.0.lock().unwrap().total
+    }
+
+    /// Returns the number of items fetched so far.
+    pub fn fetched(&self) -> u64 {
+        self.0.lock().unwrap().fetched
+    }
+
+    /// Returns the fraction of the matching items fetched so far, between 0 and 1, if
+    /// the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        let state = self.0.lock().unwrap();
+        let total = state.total?;
+        if total <= 0 {
+            return Some(1.0);
+        }
+        Some((state.fetched as f64 / total as f64).min(1.0))
+    }
+
+    /// Estimates how much longer the stream will take to finish, from the rate at which
+    /// items have been fetched so far.
+    ///
+    /// Returns `None` if the total isn't known, or nothing has been fetched yet.
+    pub fn eta(&self) -> Option<Duration> {
+        let state = self.0.lock().unwrap();
+        eta(state.total?, state.fetched, state.started.elapsed())
+    }
+}
+
+/// Estimates the time left to fetch `total` items, given that `fetched` took `elapsed`.
+fn eta(total: i64, fetched: u64, elapsed: Duration) -> Option<Duration> {
+    if fetched == 0 {
+        return None;
+    }
+    let remaining = (total.max(0) as u64).saturating_sub(fetched);
+    Some(elapsed.mul_f64(remaining as f64 / fetched as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eta() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(eta(1000, 0, minute), None);
+        assert_eq!(eta(1000, 250, minute), Some(minute * 3));
+        assert_eq!(eta(1000, 1200, minute), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_progress() {
+        let progress = Progress::new();
+        progress.record(50);
+        assert_eq!(progress.fraction(), None);
+        assert_eq!(progress.eta(), None);
+
+        progress.add_total(150);
+        progress.add_total(50);
+        progress.record(50);
+        assert_eq!(progress.fetched(), 100);
+        assert_eq!(progress.fraction(), Some(0.5));
+        assert!(progress.eta().is_some());
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+
+/// A full text search query, rendered in PushShift's query syntax.
+///
+/// Building queries from terms and phrases, rather than by hand, means special
+/// characters in the search terms are always escaped correctly.  A query can be passed
+/// to any of the [`Filter`](crate::Filter) methods which take search terms, e.g.
+/// [`Filter::query`](crate::Filter::query) or
+/// [`Filter::title_contains`](crate::Filter::title_contains).
+///
+/// # Example
+/// ```rust
+/// use pullcaps::{Filter, Query};
+///
+/// let query = Query::term("rust")
+///     .and(Query::phrase("memory safety"))
+///     .not(Query::term("game"));
+/// assert_eq!(query.to_string(), r#"rust+"memory safety"+-game"#);
+///
+/// let filter = Filter::new().query(query);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query(Node);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Term(String),
+    Phrase(String),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+}
+
+impl Query {
+    /// Matches items containing a single word.
+    ///
+    /// A term containing whitespace is searched for as a [phrase](Query::phrase).
+    pub fn term<S: Into<String>>(term: S) -> Self {
+        let term = term.into();
+        if term.chars().any(char::is_whitespace) {
+            Self(Node::Phrase(term))
+        } else {
+            Self(Node::Term(term))
+        }
+    }
+
+    /// Matches items containing the given words, next to each other and in order.
+    pub fn phrase<S: Into<String>>(phrase: S) -> Self {
+        Self(Node::Phrase(phrase.into()))
+    }
+
+    /// Matches items which match both this query and `other`.
+    #[must_use]
+    pub fn and(self, other: Query) -> Self {
+        Self(match self.0 {
+            Node::And(mut nodes) => {
+                nodes.push(other.0);
+                Node::And(nodes)
+            }
+            node => Node::And(vec![node, other.0]),
+        })
+    }
+
+    /// Matches items which match either this query or `other`.
+    #[must_use]
+    pub fn or(self, other: Query) -> Self {
+        Self(match self.0 {
+            Node::Or(mut nodes) => {
+                nodes.push(other.0);
+                Node::Or(nodes)
+            }
+            node => Node::Or(vec![node, other.0]),
+        })
+    }
+
+    /// Matches items which match this query, but not `other`.
+    #[must_use]
+    pub fn not(self, other: Query) -> Self {
+        self.and(Self(Node::Not(Box::new(other.0))))
+    }
+}
+
+impl Node {
+    /// Writes the node, wrapped in parentheses if it's a list of alternatives joined by
+    /// a different operator than the one it's being joined with.
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, in_and: bool) -> fmt::Result {
+        match self {
+            Self::And(_) if !in_and => write!(f, "({})", self),
+            Self::Or(_) if in_and => write!(f, "({})", self),
+            node => write!(f, "{}", node),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Term(term) => {
+                for c in term.chars() {
+                    if is_special(c) {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                Ok(())
+            }
+            Self::Phrase(phrase) => {
+                write!(f, "\"")?;
+                for c in phrase.chars() {
+                    if matches!(c, '"' | '\\') {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\"")
+            }
+            Self::And(nodes) | Self::Or(nodes) => {
+                let (in_and, separator) = match self {
+                    Self::And(_) => (true, "+"),
+                    _ => (false, "|"),
+                };
+                for (i, node) in nodes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{}", separator)?;
+                    }
+                    node.fmt_operand(f, in_and)?;
+                }
+                Ok(())
+            }
+            Self::Not(node) => match **node {
+                Self::And(_) | Self::Or(_) => write!(f, "-({})", node),
+                _ => write!(f, "-{}", node),
+            },
+        }
+    }
+}
+
+/// Returns whether a character has a special meaning in PushShift's query syntax.
+fn is_special(c: char) -> bool {
+    matches!(c, '+' | '|' | '-' | '"' | '*' | '(' | ')' | '~' | '\\')
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Query> for String {
+    fn from(query: Query) -> Self {
+        query.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let query = Query::term("rust")
+            .and(Query::phrase("memory safety"))
+            .not(Query::term("game"));
+        assert_eq!(query.to_string(), r#"rust+"memory safety"+-game"#);
+
+        let query = Query::term("rust")
+            .or(Query::term("go"))
+            .and(Query::term("async"));
+        assert_eq!(query.to_string(), "(rust|go)+async");
+
+        let query = Query::term("a").and(Query::term("b")).or(Query::term("c"));
+        assert_eq!(query.to_string(), "(a+b)|c");
+
+        let query = Query::term("news").not(Query::term("crypto").or(Query::term("nft")));
+        assert_eq!(query.to_string(), "news+-(crypto|nft)");
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(Query::term("c++").to_string(), r"c\+\+");
+        assert_eq!(Query::term("-1").to_string(), r"\-1");
+        assert_eq!(Query::term("two words").to_string(), r#""two words""#);
+        assert_eq!(
+            Query::phrase(r#"say "hi" \o/"#).to_string(),
+            r#""say \"hi\" \\o/""#
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! Parsing of responses from the PushShift API.
+use crate::models::{canonical_field, Metadata};
+use crate::Error;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct PushShiftAggregation {
+    pub(crate) key: serde_json::Value,
+    pub(crate) doc_count: i64,
+}
+
+#[derive(Debug)]
+pub(crate) struct PushShiftResponse<T> {
+    pub(crate) data: Vec<T>,
+    pub(crate) metadata: Option<Metadata>,
+    pub(crate) aggs: HashMap<String, Vec<PushShiftAggregation>>,
+}
+
+impl<T> PushShiftResponse<T> {
+    /// Returns the buckets of a terms aggregation (e.g. `subreddit`), as `(term, count)`
+    /// pairs.
+    pub(crate) fn terms(&self, name: &str) -> Vec<(String, i64)> {
+        self.aggs
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|bucket| Some((bucket.key.as_str()?.to_string(), bucket.doc_count)))
+            .collect()
+    }
+}
+
+/// The number of bytes of an already downloaded body [`parse_response`] hands the parser
+/// at a time, which bounds the copy of the body the parser holds.
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Parses the whole body of a response; see [`ResponseParser`].
+pub(crate) fn parse_response<T: DeserializeOwned + Serialize>(
+    body: &[u8],
+    strict: bool,
+    on_error: impl FnMut(Error),
+) -> Result<PushShiftResponse<T>, serde_json::Error> {
+    let mut parser = ResponseParser::new(strict);
+    for chunk in body.chunks(CHUNK_LEN) {
+        parser.feed(chunk);
+    }
+    parser.finish(on_error)
+}
+
+/// Parses the body of a response as it arrives.
+///
+/// Each item of the `data` array is deserialized as soon as the last of its bytes has
+/// been fed to the parser, and the bytes are then dropped, so that neither the body
+/// nor a tree of it needs to be held in memory before the items are.  Items are
+/// deserialized individually, so that a single malformed item doesn't cause the whole
+/// response to be lost; items which fail are passed to `on_error` once the response
+/// has been parsed.  If `strict` is set, any fields of an item which aren't captured
+/// by the model are also passed to `on_error`.
+pub(crate) struct ResponseParser<T> {
+    items: ItemParser,
+    /// The bytes which have been fed to the parser, but not yet parsed.
+    buf: Vec<u8>,
+    /// The number of bytes which have been parsed, and dropped from `buf`.
+    offset: usize,
+    state: State,
+    /// How far through the value at the start of `buf` the parser has looked.
+    scanner: ValueScanner,
+    /// The key of the value being parsed.
+    key: String,
+    data: Option<Vec<T>>,
+    metadata: Option<Metadata>,
+    aggs: HashMap<String, Vec<PushShiftAggregation>>,
+    errors: Vec<Error>,
+    /// Why the body couldn't be parsed, after which the rest of it is ignored.
+    failed: Option<serde_json::Error>,
+}
+
+/// Where a [`ResponseParser`] has got to in the body of a response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Expecting the opening brace of the response.
+    Start,
+    /// Expecting the first key, or the closing brace of an empty response.
+    FirstKey,
+    /// Expecting a key, after a comma.
+    Key,
+    /// Expecting the colon after a key.
+    Colon,
+    /// Expecting the value of a key.
+    Value,
+    /// Expecting the first item of the `data` array, or the end of an empty one.
+    FirstItem,
+    /// Expecting an item of the `data` array, after a comma.
+    Item,
+    /// Expecting a comma or the end of the `data` array.
+    AfterItem,
+    /// Expecting a comma or the closing brace of the response.
+    AfterValue,
+    /// The response has been parsed.
+    End,
+}
+
+impl<T: DeserializeOwned + Serialize> ResponseParser<T> {
+    pub(crate) fn new(strict: bool) -> Self {
+        Self {
+            items: ItemParser::new(strict),
+            buf: Vec::new(),
+            offset: 0,
+            state: State::Start,
+            scanner: ValueScanner::default(),
+            key: String::new(),
+            data: None,
+            metadata: None,
+            aggs: HashMap::new(),
+            errors: Vec::new(),
+            failed: None,
+        }
+    }
+
+    /// Parses the next chunk of the body, deserializing any items it completes.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        if self.failed.is_some() {
+            return;
+        }
+
+        self.buf.extend_from_slice(chunk);
+        match self.parse() {
+            Ok(parsed) => {
+                self.buf.drain(..parsed);
+                self.offset += parsed;
+            }
+            Err(error) => {
+                self.failed = Some(error);
+                self.buf = Vec::new();
+            }
+        }
+    }
+
+    /// Returns the parsed response, once the whole body has been fed to the parser.
+    pub(crate) fn finish(
+        self,
+        on_error: impl FnMut(Error),
+    ) -> Result<PushShiftResponse<T>, serde_json::Error> {
+        if let Some(error) = self.failed {
+            return Err(error);
+        }
+        if self.state != State::End {
+            return Err(serde::de::Error::custom(format_args!(
+                "EOF while parsing a response at byte {}",
+                self.offset + self.buf.len()
+            )));
+        }
+
+        let data = self
+            .data
+            .ok_or_else(|| serde::de::Error::missing_field("data"))?;
+        self.errors.into_iter().for_each(on_error);
+        Ok(PushShiftResponse {
+            data,
+            metadata: self.metadata,
+            aggs: self.aggs,
+        })
+    }
+
+    /// Parses as much of `buf` as has been received, returning the number of bytes
+    /// parsed.
+    fn parse(&mut self) -> Result<usize, serde_json::Error> {
+        let mut pos = 0;
+        loop {
+            while self.buf.get(pos).is_some_and(|byte| is_whitespace(*byte)) {
+                pos += 1;
+            }
+            let Some(&byte) = self.buf.get(pos) else {
+                return Ok(pos);
+            };
+
+            self.state = match self.state {
+                State::Start => {
+                    self.expect(byte, b'{', "`{`", pos)?;
+                    pos += 1;
+                    State::FirstKey
+                }
+                State::FirstKey if byte == b'}' => {
+                    pos += 1;
+                    State::End
+                }
+                State::FirstKey | State::Key => {
+                    self.expect(byte, b'"', "a key", pos)?;
+                    let Some(len) = self.value_len(pos) else {
+                        return Ok(pos);
+                    };
+                    self.key = serde_json::from_slice(&self.buf[pos..pos + len])?;
+                    pos += len;
+                    State::Colon
+                }
+                State::Colon => {
+                    self.expect(byte, b':', "`:`", pos)?;
+                    pos += 1;
+                    State::Value
+                }
+                State::Value if self.key == "data" => {
+                    self.expect(byte, b'[', "an array of items", pos)?;
+                    pos += 1;
+                    self.data = Some(Vec::new());
+                    State::FirstItem
+                }
+                State::Value => {
+                    let Some(len) = self.value_len(pos) else {
+                        return Ok(pos);
+                    };
+                    let value = &self.buf[pos..pos + len];
+                    match self.key.as_str() {
+                        "metadata" => self.metadata = serde_json::from_slice(value)?,
+                        "aggs" => self.aggs = serde_json::from_slice(value)?,
+                        _ => {
+                            serde_json::from_slice::<IgnoredAny>(value)?;
+                        }
+                    }
+                    pos += len;
+                    State::AfterValue
+                }
+                State::FirstItem if byte == b']' => {
+                    pos += 1;
+                    State::AfterValue
+                }
+                State::FirstItem | State::Item => {
+                    let Some(len) = self.value_len(pos) else {
+                        return Ok(pos);
+                    };
+                    let item = &mut self.buf[pos..pos + len];
+                    if let Some(item) = self.items.parse(item, &mut self.errors)? {
+                        self.data.get_or_insert_with(Vec::new).push(item);
+                    }
+                    pos += len;
+                    State::AfterItem
+                }
+                State::AfterItem => {
+                    pos += 1;
+                    match byte {
+                        b',' => State::Item,
+                        b']' => State::AfterValue,
+                        _ => return Err(self.error("`,` or `]`", pos - 1)),
+                    }
+                }
+                State::AfterValue => {
+                    pos += 1;
+                    match byte {
+                        b',' => State::Key,
+                        b'}' => State::End,
+                        _ => return Err(self.error("`,` or `}`", pos - 1)),
+                    }
+                }
+                State::End => return Err(self.error("the end of the response", pos)),
+            };
+        }
+    }
+
+    /// Returns the length of the value starting at `pos`, once all of it has been
+    /// received.
+    fn value_len(&mut self, pos: usize) -> Option<usize> {
+        let len = self.scanner.scan(&self.buf[pos..])?;
+        self.scanner = ValueScanner::default();
+        Some(len)
+    }
+
+    fn expect(
+        &self,
+        byte: u8,
+        expected: u8,
+        name: &str,
+        pos: usize,
+    ) -> Result<(), serde_json::Error> {
+        if byte == expected {
+            Ok(())
+        } else {
+            Err(self.error(name, pos))
+        }
+    }
+
+    fn error(&self, expected: &str, pos: usize) -> serde_json::Error {
+        serde::de::Error::custom(format_args!(
+            "expected {} at byte {}",
+            expected,
+            self.offset + pos
+        ))
+    }
+}
+
+/// Returns whether a byte is whitespace between JSON tokens.
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Finds where a JSON value ends, without parsing it, as its bytes arrive.
+#[derive(Clone, Copy, Debug, Default)]
+struct ValueScanner {
+    /// The number of bytes of the value which have been looked at.
+    scanned: usize,
+    /// The number of arrays and objects the scanner is inside.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl ValueScanner {
+    /// Returns the length of the value at the start of `bytes`, if it's complete,
+    /// carrying on from wherever the last call left off.
+    ///
+    /// Numbers and literals end at the first byte which can't be part of them, so are
+    /// only complete once that byte has been received.  Whether the value is valid is
+    /// left to whatever deserializes it.
+    fn scan(&mut self, bytes: &[u8]) -> Option<usize> {
+        // The state is kept in locals while scanning, so that it can live in registers.
+        let Self {
+            mut scanned,
+            mut depth,
+            mut in_string,
+            mut escaped,
+        } = *self;
+
+        let len = loop {
+            // Most of an item is the contents of its strings, which can be skipped to
+            // the next byte that might end them.
+            if in_string && !escaped {
+                match bytes[scanned..]
+                    .iter()
+                    .position(|&byte| byte == b'"' || byte == b'\\')
+                {
+                    Some(skipped) => scanned += skipped,
+                    None => {
+                        scanned = bytes.len();
+                        break None;
+                    }
+                }
+            }
+
+            let Some(&byte) = bytes.get(scanned) else {
+                break None;
+            };
+            scanned += 1;
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    _ => {
+                        in_string = false;
+                        if depth == 0 {
+                            break Some(scanned);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break Some(scanned);
+                    }
+                }
+                b',' | b'}' | b']' if depth == 0 => break Some(scanned - 1),
+                _ if depth == 0 && is_whitespace(byte) => break Some(scanned - 1),
+                _ => {}
+            }
+        };
+
+        *self = Self {
+            scanned,
+            depth,
+            in_string,
+            escaped,
+        };
+        len
+    }
+}
+
+/// Deserializes the items of a response, one at a time.
+struct ItemParser {
+    strict: bool,
+    /// Scratch space for parsing, which is reused between items.
+    #[cfg(feature = "simd-json")]
+    buffers: simd_json::Buffers,
+}
+
+impl ItemParser {
+    fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            #[cfg(feature = "simd-json")]
+            buffers: simd_json::Buffers::default(),
+        }
+    }
+
+    /// Parses a single item, recording it in `errors` if it doesn't match `T`.  Fails
+    /// if the item isn't valid JSON.
+    #[cfg(not(feature = "simd-json"))]
+    fn parse<T: DeserializeOwned + Serialize>(
+        &mut self,
+        item: &mut [u8],
+        errors: &mut Vec<Error>,
+    ) -> Result<Option<T>, serde_json::Error> {
+        // Finding which fields weren't captured needs all of them.
+        if self.strict {
+            let raw: serde_json::Value = serde_json::from_slice(item)?;
+            let id = raw.get("id").and_then(|id| id.as_str()).map(String::from);
+            let item = match T::deserialize(&raw) {
+                Ok(item) => item,
+                Err(error) => {
+                    errors.push(Error::InvalidItem { id, error });
+                    return Ok(None);
+                }
+            };
+            let keys = raw.as_object().into_iter().flat_map(|raw| raw.keys());
+            check_fields(keys.map(String::as_str), &item, id, errors);
+            return Ok(Some(item));
+        }
+
+        match serde_json::from_slice(item) {
+            Ok(item) => Ok(Some(item)),
+            Err(error) if error.is_data() => {
+                errors.push(Error::InvalidItem {
+                    id: item_id(item),
+                    error,
+                });
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Parses a single item using SIMD instructions.
+    ///
+    /// The item is parsed in place, and deserialized into `T` with its strings borrowed
+    /// from it, rather than copied into a tree first.  See the non-SIMD version of this
+    /// function for details.
+    #[cfg(feature = "simd-json")]
+    fn parse<T: DeserializeOwned + Serialize>(
+        &mut self,
+        item: &mut [u8],
+        errors: &mut Vec<Error>,
+    ) -> Result<Option<T>, serde_json::Error> {
+        use serde::de::Error as _;
+        use simd_json::prelude::*;
+
+        let mut deserializer =
+            simd_json::Deserializer::from_slice_with_buffers(item, &mut self.buffers)
+                .map_err(serde_json::Error::custom)?;
+        let parsed = T::deserialize(&mut deserializer);
+        // Deserializing leaves the parsed item intact, so its ID and keys can still be
+        // read from it.
+        let raw = deserializer.as_value();
+        let id = raw.get_str("id").map(String::from);
+        let item = match parsed {
+            Ok(item) => item,
+            Err(error) => {
+                errors.push(Error::InvalidItem {
+                    id,
+                    error: serde_json::Error::custom(error),
+                });
+                return Ok(None);
+            }
+        };
+
+        if self.strict {
+            let keys = raw.as_object().into_iter().flat_map(|raw| raw.keys());
+            check_fields(keys, &item, id, errors);
+        }
+        Ok(Some(item))
+    }
+}
+
+/// Returns the ID of an item which couldn't be deserialized, if it has one.
+#[cfg(not(feature = "simd-json"))]
+fn item_id(item: &[u8]) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Item {
+        id: Option<serde_json::Value>,
+    }
+
+    serde_json::from_slice::<Item>(item)
+        .ok()?
+        .id?
+        .as_str()
+        .map(String::from)
+}
+
+/// Records the fields of an item which weren't captured when deserializing it into
+/// `item` in `errors`, given the `keys` it was parsed from.
+fn check_fields<'a, T: Serialize>(
+    keys: impl Iterator<Item = &'a str>,
+    item: &T,
+    id: Option<String>,
+    errors: &mut Vec<Error>,
+) {
+    let Ok(serde_json::Value::Object(known)) = serde_json::to_value(item) else {
+        return;
+    };
+
+    let fields: Vec<_> = keys
+        .filter(|key| !known.contains_key(canonical_field(key)))
+        .map(String::from)
+        .collect();
+    if !fields.is_empty() {
+        errors.push(Error::UnknownFields { id, fields });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Comment, Post};
+
+    #[test]
+    fn test_parse_response() {
+        let body =
+            br#"{"data": [{"id": "abc", "created_utc": 0}], "metadata": {"total_results": 1}}"#;
+        let response: PushShiftResponse<Comment> =
+            parse_response(body, false, |_| panic!()).unwrap();
+        assert_eq!(response.data.len(), 1);
+        assert_eq!(response.metadata.unwrap().total_results, 1);
+
+        assert!(parse_response::<Comment>(b"{", false, |_| {}).is_err());
+        assert!(parse_response::<Comment>(b"{}", false, |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_parse_response_skips_bad_items() {
+        let body = br#"{"data": [
+            {"id": "good", "created_utc": 1600000000},
+            {"id": "bad", "created_utc": "yesterday"}
+        ]}"#;
+
+        let mut errors = Vec::new();
+        let parsed: PushShiftResponse<Comment> =
+            parse_response(body, false, |error| errors.push(error)).unwrap();
+
+        assert_eq!(parsed.data.len(), 1);
+        assert_eq!(parsed.data[0].attrs.id, "good");
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::InvalidItem { id: Some(id), .. }] if id == "bad"
+        ));
+    }
+
+    #[test]
+    fn test_parse_response_strict() {
+        let body = br#"{"data": [
+            {"id": "abc", "created_utc": 1600000000, "gilded": 2, "body": "hi"}
+        ]}"#;
+
+        let mut errors = Vec::new();
+        let parsed: PushShiftResponse<Comment> =
+            parse_response(body, true, |error| errors.push(error)).unwrap();
+
+        assert_eq!(parsed.data.len(), 1);
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::UnknownFields { fields, .. }] if fields == &["gilded"]
+        ));
+    }
+
+    #[test]
+    fn test_parse_response_strict_aliases() {
+        let body = br#"{"data": [
+            {"id": "abc", "created_utc": "1600000000", "self_text": "hi"}
+        ]}"#;
+
+        let mut errors = Vec::new();
+        let parsed: PushShiftResponse<Post> =
+            parse_response(body, true, |error| errors.push(error)).unwrap();
+
+        assert_eq!(parsed.data[0].self_text.as_deref(), Some("hi"));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_response_parser() {
+        let body = br#"{"metadata": {"total_results": 2}, "data": [
+            {"id": "a", "created_utc": 1, "body": "a \"quoted\" } brace"},
+            {"id": "b", "created_utc": 2}
+        ], "extra": [1, true, null]}"#;
+        let end = br#"brace"}"#;
+        let first = body.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+
+        // Each item is parsed as soon as all of it has arrived, a byte at a time.
+        let mut parser = ResponseParser::<Comment>::new(false);
+        for (i, byte) in body.iter().enumerate() {
+            parser.feed(&[*byte]);
+            let parsed = parser.data.as_ref().map_or(0, Vec::len);
+            assert_eq!(parsed, usize::from(i + 1 >= first), "after {} bytes", i + 1);
+            if i + 1 == first {
+                break;
+            }
+        }
+        // Only what's left of the body is held on to.
+        assert!(parser.buf.is_empty());
+
+        parser.feed(&body[first..]);
+        let response = parser.finish(|_| panic!()).unwrap();
+        let ids: Vec<_> = response.data.iter().map(|c| c.attrs.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(response.data[0].body, "a \"quoted\" } brace");
+        assert_eq!(response.metadata.unwrap().total_results, 2);
+
+        // A body which is cut short isn't a response.
+        let mut parser = ResponseParser::<Comment>::new(false);
+        parser.feed(&body[..body.len() - 1]);
+        assert!(parser.finish(|_| {}).is_err());
+
+        for body in [
+            &br#"{"data": [{"id": "a"},]}"#[..],
+            br#"{"data": []} {}"#,
+            b"[]",
+        ] {
+            assert!(parse_response::<Comment>(body, false, |_| {}).is_err());
+        }
+    }
+
+    #[test]
+    fn test_terms() {
+        let body = br#"{"data": [], "aggs": {"author": [{"key": "a", "doc_count": 3}]}}"#;
+        let response: PushShiftResponse<serde_json::Value> =
+            parse_response(body, false, |_| {}).unwrap();
+        assert_eq!(response.terms("author"), vec![("a".to_string(), 3)]);
+    }
+}
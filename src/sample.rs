@@ -0,0 +1,76 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+
+/// Draws `n` random dates between `oldest` and `newest`, distributed like the items in
+/// `histogram` (or uniformly, without one), for [`Client::sample_posts`].
+///
+/// Each histogram entry gives the start of a bin together with the number of items in
+/// that bin; bins are assumed to extend up to the start of the next bin, and items are
+/// assumed to be spread evenly within a bin.
+///
+/// [`Client::sample_posts`]: crate::Client::sample_posts
+pub(crate) fn sample_dates<R: Rng>(
+    rng: &mut R,
+    histogram: Option<&[(DateTime<Utc>, i64)]>,
+    oldest: DateTime<Utc>,
+    newest: DateTime<Utc>,
+    n: usize,
+) -> Vec<DateTime<Utc>> {
+    let bins: Vec<(DateTime<Utc>, DateTime<Utc>, i64)> = match histogram {
+        Some(histogram) => histogram
+            .iter()
+            .enumerate()
+            .map(|(i, (start, count))| {
+                let end = histogram.get(i + 1).map_or(newest, |(next, _)| *next);
+                ((*start).max(oldest), end.min(newest), (*count).max(0))
+            })
+            .filter(|(start, end, _)| start <= end)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let weights = WeightedIndex::new(bins.iter().map(|(_, _, count)| *count));
+    (0..n)
+        .map(|_| {
+            let (start, end) = match &weights {
+                Ok(weights) => {
+                    let (start, end, _) = bins[weights.sample(rng)];
+                    (start, end)
+                }
+                Err(_) => (oldest, newest),
+            };
+            let span = (end - start).num_seconds().max(0);
+            start + Duration::seconds(rng.gen_range(0..=span))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rand::rngs::StdRng;
+
+    fn date(ts: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(ts, 0).unwrap()
+    }
+
+    #[test]
+    fn test_sample_dates_follows_histogram() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let histogram = [(date(0), 0), (date(100), 10), (date(200), 0)];
+
+        let dates = sample_dates(&mut rng, Some(&histogram), date(0), date(300), 100);
+        assert_eq!(dates.len(), 100);
+        assert!(dates.iter().all(|d| (date(100)..=date(200)).contains(d)));
+    }
+
+    #[test]
+    fn test_sample_dates_without_histogram() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let dates = sample_dates(&mut rng, None, date(50), date(60), 100);
+        assert!(dates.iter().all(|d| (date(50)..=date(60)).contains(d)));
+    }
+}
@@ -0,0 +1,63 @@
+//! Searching endpoints which aren't wrapped by the client, see [`Client::search`].
+//!
+//! [`Client::search`]: crate::Client::search
+use crate::models::{AsAttrs, Attrs};
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An item of an arbitrary type, along with the attributes needed to paginate over it.
+pub(crate) struct Searched<T> {
+    attrs: Attrs,
+    pub(crate) item: T,
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Searched<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let attrs = Attrs::deserialize(&value).map_err(D::Error::custom)?;
+        let item = T::deserialize(value).map_err(D::Error::custom)?;
+        Ok(Self { attrs, item })
+    }
+}
+
+/// The fields captured by `T` aren't known, so searched items serialize as a unit,
+/// which skips the unknown field checks of strict mode.
+impl<T> Serialize for Searched<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<T> AsAttrs for Searched<T> {
+    fn attrs(&self) -> &Attrs {
+        &self.attrs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Custom {
+        body: String,
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let searched: Searched<Custom> = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "created_utc": 1_600_000_000,
+            "body": "hello",
+        }))
+        .unwrap();
+        assert_eq!(searched.attrs().id, "abc");
+        assert_eq!(searched.item.body, "hello");
+
+        // Items without the attributes needed for pagination are rejected.
+        let missing = serde_json::from_value::<Searched<Custom>>(serde_json::json!({
+            "body": "hello",
+        }));
+        assert!(missing.is_err());
+    }
+}
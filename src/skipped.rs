@@ -0,0 +1,80 @@
+use crate::stream::FailedRange;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+
+/// A record of data a [`Client`](crate::Client) skipped over, see
+/// [`ClientBuilder::on_skipped`](crate::ClientBuilder::on_skipped).
+///
+/// Unlike the [`Error`](crate::Error)s passed to the error handler, a record describes
+/// *what* was lost rather than why, so that the gaps in a crawl can be added up or
+/// fetched again later.
+#[derive(Clone, Debug)]
+pub struct Skipped {
+    /// The URL of a request for the skipped data, including its query parameters.
+    pub url: Url,
+
+    /// Why the data was skipped.
+    pub reason: SkipReason,
+
+    /// An estimate of the number of items skipped, if one is available.
+    ///
+    /// Estimates for failed ranges of a chunked query assume the items were spread
+    /// evenly between its buckets.
+    pub count: Option<u64>,
+}
+
+impl Skipped {
+    /// Returns the path of the endpoint the data was requested from.
+    pub fn endpoint(&self) -> &str {
+        self.url.path()
+    }
+
+    /// Returns the query parameters of the request for the skipped data.
+    pub fn params(&self) -> Vec<(String, String)> {
+        self.url.query_pairs().into_owned().collect()
+    }
+}
+
+/// Why a [`Skipped`] record's data was skipped.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// A request failed, so the stream gave up on the rest of a range of dates, e.g. the
+    /// remaining pages of a query or of one bucket of a chunked query.
+    FailedRange(FailedRange),
+    /// A single item in a response couldn't be deserialized.
+    InvalidItem {
+        /// The ID of the item, if it had one.
+        id: Option<String>,
+    },
+    /// More items were created in a single second than fit in a page, and the backend
+    /// ignored the ID bound used to page through the rest of them, so the stream moved
+    /// past the second without them.
+    OverfullSecond {
+        /// The second the items were created in.
+        date: DateTime<Utc>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_and_params() {
+        let skipped = Skipped {
+            url: Url::parse("https://api.pushshift.io/reddit/comment/search/?q=rust&size=100")
+                .unwrap(),
+            reason: SkipReason::InvalidItem { id: None },
+            count: Some(1),
+        };
+        assert_eq!(skipped.endpoint(), "/reddit/comment/search/");
+        assert_eq!(
+            skipped.params(),
+            vec![
+                ("q".to_string(), "rust".to_string()),
+                ("size".to_string(), "100".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,923 @@
+//! The streams returned by a [`Client`], and the internal adapters used to assemble
+//! them.
+//!
+//! [`Client`]: crate::Client
+use crate::buffer::ItemBuffer;
+use crate::chunking::restrict_values;
+use crate::control::StreamControl;
+use crate::models::{AsAttrs, Comment, Post};
+use crate::progress::Progress;
+use crate::{Filter, SortDirection};
+use async_stream::stream;
+use chrono::{DateTime, Utc};
+use futures::future::{join_all, Either};
+use futures::stream::Peekable;
+use futures::{future, stream, FutureExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{timeout, timeout_at, Instant};
+
+/// Remembers the items created close to the boundaries of the buckets of a chunked
+/// query, so that duplicates can be removed.
+///
+/// Bucket boundaries are computed in whole seconds, and PushShift may treat them
+/// inclusively, so items created right on a boundary can be returned by both of
+/// the adjacent buckets.  Only items created within a second of a boundary can be
+/// duplicated, so we only need to remember the IDs of those items.
+struct BoundaryDedup {
+    boundaries: HashSet<i64>,
+    seen: HashSet<String>,
+}
+
+impl BoundaryDedup {
+    fn new(buckets: &[(DateTime<Utc>, DateTime<Utc>)]) -> Self {
+        let boundaries = buckets
+            .iter()
+            .flat_map(|(l, r)| [l.timestamp(), r.timestamp()])
+            .flat_map(|ts| [ts - 1, ts, ts + 1])
+            .collect();
+        Self {
+            boundaries,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Returns whether the item hasn't been seen before.
+    fn keep<T: AsAttrs>(&mut self, item: &T) -> bool {
+        let attrs = item.attrs();
+        !self.boundaries.contains(&attrs.date.timestamp()) || self.seen.insert(attrs.id.clone())
+    }
+}
+
+/// Removes items which appear in more than one bucket of a chunked query.
+pub(crate) fn dedup_boundaries<S, T>(
+    stream: S,
+    buckets: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> impl Stream<Item = T>
+where
+    S: Stream<Item = T>,
+    T: AsAttrs,
+{
+    let mut dedup = BoundaryDedup::new(buckets);
+    stream.filter(move |item| future::ready(dedup.keep(item)))
+}
+
+/// Removes items which appear in more than one bucket of a chunked query from a stream
+/// of pages, dropping pages which end up empty.
+pub(crate) fn dedup_boundary_pages<S, T>(
+    stream: S,
+    buckets: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> impl Stream<Item = Vec<T>>
+where
+    S: Stream<Item = Vec<T>>,
+    T: AsAttrs,
+{
+    let mut dedup = BoundaryDedup::new(buckets);
+    stream.filter_map(move |mut page| {
+        page.retain(|item| dedup.keep(item));
+        future::ready(Some(page).filter(|page| !page.is_empty()))
+    })
+}
+
+/// Truncates a stream of pages after `limit` items, without polling for any more pages.
+pub(crate) fn take_items<S, T>(pages: S, limit: usize) -> impl Stream<Item = Vec<T>>
+where
+    S: Stream<Item = Vec<T>> + Unpin,
+{
+    let mut pages = pages;
+    let mut remaining = limit;
+    stream! {
+        while remaining > 0 {
+            let mut page = match pages.next().await {
+                Some(page) => page,
+                None => break,
+            };
+            page.truncate(remaining);
+            remaining -= page.len();
+            yield page;
+        }
+    }
+}
+
+/// Merges streams which are each sorted by creation date in the given direction into a
+/// single stream sorted by creation date, breaking ties by ID.
+pub(crate) fn merge_by_date<S, T>(
+    streams: Vec<S>,
+    direction: SortDirection,
+) -> impl Stream<Item = T>
+where
+    S: Stream<Item = T> + Unpin,
+    T: AsAttrs,
+{
+    let mut streams: Vec<Peekable<S>> = streams.into_iter().map(StreamExt::peekable).collect();
+
+    stream! {
+        loop {
+            // Wait for the head of every stream, so that we know which comes first.
+            join_all(streams.iter_mut().map(|s| async move {
+                Pin::new(s).peek().await;
+            }))
+            .await;
+            let (first, exhausted) = {
+                // Every head is ready now, so peeking again doesn't wait.
+                let heads: Vec<Option<&T>> = streams
+                    .iter_mut()
+                    .map(|s| Pin::new(s).peek().now_or_never().flatten())
+                    .collect();
+                let first = heads
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, head)| head.map(|head| (i, head.attrs())))
+                    .min_by(|(_, a), (_, b)| {
+                        let ordering = a.sort_key().cmp(&b.sort_key());
+                        match direction {
+                            SortDirection::Asc => ordering,
+                            SortDirection::Desc => ordering.reverse(),
+                        }
+                    })
+                    .map(|(i, _)| i);
+
+                // Exhausted streams are no longer interesting.
+                let exhausted: Vec<bool> = heads.iter().map(Option::is_none).collect();
+                (first, exhausted)
+            };
+
+            let first = match first {
+                Some(first) => first,
+                None => break,
+            };
+
+            if let Some(item) = streams[first].next().await {
+                yield item;
+            }
+
+            let mut exhausted = exhausted.into_iter();
+            streams.retain(|_| !exhausted.next().unwrap());
+        }
+    }
+}
+
+/// Ends `stream` once `deadline` has passed, calling `on_expired` if it was cut short.
+pub(crate) fn with_deadline<S, T>(
+    mut stream: S,
+    deadline: Instant,
+    on_expired: impl FnOnce(),
+) -> impl Stream<Item = T>
+where
+    S: Stream<Item = T> + Unpin,
+{
+    stream! {
+        loop {
+            match timeout_at(deadline, stream.next()).await {
+                Ok(Some(item)) => yield item,
+                Ok(None) => break,
+                Err(_) => {
+                    on_expired();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Waits until `idle` has passed without `control` (if there is one) pausing the stream.
+/// Time spent paused doesn't count.
+pub(crate) async fn idle_for(idle: Duration, control: Option<&StreamControl>) {
+    let control = match control {
+        Some(control) => control,
+        None => return tokio::time::sleep(idle).await,
+    };
+    let mut remaining = idle;
+    loop {
+        control.until_resumed().await;
+        let started = Instant::now();
+        match timeout(remaining, control.until_paused()).await {
+            Ok(()) => remaining = remaining.saturating_sub(started.elapsed()),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Runs `future` unless it goes `idle` first (not counting time spent paused by
+/// `control`), returning `None` if it did.
+pub(crate) async fn with_idle_limit<F: Future>(
+    future: F,
+    idle: Duration,
+    control: Option<&StreamControl>,
+) -> Option<F::Output> {
+    let idle = Box::pin(idle_for(idle, control));
+    match future::select(Box::pin(future), idle).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+/// Ends `stream` if it goes `idle` without yielding anything, calling `on_idle` if it was
+/// cut short.
+///
+/// Only the time spent waiting on `stream` counts, not the time the consumer takes to
+/// ask for the next item, nor the time the stream spends paused by `control`.
+pub(crate) fn with_idle_timeout<S, T>(
+    mut stream: S,
+    idle: Duration,
+    control: Option<StreamControl>,
+    on_idle: impl FnOnce(),
+) -> impl Stream<Item = T>
+where
+    S: Stream<Item = T> + Unpin,
+{
+    stream! {
+        loop {
+            match with_idle_limit(stream.next(), idle, control.as_ref()).await {
+                Some(Some(item)) => yield item,
+                Some(None) => break,
+                None => {
+                    on_idle();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Waits for room in `buffer` for a page of up to `page_size` items before each page of
+/// `pages` is fetched, freeing up whatever room the page didn't need.
+///
+/// The room taken by a page is only freed up once its items are yielded to the consumer,
+/// so that at most the buffer's capacity is fetched ahead of the consumer.
+pub(crate) fn bounded<S, T>(
+    mut pages: S,
+    buffer: Arc<ItemBuffer>,
+    page_size: usize,
+) -> impl Stream<Item = Vec<T>>
+where
+    S: Stream<Item = Vec<T>> + Unpin,
+{
+    stream! {
+        loop {
+            let reserved = buffer.reserve(page_size).await;
+            match pages.next().await {
+                Some(page) => {
+                    buffer.release(reserved.saturating_sub(page.len()));
+                    yield page;
+                }
+                None => {
+                    buffer.release(reserved);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// The number of streams [`in_order`] fetches at once by default.
+pub(crate) const DEFAULT_LOOKAHEAD: usize = 4;
+
+/// The most pages [`in_order`] holds back of each stream fetched ahead of the one being
+/// yielded, after which the stream isn't polled until it's the one being yielded.
+pub(crate) const MAX_HELD_PAGES: usize = 8;
+
+/// Yields the pages of each of `streams` in turn, while up to `n` of them (or
+/// [`DEFAULT_LOOKAHEAD`]) are fetched concurrently.
+///
+/// The pages of the first stream are yielded as they're fetched, while up to
+/// [`MAX_HELD_PAGES`] of each of the streams after it are held back until the streams
+/// before them have been yielded, so that the first streams are delivered first while
+/// the next few are fetched in the background.
+pub(crate) fn in_order<S, T>(streams: Vec<S>, n: Option<usize>) -> impl Stream<Item = Vec<T>>
+where
+    S: Stream<Item = Vec<T>>,
+{
+    let n = n.unwrap_or(DEFAULT_LOOKAHEAD).max(1);
+    let mut pending = streams.into_iter().map(Box::pin).enumerate();
+
+    stream! {
+        let mut active: Vec<_> = pending.by_ref().take(n).collect();
+        // The pages of the streams after the one being yielded, and whether they've
+        // ended.
+        let mut held: HashMap<usize, (Vec<Vec<T>>, bool)> = HashMap::new();
+        let mut head = 0;
+
+        while !active.is_empty() {
+            let full: HashSet<usize> = held
+                .iter()
+                .filter(|(_, (pages, _))| pages.len() >= MAX_HELD_PAGES)
+                .map(|(i, _)| *i)
+                .collect();
+            let (i, page) = future::poll_fn(|cx| {
+                for (i, pages) in active.iter_mut() {
+                    if *i != head && full.contains(i) {
+                        continue;
+                    }
+                    if let Poll::Ready(page) = pages.as_mut().poll_next(cx) {
+                        return Poll::Ready((*i, page));
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match page {
+                Some(page) if i == head => yield page,
+                Some(page) => held.entry(i).or_default().0.push(page),
+                None => {
+                    active.retain(|(j, _)| *j != i);
+                    if i != head {
+                        held.entry(i).or_default().1 = true;
+                        continue;
+                    }
+                    loop {
+                        // Move on to the next stream, yielding what's been held back of it.
+                        head += 1;
+                        active.extend(pending.next());
+                        let (pages, ended) = held.remove(&head).unwrap_or_default();
+                        for page in pages {
+                            yield page;
+                        }
+                        if !ended {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Collects the items of `pages` and rearranges them with `arrange`, before yielding
+/// them in pages of `page_size`.
+pub(crate) fn arranged<S, T, F>(
+    pages: S,
+    page_size: usize,
+    arrange: F,
+) -> impl Stream<Item = Vec<T>>
+where
+    S: Stream<Item = Vec<T>>,
+    F: FnOnce(&mut Vec<T>),
+{
+    pages
+        .concat()
+        .map(move |mut items| {
+            arrange(&mut items);
+            let mut items = items.into_iter();
+            let pages = std::iter::from_fn(move || {
+                let page: Vec<T> = items.by_ref().take(page_size.max(1)).collect();
+                (!page.is_empty()).then_some(page)
+            });
+            stream::iter(pages)
+        })
+        .flatten_stream()
+}
+
+/// Like [`in_order`], but rearranges the items of each stream with `arrange` (which is
+/// also passed the stream's index), see [`arranged`].
+pub(crate) fn in_order_by<S, T, F>(
+    streams: Vec<S>,
+    n: Option<usize>,
+    page_size: usize,
+    arrange: F,
+) -> impl Stream<Item = Vec<T>>
+where
+    S: Stream<Item = Vec<T>>,
+    F: Fn(usize, &mut Vec<T>) + Clone,
+{
+    let streams = streams
+        .into_iter()
+        .enumerate()
+        .map(|(i, pages)| {
+            let arrange = arrange.clone();
+            arranged(pages, page_size, move |items| arrange(i, items))
+        })
+        .collect();
+    in_order(streams, n)
+}
+
+/// Drives `stream` on a background task, buffering up to `n` items ahead of the consumer.
+///
+/// The task is aborted when the returned stream is dropped.
+pub(crate) fn prefetch<S>(stream: S, n: usize) -> impl Stream<Item = S::Item>
+where
+    S: Stream + Send + 'static,
+    S::Item: Send,
+{
+    let (tx, mut rx) = tokio::sync::mpsc::channel(n);
+    let task = tokio::spawn(async move {
+        let mut stream = Box::pin(stream);
+        while let Some(item) = stream.next().await {
+            if tx.send(item).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    stream! {
+        let _task = AbortOnDrop(task);
+        while let Some(item) = rx.recv().await {
+            yield item;
+        }
+    }
+}
+
+/// Aborts a task when dropped.
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A range of creation dates which a stream failed to fetch the items of, e.g. because
+/// PushShift kept failing requests for one of the buckets of a chunked query.
+///
+/// The rest of the stream carries on regardless, so the range can be retried afterwards
+/// with [`FailedRange::apply`].  Either bound is `None` if the query wasn't bounded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedRange {
+    pub after: Option<DateTime<Utc>>,
+    pub before: Option<DateTime<Utc>>,
+
+    /// The inclusive range of scores or numbers of comments (whichever the query is
+    /// sorted by) the range was restricted to, if it was one of the buckets of a query
+    /// chunked by value.
+    pub values: Option<(Option<i64>, Option<i64>)>,
+}
+
+impl FailedRange {
+    /// Restricts a filter to the failed range, so that it can be retried.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// use futures::StreamExt;
+    /// use pullcaps::{Client, Filter};
+    ///
+    /// let client = Client::new();
+    /// let filter = Filter::new().subreddit("rust");
+    ///
+    /// let mut comments = client.get_comments(filter.clone()).await;
+    /// while let Some(comment) = comments.next().await {
+    ///     println!("{}", comment.body);
+    /// }
+    ///
+    /// for range in comments.failed_ranges() {
+    ///     let retried = client.get_comments(range.apply(filter.clone())).await;
+    ///     // ...
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn apply(&self, mut filter: Filter) -> Filter {
+        filter.after = self.after;
+        filter.before = self.before;
+        filter.after_relative = None;
+        filter.before_relative = None;
+        if let Some(values) = self.values {
+            filter = restrict_values(filter, values);
+        }
+        filter
+    }
+}
+
+/// The `(after, before)` date range of a bucket of a chunked query.
+type Bucket = (DateTime<Utc>, DateTime<Utc>);
+
+/// What a stream records about itself as it's fetched, shared between the stream and
+/// the clients making its requests.
+#[derive(Clone)]
+pub struct Tracking {
+    /// The date ranges the stream failed to fetch.
+    pub(crate) failed: Arc<Mutex<Vec<FailedRange>>>,
+    /// The date ranges the stream's query was chunked into, as they're planned.
+    pub(crate) buckets: Arc<Mutex<Vec<Bucket>>>,
+    /// The date ranges the stream's queries have yet to fetch, indexed by query, which
+    /// fail if the stream is cut short.  A query's range is cleared once it ends.
+    remaining: Arc<Mutex<Vec<Option<FailedRange>>>>,
+    pub(crate) progress: Progress,
+}
+
+impl Tracking {
+    pub(crate) fn new() -> Self {
+        Self {
+            failed: Arc::default(),
+            buckets: Arc::default(),
+            remaining: Arc::default(),
+            progress: Progress::new(),
+        }
+    }
+
+    /// Records the range a query has yet to fetch, returning the query's index.
+    pub(crate) fn start(&self, range: FailedRange) -> usize {
+        let mut remaining = self.remaining.lock().unwrap();
+        remaining.push(Some(range));
+        remaining.len() - 1
+    }
+
+    /// Records the range a query has yet to fetch as it makes progress, or that it's
+    /// ended if `range` is `None`.
+    pub(crate) fn update(&self, query: usize, range: Option<FailedRange>) {
+        self.remaining.lock().unwrap()[query] = range;
+    }
+
+    /// Records the ranges every unfinished query had yet to fetch as failed, for a
+    /// stream which has been cut short.
+    pub(crate) fn cut_short(&self) {
+        let mut remaining = self.remaining.lock().unwrap();
+        let mut failed = self.failed.lock().unwrap();
+        failed.extend(remaining.iter_mut().filter_map(Option::take));
+    }
+
+    /// Returns a record of the same stream whose unfinished queries are kept apart, so
+    /// that part of the stream can be cut short on its own.
+    pub(crate) fn scoped(&self) -> Self {
+        Self {
+            remaining: Arc::default(),
+            ..self.clone()
+        }
+    }
+}
+
+/// The streams of items returned by a [`Client`](crate::Client), i.e. [`CommentStream`]
+/// and [`PostStream`], which record how their query was fetched as they're polled, e.g.
+/// for a [`ManifestWriter`](crate::manifest::ManifestWriter) to describe.
+///
+/// This trait is sealed, and can't be implemented outside of pullcaps.
+pub trait ItemStream: Stream + private::Sealed {}
+
+mod private {
+    pub trait Sealed {
+        /// Returns what the stream records about itself.
+        #[cfg_attr(not(feature = "manifest"), allow(dead_code))]
+        fn tracking(&self) -> &super::Tracking;
+    }
+}
+
+/// Defines a named, `Send` stream of items wrapping the streams assembled by a client.
+macro_rules! item_stream {
+    ($(#[$meta:meta])* $name:ident, $item:ty) => {
+        $(#[$meta])*
+        ///
+        /// The stream is `Send` and `'static`: it owns a clone of the client it was
+        /// created by, so it can be spawned onto another task or stored in a struct, and
+        /// outlives the original client.
+        #[must_use = "streams do nothing unless polled"]
+        pub struct $name {
+            stream: Pin<Box<dyn Stream<Item = $item> + Send>>,
+            tracking: Tracking,
+        }
+
+        impl $name {
+            pub(crate) fn new(stream: impl Stream<Item = $item> + Send + 'static) -> Self {
+                Self::tracked(stream, Tracking::new())
+            }
+
+            /// Creates a stream whose failures and progress are recorded in `tracking`.
+            pub(crate) fn tracked(
+                stream: impl Stream<Item = $item> + Send + 'static,
+                tracking: Tracking,
+            ) -> Self {
+                Self {
+                    stream: Box::pin(stream),
+                    tracking,
+                }
+            }
+
+            /// Returns the date ranges the stream has failed to fetch so far.
+            ///
+            /// Once the stream has ended, these are the ranges which are missing from
+            /// its results and should be retried.  Each failure is also reported to the
+            /// client's [error handler](crate::ClientBuilder::on_error) as an
+            /// [`Error::RangeFailed`](crate::Error::RangeFailed), except for a second
+            /// with more items than could be paged through, which is only reported as
+            /// [skipped](crate::SkipReason::OverfullSecond), and the ranges left over
+            /// when the stream goes idle, which are reported as a single
+            /// [`Error::IdleTimeout`](crate::Error::IdleTimeout).
+            pub fn failed_ranges(&self) -> Vec<FailedRange> {
+                self.tracking.failed.lock().unwrap().clone()
+            }
+
+            /// Returns a handle to follow how far through its query the stream is.
+            pub fn progress(&self) -> Progress {
+                self.tracking.progress.clone()
+            }
+        }
+
+        impl private::Sealed for $name {
+            fn tracking(&self) -> &Tracking {
+                &self.tracking
+            }
+        }
+
+        impl ItemStream for $name {}
+
+        impl Stream for $name {
+            type Item = $item;
+
+            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<$item>> {
+                self.stream.as_mut().poll_next(cx)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.stream.size_hint()
+            }
+        }
+    };
+}
+
+item_stream!(
+    /// A [`Stream`] of the comments matching a filter, returned by
+    /// [`Client::get_comments`](crate::Client::get_comments).
+    CommentStream,
+    Comment
+);
+
+item_stream!(
+    /// A [`Stream`] of the posts matching a filter, returned by
+    /// [`Client::get_posts`](crate::Client::get_posts).
+    PostStream,
+    Post
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::comment;
+    use crate::SortType;
+    use chrono::TimeZone;
+
+    fn date(ts: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(ts, 0).unwrap()
+    }
+
+    #[test]
+    fn test_item_streams_are_send() {
+        fn is_send<T: Send>() {}
+        is_send::<CommentStream>();
+        is_send::<PostStream>();
+    }
+
+    #[tokio::test]
+    async fn test_dedup_boundaries_yields_each_id_once() {
+        let buckets = [(date(0), date(100)), (date(101), date(200))];
+        let items = vec![
+            comment("a", 50),
+            comment("b", 100),
+            comment("c", 101),
+            // Returned again by the adjacent bucket.
+            comment("b", 100),
+            comment("c", 101),
+            comment("d", 150),
+        ];
+
+        let ids: Vec<String> = dedup_boundaries(stream::iter(items), &buckets)
+            .map(|comment| comment.attrs.id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_by_date() {
+        let a = stream::iter(vec![comment("a3", 30), comment("a1", 10)]);
+        let b = stream::iter(vec![
+            comment("b4", 40),
+            comment("b2", 20),
+            comment("b1", 10),
+        ]);
+
+        let ids: Vec<String> = merge_by_date(vec![a, b], SortDirection::Desc)
+            .map(|comment| comment.attrs.id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["b4", "a3", "b2", "b1", "a1"]);
+
+        let a = stream::iter(vec![comment("a1", 10), comment("a3", 30)]);
+        let b = stream::iter(vec![comment("b1", 10), comment("b2", 20)]);
+
+        let ids: Vec<String> = merge_by_date(vec![a, b], SortDirection::Asc)
+            .map(|comment| comment.attrs.id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["a1", "b1", "b2", "a3"]);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_boundaries_ignores_interior_items() {
+        let buckets = [(date(0), date(100))];
+        let items = vec![comment("a", 50), comment("b", 100), comment("b", 100)];
+
+        let count = dedup_boundaries(stream::iter(items), &buckets)
+            .count()
+            .await;
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_boundary_pages() {
+        let buckets = [(date(0), date(100)), (date(101), date(200))];
+        let pages = vec![
+            vec![comment("a", 50), comment("b", 100)],
+            vec![comment("b", 100)],
+            vec![comment("c", 150)],
+        ];
+
+        let pages: Vec<usize> = dedup_boundary_pages(stream::iter(pages), &buckets)
+            .map(|page| page.len())
+            .collect()
+            .await;
+        assert_eq!(pages, vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_take_items() {
+        let pages = stream::iter(vec![
+            vec![comment("a", 3), comment("b", 2)],
+            vec![comment("c", 1), comment("d", 0)],
+        ])
+        .chain(stream::pending());
+
+        let pages: Vec<usize> = take_items(pages, 3).map(|page| page.len()).collect().await;
+        assert_eq!(pages, vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        let items = stream::iter(vec![comment("a", 10), comment("b", 9)]);
+        let ids: Vec<String> = prefetch(items, 1)
+            .map(|comment| comment.attrs.id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline() {
+        let items = stream::iter(vec![comment("a", 10)]).chain(stream::pending());
+        let deadline = Instant::now() + std::time::Duration::from_millis(20);
+
+        let mut expired = false;
+        let ids: Vec<String> = with_deadline(items, deadline, || expired = true)
+            .map(|comment| comment.attrs.id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["a"]);
+        assert!(expired);
+    }
+
+    #[tokio::test]
+    async fn test_with_idle_timeout() {
+        let idle = std::time::Duration::from_millis(20);
+        let slow = stream::iter(vec![comment("b", 9)]).then(|comment| async move {
+            tokio::time::sleep(idle / 2).await;
+            comment
+        });
+        let items = stream::iter(vec![comment("a", 10)])
+            .chain(slow)
+            .chain(stream::pending());
+
+        let mut timed_out = false;
+        let ids: Vec<String> = with_idle_timeout(Box::pin(items), idle, None, || timed_out = true)
+            .map(|comment| comment.attrs.id)
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["a", "b"]);
+        assert!(timed_out);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_is_suspended_while_paused() {
+        let idle = std::time::Duration::from_millis(20);
+        let control = StreamControl::new();
+        control.pause();
+        let items = stream::once(async move {
+            tokio::time::sleep(idle * 3).await;
+            comment("a", 10)
+        });
+
+        let ids: Vec<String> =
+            with_idle_timeout(Box::pin(items), idle, Some(control), || unreachable!())
+                .map(|comment| comment.attrs.id)
+                .collect()
+                .await;
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn test_failed_range_apply() {
+        let range = FailedRange {
+            after: Some(date(100)),
+            before: None,
+            values: None,
+        };
+        let filter = range.apply(Filter::new().subreddit("rust").before(date(500)));
+        assert_eq!(filter.after, Some(date(100)));
+        assert_eq!(filter.before, None);
+        assert_eq!(filter.subreddit.as_deref(), Some("rust"));
+        assert_eq!(filter.value_range, None);
+
+        // The range of a bucket of a query chunked by value keeps its values.
+        let range = FailedRange {
+            values: Some((Some(10), Some(20))),
+            ..range
+        };
+        let filter = range.apply(Filter::new().sort_type(SortType::Score));
+        assert_eq!(filter.value_range, Some(("score", (Some(10), Some(20)))));
+    }
+
+    #[tokio::test]
+    async fn test_in_order() {
+        // The later stream finishes first, but is yielded last.
+        let slow = stream::once(tokio::time::sleep(std::time::Duration::from_millis(10)))
+            .map(|_| vec![1, 2])
+            .boxed();
+        let fast = stream::iter(vec![vec![3], vec![4]]).boxed();
+
+        let pages: Vec<_> = in_order(vec![slow, fast], None).collect().await;
+        assert_eq!(pages, vec![vec![1, 2], vec![3], vec![4]]);
+    }
+
+    #[tokio::test]
+    async fn test_in_order_holds_back_a_few_pages() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let first = stream::once(async { vec![0] })
+            .chain(stream::pending())
+            .boxed();
+        let counted = fetched.clone();
+        let second = stream::iter(0..100)
+            .map(move |i| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                vec![i]
+            })
+            .boxed();
+
+        let mut pages = Box::pin(in_order(vec![first, second], None));
+        assert_eq!(pages.next().await, Some(vec![0]));
+        let timeout = std::time::Duration::from_millis(10);
+        assert!(tokio::time::timeout(timeout, pages.next()).await.is_err());
+        // The second stream stops being fetched once enough of it is held back.
+        assert_eq!(fetched.load(Ordering::SeqCst), MAX_HELD_PAGES);
+    }
+
+    #[tokio::test]
+    async fn test_in_order_streams_first_and_bounds_lookahead() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let started = Arc::new(AtomicUsize::new(0));
+        let bucket = |page: Vec<i32>| {
+            let started = started.clone();
+            stream::once(async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                page
+            })
+            .boxed()
+        };
+        // The first stream never finishes, but its pages are yielded as they arrive.
+        let first = bucket(vec![1]).chain(stream::pending()).boxed();
+        let streams = vec![first, bucket(vec![2]), bucket(vec![3])];
+
+        let mut pages = Box::pin(in_order(streams, Some(2)));
+        assert_eq!(pages.next().await, Some(vec![1]));
+        let timeout = std::time::Duration::from_millis(10);
+        assert!(tokio::time::timeout(timeout, pages.next()).await.is_err());
+        // Only the first two streams have been started.
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_in_order_by() {
+        let first = stream::iter(vec![vec![3, 1], vec![2]]);
+        let second = stream::iter(vec![vec![4], vec![6, 5]]);
+
+        let pages: Vec<_> = in_order_by(vec![first, second], None, 2, |_, items| {
+            items.sort_by(|a, b| b.cmp(a))
+        })
+        .collect()
+        .await;
+        assert_eq!(pages, vec![vec![3, 2], vec![1], vec![6, 5], vec![4]]);
+    }
+
+    #[tokio::test]
+    async fn test_bounded() {
+        let pages = stream::iter(vec![vec![1, 2], vec![3], vec![4, 5]]);
+        let buffer = Arc::new(ItemBuffer::new(4));
+        let mut pages = Box::pin(bounded(pages, buffer.clone(), 2));
+
+        assert_eq!(pages.next().await, Some(vec![1, 2]));
+        assert_eq!(pages.next().await, Some(vec![3]));
+
+        // The first two pages haven't been consumed, so there's no room for a third.
+        let timeout = std::time::Duration::from_millis(10);
+        assert!(tokio::time::timeout(timeout, pages.next()).await.is_err());
+
+        buffer.release(3);
+        assert_eq!(pages.next().await, Some(vec![4, 5]));
+        assert_eq!(pages.next().await, None);
+    }
+}
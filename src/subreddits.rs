@@ -0,0 +1,97 @@
+//! Resolving subreddit patterns, see [`Filter::subreddit_matching`].
+//!
+//! [`Filter::subreddit_matching`]: crate::Filter::subreddit_matching
+use crate::models::{AsAttrs, Attrs};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// A subreddit returned by PushShift's subreddit search.
+///
+/// Its ID and creation date are captured too, so that the search can be paginated
+/// through like any other query.
+#[derive(Deserialize)]
+pub(crate) struct SubredditName {
+    pub(crate) display_name: String,
+    #[serde(flatten)]
+    attrs: Attrs,
+}
+
+impl AsAttrs for SubredditName {
+    fn attrs(&self) -> &Attrs {
+        &self.attrs
+    }
+}
+
+/// Only the name of a subreddit is captured, so subreddits serialize as a unit, which
+/// skips the unknown field checks of strict mode.
+impl Serialize for SubredditName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+/// Returns the term to search subreddits for, which is the longest literal part of the
+/// pattern; the results are then narrowed down with [`matches()`].
+pub(crate) fn search_term(pattern: &str) -> &str {
+    pattern
+        .split(['*', '?'])
+        .max_by_key(|part| part.len())
+        .unwrap_or_default()
+}
+
+/// Returns whether a subreddit name matches a pattern, ignoring case, where `*` matches
+/// any number of characters and `?` matches a single character.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in the pattern, and of the name when it was reached.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` match one more character.
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_term() {
+        assert_eq!(search_term("ask*"), "ask");
+        assert_eq!(search_term("*history*"), "history");
+        assert_eq!(search_term("r?st*lang"), "lang");
+        assert_eq!(search_term("*"), "");
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("ask*", "AskReddit"));
+        assert!(matches("ask*", "ask"));
+        assert!(!matches("ask*", "NoStupidQuestions"));
+        assert!(matches("*history", "AskHistory"));
+        assert!(!matches("*history", "HistoryMemes"));
+        assert!(matches("r?st", "rust"));
+        assert!(!matches("r?st", "roast"));
+        assert!(matches("a*b*c", "aXXbYYbZc"));
+    }
+}
@@ -0,0 +1,414 @@
+//! Incremental syncing of new content, remembering how far previous runs got.
+//!
+//! A [`SyncJob`] fetches the items matching a filter in order of creation, oldest
+//! first, passing them to a callback a page at a time.  After each page is processed
+//! successfully, the job saves a [`Watermark`] to a [`WatermarkStore`], so that the
+//! next run only fetches content created since then - even if the previous run was
+//! interrupted part of the way through.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() {
+//! use pullcaps::sync::{FileWatermarks, SyncJob};
+//! use pullcaps::{Client, Filter};
+//! use std::time::Duration;
+//!
+//! let client = Client::new();
+//! let store = FileWatermarks::new("watermarks.json");
+//! let job = SyncJob::comments(client, Filter::new().subreddit("rust"), store);
+//!
+//! job.run_every(Duration::from_secs(15 * 60), |comments| async move {
+//!     println!("archiving {} comments", comments.len());
+//!     Ok::<_, std::io::Error>(())
+//! })
+//! .await
+//! .unwrap();
+//! # }
+//! ```
+use crate::api::Endpoint;
+use crate::client::Item;
+use crate::models::{AsAttrs, Comment, Post};
+use crate::{Client, Filter, SortDirection};
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How far a [`SyncJob`] has got: the creation date of the newest item processed, and
+/// the IDs of the processed items created in that second.
+///
+/// PushShift's date filters are inclusive and have second resolution, so the next run
+/// fetches items created from `date` onwards and skips those in `ids`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Watermark {
+    #[serde(with = "ts_seconds")]
+    pub date: DateTime<Utc>,
+    pub ids: Vec<String>,
+}
+
+/// Persists the [`Watermark`]'s of sync jobs, keyed by the name of the job.
+pub trait WatermarkStore: Send + Sync {
+    /// Returns the watermark saved under the given key, if there is one.
+    fn load(&self, key: &str) -> io::Result<Option<Watermark>>;
+
+    /// Saves the watermark under the given key, replacing any previous watermark.
+    ///
+    /// Once this returns successfully, the watermark should survive a crash.
+    fn save(&self, key: &str, watermark: &Watermark) -> io::Result<()>;
+}
+
+/// A [`WatermarkStore`] which keeps watermarks in memory, e.g. for a long-running
+/// process which syncs on a schedule and can afford to start over when restarted.
+#[derive(Debug, Default)]
+pub struct MemoryWatermarks {
+    watermarks: Mutex<HashMap<String, Watermark>>,
+}
+
+impl MemoryWatermarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl WatermarkStore for MemoryWatermarks {
+    fn load(&self, key: &str) -> io::Result<Option<Watermark>> {
+        Ok(self.watermarks.lock().unwrap().get(key).cloned())
+    }
+
+    fn save(&self, key: &str, watermark: &Watermark) -> io::Result<()> {
+        self.watermarks
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), watermark.clone());
+        Ok(())
+    }
+}
+
+/// A [`WatermarkStore`] which keeps watermarks in a JSON file.
+///
+/// The file is replaced atomically on every save, so a crash never leaves it half
+/// written.
+#[derive(Debug)]
+pub struct FileWatermarks {
+    path: PathBuf,
+    /// Serializes saves, which read and rewrite the whole file.
+    lock: Mutex<()>,
+}
+
+impl FileWatermarks {
+    /// Creates a store keeping watermarks in the file at `path`, which is created when
+    /// the first watermark is saved.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> io::Result<HashMap<String, Watermark>> {
+        match fs::read(&self.path) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl WatermarkStore for FileWatermarks {
+    fn load(&self, key: &str) -> io::Result<Option<Watermark>> {
+        Ok(self.read()?.remove(key))
+    }
+
+    fn save(&self, key: &str, watermark: &Watermark) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let mut watermarks = self.read()?;
+        watermarks.insert(key.to_string(), watermark.clone());
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&watermarks)?)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Fetches the content matching a filter which is newer than the stored [`Watermark`].
+///
+/// See the [module documentation](self).
+pub struct SyncJob<T, S> {
+    client: Client,
+    filter: Filter,
+    store: S,
+    key: String,
+    endpoint: Endpoint,
+    item: PhantomData<fn() -> T>,
+}
+
+/// The outcome of a successful [`SyncJob`] run.
+#[derive(Clone, Debug)]
+pub struct SyncReport {
+    /// The number of pages passed to the callback.
+    pub pages: usize,
+    /// The number of items passed to the callback.
+    pub items: usize,
+    /// The watermark after the run, if anything has ever been synced.
+    pub watermark: Option<Watermark>,
+}
+
+/// The error returned when a [`SyncJob`] run fails.
+///
+/// Requests which fail are reported to the client's
+/// [error handler](crate::ClientBuilder::on_error) and end the run early, but aren't
+/// errors: the next run picks up where this one stopped.
+#[derive(Debug)]
+pub enum SyncError<E> {
+    /// The watermark couldn't be loaded or saved.
+    Store(io::Error),
+    /// The callback failed to process a page, which will be fetched again next run.
+    Process(E),
+}
+
+impl<E: fmt::Display> fmt::Display for SyncError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(error) => write!(f, "watermark store failed: {}", error),
+            Self::Process(error) => write!(f, "processing failed: {}", error),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for SyncError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(error) => Some(error),
+            Self::Process(error) => Some(error),
+        }
+    }
+}
+
+impl<S: WatermarkStore> SyncJob<Post, S> {
+    /// Creates a job syncing the posts matching `filter`.
+    ///
+    /// If there's no stored watermark, the first run starts from the filter's `after`
+    /// date (or from the oldest matching post).
+    pub fn posts(client: Client, filter: Filter, store: S) -> Self {
+        Self::new(client, filter, store, Endpoint::Posts)
+    }
+
+    /// Runs the job once, passing each page of new posts to `process` in order of
+    /// creation, and saving the watermark after each page is processed.
+    ///
+    /// The run stops at the first page which fails to be processed; since the
+    /// watermark isn't advanced past that page, it's fetched again by the next run.
+    pub async fn run<F, Fut, E>(&self, process: F) -> Result<SyncReport, SyncError<E>>
+    where
+        F: FnMut(Vec<Post>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        sync(self, process).await
+    }
+
+    /// Runs the job repeatedly, waiting `interval` between the end of one run and the
+    /// start of the next, until a run fails.
+    pub async fn run_every<F, Fut, E>(
+        &self,
+        interval: Duration,
+        mut process: F,
+    ) -> Result<Infallible, SyncError<E>>
+    where
+        F: FnMut(Vec<Post>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        loop {
+            sync(self, &mut process).await?;
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+impl<S: WatermarkStore> SyncJob<Comment, S> {
+    /// Creates a job syncing the comments matching `filter`.
+    ///
+    /// See [`SyncJob::posts`].
+    pub fn comments(client: Client, filter: Filter, store: S) -> Self {
+        Self::new(client, filter, store, Endpoint::Comments)
+    }
+
+    /// Runs the job once; see [`SyncJob::<Post, S>::run`](SyncJob#method.run).
+    pub async fn run<F, Fut, E>(&self, process: F) -> Result<SyncReport, SyncError<E>>
+    where
+        F: FnMut(Vec<Comment>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        sync(self, process).await
+    }
+
+    /// Runs the job repeatedly; see
+    /// [`SyncJob::<Post, S>::run_every`](SyncJob#method.run_every).
+    pub async fn run_every<F, Fut, E>(
+        &self,
+        interval: Duration,
+        mut process: F,
+    ) -> Result<Infallible, SyncError<E>>
+    where
+        F: FnMut(Vec<Comment>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        loop {
+            sync(self, &mut process).await?;
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+impl<T, S> SyncJob<T, S> {
+    fn new(client: Client, filter: Filter, store: S, endpoint: Endpoint) -> Self {
+        let endpoint_name = match endpoint {
+            Endpoint::Comments => "comments",
+            Endpoint::Posts => "posts",
+        };
+        let query = serde_urlencoded::to_string(&filter).unwrap_or_default();
+        Self {
+            client,
+            filter,
+            store,
+            key: format!("{}?{}", endpoint_name, query),
+            endpoint,
+            item: PhantomData,
+        }
+    }
+
+    /// Sets the key under which the job's watermark is stored.
+    ///
+    /// Defaults to the endpoint and query string of the filter, so jobs with different
+    /// filters don't share a watermark.  Set a key to keep the watermark when the
+    /// filter changes.
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.key = key.into();
+        self
+    }
+}
+
+/// Runs a job once, see [`SyncJob::run`].
+async fn sync<T, S, F, Fut, E>(
+    job: &SyncJob<T, S>,
+    mut process: F,
+) -> Result<SyncReport, SyncError<E>>
+where
+    T: Item,
+    S: WatermarkStore,
+    F: FnMut(Vec<T>) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    let mut watermark = job.store.load(&job.key).map_err(SyncError::Store)?;
+
+    let mut filter = job.filter.clone().sort(SortDirection::Asc);
+    filter.limit = None;
+    if let Some(watermark) = &watermark {
+        filter = filter.after(watermark.date);
+    }
+
+    let mut report = SyncReport {
+        pages: 0,
+        items: 0,
+        watermark: None,
+    };
+
+    // Pages are fetched one at a time (rather than in concurrent chunks), so that a
+    // failed request ends the run rather than leaving a gap behind the watermark.
+    let mut pages = job.client.sequential_pages::<T>(job.endpoint, filter);
+    while let Some(Ok(page)) = pages.next().await {
+        let page = advance(&mut watermark, page);
+        if page.is_empty() {
+            continue;
+        }
+
+        report.pages += 1;
+        report.items += page.len();
+        let next = watermark.clone();
+        process(page).await.map_err(SyncError::Process)?;
+
+        if let Some(next) = &next {
+            job.store.save(&job.key, next).map_err(SyncError::Store)?;
+        }
+    }
+
+    report.watermark = watermark;
+    Ok(report)
+}
+
+/// Removes the items in `page` which were processed by a previous run, and advances
+/// the watermark past the rest.
+fn advance<T: AsAttrs>(watermark: &mut Option<Watermark>, page: Vec<T>) -> Vec<T> {
+    let page: Vec<T> = page
+        .into_iter()
+        .filter(|item| {
+            let attrs = item.attrs();
+            watermark.as_ref().is_none_or(|w| {
+                attrs.date > w.date || (attrs.date == w.date && !w.ids.contains(&attrs.id))
+            })
+        })
+        .collect();
+
+    for item in &page {
+        let attrs = item.attrs();
+        match watermark {
+            Some(w) if attrs.date == w.date => w.ids.push(attrs.id.clone()),
+            Some(w) if attrs.date < w.date => {}
+            _ => {
+                *watermark = Some(Watermark {
+                    date: attrs.date,
+                    ids: vec![attrs.id.clone()],
+                })
+            }
+        }
+    }
+
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::comment;
+
+    #[test]
+    fn test_advance() {
+        let mut watermark = None;
+        let page = advance(&mut watermark, vec![comment("a", 1), comment("b", 2)]);
+        assert_eq!(page.len(), 2);
+        assert_eq!(watermark.as_ref().unwrap().ids, vec!["b"]);
+
+        // The next run starts from the watermark's second, which includes `b` again.
+        let page = advance(&mut watermark, vec![comment("b", 2), comment("c", 2)]);
+        assert_eq!(page.len(), 1);
+        let watermark = watermark.unwrap();
+        assert_eq!(watermark.date.timestamp(), 2);
+        assert_eq!(watermark.ids, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_file_watermarks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileWatermarks::new(dir.path().join("watermarks.json"));
+        assert_eq!(store.load("job").unwrap(), None);
+
+        let mut watermark = None;
+        advance(&mut watermark, vec![comment("a", 1)]);
+        let watermark = watermark.unwrap();
+        store.save("job", &watermark).unwrap();
+        store.save("other", &watermark).unwrap();
+
+        let store = FileWatermarks::new(dir.path().join("watermarks.json"));
+        assert_eq!(store.load("job").unwrap(), Some(watermark));
+    }
+}
@@ -0,0 +1,46 @@
+//! Emits [`metrics`] for the requests made by clients, for services which export them
+//! (e.g. to Prometheus or StatsD) with a recorder of their choosing.
+//!
+//! Nothing is recorded unless the application installs a recorder.
+use reqwest::StatusCode;
+use std::time::Duration;
+
+/// Called before a request is sent to PushShift.
+pub(crate) fn request() {
+    metrics::counter!("pullcaps_requests_total").increment(1);
+}
+
+/// Called when PushShift responds to a request, successfully or otherwise.
+pub(crate) fn response(status: StatusCode, elapsed: Duration) {
+    metrics::counter!("pullcaps_responses_total", "status" => status.as_u16().to_string())
+        .increment(1);
+    metrics::histogram!("pullcaps_request_duration_seconds").record(elapsed.as_secs_f64());
+}
+
+/// Called before a request is retried.
+pub(crate) fn retry() {
+    metrics::counter!("pullcaps_retries_total").increment(1);
+}
+
+/// Called with the time a request spent waiting for the rate limiter.
+pub(crate) fn rate_limit_wait(elapsed: Duration) {
+    metrics::histogram!("pullcaps_rate_limit_wait_seconds").record(elapsed.as_secs_f64());
+}
+
+/// Called with the number of items in each response, whose rate is the number of items
+/// fetched per second.
+pub(crate) fn items(count: usize) {
+    metrics::counter!("pullcaps_items_total").increment(count as u64);
+}
+
+/// Called with the size of each response body downloaded, as transferred and once
+/// decompressed.
+pub(crate) fn body(transferred: usize, decoded: usize) {
+    metrics::counter!("pullcaps_transferred_bytes_total").increment(transferred as u64);
+    metrics::counter!("pullcaps_decoded_bytes_total").increment(decoded as u64);
+}
+
+/// Called with every error reported by a client.
+pub(crate) fn error() {
+    metrics::counter!("pullcaps_errors_total").increment(1);
+}
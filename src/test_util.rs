@@ -0,0 +1,96 @@
+//! Helpers shared between unit tests.
+use crate::models::Comment;
+
+/// Returns a minimal comment with the given ID and creation timestamp.
+pub(crate) fn comment(id: &str, created_utc: i64) -> Comment {
+    serde_json::from_value(serde_json::json!({
+        "author": "reddit",
+        "subreddit": "rust",
+        "subreddit_id": "t5_2s7lj",
+        "id": id,
+        "score": 1,
+        "created_utc": created_utc,
+        "body": "",
+        "parent_id": "t3_abc",
+    }))
+    .unwrap()
+}
+
+/// A response served by [`serve_responses`].
+pub(crate) struct Response {
+    status: u16,
+    headers: Vec<(&'static str, &'static str)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// A response with a JSON content type.
+    pub(crate) fn json<B: Into<Vec<u8>>>(status: u16, body: B) -> Self {
+        Self {
+            status,
+            headers: vec![("content-type", "application/json")],
+            body: body.into(),
+        }
+    }
+
+    /// Adds a header to the response.
+    #[cfg_attr(not(feature = "compression"), allow(dead_code))]
+    pub(crate) fn header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+/// Serves each of the given `(status, body)` responses in turn, one per connection, on
+/// a local port, returning the URL to request them from.
+pub(crate) async fn serve(responses: Vec<(u16, &'static str)>) -> reqwest::Url {
+    serve_responses(json(responses), false).await
+}
+
+/// Like [`serve`], but never answers the requests made after the given responses.
+pub(crate) async fn serve_then_stall(responses: Vec<(u16, &'static str)>) -> reqwest::Url {
+    serve_responses(json(responses), true).await
+}
+
+fn json(responses: Vec<(u16, &'static str)>) -> Vec<Response> {
+    responses
+        .into_iter()
+        .map(|(status, body)| Response::json(status, body))
+        .collect()
+}
+
+/// Serves each of the given responses in turn, one per connection, on a local port,
+/// returning the URL to request them from.  If `stall` is set, the requests made after
+/// them are never answered.
+pub(crate) async fn serve_responses(responses: Vec<Response>, stall: bool) -> reqwest::Url {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 8192];
+            let _ = socket.read(&mut request).await.unwrap();
+            let mut head = format!("HTTP/1.1 {} Status\r\n", response.status);
+            for (name, value) in &response.headers {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            head.push_str(&format!(
+                "content-length: {}\r\nconnection: close\r\n\r\n",
+                response.body.len()
+            ));
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&response.body).await.unwrap();
+        }
+        let mut stalled = Vec::new();
+        if stall {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                stalled.push(socket);
+            }
+        }
+    });
+    reqwest::Url::parse(&url).unwrap()
+}
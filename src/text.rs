@@ -0,0 +1,146 @@
+//! Converts the Markdown of comments and self-posts into plain text.
+//!
+//! # Example
+//! ```rust
+//! use pullcaps::text::{to_plain_text, PlainText};
+//!
+//! let markdown = "> quoted\n\nSee [the docs](https://docs.rs) &amp; **more**.";
+//! assert_eq!(to_plain_text(markdown), "quoted\n\nSee the docs & more.");
+//!
+//! let converter = PlainText::new().quotes(false).link_urls(true);
+//! assert_eq!(converter.convert(markdown), "See the docs (https://docs.rs) & more.");
+//! ```
+use crate::models::{decode_html_entities, Comment, Post};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+/// Converts Markdown into plain text, with configurable handling of quotes, code
+/// blocks and links.
+///
+/// By default the text of quotes, code blocks and links is kept, and link URLs are
+/// dropped.
+#[derive(Clone, Debug)]
+pub struct PlainText {
+    quotes: bool,
+    code_blocks: bool,
+    link_urls: bool,
+}
+
+impl PlainText {
+    /// Creates a converter with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            quotes: true,
+            code_blocks: true,
+            link_urls: false,
+        }
+    }
+
+    /// Sets whether the text of block quotes is kept.
+    pub fn quotes(mut self, keep: bool) -> Self {
+        self.quotes = keep;
+        self
+    }
+
+    /// Sets whether the contents of code blocks are kept.
+    pub fn code_blocks(mut self, keep: bool) -> Self {
+        self.code_blocks = keep;
+        self
+    }
+
+    /// Sets whether the URL of each link is kept, in parentheses after its text.
+    pub fn link_urls(mut self, keep: bool) -> Self {
+        self.link_urls = keep;
+        self
+    }
+
+    /// Converts the given Markdown (which may contain HTML entities, as returned by
+    /// PushShift) into plain text.
+    pub fn convert(&self, markdown: &str) -> String {
+        let markdown = decode_html_entities(markdown);
+        let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES;
+
+        let mut text = String::new();
+        // The number of enclosing elements whose text is being dropped.
+        let mut skipping = 0;
+        let mut link_urls = Vec::new();
+
+        for event in Parser::new_ext(&markdown, options) {
+            match event {
+                Event::Start(Tag::BlockQuote(_)) if !self.quotes => skipping += 1,
+                Event::End(TagEnd::BlockQuote(_)) if !self.quotes => skipping -= 1,
+                Event::Start(Tag::CodeBlock(_)) if !self.code_blocks => skipping += 1,
+                Event::End(TagEnd::CodeBlock) if !self.code_blocks => skipping -= 1,
+                _ if skipping > 0 => {}
+                Event::Start(Tag::Link { dest_url, .. }) => link_urls.push(dest_url),
+                Event::End(TagEnd::Link) => {
+                    if let Some(url) = link_urls.pop() {
+                        if self.link_urls {
+                            text.push_str(&format!(" ({})", url));
+                        }
+                    }
+                }
+                Event::Text(t) | Event::Code(t) => text.push_str(&t),
+                Event::SoftBreak | Event::HardBreak => text.push('\n'),
+                Event::End(
+                    TagEnd::Paragraph
+                    | TagEnd::Heading(_)
+                    | TagEnd::CodeBlock
+                    | TagEnd::List(_)
+                    | TagEnd::Table,
+                ) => {
+                    // Separate blocks by a single blank line.
+                    text.truncate(text.trim_end_matches('\n').len());
+                    text.push_str("\n\n");
+                }
+                Event::End(TagEnd::Item | TagEnd::TableRow | TagEnd::TableHead) => text.push('\n'),
+                Event::End(TagEnd::TableCell) => text.push('\t'),
+                _ => {}
+            }
+        }
+
+        text.trim().to_string()
+    }
+
+    /// Returns the body of the given comment as plain text.
+    pub fn comment(&self, comment: &Comment) -> String {
+        self.convert(&comment.body)
+    }
+
+    /// Returns the text of the given post as plain text, if it's a self-post.
+    pub fn post(&self, post: &Post) -> Option<String> {
+        post.self_text.as_deref().map(|text| self.convert(text))
+    }
+}
+
+impl Default for PlainText {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts the given Markdown into plain text, using the default [`PlainText`]
+/// configuration.
+pub fn to_plain_text(markdown: &str) -> String {
+    PlainText::new().convert(markdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_plain_text() {
+        let markdown = "# Title\n\n* one\n* two\n\n```\nlet x = 1;\n```\n\n~~old~~ `code`";
+        assert_eq!(
+            to_plain_text(markdown),
+            "Title\n\none\ntwo\n\nlet x = 1;\n\nold code"
+        );
+    }
+
+    #[test]
+    fn test_strip_code_blocks_and_quotes() {
+        let converter = PlainText::new().quotes(false).code_blocks(false);
+        let markdown = "> a quote\n\nreply\n\n    indented code\n\nend";
+        assert_eq!(converter.convert(markdown), "reply\n\nend");
+    }
+}
@@ -0,0 +1,80 @@
+use reqwest::Url;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long fetching a page of a query took, see [`Hooks::on_page`] and
+/// [`Client::get_timed_post_pages`].
+///
+/// Comparing the time spent waiting for the rate limiter with the time PushShift says it
+/// took to execute the query shows whether a slow stream is held back by the client or
+/// by the cost of its query.
+///
+/// [`Hooks::on_page`]: crate::Hooks::on_page
+/// [`Client::get_timed_post_pages`]: crate::Client::get_timed_post_pages
+#[derive(Clone, Debug)]
+pub struct PageTiming {
+    /// The URL of the request for the page, including its query parameters.
+    pub url: Url,
+
+    /// The number of items in the response.
+    pub items: usize,
+
+    /// How long the page took to fetch in total, from waiting for the rate limiter to
+    /// parsing the response, including any retries.
+    pub elapsed: Duration,
+
+    /// How much of `elapsed` was spent waiting for the rate limiter.
+    pub rate_limit_wait: Duration,
+
+    /// How long PushShift says it took to execute the query, if it reported it.
+    pub took: Option<Duration>,
+}
+
+impl PageTiming {
+    /// Returns how much of `elapsed` wasn't spent waiting for the rate limiter, i.e. on
+    /// sending requests, waiting for PushShift, and reading its responses.
+    pub fn request_time(&self) -> Duration {
+        self.elapsed.saturating_sub(self.rate_limit_wait)
+    }
+}
+
+/// Adds up the time the requests for a page spent waiting for the rate limiter.
+#[derive(Debug, Default)]
+pub(crate) struct WaitTimer(AtomicU64);
+
+impl WaitTimer {
+    pub(crate) fn record(&self, waited: Duration) {
+        let nanos = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+        self.0.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn total(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// The timings of the pages fetched for a stream which haven't been handed out yet.
+pub(crate) type PageTimings = Arc<Mutex<Vec<PageTiming>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_time() {
+        let timer = WaitTimer::default();
+        timer.record(Duration::from_millis(300));
+        timer.record(Duration::from_millis(200));
+
+        let timing = PageTiming {
+            url: Url::parse("https://api.pushshift.io/reddit/comment/search/").unwrap(),
+            items: 100,
+            elapsed: Duration::from_millis(800),
+            rate_limit_wait: timer.total(),
+            took: Some(Duration::from_millis(120)),
+        };
+        assert_eq!(timing.rate_limit_wait, Duration::from_millis(500));
+        assert_eq!(timing.request_time(), Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,168 @@
+use bytes::Bytes;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The amount of data a [`Client`](crate::Client) and its clones have downloaded, see
+/// [`Client::transfer_stats`](crate::Client::transfer_stats).
+///
+/// Responses answered from the [cache](crate::ClientBuilder::cache) aren't counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    /// The number of response bodies downloaded.
+    pub responses: u64,
+
+    /// The number of bytes of response bodies downloaded, as sent over the network.
+    pub transferred_bytes: u64,
+
+    /// The number of bytes of response bodies once decompressed, which is the same as
+    /// `transferred_bytes` unless responses were compressed.
+    pub decoded_bytes: u64,
+}
+
+impl TransferStats {
+    /// Returns the size of the downloaded bodies relative to their decompressed size,
+    /// between 0 and 1, or `None` if nothing has been downloaded.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.decoded_bytes == 0 {
+            return None;
+        }
+        Some(self.transferred_bytes as f64 / self.decoded_bytes as f64)
+    }
+}
+
+/// Counts the response bodies downloaded by a client and its clones.
+#[derive(Debug, Default)]
+pub(crate) struct TransferCounter {
+    responses: AtomicU64,
+    transferred: AtomicU64,
+    decoded: AtomicU64,
+}
+
+impl TransferCounter {
+    pub(crate) fn record(&self, body: &Body) {
+        self.responses.fetch_add(1, Ordering::Relaxed);
+        self.transferred
+            .fetch_add(body.raw_len as u64, Ordering::Relaxed);
+        self.decoded
+            .fetch_add(body.decoded.len() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> TransferStats {
+        TransferStats {
+            responses: self.responses.load(Ordering::Relaxed),
+            transferred_bytes: self.transferred.load(Ordering::Relaxed),
+            decoded_bytes: self.decoded.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A response body, as it was sent over the network and once decompressed.
+pub(crate) struct Body {
+    /// The number of bytes sent over the network.
+    pub(crate) raw_len: usize,
+    /// The body as it was sent, if it was asked to be kept, e.g. to be recorded in a
+    /// WARC file.
+    #[cfg_attr(not(feature = "warc"), allow(dead_code))]
+    pub(crate) raw: Option<Bytes>,
+    pub(crate) decoded: Bytes,
+}
+
+impl Body {
+    /// A body which wasn't compressed.
+    pub(crate) fn identity(body: Bytes) -> Self {
+        Self {
+            raw_len: body.len(),
+            raw: Some(body.clone()),
+            decoded: body,
+        }
+    }
+}
+
+/// Reads a zstd-compressed response body, decompressing each chunk as it arrives and
+/// passing what it decompresses to `sink`.  The compressed body is only kept if
+/// `keep_raw` is set.
+#[cfg(feature = "compression")]
+pub(crate) async fn read_zstd(
+    mut response: reqwest::Response,
+    keep_raw: bool,
+    sink: &mut (dyn FnMut(&[u8]) + Send),
+) -> Result<Body, crate::Error> {
+    use std::io::Write;
+
+    let mut raw_len = 0;
+    let mut raw = Vec::new();
+    let mut decoder =
+        zstd::stream::write::Decoder::new(Vec::new()).map_err(crate::Error::Decompress)?;
+    let mut decoded = 0;
+    while let Some(chunk) = response.chunk().await.map_err(crate::Error::Http)? {
+        decoder
+            .write_all(&chunk)
+            .map_err(crate::Error::Decompress)?;
+        sink(&decoder.get_ref()[decoded..]);
+        decoded = decoder.get_ref().len();
+        raw_len += chunk.len();
+        if keep_raw {
+            raw.extend_from_slice(&chunk);
+        }
+    }
+    decoder.flush().map_err(crate::Error::Decompress)?;
+    sink(&decoder.get_ref()[decoded..]);
+    Ok(Body {
+        raw_len,
+        raw: keep_raw.then(|| Bytes::from(raw)),
+        decoded: Bytes::from(decoder.into_inner()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter() {
+        let counter = TransferCounter::default();
+        assert_eq!(counter.stats().compression_ratio(), None);
+
+        counter.record(&Body {
+            raw_len: 3,
+            raw: None,
+            decoded: Bytes::from_static(b"abcabcabcabc"),
+        });
+        counter.record(&Body::identity(Bytes::from_static(b"abcd")));
+
+        let stats = counter.stats();
+        assert_eq!(stats.responses, 2);
+        assert_eq!(stats.transferred_bytes, 7);
+        assert_eq!(stats.decoded_bytes, 16);
+        assert_eq!(stats.compression_ratio(), Some(7.0 / 16.0));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_read_zstd() {
+        use crate::test_util::{serve_responses, Response};
+
+        let json = br#"{"data": [{"id": "a", "created_utc": 10}]}"#.repeat(10);
+        let compressed = zstd::encode_all(&json[..], 0).unwrap();
+        let response =
+            || Response::json(200, compressed.clone()).header("content-encoding", "zstd");
+        let url = serve_responses(vec![response(), response()], false).await;
+
+        // Everything that's decompressed is passed on as it arrives.
+        let mut streamed = Vec::new();
+        let mut sink = |chunk: &[u8]| streamed.extend_from_slice(chunk);
+        let body = read_zstd(reqwest::get(url.clone()).await.unwrap(), false, &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(body.decoded, json);
+        assert_eq!(streamed, json);
+        assert_eq!(body.raw_len, compressed.len());
+        assert!(body.raw.is_none());
+
+        // The compressed body is kept when asked for, e.g. for a WARC file.
+        let body = read_zstd(reqwest::get(url).await.unwrap(), true, &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(body.decoded, json);
+        assert_eq!(body.raw.unwrap(), compressed);
+    }
+}
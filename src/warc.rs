@@ -0,0 +1,296 @@
+//! Recording the HTTP requests made to PushShift in [WARC] files.
+//!
+//! A [`WarcWriter`] given to [`ClientBuilder::warc`] records every request a client
+//! makes, together with PushShift's response, as a pair of WARC records.  This preserves
+//! the provenance of the items yielded by the client's streams in the format used by web
+//! archiving tools, e.g. so that an archive can show exactly what PushShift returned and
+//! when.
+//!
+//! Only requests which succeed are recorded; responses served from the client's
+//! [cache](crate::cache) aren't requested again, so aren't recorded either.  Requests
+//! are recorded without their `Authorization` header, so that access tokens aren't
+//! written to the archive.
+//!
+//! # Example
+//! ```rust,no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use futures::StreamExt;
+//! use pullcaps::warc::WarcWriter;
+//! use pullcaps::{Client, Filter};
+//!
+//! let client = Client::builder()
+//!     .warc(WarcWriter::create("rust.warc")?)
+//!     .build();
+//!
+//! let mut comments = client.get_comments(Filter::new().subreddit("rust")).await;
+//! while let Some(comment) = comments.next().await {
+//!     println!("{}", comment.body);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [WARC]: https://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/
+//! [`ClientBuilder::warc`]: crate::ClientBuilder::warc
+use chrono::{SecondsFormat, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, HOST, TRANSFER_ENCODING};
+use reqwest::{Request, Response, StatusCode, Url, Version};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Writes the requests made by a client, and their responses, to a WARC file.
+///
+/// See the [module documentation](self).
+pub struct WarcWriter {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl WarcWriter {
+    /// Opens the WARC file at the given path, creating it if necessary.
+    ///
+    /// Records are appended to an existing file, after a `warcinfo` record describing
+    /// the writer.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let writer = Self {
+            file: Mutex::new(BufWriter::new(file)),
+        };
+
+        let info = format!(
+            "software: pullcaps/{}\r\nformat: WARC File Format 1.1\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        writer.write(|out| {
+            write_record(
+                out,
+                "warcinfo",
+                &record_id(),
+                &[],
+                "application/warc-fields",
+                info.as_bytes(),
+            )
+        })?;
+        Ok(writer)
+    }
+
+    /// Writes any buffered records to the file.
+    ///
+    /// Records are also flushed after each request is recorded.
+    pub fn flush(&self) -> io::Result<()> {
+        self.file.lock().unwrap().flush()
+    }
+
+    /// Records a request, together with the response to it.
+    pub(crate) fn record(
+        &self,
+        request: &Request,
+        response: &ResponseHead,
+        body: &[u8],
+    ) -> io::Result<()> {
+        let (request_id, response_id) = (record_id(), record_id());
+        let url = request.url().as_str();
+
+        let mut request_block = format!(
+            "{} {} HTTP/1.1\r\n",
+            request.method(),
+            request_target(request.url())
+        );
+        if let Some(host) = request.url().host_str() {
+            request_block.push_str(&format!("{}: {}\r\n", HOST, host));
+        }
+        let headers = request
+            .headers()
+            .iter()
+            .filter(|(name, _)| *name != AUTHORIZATION);
+        push_headers(&mut request_block, headers);
+
+        let mut response_block = format!(
+            "{:?} {} {}\r\n",
+            response.version,
+            response.status.as_str(),
+            response.status.canonical_reason().unwrap_or_default()
+        )
+        .into_bytes();
+        let mut response_headers = String::new();
+        // The body has already been decoded from its transfer encoding.
+        let headers = response
+            .headers
+            .iter()
+            .filter(|(name, _)| *name != TRANSFER_ENCODING);
+        push_headers(&mut response_headers, headers);
+        response_block.extend_from_slice(response_headers.as_bytes());
+        response_block.extend_from_slice(body);
+
+        self.write(|out| {
+            write_record(
+                out,
+                "request",
+                &request_id,
+                &[
+                    ("WARC-Target-URI", url),
+                    ("WARC-Concurrent-To", &response_id),
+                ],
+                "application/http;msgtype=request",
+                request_block.as_bytes(),
+            )?;
+            write_record(
+                out,
+                "response",
+                &response_id,
+                &[("WARC-Target-URI", url)],
+                "application/http;msgtype=response",
+                &response_block,
+            )?;
+            out.flush()
+        })
+    }
+
+    fn write(&self, f: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) -> io::Result<()> {
+        f(&mut self.file.lock().unwrap())
+    }
+}
+
+/// The parts of a response which are recorded along with its body, kept before the
+/// body is read.
+pub(crate) struct ResponseHead {
+    version: Version,
+    status: StatusCode,
+    headers: HeaderMap,
+}
+
+impl ResponseHead {
+    pub(crate) fn new(response: &Response) -> Self {
+        Self {
+            version: response.version(),
+            status: response.status(),
+            headers: response.headers().clone(),
+        }
+    }
+}
+
+/// Returns the path and query of a URL, as in an HTTP request line.
+fn request_target(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// Appends headers to the head of an HTTP message, followed by the blank line which
+/// ends the head.
+fn push_headers<'a>(
+    head: &mut String,
+    headers: impl Iterator<Item = (&'a HeaderName, &'a HeaderValue)>,
+) {
+    for (name, value) in headers {
+        head.push_str(&format!(
+            "{}: {}\r\n",
+            name,
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    head.push_str("\r\n");
+}
+
+/// Returns a new, random record ID.
+fn record_id() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    // A version 4 (random) UUID.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "<urn:uuid:{}-{}-{}-{}-{}>",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Writes a single WARC record.
+fn write_record(
+    out: &mut impl Write,
+    warc_type: &str,
+    id: &str,
+    fields: &[(&str, &str)],
+    content_type: &str,
+    block: &[u8],
+) -> io::Result<()> {
+    write!(out, "WARC/1.1\r\n")?;
+    write!(out, "WARC-Type: {}\r\n", warc_type)?;
+    write!(out, "WARC-Record-ID: {}\r\n", id)?;
+    write!(
+        out,
+        "WARC-Date: {}\r\n",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+    )?;
+    for (name, value) in fields {
+        write!(out, "{}: {}\r\n", name, value)?;
+    }
+    write!(out, "Content-Type: {}\r\n", content_type)?;
+    write!(out, "Content-Length: {}\r\n\r\n", block.len())?;
+    out.write_all(block)?;
+    write!(out, "\r\n\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Method;
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.warc");
+        let writer = WarcWriter::create(&path).unwrap();
+
+        let url = Url::parse("https://api.pushshift.io/reddit/comment/search/?q=rust").unwrap();
+        let mut request = Request::new(Method::GET, url);
+        let headers = request.headers_mut();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert("user-agent", HeaderValue::from_static("pullcaps"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        let response = ResponseHead {
+            version: Version::HTTP_11,
+            status: StatusCode::OK,
+            headers,
+        };
+        writer
+            .record(&request, &response, br#"{"data":[]}"#)
+            .unwrap();
+
+        let warc = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<&str> = warc.split("WARC/1.1\r\n").skip(1).collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].starts_with("WARC-Type: warcinfo\r\n"));
+
+        assert!(records[1].starts_with("WARC-Type: request\r\n"));
+        assert!(records[1].contains("GET /reddit/comment/search/?q=rust HTTP/1.1\r\n"));
+        assert!(records[1].contains("host: api.pushshift.io\r\n"));
+        assert!(!records[1].contains("secret"));
+
+        assert!(records[2].starts_with("WARC-Type: response\r\n"));
+        assert!(records[2].contains("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n"));
+        assert!(records[2].ends_with("\r\n\r\n{\"data\":[]}\r\n\r\n"));
+
+        // The request and response are linked to each other.
+        let response_id = records[2].lines().nth(1).unwrap();
+        let response_id = response_id.strip_prefix("WARC-Record-ID: ").unwrap();
+        assert!(records[1].contains(&format!("WARC-Concurrent-To: {}", response_id)));
+    }
+
+    #[test]
+    fn test_record_id() {
+        let id = record_id();
+        assert_eq!(id.len(), "<urn:uuid:>".len() + 36);
+        assert_eq!(&id[24..25], "4");
+        assert_ne!(id, record_id());
+    }
+}